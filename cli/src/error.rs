@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+
+use algod_config::ConfigError;
+use data::ledger::LedgerError;
+use node::NodeError;
+use util::telemetry::TelemetryError;
+
+// The exit codes of algod, for supervisors to tell failures apart. They
+// start at 3: a panic exits with 101 and clap exits with 2 on bad usage.
+
+/// The data directory is not set, missing or not usable.
+pub const EXIT_DATA_DIR: u8 = 3;
+/// The genesis file can not be read, parsed or used to make the ledger.
+pub const EXIT_GENESIS: u8 = 4;
+/// Another instance holds the lock of the data directory.
+pub const EXIT_LOCKED: u8 = 5;
+/// The configuration files of the data directory are invalid.
+pub const EXIT_CONFIG: u8 = 6;
+/// The node failed to start.
+pub const EXIT_NODE: u8 = 7;
+/// The participation keys or their registry can not be used.
+pub const EXIT_PARTICIPATION: u8 = 8;
+
+/// CliError is an error starting algod.
+#[derive(Debug, thiserror::Error)]
+pub enum CliError {
+    #[error("data directory not set use -d option or set ALGORAND_DATA in your environment")]
+    NoDataDir,
+    #[error("data directory {path:?} does not appear to be valid")]
+    DataDir {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("cannot read genesis file {path:?}")]
+    GenesisRead {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("cannot parse genesis file {path:?}")]
+    GenesisParse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("unexpected failure in establishing {path:?}")]
+    LockFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error(
+        "failed to lock {path:?}, is an instance of algod already running on this data directory?"
+    )]
+    Locked { path: PathBuf },
+    #[error("cannot load config")]
+    Config(#[from] ConfigError),
+    /// A seed makes the participation keys of the node predictable.
+    #[error("--seed is refused on the public network {network}")]
+    SeedRefused { network: String },
+    #[error(transparent)]
+    Telemetry(#[from] TelemetryError),
+    #[error("cannot start the node")]
+    Node(#[from] NodeError),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            CliError::NoDataDir | CliError::DataDir { .. } | CliError::LockFile { .. } => {
+                EXIT_DATA_DIR
+            }
+            CliError::GenesisRead { .. }
+            | CliError::GenesisParse { .. }
+            | CliError::Node(NodeError::Genesis(_))
+            | CliError::Node(NodeError::Ledger(
+                LedgerError::Genesis(_) | LedgerError::GenesisMismatch { .. },
+            )) => EXIT_GENESIS,
+            CliError::Locked { .. } => EXIT_LOCKED,
+            CliError::Config(_) | CliError::Telemetry(_) | CliError::SeedRefused { .. } => {
+                EXIT_CONFIG
+            }
+            CliError::Node(
+                NodeError::Participation(_)
+                | NodeError::Registry(_)
+                | NodeError::ParticipationKeyNotFound(_)
+                | NodeError::RegistryPoisoned,
+            ) => EXIT_PARTICIPATION,
+            CliError::Node(_) => EXIT_NODE,
+        }
+    }
+}
+
+/// Formats `err` with the chain of its sources, most general first.
+pub fn report(err: &dyn std::error::Error) -> String {
+    let mut report = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        report.push_str(": ");
+        report.push_str(&err.to_string());
+        source = err.source();
+    }
+    report
+}
@@ -1,14 +1,22 @@
 use std::io::Read;
 use std::path::Path;
+use std::process::ExitCode;
 use std::{env, fs, path::PathBuf};
 
 use algod_config::consensus::{self};
+use algod_config::version::Version;
 use clap::Parser;
 use data::bookkeeping::genesis;
-use rand::prelude::*;
-use rand::SeedableRng;
+use error::CliError;
+use util::rng::NodeRng;
+use util::telemetry::{self, TelemetryConfig, TelemetryEvent};
 
 mod config;
+mod error;
+
+/// The networks whose stake is at risk if the participation keys of a node
+/// can be derived from its seed.
+const PUBLIC_NETWORKS: [&str; 4] = ["mainnet", "testnet", "betanet", "devnet"];
 
 #[derive(Parser, Debug)]
 #[clap(name = "jatayud")]
@@ -41,63 +49,135 @@ struct Args {
     seed: Option<u64>,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> ExitCode {
     let args = Args::parse();
     //dbg!(&args);
-    run(args)?;
-    Ok(())
+    let code = match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let message = error::report(&e);
+            eprintln!("{}", message);
+            telemetry::event(TelemetryEvent::Error { message });
+            ExitCode::from(e.exit_code())
+        }
+    };
+    telemetry::flush();
+    code
 }
 
-fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
+fn run(args: Args) -> Result<(), CliError> {
+    if let Some(info) = build_info(&args, &algod_config::version::current_version()) {
+        println!("{}", info);
+        return Ok(());
+    }
+
     consensus::init();
-    println!("Loading Ledger");
-    let data_dir = match resolve_data_dir(&args.data_dir) {
-        Some(data_dir) => data_dir,
-        None => {
-            panic!("data directory not set use -d option or set ALGORAND_DATA in your environment")
-        }
-    };
-    let data_dir = fs::canonicalize(data_dir).expect("Data directory does not appear to be valid");
-    let _rng: Box<dyn RngCore> = if let Some(s) = args.seed {
-        Box::new(rand::rngs::StdRng::seed_from_u64(s))
-    } else {
-        Box::new(rand::rngs::OsRng)
-    };
+    let data_dir = resolve_data_dir(&args.data_dir).ok_or(CliError::NoDataDir)?;
+    let data_dir = fs::canonicalize(&data_dir).map_err(|source| CliError::DataDir {
+        path: data_dir,
+        source,
+    })?;
     let genesis_path = data_dir.as_path().join(config::GENESIS_JSON_FILE);
-    let mut genesis_file = fs::File::open(genesis_path).expect("cannot open genesis file");
     let mut genesis_text = "".to_string();
-    genesis_file
-        .read_to_string(&mut genesis_text)
-        .expect("cannot read genesis file");
+    fs::File::open(&genesis_path)
+        .and_then(|mut genesis_file| genesis_file.read_to_string(&mut genesis_text))
+        .map_err(|source| CliError::GenesisRead {
+            path: genesis_path.clone(),
+            source,
+        })?;
     let genesis: genesis::Genesis =
-        serde_json::from_str(genesis_text.as_str()).expect("cannot parse genesis file");
+        serde_json::from_str(genesis_text.as_str()).map_err(|source| CliError::GenesisParse {
+            path: genesis_path,
+            source,
+        })?;
     if args.genesis_print {
         println!("{}", genesis.id());
         return Ok(());
     }
+    check_seed(&args, &genesis)?;
+    let rng = node_rng(&args);
     let lock_path = Path::join(&data_dir, "algod.lock");
-    let mut file_lock =
-        fslock::LockFile::open(&lock_path).expect("unexpected failure in establishing algod.lock");
-    file_lock.lock().expect(
-        "failed to lock algod.lock, is an instance of algod already running on this data directory?"
-    );
-
-    let config = match algod_config::load_config_from_disk(&data_dir) {
-        Ok(c) => c,
-        Err(e) => panic!("Cannot load config: {:?}", e),
+    let lock_error = |source| CliError::LockFile {
+        path: lock_path.clone(),
+        source,
     };
+    let mut file_lock = fslock::LockFile::open(&lock_path).map_err(lock_error)?;
+    if !file_lock.try_lock().map_err(lock_error)? {
+        return Err(CliError::Locked { path: lock_path });
+    }
+
+    let config = algod_config::load_config_from_disk(&data_dir)?;
     algod_config::consensus::load_configurable_consensus_protocols(&data_dir)?;
+    if args.init_and_exit {
+        node::AlgorandFullNode::initialize(&data_dir, &genesis)?;
+        println!("initialized {:?} for {}", data_dir, genesis.id());
+        return Ok(());
+    }
+    let mut telemetry = TelemetryConfig {
+        session_guid: session_guid(&args, &rng),
+        to_log: config.telemetry_to_log,
+        ..Default::default()
+    };
+    if let Some(value) = &args.telemetry_override {
+        telemetry.apply_override(value)?;
+    }
     let init = daemon::jatayud::ServerInit {
         root_path: data_dir.clone(),
         genesis,
         genesis_text,
         cfg: config,
+        log_to_stdout: args.log_to_stdout,
+        telemetry,
+        rng,
     };
-    let _ = daemon::jatayud::Server::new(init);
+    daemon::jatayud::Server::new(init)?.run()?;
+    Ok(())
+}
 
+/// What the version, branch and channel flags print, if one is set.
+fn build_info(args: &Args, version: &Version) -> Option<String> {
+    if args.version_check {
+        Some(format!("{}\n{}", version.as_u64(), version))
+    } else if args.brach_check {
+        Some(version.branch.to_string())
+    } else if args.channel_check {
+        Some(version.channel.to_string())
+    } else {
+        None
+    }
+}
+
+/// Refuses `--seed` on the public networks: only dev mode and private test
+/// networks may run with predictable participation keys.
+fn check_seed(args: &Args, genesis: &genesis::Genesis) -> Result<(), CliError> {
+    if args.seed.is_none() {
+        return Ok(());
+    }
+    if !genesis.dev_mode && PUBLIC_NETWORKS.contains(&genesis.network.as_str()) {
+        return Err(CliError::SeedRefused {
+            network: genesis.network.clone(),
+        });
+    }
+    eprintln!("WARNING: running with --seed, the participation keys of this node are predictable");
     Ok(())
 }
 
+/// The randomness of the node: seeded with `--seed` for reproducible runs.
+fn node_rng(args: &Args) -> NodeRng {
+    match args.seed {
+        Some(seed) => NodeRng::seeded(seed),
+        None => NodeRng::from_entropy(),
+    }
+}
+
+/// The telemetry session of this run, unless `-s` names one. The session
+/// is drawn from `rng` either way, so that naming it does not shift the
+/// streams the node splits next.
+fn session_guid(args: &Args, rng: &NodeRng) -> String {
+    let drawn = telemetry::new_session_guid(&mut rng.split());
+    args.session_guid.clone().unwrap_or(drawn)
+}
+
 fn resolve_data_dir(data_dir: &Option<PathBuf>) -> Option<PathBuf> {
     match data_dir {
         Some(d) => Some(d.clone()),
@@ -107,3 +187,137 @@ fn resolve_data_dir(data_dir: &Option<PathBuf>) -> Option<PathBuf> {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::basics::{AccountData, Address, MicroAlgos};
+
+    fn version() -> Version {
+        Version {
+            major: 1,
+            minor: 2,
+            patch: 3,
+            commit_hash: "feedfacecafe",
+            branch: "master",
+            channel: "beta",
+        }
+    }
+
+    #[test]
+    fn build_info_flags() {
+        let info = |flags: &[&str]| {
+            let args = Args::parse_from(std::iter::once("jatayud").chain(flags.iter().copied()));
+            build_info(&args, &version())
+        };
+        assert_eq!(
+            info(&["-v"]).as_deref(),
+            Some("4295098371\n1.2.3.beta [master] (commit #feedface)")
+        );
+        assert_eq!(info(&["-n"]).as_deref(), Some("master"));
+        assert_eq!(info(&["--channel-check"]).as_deref(), Some("beta"));
+        assert_eq!(info(&["-d", "/nonexistent"]), None);
+    }
+
+    fn test_genesis() -> genesis::Genesis {
+        let pool = Address::from([1; 32]).string();
+        genesis::Genesis {
+            schema_id: "v1".to_string(),
+            network: "clitest".to_string(),
+            proto: protocol::CONSENSUS_V7.to_string(),
+            allocation: vec![genesis::GenesisAllocation {
+                address: pool.clone(),
+                state: AccountData {
+                    microalgos: MicroAlgos(1_000_000),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            rewards_pool: pool.clone(),
+            fee_sink: pool,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn init_and_exit_does_not_start_the_node() {
+        let dir = env::temp_dir().join(format!("cli-init-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let genesis = test_genesis();
+        fs::write(
+            dir.join(config::GENESIS_JSON_FILE),
+            serde_json::to_string(&genesis).unwrap(),
+        )
+        .unwrap();
+
+        let data_dir = dir.to_str().unwrap();
+        run(Args::parse_from(["jatayud", "-x", "-d", data_dir])).unwrap();
+        let genesis_dir = dir.join(genesis.id());
+        assert!(genesis_dir
+            .join(algod_config::PARTICIPATION_REGISTRY_FILENAME)
+            .exists());
+        assert!(!dir.join("algod.pid").exists());
+        assert!(!dir.join("node.log").exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn node_errors_map_to_exit_codes() {
+        use data::ledger::LedgerError;
+        use node::NodeError;
+
+        let code = |err: NodeError| CliError::from(err).exit_code();
+        let mismatch = LedgerError::GenesisMismatch {
+            expected: "00".into(),
+            found: "01".into(),
+        };
+        assert_eq!(code(NodeError::Ledger(mismatch)), error::EXIT_GENESIS);
+        assert_eq!(code(NodeError::RegistryPoisoned), error::EXIT_PARTICIPATION);
+        let missing = LedgerError::MissingBlock(Default::default());
+        assert_eq!(code(NodeError::Ledger(missing)), error::EXIT_NODE);
+    }
+
+    #[test]
+    fn seed_reproduces_the_session_and_participation_keys() {
+        consensus::init();
+        let start = |flags: &[&str], run: usize| {
+            let dir = env::temp_dir().join(format!("cli-seed-test-{}-{}", std::process::id(), run));
+            fs::create_dir_all(&dir).unwrap();
+            let args = Args::parse_from([&["jatayud"], flags].concat());
+            let rng = node_rng(&args);
+            let guid = session_guid(&args, &rng);
+            let config = algod_config::default_local();
+            let node =
+                node::AlgorandFullNode::new(dir.clone(), config, &test_genesis(), rng).unwrap();
+            let key = node
+                .generate_participation_key(Address::from([1; 32]), 1, 1000, 100)
+                .unwrap();
+            drop(node);
+            fs::remove_dir_all(&dir).ok();
+            (guid, key)
+        };
+        let (guid, key) = start(&["--seed", "7"], 0);
+        assert_eq!((guid.clone(), key), start(&["--seed", "7"], 1));
+        let (other_guid, other_key) = start(&["--seed", "8"], 2);
+        assert_ne!(guid, other_guid);
+        assert_ne!(key, other_key);
+
+        // Naming the session does not shift the keys.
+        let named = start(&["--seed", "7", "-s", "named"], 3);
+        assert_eq!(named, ("named".to_string(), key));
+    }
+
+    #[test]
+    fn seed_is_refused_on_public_networks() {
+        let args = Args::parse_from(["jatayud", "--seed", "7"]);
+        let mut genesis = test_genesis();
+        check_seed(&args, &genesis).unwrap();
+        genesis.network = "mainnet".to_string();
+        let refused = check_seed(&args, &genesis).unwrap_err();
+        assert!(matches!(refused, CliError::SeedRefused { .. }));
+        assert_eq!(refused.exit_code(), error::EXIT_CONFIG);
+        genesis.dev_mode = true;
+        check_seed(&args, &genesis).unwrap();
+        check_seed(&Args::parse_from(["jatayud"]), &test_genesis()).unwrap();
+    }
+}
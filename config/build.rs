@@ -0,0 +1,44 @@
+//! Embeds the build metadata of the node: the git commit and branch, and
+//! the release channel, from `JATAYU_CHANNEL`. Release builds made outside
+//! of a git checkout may set `JATAYU_COMMIT_HASH` and `JATAYU_BRANCH`.
+//! Metadata that cannot be found is left out, and `version::current_version`
+//! falls back to defaults for it.
+
+use std::process::Command;
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let value = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+fn env(var: &str) -> Option<String> {
+    std::env::var(var).ok().filter(|value| !value.is_empty())
+}
+
+fn main() {
+    for var in ["JATAYU_COMMIT_HASH", "JATAYU_BRANCH", "JATAYU_CHANNEL"] {
+        println!("cargo:rerun-if-env-changed={}", var);
+    }
+    for path in ["../.git/HEAD", "../.git/refs"] {
+        if std::path::Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+
+    let commit_hash = env("JATAYU_COMMIT_HASH").or_else(|| git(&["rev-parse", "HEAD"]));
+    let branch = env("JATAYU_BRANCH").or_else(|| git(&["rev-parse", "--abbrev-ref", "HEAD"]));
+    let metadata = [
+        ("JATAYU_COMMIT_HASH", commit_hash),
+        ("JATAYU_BRANCH", branch),
+        ("JATAYU_CHANNEL", env("JATAYU_CHANNEL")),
+    ];
+    for (var, value) in metadata {
+        if let Some(value) = value {
+            println!("cargo:rustc-env={}={}", var, value);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::ConfigResult;
+use crate::{ConfigError, ConfigResult};
 use once_cell::sync::OnceCell;
 use protocol::*;
 use serde::{Deserialize, Serialize};
@@ -151,11 +151,18 @@ mod from_nano_sec {
     }
 }
 
+/// Returns a copy of the parameters of consensus version `proto`, if it is
+/// supported.
+pub fn get_params(proto: &str) -> Option<ConsensusParams> {
+    CONSENSUS.get()?.read().ok()?.get(proto).cloned()
+}
+
 pub fn load_configurable_consensus_protocols<P: AsRef<Path>>(
     data_directory: P,
 ) -> ConfigResult<()> {
     let new_consensus = preload_configurable_consensus_protocols(data_directory)?;
-    let mut w = CONSENSUS.get().unwrap().write()?;
+    let consensus = CONSENSUS.get().ok_or(ConfigError::Uninitialized)?;
+    let mut w = consensus.write().map_err(|_| ConfigError::Poisoned)?;
     *w = new_consensus;
     Ok(())
 }
@@ -166,19 +173,27 @@ fn preload_configurable_consensus_protocols<P: AsRef<Path>>(
     let consensus_protocol_path = data_directory
         .as_ref()
         .join(ConfigurableConsensusProtocolsFilename);
-    match fs::File::open(consensus_protocol_path) {
+    let consensus = CONSENSUS.get().ok_or(ConfigError::Uninitialized)?;
+    let current = consensus.read().map_err(|_| ConfigError::Poisoned)?;
+    match fs::File::open(&consensus_protocol_path) {
         Ok(file) => {
-            let configurable_consensus = serde_json::from_reader(file)?;
-            return Ok(merge(
-                CONSENSUS.get().unwrap().read()?.deref(),
-                configurable_consensus,
-            ));
+            let configurable_consensus =
+                serde_json::from_reader(file).map_err(|source| ConfigError::Parse {
+                    path: consensus_protocol_path,
+                    source,
+                })?;
+            return Ok(merge(current.deref(), configurable_consensus));
         }
         Err(err) => match err.kind() {
             std::io::ErrorKind::NotFound => {
-                return Ok(CONSENSUS.get().unwrap().read()?.clone());
+                return Ok(current.clone());
+            }
+            _ => {
+                return Err(ConfigError::Io {
+                    path: consensus_protocol_path,
+                    source: err,
+                })
             }
-            _ => return Err(err.into()),
         },
     }
 }
@@ -543,21 +558,27 @@ fn check_set_alloc_bounds(consensus: &ConsensusProtocols) {
             p.max_proposed_expired_online_accounts,
             &mut max_proposed_expired_online_accounts,
         );
-        MAX_VOTE_THRESHOLD.get_or_init(|| max_vote_threshold);
-        MAX_EVAL_DELTA_KEYS.get_or_init(|| max_eval_delta_keys);
-        MAX_EVAL_DELTA_ACCOUNTS.get_or_init(|| max_eval_delta_accounts);
-        MAX_APP_PROGRAM_LEN.get_or_init(|| max_app_program_len);
-        MAX_LOGIC_SIG_MAX_SIZE.get_or_init(|| max_logic_sig_max_size);
-        MAX_TXN_NOTE_BYTES.get_or_init(|| max_txn_note_bytes);
-        MAX_TX_GROUP_SIZE.get_or_init(|| max_tx_group_size);
-        MAX_BYTES_KEY_VALUE_LEN.get_or_init(|| max_bytes_key_value_len);
-        MAX_EXTRA_APP_PROGRAM_LEN.get_or_init(|| max_extra_app_program_len);
-        MAX_AVAILABLE_APP_PROGRAM_LEN.get_or_init(|| max_available_app_program_len);
-        MAX_LOG_CALLS.get_or_init(|| max_log_calls);
-        MAX_INNER_TRANSACTIONS_PER_DELTA.get_or_init(|| max_inner_transactions_per_delta);
-        MAX_PROPOSED_EXPIRED_ONLINE_ACCOUNTS.get_or_init(|| max_proposed_expired_online_accounts);
     }
+    MAX_VOTE_THRESHOLD.get_or_init(|| max_vote_threshold);
+    MAX_EVAL_DELTA_KEYS.get_or_init(|| max_eval_delta_keys);
+    MAX_EVAL_DELTA_ACCOUNTS.get_or_init(|| max_eval_delta_accounts);
+    MAX_APP_PROGRAM_LEN.get_or_init(|| max_app_program_len);
+    MAX_LOGIC_SIG_MAX_SIZE.get_or_init(|| max_logic_sig_max_size);
+    MAX_TXN_NOTE_BYTES.get_or_init(|| max_txn_note_bytes);
+    MAX_TX_GROUP_SIZE.get_or_init(|| max_tx_group_size);
+    MAX_BYTES_KEY_VALUE_LEN.get_or_init(|| max_bytes_key_value_len);
+    MAX_EXTRA_APP_PROGRAM_LEN.get_or_init(|| max_extra_app_program_len);
+    MAX_AVAILABLE_APP_PROGRAM_LEN.get_or_init(|| max_available_app_program_len);
+    MAX_LOG_CALLS.get_or_init(|| max_log_calls);
+    MAX_INNER_TRANSACTIONS_PER_DELTA.get_or_init(|| max_inner_transactions_per_delta);
+    MAX_PROPOSED_EXPIRED_ONLINE_ACCOUNTS.get_or_init(|| max_proposed_expired_online_accounts);
 }
+
+/// The largest transaction group allowed by any supported protocol.
+pub fn max_tx_group_size() -> usize {
+    MAX_TX_GROUP_SIZE.get().copied().unwrap_or_default().max(0) as usize
+}
+
 pub fn init() {
     let _ = CONSENSUS.get_or_init(|| {
         let mut consensus_map = ConsensusProtocols::new();
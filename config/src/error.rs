@@ -0,0 +1,27 @@
+use std::path::PathBuf;
+
+/// ConfigError is an error loading the configuration of the node from its
+/// data directory.
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    /// The file exists but could not be read.
+    #[error("cannot read {path:?}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The file is not valid JSON for what it configures.
+    #[error("cannot parse {path:?}")]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    /// The consensus protocols were not initialized with `consensus::init`.
+    #[error("consensus protocols are not initialized")]
+    Uninitialized,
+    /// A thread panicked while updating the consensus protocols.
+    #[error("consensus protocols lock poisoned")]
+    Poisoned,
+}
+
+pub type ConfigResult<T> = Result<T, ConfigError>;
@@ -1,11 +1,13 @@
 pub mod consensus;
+mod error;
 mod local_template;
+pub mod version;
 
+pub use error::{ConfigError, ConfigResult};
 pub use local_template::{default_local, Local};
 
 use std::path::Path;
 
-type ConfigResult<T> = Result<T, Box<dyn std::error::Error>>;
 // Devnet identifies the 'development network' use for development and not generally accessible publicly
 //const Devnet protocol.NetworkID = "devnet"
 
@@ -45,7 +47,7 @@ const CompactCertFilename: &str = "compactcert.sqlite";
 
 // ParticipationRegistryFilename is the name of the participation registry database file.
 // It is used for tracking participation key metadata.
-const ParticipationRegistryFilename: &str = "partregistry.sqlite";
+pub const PARTICIPATION_REGISTRY_FILENAME: &str = "partregistry.sqlite";
 
 // ConfigurableConsensusProtocolsFilename defines a set of consensus prototocols that
 // are to be loaded from the data directory ( if present ), to override the
@@ -69,16 +71,23 @@ fn load_config_from_file<P: AsRef<Path>>(config_file: P) -> ConfigResult<Local>
 
 fn merge_config_file(config_file: &Path, c: Local) -> ConfigResult<Local> {
     match std::fs::File::open(config_file) {
-        Ok(f) => load_config(&f, c),
+        Ok(f) => load_config(config_file, &f, c),
         Err(e) => match e.kind() {
             std::io::ErrorKind::NotFound => Ok(c),
-            _ => Err(e.into()),
+            _ => Err(ConfigError::Io {
+                path: config_file.to_path_buf(),
+                source: e,
+            }),
         },
     }
 }
 
-fn load_config(config_file: &std::fs::File, c: Local) -> ConfigResult<Local> {
-    let _value: serde_json::Value = serde_json::from_reader(config_file)?;
+fn load_config(path: &Path, config_file: &std::fs::File, c: Local) -> ConfigResult<Local> {
+    let _value: serde_json::Value =
+        serde_json::from_reader(config_file).map_err(|source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
     Ok(c)
 }
 
@@ -0,0 +1,112 @@
+use std::fmt;
+
+/// Version describes the build of the node, as embedded at compile time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub commit_hash: &'static str,
+    pub branch: &'static str,
+    /// The release channel, such as `dev`, `beta` or `stable`.
+    pub channel: &'static str,
+}
+
+impl Version {
+    pub fn semver(&self) -> String {
+        format!("{}.{}.{}", self.major, self.minor, self.patch)
+    }
+
+    /// The version packed in a number, as go-algorand prints it: the major
+    /// version in the high 32 bits, then 16 bits each of minor and patch.
+    pub fn as_u64(&self) -> u64 {
+        (self.major << 32) | (self.minor << 16) | self.patch
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let commit = self.commit_hash.get(..8).unwrap_or(self.commit_hash);
+        write!(
+            f,
+            "{}.{} [{}] (commit #{})",
+            self.semver(),
+            self.channel,
+            self.branch,
+            commit
+        )
+    }
+}
+
+/// Stands for the commit or branch of a build made without git metadata.
+pub const UNKNOWN: &str = "unknown";
+
+/// The channel of a build made without `JATAYU_CHANNEL`.
+pub const DEFAULT_CHANNEL: &str = "dev";
+
+/// The version of this build.
+pub fn current_version() -> Version {
+    build_version(
+        option_env!("JATAYU_COMMIT_HASH"),
+        option_env!("JATAYU_BRANCH"),
+        option_env!("JATAYU_CHANNEL"),
+    )
+}
+
+/// The version of this package with the metadata the build script found,
+/// falling back to defaults for what it did not.
+fn build_version(
+    commit_hash: Option<&'static str>,
+    branch: Option<&'static str>,
+    channel: Option<&'static str>,
+) -> Version {
+    let or =
+        |value: Option<&'static str>, default| value.filter(|v| !v.is_empty()).unwrap_or(default);
+    Version {
+        major: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or_default(),
+        minor: env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or_default(),
+        patch: env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or_default(),
+        commit_hash: or(commit_hash, UNKNOWN),
+        branch: or(branch, UNKNOWN),
+        channel: or(channel, DEFAULT_CHANNEL),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_packing_and_display() {
+        let version = Version {
+            major: 3,
+            minor: 9,
+            patch: 2,
+            commit_hash: "0123456789abcdef",
+            branch: "rel/stable",
+            channel: "stable",
+        };
+        assert_eq!(version.as_u64(), (3 << 32) | (9 << 16) | 2);
+        assert_eq!(version.as_u64(), 12885491714);
+        assert_eq!(
+            version.to_string(),
+            "3.9.2.stable [rel/stable] (commit #01234567)"
+        );
+    }
+
+    #[test]
+    fn missing_metadata_falls_back() {
+        let version = build_version(None, Some(""), None);
+        assert_eq!(version.commit_hash, UNKNOWN);
+        assert_eq!(version.branch, UNKNOWN);
+        assert_eq!(version.channel, DEFAULT_CHANNEL);
+        assert_eq!(version.semver(), env!("CARGO_PKG_VERSION"));
+        assert!(version.to_string().ends_with("(commit #unknown)"));
+
+        let version = build_version(Some("abc"), Some("master"), Some("beta"));
+        assert_eq!(
+            (version.commit_hash, version.branch, version.channel),
+            ("abc", "master", "beta")
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use std::cmp::Ordering;
+
+/// BigFloat represents the number `mantissa * 2^exp`. The mantissa is kept
+/// normalized (most-significant bit set) unless the number is zero.
+///
+/// Every operation either rounds up or rounds down, so that comparisons in
+/// `num_reveals` stay conservative regardless of the precision loss.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) struct BigFloat {
+    mantissa: u32,
+    exp: i32,
+}
+
+impl BigFloat {
+    fn canonicalize(&mut self) {
+        if self.mantissa == 0 {
+            self.exp = 0;
+            return;
+        }
+        while self.mantissa & (1 << 31) == 0 {
+            self.mantissa <<= 1;
+            self.exp -= 1;
+        }
+    }
+
+    fn from_u64(mut x: u64, exp: i32, round_up: bool) -> Self {
+        let mut exp = exp;
+        while x >= 1 << 32 {
+            x = if round_up { (x >> 1) + (x & 1) } else { x >> 1 };
+            exp += 1;
+        }
+        let mut f = Self {
+            mantissa: x as u32,
+            exp,
+        };
+        f.canonicalize();
+        f
+    }
+
+    /// Returns `x`, rounded down to the precision of a BigFloat.
+    pub(super) fn dn(x: u64) -> Self {
+        Self::from_u64(x, 0, false)
+    }
+
+    /// Returns `x`, rounded up to the precision of a BigFloat.
+    pub(super) fn up(x: u64) -> Self {
+        Self::from_u64(x, 0, true)
+    }
+
+    pub(super) fn pow2(exp: i32) -> Self {
+        Self::from_u64(1, exp, false)
+    }
+
+    pub(super) fn mul_dn(&self, other: &Self) -> Self {
+        let m = self.mantissa as u64 * other.mantissa as u64;
+        Self::from_u64(m, self.exp + other.exp, false)
+    }
+
+    pub(super) fn mul_up(&self, other: &Self) -> Self {
+        let m = self.mantissa as u64 * other.mantissa as u64;
+        Self::from_u64(m, self.exp + other.exp, true)
+    }
+}
+
+impl PartialOrd for BigFloat {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigFloat {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.mantissa, other.mantissa) {
+            (0, 0) => Ordering::Equal,
+            (0, _) => Ordering::Less,
+            (_, 0) => Ordering::Greater,
+            _ => self
+                .exp
+                .cmp(&other.exp)
+                .then(self.mantissa.cmp(&other.mantissa)),
+        }
+    }
+}
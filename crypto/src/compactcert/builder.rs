@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+
+use super::common::{num_reveals, ots_id_for_round, CoinChoice, MAX_REVEALS};
+use super::{Cert, CompactCertResult, Params, Participant, Reveal, SigslotCommit};
+use crate::merklearray::{Array, MerkleResult, Tree};
+use crate::onetimesig::{self, OneTimeSignature};
+use crate::util::{hash_obj, hash_rep, HashType, MsgpHashable};
+
+/// Sigslot is a single slot in the sigs array of the builder; `sig` is set
+/// once the corresponding participant signed.
+#[derive(Debug, Clone, Default)]
+struct Sigslot {
+    /// The weight of the participant, or zero if it did not sign (yet).
+    weight: u64,
+    sig: Option<OneTimeSignature>,
+    l: u64,
+}
+
+impl Sigslot {
+    fn commit(&self) -> SigslotCommit {
+        SigslotCommit {
+            sig: self.sig.clone().unwrap_or_default(),
+            l: self.l,
+        }
+    }
+}
+
+struct SigslotArray<'a>(&'a [Sigslot]);
+
+impl Array for SigslotArray<'_> {
+    fn length(&self) -> u64 {
+        self.0.len() as u64
+    }
+
+    fn marshal(&self, pos: u64) -> MerkleResult<Vec<u8>> {
+        let slot = self
+            .0
+            .get(pos as usize)
+            .ok_or_else(|| format!("sigslot position {} is out of bound", pos))?;
+        Ok(hash_rep(&slot.commit()))
+    }
+}
+
+/// Builder keeps track of signatures on a message and eventually produces
+/// a compact certificate for that message.
+pub struct Builder<M> {
+    params: Params<M>,
+    sigs: Vec<Sigslot>,
+    signed_weight: u64,
+    participants: Vec<Participant>,
+    parttree: Tree,
+}
+
+impl<M: MsgpHashable> Builder<M> {
+    /// Constructs an empty builder. `parttree` must be the Merkle tree of
+    /// `participants`, built over a `ParticipantsArray`.
+    pub fn new(params: Params<M>, participants: Vec<Participant>, parttree: Tree) -> Self {
+        Self {
+            params,
+            sigs: vec![Sigslot::default(); participants.len()],
+            signed_weight: 0,
+            participants,
+            parttree,
+        }
+    }
+
+    /// Returns whether there is already a signature for the participant at
+    /// `pos`.
+    pub fn present(&self, pos: u64) -> bool {
+        self.sigs
+            .get(pos as usize)
+            .is_some_and(|slot| slot.weight != 0)
+    }
+
+    /// Adds the signature of the participant at `pos`. When `verify_sig` is
+    /// false the caller must have verified the signature already.
+    pub fn add(
+        &mut self,
+        pos: u64,
+        sig: OneTimeSignature,
+        verify_sig: bool,
+    ) -> CompactCertResult<()> {
+        let part = *self
+            .participants
+            .get(pos as usize)
+            .ok_or_else(|| format!("participant position {} is out of bound", pos))?;
+        if self.present(pos) {
+            return Err(format!("position {} already added", pos).into());
+        }
+        if part.weight == 0 {
+            return Err(format!("position {} has zero weight", pos).into());
+        }
+        if part.key_dilution == 0 {
+            return Err(format!("position {} has zero key dilution", pos).into());
+        }
+        let signed_weight = self
+            .signed_weight
+            .checked_add(part.weight)
+            .ok_or_else(|| format!("position {} overflows the signed weight", pos))?;
+        if verify_sig {
+            let id = ots_id_for_round(self.params.sig_round, part.key_dilution);
+            if !onetimesig::verify(&part.pk, id, &self.params.msg, &sig) {
+                return Err(format!("signature of position {} does not verify", pos).into());
+            }
+        }
+        self.sigs[pos as usize] = Sigslot {
+            weight: part.weight,
+            sig: Some(sig),
+            l: 0,
+        };
+        self.signed_weight = signed_weight;
+        Ok(())
+    }
+
+    /// Returns whether enough weight signed to build a certificate.
+    pub fn ready(&self) -> bool {
+        self.signed_weight > self.params.proven_weight
+    }
+
+    pub fn signed_weight(&self) -> u64 {
+        self.signed_weight
+    }
+
+    /// Returns the position of the sigslot whose weight interval contains
+    /// `coin`.
+    fn coin_index(&self, coin: u64) -> CompactCertResult<u64> {
+        let pos = self
+            .sigs
+            .partition_point(|slot| slot.l + slot.weight <= coin);
+        match self.sigs.get(pos) {
+            Some(slot) if slot.l <= coin && coin < slot.l + slot.weight => Ok(pos as u64),
+            _ => Err(format!("coin {} is out of range", coin).into()),
+        }
+    }
+
+    /// Builds a compact certificate out of the signatures added so far.
+    pub fn build(&mut self) -> CompactCertResult<Cert> {
+        if !self.ready() {
+            return Err(format!(
+                "not enough signed weight: {} <= {}",
+                self.signed_weight, self.params.proven_weight
+            )
+            .into());
+        }
+
+        let mut l = 0;
+        for slot in self.sigs.iter_mut() {
+            slot.l = l;
+            l += slot.weight;
+        }
+
+        let sigtree = Tree::build(&SigslotArray(&self.sigs), HashType::Sha512_256)?;
+        let sig_commit = sigtree.root();
+        let nr = num_reveals(
+            self.signed_weight,
+            self.params.proven_weight,
+            self.params.sec_kq,
+            MAX_REVEALS,
+        )?;
+
+        let mut reveals = BTreeMap::new();
+        for j in 0..nr {
+            let choice = CoinChoice {
+                j,
+                signed_weight: self.signed_weight,
+                proven_weight: self.params.proven_weight,
+                sigcom: sig_commit.clone(),
+                partcom: self.parttree.root(),
+                msg_hash: hash_obj(&self.params.msg),
+            };
+            let pos = self.coin_index(choice.hash_coin())?;
+            reveals.entry(pos).or_insert_with(|| Reveal {
+                sig_slot: self.sigs[pos as usize].commit(),
+                part: self.participants[pos as usize],
+            });
+        }
+
+        let positions: Vec<u64> = reveals.keys().copied().collect();
+        Ok(Cert {
+            sig_commit,
+            signed_weight: self.signed_weight,
+            sig_proofs: sigtree.prove(&positions)?,
+            part_proofs: self.parttree.prove(&positions)?,
+            reveals,
+        })
+    }
+}
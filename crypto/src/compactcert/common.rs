@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+
+use super::bigfloat::BigFloat;
+use super::CompactCertResult;
+use crate::merklearray::GenericDigest;
+use crate::onetimesig::OneTimeSignatureIdentifier;
+use crate::util::{hash_obj, HashDigest, MsgpHashable};
+
+/// MAX_REVEALS is a bound on allocation and on the number of reveals in a
+/// certificate.
+pub const MAX_REVEALS: u64 = 1024;
+
+/// CoinChoice is the input of the pseudo-random choice of the positions to
+/// reveal. It commits to everything the reveals depend on.
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct CoinChoice {
+    #[serde(rename = "j")]
+    pub j: u64,
+    #[serde(rename = "sigweight")]
+    pub signed_weight: u64,
+    #[serde(rename = "provenweight")]
+    pub proven_weight: u64,
+    #[serde(rename = "sigcom")]
+    pub sigcom: GenericDigest,
+    #[serde(rename = "partcom")]
+    pub partcom: GenericDigest,
+    #[serde(rename = "msghash")]
+    pub msg_hash: HashDigest,
+}
+
+impl MsgpHashable for CoinChoice {
+    fn hash_id(&self) -> protocol::HashId {
+        protocol::COMPACT_CERT_COIN
+    }
+}
+
+impl CoinChoice {
+    /// Maps the choice to a coin in `[0, signed_weight)`.
+    pub fn hash_coin(&self) -> u64 {
+        let h = hash_obj(self);
+        let mut coin = [0u8; 8];
+        coin.copy_from_slice(&h.0[..8]);
+        u64::from_le_bytes(coin) % self.signed_weight
+    }
+}
+
+/// Returns the number of reveals needed so that an adversary holding less
+/// than `proven_weight` has a chance below `2^-sec_kq` to produce a valid
+/// certificate claiming `signed_weight`, i.e. the smallest `n` such that
+/// `signed_weight^n >= 2^sec_kq * proven_weight^n`.
+pub(super) fn num_reveals(
+    signed_weight: u64,
+    proven_weight: u64,
+    sec_kq: u64,
+    bound: u64,
+) -> CompactCertResult<u64> {
+    let sw = BigFloat::dn(signed_weight);
+    let pw = BigFloat::up(proven_weight);
+    let mut lhs = BigFloat::dn(1);
+    let mut rhs = BigFloat::pow2(sec_kq as i32);
+    let mut n = 0;
+    loop {
+        if lhs >= rhs {
+            return Ok(n);
+        }
+        if n >= bound {
+            return Err(format!(
+                "num_reveals({}, {}, {}) > {}",
+                signed_weight, proven_weight, sec_kq, bound
+            )
+            .into());
+        }
+        lhs = lhs.mul_dn(&sw);
+        rhs = rhs.mul_up(&pw);
+        n += 1;
+    }
+}
+
+/// Maps a round to the identifier of the one-time key that signs for it.
+pub(super) fn ots_id_for_round(round: u64, key_dilution: u64) -> OneTimeSignatureIdentifier {
+    OneTimeSignatureIdentifier {
+        batch: round / key_dilution,
+        offset: round % key_dilution,
+    }
+}
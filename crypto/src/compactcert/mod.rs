@@ -0,0 +1,17 @@
+//! Compact certificates let a verifier that knows the commitment to a set
+//! of weighted participants check that participants with a large enough
+//! total weight signed a message, by only looking at a few signatures
+//! chosen pseudo-randomly from the signers.
+
+mod bigfloat;
+mod builder;
+mod common;
+mod structs;
+mod verifier;
+
+pub use builder::*;
+pub use common::MAX_REVEALS;
+pub use structs::*;
+pub use verifier::*;
+
+pub type CompactCertResult<T> = Result<T, Box<dyn std::error::Error>>;
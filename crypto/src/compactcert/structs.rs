@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::merklearray::{Array, GenericDigest, MerkleResult, Proof};
+use crate::onetimesig::{OneTimeSignature, OneTimeSignatureVerifier};
+use crate::util::{hash_rep, MsgpHashable};
+
+/// Params defines common parameters for the verifier and builder.
+#[derive(Debug, Clone)]
+pub struct Params<M> {
+    /// The message to be certified.
+    pub msg: M,
+    /// Participants must have signed with more than this weight.
+    pub proven_weight: u64,
+    /// The round whose one-time keys sign `msg`.
+    pub sig_round: u64,
+    /// Security parameter, in bits.
+    pub sec_kq: u64,
+}
+
+/// A Participant corresponds to an account whose one-time signature key
+/// may be used to sign a compact certificate, weighted by its stake.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Participant {
+    #[serde(rename = "p")]
+    pub pk: OneTimeSignatureVerifier,
+    #[serde(rename = "w")]
+    pub weight: u64,
+    #[serde(rename = "d")]
+    pub key_dilution: u64,
+}
+
+impl MsgpHashable for Participant {
+    fn hash_id(&self) -> protocol::HashId {
+        protocol::COMPACT_CERT_PART
+    }
+}
+
+/// ParticipantsArray is the Merkle array of the participants; its root is
+/// the commitment a verifier has to know.
+pub struct ParticipantsArray<'a>(pub &'a [Participant]);
+
+impl Array for ParticipantsArray<'_> {
+    fn length(&self) -> u64 {
+        self.0.len() as u64
+    }
+
+    fn marshal(&self, pos: u64) -> MerkleResult<Vec<u8>> {
+        let part = self
+            .0
+            .get(pos as usize)
+            .ok_or_else(|| format!("participant position {} is out of bound", pos))?;
+        Ok(hash_rep(part))
+    }
+}
+
+/// SigslotCommit is a single slot in the sigs array that forms the
+/// certificate. `l` is the total weight of the signatures before it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigslotCommit {
+    #[serde(rename = "s")]
+    pub sig: OneTimeSignature,
+    #[serde(rename = "l")]
+    pub l: u64,
+}
+
+impl MsgpHashable for SigslotCommit {
+    fn hash_id(&self) -> protocol::HashId {
+        protocol::COMPACT_CERT_SIG
+    }
+}
+
+/// A Reveal is a single array position revealed as part of a compact
+/// certificate.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reveal {
+    #[serde(rename = "s")]
+    pub sig_slot: SigslotCommit,
+    #[serde(rename = "p")]
+    pub part: Participant,
+}
+
+/// Cert represents a compact certificate.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cert {
+    #[serde(rename = "c")]
+    pub sig_commit: GenericDigest,
+    #[serde(rename = "w")]
+    pub signed_weight: u64,
+    #[serde(rename = "S")]
+    pub sig_proofs: Proof,
+    #[serde(rename = "P")]
+    pub part_proofs: Proof,
+    /// Reveals is a sparse map from the position being revealed to the
+    /// corresponding elements from the sigs and participants arrays.
+    #[serde(rename = "r")]
+    pub reveals: BTreeMap<u64, Reveal>,
+}
@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+
+use super::common::{num_reveals, ots_id_for_round, CoinChoice, MAX_REVEALS};
+use super::{Cert, CompactCertResult, Params};
+use crate::merklearray::{self, GenericDigest};
+use crate::onetimesig;
+use crate::util::{hash_obj, hash_rep, MsgpHashable};
+
+/// Verifier is used to verify a compact certificate against the commitment
+/// to the participants.
+pub struct Verifier<M> {
+    params: Params<M>,
+    partcom: GenericDigest,
+}
+
+impl<M: MsgpHashable> Verifier<M> {
+    pub fn new(params: Params<M>, partcom: GenericDigest) -> Self {
+        Self { params, partcom }
+    }
+
+    /// Checks whether `cert` is a valid compact certificate for the message
+    /// in the parameters of the verifier.
+    pub fn verify(&self, cert: &Cert) -> CompactCertResult<()> {
+        if cert.signed_weight <= self.params.proven_weight {
+            return Err(format!(
+                "cert signed weight {} <= proven weight {}",
+                cert.signed_weight, self.params.proven_weight
+            )
+            .into());
+        }
+
+        let mut sigs = BTreeMap::new();
+        let mut parts = BTreeMap::new();
+        for (&pos, reveal) in cert.reveals.iter() {
+            // The coins must fall in [l, l + weight) of some reveal; a
+            // crafted cert must not wrap that range around.
+            if reveal.sig_slot.l.checked_add(reveal.part.weight).is_none() {
+                return Err(
+                    format!("reveal of position {} overflows the signed weight", pos).into(),
+                );
+            }
+            if reveal.part.key_dilution == 0 {
+                return Err(format!("reveal of position {} has zero key dilution", pos).into());
+            }
+            let id = ots_id_for_round(self.params.sig_round, reveal.part.key_dilution);
+            if !onetimesig::verify(&reveal.part.pk, id, &self.params.msg, &reveal.sig_slot.sig) {
+                return Err(
+                    format!("signature in reveal of position {} does not verify", pos).into(),
+                );
+            }
+            sigs.insert(pos, hash_rep(&reveal.sig_slot));
+            parts.insert(pos, hash_rep(&reveal.part));
+        }
+
+        merklearray::verify(&cert.sig_commit, &sigs, &cert.sig_proofs)?;
+        merklearray::verify(&self.partcom, &parts, &cert.part_proofs)?;
+
+        let nr = num_reveals(
+            cert.signed_weight,
+            self.params.proven_weight,
+            self.params.sec_kq,
+            MAX_REVEALS,
+        )?;
+        for j in 0..nr {
+            let choice = CoinChoice {
+                j,
+                signed_weight: cert.signed_weight,
+                proven_weight: self.params.proven_weight,
+                sigcom: cert.sig_commit.clone(),
+                partcom: self.partcom.clone(),
+                msg_hash: hash_obj(&self.params.msg),
+            };
+            let coin = choice.hash_coin();
+            let revealed = cert.reveals.values().any(|reveal| {
+                let slot = &reveal.sig_slot;
+                slot.l <= coin && coin - slot.l < reveal.part.weight
+            });
+            if !revealed {
+                return Err(format!("no reveal for coin {} at position {}", coin, j).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use serde::Serialize;
+
+    use super::super::{Builder, Participant, ParticipantsArray};
+    use super::*;
+    use crate::merklearray::Tree;
+    use crate::onetimesig::OneTimeSignatureSecrets;
+    use crate::util::HashType;
+
+    #[derive(Debug, Clone, Serialize)]
+    struct TestMessage(String);
+
+    impl MsgpHashable for TestMessage {
+        fn hash_id(&self) -> protocol::HashId {
+            protocol::TEST_HASHABLE
+        }
+    }
+
+    #[test]
+    fn build_and_verify() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(29);
+        let (key_dilution, sig_round) = (10_000, 128);
+        let params = Params {
+            msg: TestMessage("hello world".to_string()),
+            proven_weight: 500_000,
+            sig_round,
+            sec_kq: 128,
+        };
+
+        let keys: Vec<OneTimeSignatureSecrets> = (0..20)
+            .map(|_| OneTimeSignatureSecrets::generate(0, 1, &mut rng))
+            .collect();
+        let participants: Vec<Participant> = keys
+            .iter()
+            .map(|key| Participant {
+                pk: key.verifier,
+                weight: 100_000,
+                key_dilution,
+            })
+            .collect();
+        let parttree =
+            Tree::build(&ParticipantsArray(&participants), HashType::Sha512_256).unwrap();
+        let partcom = parttree.root();
+
+        let mut builder = Builder::new(params.clone(), participants, parttree);
+        let id = ots_id_for_round(sig_round, key_dilution);
+        for (pos, key) in keys.iter().enumerate().take(15) {
+            let sig = key.sign(id, &params.msg).unwrap();
+            builder.add(pos as u64, sig.clone(), true).unwrap();
+            assert!(builder.add(pos as u64, sig, true).is_err());
+        }
+        let wrong = keys[16].sign(id, &params.msg).unwrap();
+        assert!(builder.add(15, wrong, true).is_err());
+        assert!(builder.ready());
+
+        let cert = builder.build().unwrap();
+        assert!(cert.reveals.len() <= 15);
+        let verifier = Verifier::new(params.clone(), partcom.clone());
+        verifier.verify(&cert).unwrap();
+
+        let mut bad = cert.clone();
+        let first = *bad.reveals.keys().next().unwrap();
+        bad.reveals.remove(&first);
+        assert!(verifier.verify(&bad).is_err());
+
+        // A reveal whose range wraps around u64 is rejected.
+        let mut wrapping = cert.clone();
+        wrapping.reveals.get_mut(&first).unwrap().sig_slot.l = u64::MAX;
+        let err = verifier.verify(&wrapping).unwrap_err();
+        assert!(err.to_string().contains("overflows the signed weight"));
+
+        let other = Params {
+            msg: TestMessage("goodbye".to_string()),
+            ..params
+        };
+        assert!(Verifier::new(other, partcom).verify(&cert).is_err());
+    }
+
+    #[test]
+    fn signed_weight_does_not_overflow() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(29);
+        let params = Params {
+            msg: TestMessage("hello world".to_string()),
+            proven_weight: 1,
+            sig_round: 128,
+            sec_kq: 128,
+        };
+        let key = OneTimeSignatureSecrets::generate(0, 1, &mut rng);
+        let participants: Vec<Participant> = [u64::MAX, 1]
+            .into_iter()
+            .map(|weight| Participant {
+                pk: key.verifier,
+                weight,
+                key_dilution: 10_000,
+            })
+            .collect();
+        let parttree =
+            Tree::build(&ParticipantsArray(&participants), HashType::Sha512_256).unwrap();
+        let mut builder = Builder::new(params.clone(), participants, parttree);
+        let sig = key
+            .sign(ots_id_for_round(128, 10_000), &params.msg)
+            .unwrap();
+        builder.add(0, sig.clone(), false).unwrap();
+        let err = builder.add(1, sig, false).unwrap_err();
+        assert!(err.to_string().contains("overflows the signed weight"));
+        assert_eq!(builder.signed_weight(), u64::MAX);
+        assert!(!builder.present(1));
+    }
+
+    #[test]
+    fn num_reveals_bounds() {
+        assert_eq!(num_reveals(2, 1, 8, MAX_REVEALS).unwrap(), 8);
+        assert!(num_reveals(1_000_001, 1_000_000, 128, MAX_REVEALS).is_err());
+    }
+}
@@ -1,12 +1,16 @@
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use serde_big_array::BigArray;
+use std::sync::Once;
+
+use crate::util::{hash_rep, MsgpHashable};
 
 pub type Ed25519PublicKey = [u8; 32];
 pub type Ed25519PrivateKey = [u8; 64];
 pub type Ed25519Signature = [u8; 64];
 pub type Ed25519Seed = [u8; 32];
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Signature(#[serde(with = "BigArray")] pub Ed25519Signature);
 
 impl Default for Signature {
@@ -20,18 +24,111 @@ pub const BLANK_SIGNATURE: Signature = Signature([0u8; 64]);
 const MASTER_DERIVATION_KEY_LEN: usize = 32;
 pub type MaterDerivationKey = [u8; MASTER_DERIVATION_KEY_LEN];
 
-type PrivateKey = Ed25519PrivateKey;
-type PublicKey = Ed25519PublicKey;
+pub type PrivateKey = Ed25519PrivateKey;
+pub type PublicKey = Ed25519PublicKey;
+
+/// SignatureVerifier is the public key used to verify a signature.
+pub type SignatureVerifier = PublicKey;
+
+static SODIUM_INIT: Once = Once::new();
 
+fn init_sodium() {
+    SODIUM_INIT.call_once(|| {
+        // sodium_init returns 1 if it was already initialized, -1 on failure.
+        if unsafe { libsodium_sys::sodium_init() } < 0 {
+            panic!("failed to initialize libsodium");
+        }
+    });
+}
+
+pub fn ed25519_generate_key_seed(seed: &Ed25519Seed) -> (PublicKey, PrivateKey) {
+    init_sodium();
+    let mut public = [0u8; 32];
+    let mut secret = [0u8; 64];
+    unsafe {
+        libsodium_sys::crypto_sign_ed25519_seed_keypair(
+            public.as_mut_ptr(),
+            secret.as_mut_ptr(),
+            seed.as_ptr(),
+        );
+    }
+    (public, secret)
+}
+
+pub fn ed25519_generate_key(rng: &mut dyn RngCore) -> (PublicKey, PrivateKey) {
+    let mut seed = [0u8; 32];
+    rng.fill_bytes(&mut seed);
+    ed25519_generate_key_seed(&seed)
+}
+
+/// Verifies `sig` over `data`.
+///
+/// libsodium only ships the strict verifier, so the batch verification
+/// compatible variant is currently the same check.
 pub fn ed25519_verify(
-    _public: PublicKey,
-    _data: &[u8],
-    _sig: Ed25519Signature,
+    public: PublicKey,
+    data: &[u8],
+    sig: Ed25519Signature,
     _use_batch_verification_compatible_version: bool,
 ) -> bool {
-    todo!()
+    init_sodium();
+    let result = unsafe {
+        libsodium_sys::crypto_sign_ed25519_verify_detached(
+            sig.as_ptr(),
+            data.as_ptr(),
+            data.len() as u64,
+            public.as_ptr(),
+        )
+    };
+    result == 0
+}
+
+pub fn ed25519_sign(secret: PrivateKey, data: &[u8]) -> Ed25519Signature {
+    init_sodium();
+    let mut sig = [0u8; 64];
+    unsafe {
+        libsodium_sys::crypto_sign_ed25519_detached(
+            sig.as_mut_ptr(),
+            std::ptr::null_mut(),
+            data.as_ptr(),
+            data.len() as u64,
+            secret.as_ptr(),
+        );
+    }
+    sig
+}
+
+/// SignatureSecrets are used by an entity to produce unforgeable signatures over
+/// a message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignatureSecrets {
+    pub signature_verifier: SignatureVerifier,
+    #[serde(with = "BigArray")]
+    pub sk: PrivateKey,
+}
+
+impl SignatureSecrets {
+    pub fn generate(seed: &Ed25519Seed) -> Self {
+        let (signature_verifier, sk) = ed25519_generate_key_seed(seed);
+        Self {
+            signature_verifier,
+            sk,
+        }
+    }
+
+    pub fn sign(&self, message: &impl MsgpHashable) -> Signature {
+        self.sign_bytes(&hash_rep(message))
+    }
+
+    pub fn sign_bytes(&self, data: &[u8]) -> Signature {
+        Signature(ed25519_sign(self.sk, data))
+    }
+}
+
+pub fn verify(verifier: &SignatureVerifier, message: &impl MsgpHashable, sig: &Signature) -> bool {
+    ed25519_verify(*verifier, &hash_rep(message), sig.0, true)
 }
 
-pub fn ed25519_sign(_public: PrivateKey, _data: &[u8]) -> bool {
-    todo!()
+pub fn verify_bytes(verifier: &SignatureVerifier, data: &[u8], sig: &Signature) -> bool {
+    ed25519_verify(*verifier, data, sig.0, true)
 }
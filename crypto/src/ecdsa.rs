@@ -0,0 +1,127 @@
+//! ECDSA over the secp256k1 and secp256r1 curves, with public keys given as
+//! the big-endian affine coordinates of a point and signatures given as the
+//! big-endian `r` and `s` scalars, as used by the AVM.
+
+use k256::ecdsa::{RecoveryId, Signature as K256Signature, VerifyingKey as K256VerifyingKey};
+use k256::EncodedPoint as K256EncodedPoint;
+use p256::ecdsa::{Signature as P256Signature, VerifyingKey as P256VerifyingKey};
+use p256::EncodedPoint as P256EncodedPoint;
+
+pub type EcdsaResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// EcdsaCurve identifies the curve of an ECDSA operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EcdsaCurve {
+    Secp256k1,
+    Secp256r1,
+}
+
+/// A public key as its 32 byte `x` and `y` coordinates.
+pub type EcdsaPublicKey = ([u8; 32], [u8; 32]);
+
+fn signature_bytes(r: &[u8], s: &[u8]) -> EcdsaResult<[u8; 64]> {
+    if r.len() > 32 || s.len() > 32 {
+        return Err("signature scalars must be at most 32 bytes".into());
+    }
+    let mut sig = [0u8; 64];
+    sig[32 - r.len()..32].copy_from_slice(r);
+    sig[64 - s.len()..].copy_from_slice(s);
+    Ok(sig)
+}
+
+fn coordinate(c: &[u8]) -> EcdsaResult<[u8; 32]> {
+    if c.len() > 32 {
+        return Err("public key coordinates must be at most 32 bytes".into());
+    }
+    let mut out = [0u8; 32];
+    out[32 - c.len()..].copy_from_slice(c);
+    Ok(out)
+}
+
+/// Verifies the signature `(r, s)` of the 32 byte `digest` by the public key
+/// `(x, y)`. Malformed keys and signatures do not verify.
+pub fn verify(curve: EcdsaCurve, digest: &[u8], r: &[u8], s: &[u8], x: &[u8], y: &[u8]) -> bool {
+    use k256::ecdsa::signature::hazmat::PrehashVerifier;
+
+    let (sig, x, y) = match (signature_bytes(r, s), coordinate(x), coordinate(y)) {
+        (Ok(sig), Ok(x), Ok(y)) => (sig, x, y),
+        _ => return false,
+    };
+    match curve {
+        EcdsaCurve::Secp256k1 => {
+            let point = K256EncodedPoint::from_affine_coordinates(&x.into(), &y.into(), false);
+            let (Ok(key), Ok(sig)) = (
+                K256VerifyingKey::from_encoded_point(&point),
+                K256Signature::from_slice(&sig),
+            ) else {
+                return false;
+            };
+            key.verify_prehash(digest, &sig).is_ok()
+        }
+        EcdsaCurve::Secp256r1 => {
+            let point = P256EncodedPoint::from_affine_coordinates(&x.into(), &y.into(), false);
+            let (Ok(key), Ok(sig)) = (
+                P256VerifyingKey::from_encoded_point(&point),
+                P256Signature::from_slice(&sig),
+            ) else {
+                return false;
+            };
+            key.verify_prehash(digest, &sig).is_ok()
+        }
+    }
+}
+
+/// Decompresses a 33 byte compressed public key into its coordinates.
+pub fn decompress(curve: EcdsaCurve, compressed: &[u8]) -> EcdsaResult<EcdsaPublicKey> {
+    if compressed.len() != 33 {
+        return Err(format!("compressed public key has {} bytes", compressed.len()).into());
+    }
+    let uncompressed = match curve {
+        EcdsaCurve::Secp256k1 => K256VerifyingKey::from_sec1_bytes(compressed)
+            .map_err(|e| format!("invalid compressed public key: {}", e))?
+            .to_encoded_point(false)
+            .as_bytes()
+            .to_vec(),
+        EcdsaCurve::Secp256r1 => P256VerifyingKey::from_sec1_bytes(compressed)
+            .map_err(|e| format!("invalid compressed public key: {}", e))?
+            .to_encoded_point(false)
+            .as_bytes()
+            .to_vec(),
+    };
+    split_uncompressed(&uncompressed)
+}
+
+/// Recovers the public key that produced the signature `(r, s)` with
+/// recovery id `v` of the 32 byte `digest`. Only secp256k1 supports
+/// recovery.
+pub fn recover(
+    curve: EcdsaCurve,
+    digest: &[u8],
+    v: u64,
+    r: &[u8],
+    s: &[u8],
+) -> EcdsaResult<EcdsaPublicKey> {
+    if curve != EcdsaCurve::Secp256k1 {
+        return Err(format!("public key recovery is not supported on {:?}", curve).into());
+    }
+    let sig = K256Signature::from_slice(&signature_bytes(r, s)?)
+        .map_err(|e| format!("invalid signature: {}", e))?;
+    let id = u8::try_from(v)
+        .ok()
+        .and_then(RecoveryId::from_byte)
+        .ok_or_else(|| format!("invalid recovery id {}", v))?;
+    let key = K256VerifyingKey::recover_from_prehash(digest, &sig, id)
+        .map_err(|e| format!("cannot recover public key: {}", e))?;
+    split_uncompressed(key.to_encoded_point(false).as_bytes())
+}
+
+fn split_uncompressed(point: &[u8]) -> EcdsaResult<EcdsaPublicKey> {
+    if point.len() != 65 || point[0] != 4 {
+        return Err("unexpected uncompressed public key encoding".into());
+    }
+    let mut x = [0u8; 32];
+    let mut y = [0u8; 32];
+    x.copy_from_slice(&point[1..33]);
+    y.copy_from_slice(&point[33..]);
+    Ok((x, y))
+}
@@ -1,6 +1,9 @@
 pub mod curve25519;
+pub mod ecdsa;
 pub mod onetimesig;
 pub mod multisig;
 pub mod logicsig;
+pub mod compactcert;
+pub mod merklearray;
 pub mod util;
 pub mod vrf;
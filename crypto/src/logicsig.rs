@@ -1,4 +1,118 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
-pub struct LogicSig {}
+use crate::curve25519::{self, PublicKey, Signature, SignatureSecrets, BLANK_SIGNATURE};
+use crate::multisig::{MultiSig, MultisigResult};
+use crate::util::{hash, HashDigest};
+
+pub type LogicSigResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Returns the hash of `program` under the PROGRAM prefix. It is the
+/// address of the contract account controlled by the program, and the
+/// message signed to delegate an account to it.
+pub fn program_hash(program: &[u8]) -> HashDigest {
+    hash(&program_bytes(program))
+}
+
+fn program_bytes(program: &[u8]) -> Vec<u8> {
+    let mut buf = protocol::PROGRAM.as_bytes().to_vec();
+    buf.extend_from_slice(program);
+    buf
+}
+
+/// LogicSig authorizes a transaction with a program. Without a signature
+/// it authorizes transactions of the contract account whose address is the
+/// hash of the program; with a sig or msig, the signing account delegates
+/// the authorization of its transactions to the program.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogicSig {
+    /// The compiled program.
+    #[serde(rename = "l")]
+    pub logic: Vec<u8>,
+    #[serde(rename = "sig")]
+    pub sig: Signature,
+    #[serde(rename = "msig")]
+    pub msig: MultiSig,
+    /// Arguments to the program, available through `arg`. They are not
+    /// covered by the signature.
+    #[serde(rename = "arg")]
+    pub args: Vec<Vec<u8>>,
+}
+
+impl LogicSig {
+    pub fn new(logic: Vec<u8>, args: Vec<Vec<u8>>) -> Self {
+        Self {
+            logic,
+            args,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the logic signature is not in use.
+    pub fn blank(&self) -> bool {
+        self.logic.is_empty()
+    }
+
+    /// The size counted against `logic_sig_max_size`: the program plus its
+    /// arguments.
+    pub fn len(&self) -> usize {
+        self.logic.len() + self.args.iter().map(Vec::len).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The address of the contract account controlled by the program.
+    pub fn address(&self) -> HashDigest {
+        program_hash(&self.logic)
+    }
+
+    /// Delegates the account of `secrets` to the program.
+    pub fn sign(&mut self, secrets: &SignatureSecrets) {
+        self.sig = secrets.sign_bytes(&program_bytes(&self.logic));
+    }
+
+    /// Adds the signature of `secrets` to a multisig delegation of the
+    /// account of `msig` to the program.
+    pub fn append_multisig(
+        &mut self,
+        secrets: &SignatureSecrets,
+        msig: MultiSig,
+    ) -> MultisigResult<()> {
+        if self.msig.blank() {
+            self.msig = msig;
+        }
+        self.msig.sign_bytes(secrets, &program_bytes(&self.logic))
+    }
+
+    /// Verifies that the logic signature may authorize transactions of
+    /// `authorizer`: either it is the contract account of the program, or
+    /// `authorizer` delegated to the program with a sig or msig.
+    pub fn verify_delegation(&self, authorizer: &HashDigest) -> LogicSigResult<()> {
+        let has_sig = self.sig != BLANK_SIGNATURE;
+        let has_msig = !self.msig.blank();
+        match (has_sig, has_msig) {
+            (true, true) => {
+                Err("LogicSig should only have one of Sig or Msig but has more than one".into())
+            }
+            (false, false) => {
+                if self.address() != *authorizer {
+                    return Err("LogicNot signed and not a Logic-only account".into());
+                }
+                Ok(())
+            }
+            (true, false) => {
+                let pk: &PublicKey = &authorizer.0;
+                if !curve25519::verify_bytes(pk, &program_bytes(&self.logic), &self.sig) {
+                    return Err("logic signature validation failed".into());
+                }
+                Ok(())
+            }
+            (false, true) => self
+                .msig
+                .verify_bytes(authorizer, &program_bytes(&self.logic))
+                .map_err(|e| format!("logic multisig validation failed: {}", e).into()),
+        }
+    }
+}
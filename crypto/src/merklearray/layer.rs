@@ -0,0 +1,89 @@
+use super::{GenericDigest, MerkleResult};
+use crate::util::HashType;
+
+/// Hashes an inner node of the tree from its two children.
+pub(super) fn hash_pair(hash_type: HashType, l: &[u8], r: &[u8]) -> GenericDigest {
+    let prefix = protocol::MERKLE_ARRAY_NODE.as_bytes();
+    let mut buf = Vec::with_capacity(prefix.len() + l.len() + r.len());
+    buf.extend_from_slice(prefix);
+    buf.extend_from_slice(l);
+    buf.extend_from_slice(r);
+    hash_type.hash(&buf).0.to_vec()
+}
+
+/// Computes the parent layer of a complete layer.
+pub(super) fn up(layer: &[GenericDigest], hash_type: HashType) -> Vec<GenericDigest> {
+    layer
+        .chunks(2)
+        .map(|pair| hash_pair(hash_type, &pair[0], pair.get(1).map_or(&[][..], |r| r)))
+        .collect()
+}
+
+/// A LayerItem is one known node of a partial layer.
+#[derive(Debug, Clone)]
+pub(super) struct LayerItem {
+    pub pos: u64,
+    pub hash: GenericDigest,
+}
+
+/// Source of the sibling hashes that are not part of a partial layer. When
+/// proving, siblings are read from the tree and recorded as hints; when
+/// verifying, they are consumed from the hints of the proof.
+pub(super) enum Siblings<'a> {
+    Tree {
+        levels: &'a [Vec<GenericDigest>],
+        hints: Vec<GenericDigest>,
+    },
+    Proof {
+        hints: std::slice::Iter<'a, GenericDigest>,
+    },
+}
+
+impl Siblings<'_> {
+    fn get(&mut self, level: usize, pos: u64) -> MerkleResult<GenericDigest> {
+        match self {
+            Siblings::Tree { levels, hints } => {
+                let hash = levels[level].get(pos as usize).cloned().unwrap_or_default();
+                hints.push(hash.clone());
+                Ok(hash)
+            }
+            Siblings::Proof { hints } => hints
+                .next()
+                .cloned()
+                .ok_or_else(|| "merkle proof is missing sibling hints".into()),
+        }
+    }
+}
+
+/// Computes the parent partial layer of the sorted partial layer `items`.
+pub(super) fn up_partial(
+    items: &[LayerItem],
+    siblings: &mut Siblings,
+    level: usize,
+    hash_type: HashType,
+) -> MerkleResult<Vec<LayerItem>> {
+    let mut res = Vec::with_capacity(items.len());
+    let mut i = 0;
+    while i < items.len() {
+        let item = &items[i];
+        let sibling_pos = item.pos ^ 1;
+        let sibling_hash = if i + 1 < items.len() && items[i + 1].pos == sibling_pos {
+            // our sibling is part of the partial layer as well
+            i += 1;
+            items[i].hash.clone()
+        } else {
+            siblings.get(level, sibling_pos)?
+        };
+        let hash = if item.pos & 1 == 0 {
+            hash_pair(hash_type, &item.hash, &sibling_hash)
+        } else {
+            hash_pair(hash_type, &sibling_hash, &item.hash)
+        };
+        res.push(LayerItem {
+            pos: item.pos / 2,
+            hash,
+        });
+        i += 1;
+    }
+    Ok(res)
+}
@@ -0,0 +1,142 @@
+use super::layer::{self, LayerItem, Siblings};
+use super::vectorcommitment::{merkle_tree_to_vector_commitment_index, tree_depth};
+use super::{Array, GenericDigest, MerkleResult, Proof};
+use crate::util::HashType;
+
+/// Tree is a Merkle tree, represented by layers of nodes (hashes) in the tree
+/// at each height. `levels[0]` holds the leaf hashes.
+#[derive(Debug, Clone, Default)]
+pub struct Tree {
+    pub levels: Vec<Vec<GenericDigest>>,
+    pub num_of_elements: u64,
+    pub hash_type: HashType,
+    pub is_vector_commitment: bool,
+}
+
+fn leaves(array: &impl Array, hash_type: HashType) -> MerkleResult<Vec<GenericDigest>> {
+    (0..array.length())
+        .map(|pos| Ok(hash_type.hash(&array.marshal(pos)?).0.to_vec()))
+        .collect()
+}
+
+impl Tree {
+    /// Constructs a Merkle tree over the elements of `array`.
+    pub fn build(array: &impl Array, hash_type: HashType) -> MerkleResult<Self> {
+        let leaves = leaves(array, hash_type)?;
+        Ok(Self::from_leaves(leaves, array.length(), hash_type, false))
+    }
+
+    /// Constructs a vector commitment tree over the elements of `array`.
+    ///
+    /// The leaves are padded up to a power of two with the hash of
+    /// `MERKLE_VECTOR_COMMITMENT_BOTTOM_LEAF` and placed in bit-reversed
+    /// order, so that every proof has the same length and binds the index of
+    /// the element.
+    pub fn build_vector_commitment(array: &impl Array, hash_type: HashType) -> MerkleResult<Self> {
+        let n = array.length();
+        let depth = tree_depth(n);
+        let bottom = hash_type
+            .hash(protocol::MERKLE_VECTOR_COMMITMENT_BOTTOM_LEAF.as_bytes())
+            .0
+            .to_vec();
+        let size = if n == 0 { 0 } else { 1usize << depth };
+        let mut padded = vec![bottom; size];
+        for (i, leaf) in leaves(array, hash_type)?.into_iter().enumerate() {
+            padded[merkle_tree_to_vector_commitment_index(i as u64, depth)? as usize] = leaf;
+        }
+        Ok(Self::from_leaves(padded, n, hash_type, true))
+    }
+
+    fn from_leaves(
+        leaves: Vec<GenericDigest>,
+        num_of_elements: u64,
+        hash_type: HashType,
+        is_vector_commitment: bool,
+    ) -> Self {
+        let mut levels = vec![];
+        if !leaves.is_empty() {
+            levels.push(leaves);
+            while levels.last().unwrap().len() > 1 {
+                let next = layer::up(levels.last().unwrap(), hash_type);
+                levels.push(next);
+            }
+        }
+        Self {
+            levels,
+            num_of_elements,
+            hash_type,
+            is_vector_commitment,
+        }
+    }
+
+    /// Returns the root hash of the tree, or an empty digest for an empty
+    /// tree.
+    pub fn root(&self) -> GenericDigest {
+        self.levels
+            .last()
+            .map(|top| top[0].clone())
+            .unwrap_or_default()
+    }
+
+    /// Constructs a proof for the elements at positions `idxs`.
+    pub fn prove(&self, idxs: &[u64]) -> MerkleResult<Proof> {
+        let tree_depth = self.levels.len().saturating_sub(1) as u8;
+        if idxs.is_empty() {
+            return Ok(Proof {
+                path: vec![],
+                hash_type: self.hash_type,
+                tree_depth,
+            });
+        }
+        if self.num_of_elements == 0 {
+            return Err("proving in a zero-length commitment".into());
+        }
+        let mut positions = idxs
+            .iter()
+            .map(|&idx| {
+                if idx >= self.num_of_elements {
+                    return Err(format!("position {} is out of bound", idx).into());
+                }
+                if self.is_vector_commitment {
+                    merkle_tree_to_vector_commitment_index(idx, tree_depth)
+                } else {
+                    Ok(idx)
+                }
+            })
+            .collect::<MerkleResult<Vec<u64>>>()?;
+        positions.sort_unstable();
+        positions.dedup();
+
+        let mut partial: Vec<LayerItem> = positions
+            .into_iter()
+            .map(|pos| LayerItem {
+                pos,
+                hash: self.levels[0][pos as usize].clone(),
+            })
+            .collect();
+        let mut siblings = Siblings::Tree {
+            levels: &self.levels,
+            hints: vec![],
+        };
+        for level in 0..self.levels.len() - 1 {
+            partial = layer::up_partial(&partial, &mut siblings, level, self.hash_type)?;
+        }
+        if partial.len() != 1 || partial[0].hash != self.root() {
+            return Err("internal error: proof does not lead to the root".into());
+        }
+        let path = match siblings {
+            Siblings::Tree { hints, .. } => hints,
+            Siblings::Proof { .. } => unreachable!(),
+        };
+        Ok(Proof {
+            path,
+            hash_type: self.hash_type,
+            tree_depth,
+        })
+    }
+
+    /// Constructs a proof for a single element.
+    pub fn prove_single_leaf(&self, idx: u64) -> MerkleResult<Proof> {
+        self.prove(&[idx])
+    }
+}
@@ -0,0 +1,23 @@
+mod layer;
+mod merkle;
+mod proof;
+mod vectorcommitment;
+
+pub use merkle::*;
+pub use proof::*;
+
+/// A GenericDigest is the output of the hash function of a tree. Missing
+/// siblings at the end of an odd layer are represented by an empty digest.
+pub type GenericDigest = Vec<u8>;
+
+pub type MerkleResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// An Array represents a dense array of leaf elements that can be committed
+/// to with a Merkle tree.
+pub trait Array {
+    fn length(&self) -> u64;
+
+    /// Returns the hash representation (hash id followed by the encoded
+    /// element) of the element at `pos`.
+    fn marshal(&self, pos: u64) -> MerkleResult<Vec<u8>>;
+}
@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::layer::{self, LayerItem, Siblings};
+use super::vectorcommitment::merkle_tree_to_vector_commitment_index;
+use super::{GenericDigest, MerkleResult};
+use crate::util::HashType;
+
+/// Proof is used to convince a verifier about membership of leaves at
+/// positions `idxs` in a Merkle tree. `path` holds the sibling hashes that
+/// cannot be computed from the proven elements, ordered from the leaves up.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proof {
+    #[serde(rename = "pth")]
+    pub path: Vec<GenericDigest>,
+    #[serde(rename = "hsh")]
+    pub hash_type: HashType,
+    #[serde(rename = "td")]
+    pub tree_depth: u8,
+}
+
+/// Verifies that `elems` (the hash representation of each element, keyed by
+/// position) are committed to by `root`.
+pub fn verify(root: &[u8], elems: &BTreeMap<u64, Vec<u8>>, proof: &Proof) -> MerkleResult<()> {
+    let items = elems
+        .iter()
+        .map(|(&pos, elem)| LayerItem {
+            pos,
+            hash: proof.hash_type.hash(elem).0.to_vec(),
+        })
+        .collect();
+    verify_path(root, items, proof)
+}
+
+/// Verifies that `elems` are committed to by the vector commitment `root`.
+pub fn verify_vector_commitment(
+    root: &[u8],
+    elems: &BTreeMap<u64, Vec<u8>>,
+    proof: &Proof,
+) -> MerkleResult<()> {
+    let mut items = elems
+        .iter()
+        .map(|(&pos, elem)| {
+            Ok(LayerItem {
+                pos: merkle_tree_to_vector_commitment_index(pos, proof.tree_depth)?,
+                hash: proof.hash_type.hash(elem).0.to_vec(),
+            })
+        })
+        .collect::<MerkleResult<Vec<LayerItem>>>()?;
+    items.sort_by_key(|item| item.pos);
+    verify_path(root, items, proof)
+}
+
+fn verify_path(root: &[u8], mut items: Vec<LayerItem>, proof: &Proof) -> MerkleResult<()> {
+    if items.is_empty() {
+        if !proof.path.is_empty() {
+            return Err("non-empty proof for an empty set of elements".into());
+        }
+        return Ok(());
+    }
+    if proof.tree_depth >= 64 || items.len() as u64 > 1u64 << proof.tree_depth {
+        return Err(format!(
+            "proof of depth {} cannot prove {} elements",
+            proof.tree_depth,
+            items.len()
+        )
+        .into());
+    }
+    let mut siblings = Siblings::Proof {
+        hints: proof.path.iter(),
+    };
+    for level in 0..proof.tree_depth as usize {
+        items = layer::up_partial(&items, &mut siblings, level, proof.hash_type)?;
+    }
+    if let Siblings::Proof { hints } = siblings {
+        if hints.len() > 0 {
+            return Err(format!("merkle proof has {} unused hints", hints.len()).into());
+        }
+    }
+    if items.len() != 1 || items[0].pos != 0 || items[0].hash != root {
+        return Err("root mismatch".into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Array, Tree};
+    use super::*;
+
+    struct TestArray(Vec<u64>);
+
+    impl Array for TestArray {
+        fn length(&self) -> u64 {
+            self.0.len() as u64
+        }
+
+        fn marshal(&self, pos: u64) -> MerkleResult<Vec<u8>> {
+            let mut rep = protocol::TEST_HASHABLE.as_bytes().to_vec();
+            rep.extend(self.0[pos as usize].to_be_bytes());
+            Ok(rep)
+        }
+    }
+
+    fn elems(array: &TestArray, idxs: &[u64]) -> BTreeMap<u64, Vec<u8>> {
+        idxs.iter()
+            .map(|&i| (i, array.marshal(i).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn prove_and_verify() {
+        for n in [1u64, 2, 3, 7, 8, 13] {
+            let array = TestArray((0..n).map(|i| i * 31).collect());
+            for hash_type in [HashType::Sha512_256, HashType::Sha256] {
+                let tree = Tree::build(&array, hash_type).unwrap();
+                let vc = Tree::build_vector_commitment(&array, hash_type).unwrap();
+                let idxs: Vec<u64> = (0..n).step_by(2).collect();
+
+                let proof = tree.prove(&idxs).unwrap();
+                verify(&tree.root(), &elems(&array, &idxs), &proof).unwrap();
+                let proof = vc.prove(&idxs).unwrap();
+                verify_vector_commitment(&vc.root(), &elems(&array, &idxs), &proof).unwrap();
+
+                let last = n - 1;
+                let proof = tree.prove_single_leaf(last).unwrap();
+                verify(&tree.root(), &elems(&array, &[last]), &proof).unwrap();
+                if n > 1 {
+                    let wrong = BTreeMap::from([(0, array.marshal(last).unwrap())]);
+                    assert!(verify(&tree.root(), &wrong, &proof).is_err());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_tree() {
+        let tree = Tree::build(&TestArray(vec![]), HashType::Sha512_256).unwrap();
+        assert!(tree.root().is_empty());
+        assert!(tree.prove(&[0]).is_err());
+        verify(&tree.root(), &BTreeMap::new(), &tree.prove(&[]).unwrap()).unwrap();
+    }
+}
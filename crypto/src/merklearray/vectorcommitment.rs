@@ -0,0 +1,35 @@
+use super::MerkleResult;
+
+/// Maps an element index to its leaf position in a vector commitment tree
+/// of depth `path_len`. Leaves are laid out in bit-reversed order so that
+/// the authentication path of a leaf, read from the leaf up, spells out its
+/// index from the most significant bit.
+pub(super) fn merkle_tree_to_vector_commitment_index(
+    msb_index: u64,
+    path_len: u8,
+) -> MerkleResult<u64> {
+    if path_len == 0 {
+        return if msb_index == 0 {
+            Ok(0)
+        } else {
+            Err(format!("vector commitment position {} is out of bound", msb_index).into())
+        };
+    }
+    if path_len < 64 && msb_index >= 1u64 << path_len {
+        return Err(format!(
+            "vector commitment position {} is out of bound for depth {}",
+            msb_index, path_len
+        )
+        .into());
+    }
+    Ok(msb_index.reverse_bits() >> (64 - path_len as u32))
+}
+
+/// The depth of the smallest complete tree with at least `n` leaves.
+pub(super) fn tree_depth(n: u64) -> u8 {
+    if n <= 1 {
+        0
+    } else {
+        (64 - (n - 1).leading_zeros()) as u8
+    }
+}
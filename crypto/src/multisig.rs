@@ -1,5 +1,142 @@
-use serde::{Serialize,Deserialize};
-#[derive(Default, Debug,Clone, Serialize, Deserialize)]
-pub struct MultiSig{
+use serde::{Deserialize, Serialize};
 
+use crate::curve25519::{self, PublicKey, Signature, BLANK_SIGNATURE};
+use crate::util::{hash, hash_rep, HashDigest, MsgpHashable};
+
+pub type MultisigResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// The only multisig version defined so far.
+pub const MULTISIG_VERSION: u8 = 1;
+
+/// The maximum number of keys of a multisig address.
+pub const MAX_MULTISIG: usize = 255;
+
+/// MultisigSubsig is a single public key of a multisig address and its
+/// (possibly blank) signature.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MultisigSubsig {
+    #[serde(rename = "pk")]
+    pub key: PublicKey,
+    #[serde(rename = "s")]
+    pub sig: Signature,
+}
+
+/// MultiSig is a multisignature: the signatures of at least `threshold`
+/// of the keys a multisig address is derived from.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MultiSig {
+    #[serde(rename = "v")]
+    pub version: u8,
+    #[serde(rename = "thr")]
+    pub threshold: u8,
+    #[serde(rename = "subsig")]
+    pub subsigs: Vec<MultisigSubsig>,
+}
+
+/// Returns the address of the multisig account of `pks` requiring
+/// `threshold` signatures.
+pub fn multisig_addr(version: u8, threshold: u8, pks: &[PublicKey]) -> MultisigResult<HashDigest> {
+    if version != MULTISIG_VERSION {
+        return Err(format!("unknown multisig version {}", version).into());
+    }
+    if threshold == 0 || pks.is_empty() || threshold as usize > pks.len() {
+        return Err(format!("invalid threshold {} for {} keys", threshold, pks.len()).into());
+    }
+    if pks.len() > MAX_MULTISIG {
+        return Err(format!("too many keys: {} > {}", pks.len(), MAX_MULTISIG).into());
+    }
+    let mut buf = protocol::MULTISIG_ADDR.as_bytes().to_vec();
+    buf.push(version);
+    buf.push(threshold);
+    for pk in pks {
+        buf.extend_from_slice(pk);
+    }
+    Ok(hash(&buf))
+}
+
+impl MultiSig {
+    /// Returns an unsigned multisig over `pks`, to be filled in with
+    /// `sign`.
+    pub fn new(version: u8, threshold: u8, pks: &[PublicKey]) -> Self {
+        Self {
+            version,
+            threshold,
+            subsigs: pks
+                .iter()
+                .map(|&key| MultisigSubsig {
+                    key,
+                    sig: BLANK_SIGNATURE,
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether the multisig is entirely empty, i.e. not in use.
+    pub fn blank(&self) -> bool {
+        self.version == 0 && self.threshold == 0 && self.subsigs.is_empty()
+    }
+
+    /// Returns the address this multisig is signed on behalf of.
+    pub fn address(&self) -> MultisigResult<HashDigest> {
+        let pks: Vec<_> = self.subsigs.iter().map(|s| s.key).collect();
+        multisig_addr(self.version, self.threshold, &pks)
+    }
+
+    /// Signs `data` with `secrets`, whose key must be one of the keys of
+    /// the multisig.
+    pub fn sign_bytes(
+        &mut self,
+        secrets: &curve25519::SignatureSecrets,
+        data: &[u8],
+    ) -> MultisigResult<()> {
+        let subsig = self
+            .subsigs
+            .iter_mut()
+            .find(|s| s.key == secrets.signature_verifier)
+            .ok_or("key is not part of the multisig")?;
+        subsig.sig = secrets.sign_bytes(data);
+        Ok(())
+    }
+
+    pub fn sign(
+        &mut self,
+        secrets: &curve25519::SignatureSecrets,
+        message: &impl MsgpHashable,
+    ) -> MultisigResult<()> {
+        self.sign_bytes(secrets, &hash_rep(message))
+    }
+
+    /// Verifies that the multisig is a valid signature of `data` by the
+    /// multisig account `addr`.
+    pub fn verify_bytes(&self, addr: &HashDigest, data: &[u8]) -> MultisigResult<()> {
+        if self.address()? != *addr {
+            return Err("multisig does not match the address".into());
+        }
+        let signed: Vec<_> = self
+            .subsigs
+            .iter()
+            .filter(|s| s.sig != BLANK_SIGNATURE)
+            .collect();
+        if signed.len() < self.threshold as usize {
+            return Err(format!(
+                "multisig has {} signatures, {} required",
+                signed.len(),
+                self.threshold
+            )
+            .into());
+        }
+        if let Some(bad) = signed
+            .iter()
+            .find(|s| !curve25519::verify_bytes(&s.key, data, &s.sig))
+        {
+            return Err(format!("invalid multisig signature by key {:x?}", bad.key).into());
+        }
+        Ok(())
+    }
+
+    pub fn verify(&self, addr: &HashDigest, message: &impl MsgpHashable) -> MultisigResult<()> {
+        self.verify_bytes(addr, &hash_rep(message))
+    }
 }
@@ -1,2 +1,348 @@
-use super::curve25519;
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+
+use super::curve25519::{self, PrivateKey, PublicKey, Signature};
+use crate::util::{hash_rep, MsgpHashable};
+
 pub type OneTimeSignatureVerifier = curve25519::Ed25519PublicKey;
+
+/// A OneTimeSignatureIdentifier is an identifier under which a
+/// OneTimeSignature is produced on a given message. Each identifier may be
+/// used to sign a single message.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OneTimeSignatureIdentifier {
+    /// Batch represents the most-significant part of the identifier.
+    pub batch: u64,
+    /// Offset represents the least-significant part of the identifier.
+    /// When moving to a new batch, the offset is reset to 0.
+    pub offset: u64,
+}
+
+/// A OneTimeSignature is a cryptographic signature that is produced a limited
+/// number of times and provides forward integrity.
+///
+/// `pk2` is the batch subkey signed by the root key, `pk` is the offset
+/// subkey signed by `pk2`, and `sig` is the signature of `pk` on the message.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OneTimeSignature {
+    #[serde(rename = "s")]
+    pub sig: Signature,
+    #[serde(rename = "p")]
+    pub pk: PublicKey,
+    #[serde(rename = "p1s")]
+    pub pk1_sig: Signature,
+    #[serde(rename = "p2")]
+    pub pk2: PublicKey,
+    #[serde(rename = "p2s")]
+    pub pk2_sig: Signature,
+}
+
+/// OneTimeSignatureSubkeyOffsetId identifies an ephemeralSubkey of a
+/// specific offset within a batch, for the purposes of signing it with the
+/// batch subkey.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OneTimeSignatureSubkeyOffsetId {
+    #[serde(rename = "pk")]
+    pub sub_key_pk: PublicKey,
+    #[serde(rename = "batch")]
+    pub batch: u64,
+    #[serde(rename = "off")]
+    pub offset: u64,
+}
+
+impl MsgpHashable for OneTimeSignatureSubkeyOffsetId {
+    fn hash_id(&self) -> protocol::HashId {
+        protocol::ONE_TIME_SIG_KEY2
+    }
+}
+
+/// OneTimeSignatureSubkeyBatchId identifies an ephemeralSubkey of a batch
+/// for the purposes of signing it with the top-level master key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OneTimeSignatureSubkeyBatchId {
+    #[serde(rename = "pk")]
+    pub sub_key_pk: PublicKey,
+    #[serde(rename = "batch")]
+    pub batch: u64,
+}
+
+impl MsgpHashable for OneTimeSignatureSubkeyBatchId {
+    fn hash_id(&self) -> protocol::HashId {
+        protocol::ONE_TIME_SIG_KEY1
+    }
+}
+
+/// An EphemeralSubkey is a subkey together with the signature of its
+/// parent key over it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EphemeralSubkey {
+    pub pk: PublicKey,
+    #[serde(with = "BigArray")]
+    pub sk: PrivateKey,
+    #[serde(rename = "sig")]
+    pub pk_sig: Signature,
+}
+
+/// OneTimeSignatureSecrets are used to produced unforgeable signatures over a
+/// message. The root key signs one subkey per batch, so that the keys of past
+/// batches can be deleted while the root verifier stays valid.
+///
+/// The batch that is currently in use may be expanded into per-offset keys
+/// (`offsets`), so that single offsets can be deleted as they are used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OneTimeSignatureSecrets {
+    #[serde(rename = "vid")]
+    pub verifier: OneTimeSignatureVerifier,
+    #[serde(rename = "fb")]
+    pub first_batch: u64,
+    #[serde(rename = "b")]
+    pub batches: Vec<EphemeralSubkey>,
+    #[serde(rename = "fo", default)]
+    pub first_offset: u64,
+    #[serde(rename = "o", default)]
+    pub offsets: Vec<EphemeralSubkey>,
+    #[serde(rename = "opk2", default)]
+    pub offsets_pk2: PublicKey,
+    #[serde(rename = "opk2s", default)]
+    pub offsets_pk2_sig: Signature,
+}
+
+impl OneTimeSignatureSecrets {
+    /// Creates a fresh root key and signs `num_batches` batch subkeys with it,
+    /// starting at `start_batch`. The root secret key is discarded once the
+    /// batch subkeys are signed.
+    pub fn generate(start_batch: u64, num_batches: u64, rng: &mut dyn RngCore) -> Self {
+        let (verifier, root_sk) = curve25519::ed25519_generate_key(rng);
+        let batches = (0..num_batches)
+            .map(|i| {
+                let (pk, sk) = curve25519::ed25519_generate_key(rng);
+                let id = OneTimeSignatureSubkeyBatchId {
+                    sub_key_pk: pk,
+                    batch: start_batch + i,
+                };
+                EphemeralSubkey {
+                    pk,
+                    sk,
+                    pk_sig: Signature(curve25519::ed25519_sign(root_sk, &hash_rep(&id))),
+                }
+            })
+            .collect();
+        Self {
+            verifier,
+            first_batch: start_batch,
+            batches,
+            first_offset: 0,
+            offsets: vec![],
+            offsets_pk2: Default::default(),
+            offsets_pk2_sig: Default::default(),
+        }
+    }
+
+    /// Produces a OneTimeSignature of `message` under `id`. Returns `None`
+    /// if the secrets for `id` were never generated or already deleted.
+    pub fn sign(
+        &self,
+        id: OneTimeSignatureIdentifier,
+        message: &impl MsgpHashable,
+    ) -> Option<OneTimeSignature> {
+        // The batch right before `first_batch` may have been expanded into
+        // individual offsets.
+        if id.batch + 1 == self.first_batch
+            && id.offset >= self.first_offset
+            && id.offset - self.first_offset < self.offsets.len() as u64
+        {
+            let subkey = &self.offsets[(id.offset - self.first_offset) as usize];
+            return Some(OneTimeSignature {
+                sig: Signature(curve25519::ed25519_sign(subkey.sk, &hash_rep(message))),
+                pk: subkey.pk,
+                pk1_sig: subkey.pk_sig.clone(),
+                pk2: self.offsets_pk2,
+                pk2_sig: self.offsets_pk2_sig.clone(),
+            });
+        }
+
+        if id.batch >= self.first_batch && id.batch - self.first_batch < self.batches.len() as u64 {
+            let batch = &self.batches[(id.batch - self.first_batch) as usize];
+            let subkey = offset_subkey(batch, id);
+            return Some(OneTimeSignature {
+                sig: Signature(curve25519::ed25519_sign(subkey.sk, &hash_rep(message))),
+                pk: subkey.pk,
+                pk1_sig: subkey.pk_sig,
+                pk2: batch.pk,
+                pk2_sig: batch.pk_sig.clone(),
+            });
+        }
+        None
+    }
+
+    /// Deletes every secret that could sign under an identifier before
+    /// `current`, so that signatures for past rounds cannot be forged if the
+    /// secrets leak later on. `num_keys_per_batch` is the key dilution.
+    ///
+    /// The batch of `current` is expanded into per-offset keys so that its
+    /// batch key can be deleted as well.
+    pub fn delete_before_fine_grained(
+        &mut self,
+        current: OneTimeSignatureIdentifier,
+        num_keys_per_batch: u64,
+    ) {
+        // Still inside the expanded batch: drop the offsets that were used.
+        if current.batch + 1 == self.first_batch {
+            if current.offset > self.first_offset {
+                let jump = (current.offset - self.first_offset).min(self.offsets.len() as u64);
+                self.offsets.drain(..jump as usize);
+                self.first_offset += jump;
+            }
+            return;
+        }
+
+        // Nothing to delete yet.
+        if current.batch + 1 < self.first_batch {
+            return;
+        }
+
+        // We are past the expanded batch, if any.
+        self.offsets.clear();
+        self.offsets_pk2 = Default::default();
+        self.offsets_pk2_sig = Default::default();
+        self.first_offset = 0;
+
+        let skip = current.batch - self.first_batch;
+        if skip >= self.batches.len() as u64 {
+            self.first_batch += self.batches.len() as u64;
+            self.batches.clear();
+            return;
+        }
+
+        let batch = self.batches[skip as usize].clone();
+        self.offsets = (current.offset..num_keys_per_batch)
+            .map(|offset| {
+                offset_subkey(
+                    &batch,
+                    OneTimeSignatureIdentifier {
+                        batch: current.batch,
+                        offset,
+                    },
+                )
+            })
+            .collect();
+        self.first_offset = current.offset;
+        self.offsets_pk2 = batch.pk;
+        self.offsets_pk2_sig = batch.pk_sig;
+        self.batches.drain(..=skip as usize);
+        self.first_batch = current.batch + 1;
+    }
+}
+
+/// Generates a fresh offset subkey for `id` and signs it with the batch key.
+/// As in go-algorand the key is random rather than derived from the batch
+/// key, so that a leaked batch key does not give away the offset keys
+/// expanded from it, which may outlive it.
+fn offset_subkey(batch: &EphemeralSubkey, id: OneTimeSignatureIdentifier) -> EphemeralSubkey {
+    let (pk, sk) = curve25519::ed25519_generate_key(&mut OsRng);
+    let offset_id = OneTimeSignatureSubkeyOffsetId {
+        sub_key_pk: pk,
+        batch: id.batch,
+        offset: id.offset,
+    };
+    EphemeralSubkey {
+        pk,
+        sk,
+        pk_sig: Signature(curve25519::ed25519_sign(batch.sk, &hash_rep(&offset_id))),
+    }
+}
+
+/// Verifies that `sig` is a valid signature of `message` under `id`, rooted
+/// at `verifier`.
+pub fn verify(
+    verifier: &OneTimeSignatureVerifier,
+    id: OneTimeSignatureIdentifier,
+    message: &impl MsgpHashable,
+    sig: &OneTimeSignature,
+) -> bool {
+    let offset_id = OneTimeSignatureSubkeyOffsetId {
+        sub_key_pk: sig.pk,
+        batch: id.batch,
+        offset: id.offset,
+    };
+    let batch_id = OneTimeSignatureSubkeyBatchId {
+        sub_key_pk: sig.pk2,
+        batch: id.batch,
+    };
+    curve25519::ed25519_verify(*verifier, &hash_rep(&batch_id), sig.pk2_sig.0, true)
+        && curve25519::ed25519_verify(sig.pk2, &hash_rep(&offset_id), sig.pk1_sig.0, true)
+        && curve25519::ed25519_verify(sig.pk, &hash_rep(message), sig.sig.0, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct TestMessage(u64);
+
+    impl MsgpHashable for TestMessage {
+        fn hash_id(&self) -> protocol::HashId {
+            protocol::TEST_HASHABLE
+        }
+    }
+
+    fn id(batch: u64, offset: u64) -> OneTimeSignatureIdentifier {
+        OneTimeSignatureIdentifier { batch, offset }
+    }
+
+    #[test]
+    fn sign_verify_and_delete() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let mut secrets = OneTimeSignatureSecrets::generate(10, 3, &mut rng);
+        let verifier = secrets.verifier;
+        let msg = TestMessage(42);
+
+        let sig = secrets.sign(id(11, 4), &msg).unwrap();
+        assert!(verify(&verifier, id(11, 4), &msg, &sig));
+        assert!(!verify(&verifier, id(11, 5), &msg, &sig));
+        assert!(!verify(&verifier, id(11, 4), &TestMessage(43), &sig));
+        assert!(secrets.sign(id(13, 0), &msg).is_none());
+
+        secrets.delete_before_fine_grained(id(11, 4), 8);
+        assert_eq!(secrets.first_batch, 12);
+        assert!(secrets.sign(id(10, 0), &msg).is_none());
+        assert!(secrets.sign(id(11, 3), &msg).is_none());
+        // expanded offsets are fresh keys signed by the batch key
+        let expanded = secrets.sign(id(11, 4), &msg).unwrap();
+        assert!(verify(&verifier, id(11, 4), &msg, &expanded));
+        assert_eq!(expanded.pk2, sig.pk2);
+        assert_ne!(expanded.pk, sig.pk);
+
+        secrets.delete_before_fine_grained(id(11, 6), 8);
+        assert!(secrets.sign(id(11, 5), &msg).is_none());
+        let sig = secrets.sign(id(11, 7), &msg).unwrap();
+        assert!(verify(&verifier, id(11, 7), &msg, &sig));
+        let sig = secrets.sign(id(12, 0), &msg).unwrap();
+        assert!(verify(&verifier, id(12, 0), &msg, &sig));
+
+        secrets.delete_before_fine_grained(id(20, 0), 8);
+        assert!(secrets.batches.is_empty());
+        assert!(secrets.sign(id(12, 7), &msg).is_none());
+    }
+
+    #[test]
+    fn offset_keys_are_not_derived_from_the_batch_key() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let secrets = OneTimeSignatureSecrets::generate(0, 2, &mut rng);
+
+        // Whoever holds the batch key can not recompute the offset keys
+        // expanded from it.
+        let (mut a, mut b) = (secrets.clone(), secrets);
+        a.delete_before_fine_grained(id(0, 0), 4);
+        b.delete_before_fine_grained(id(0, 0), 4);
+        assert_eq!(a.offsets_pk2, b.offsets_pk2);
+        for (a, b) in a.offsets.iter().zip(&b.offsets) {
+            assert_ne!(a.pk, b.pk);
+            assert_ne!(a.sk, b.sk);
+        }
+    }
+}
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 
 pub const DIGEST_SIZE: usize = 32;
 
-#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug, Hash, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct HashDigest(pub [u8; DIGEST_SIZE]);
 
 impl HashDigest {
@@ -52,3 +52,30 @@ pub fn hash(data: &[u8]) -> HashDigest {
     let dg = sha2::Sha512_256::digest(data);
     HashDigest(dg.into())
 }
+
+/// Returns the Keccak-256 hash of `data`, as used by Ethereum.
+pub fn keccak256(data: &[u8]) -> HashDigest {
+    HashDigest(sha3::Keccak256::digest(data).into())
+}
+
+/// Returns the SHA3-256 hash of `data`, as defined in FIPS 202.
+pub fn sha3_256(data: &[u8]) -> HashDigest {
+    HashDigest(sha3::Sha3_256::digest(data).into())
+}
+
+/// HashType identifies the hash function used by a commitment.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum HashType {
+    #[default]
+    Sha512_256,
+    Sha256,
+}
+
+impl HashType {
+    pub fn hash(&self, data: &[u8]) -> HashDigest {
+        match self {
+            HashType::Sha512_256 => hash(data),
+            HashType::Sha256 => HashDigest(sha2::Sha256::digest(data).into()),
+        }
+    }
+}
@@ -1,7 +1,29 @@
-type VrfPubkey = [u8; 32];
-type VrfPrivkey = [u8; 64];
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+
+use crate::curve25519;
+
+pub type VrfPubkey = [u8; 32];
+pub type VrfPrivkey = [u8; 64];
 type VrfProof = [u8; 80];
 type VrfOutput = [u8; 64];
 
 pub type VRFVerifier = VrfPubkey;
 pub type VRFProof = VrfProof;
+
+/// VRFSecrets is a wrapper for a VRF keypair.
+///
+/// Keys are derived from a 32 byte seed exactly like libsodium's
+/// `crypto_vrf_keypair_from_seed`, which shares the ed25519 key layout.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VRFSecrets {
+    pub pk: VrfPubkey,
+    #[serde(with = "BigArray")]
+    pub sk: VrfPrivkey,
+}
+
+pub fn generate_vrf_secrets(rng: &mut dyn RngCore) -> VRFSecrets {
+    let (pk, sk) = curve25519::ed25519_generate_key(rng);
+    VRFSecrets { pk, sk }
+}
@@ -0,0 +1,140 @@
+//! The metrics exporter: serves the metrics of the default registry in the
+//! Prometheus text format, at `/metrics`, for Prometheus to scrape.
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+/// The path the metrics are served at.
+pub const METRICS_PATH: &str = "/metrics";
+
+/// How long a scrape may wait on a slow client, which would otherwise hold
+/// up the requests queued behind it.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// MetricsExporter serves the metrics from a thread of its own, for the
+/// life of the process.
+#[derive(Debug)]
+pub struct MetricsExporter {
+    local_addr: SocketAddr,
+}
+
+impl MetricsExporter {
+    /// Listens on `address`, such as `:9100` for every interface, as in
+    /// `node_exporter_listen_address`.
+    pub fn start(address: &str) -> io::Result<Self> {
+        Self::listen(address, IO_TIMEOUT)
+    }
+
+    fn listen(address: &str, timeout: Duration) -> io::Result<Self> {
+        let listener = match address.strip_prefix(':') {
+            Some(port) => TcpListener::bind(format!("0.0.0.0:{}", port))?,
+            None => TcpListener::bind(address)?,
+        };
+        let local_addr = listener.local_addr()?;
+        thread::Builder::new()
+            .name("metrics_exporter".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let served = stream.and_then(|stream| serve(stream, timeout));
+                    if let Err(e) = served {
+                        tracing::debug!("could not serve metrics: {}", e);
+                    }
+                }
+            })?;
+        Ok(Self { local_addr })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+/// Answers the request on `stream`, then closes it. A client that does
+/// not send its request or read the answer within `timeout` is dropped.
+fn serve(mut stream: TcpStream, timeout: Duration) -> io::Result<()> {
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    // Only the request line matters; the headers are read to be discarded.
+    let mut request = vec![];
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+        let read = stream.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let (method, path) = (request_line.next(), request_line.next());
+
+    let (status, body) = match (method, path) {
+        (Some("GET"), Some(METRICS_PATH)) => (
+            "200 OK",
+            util::metrics::default_registry().write_metrics(&[]),
+        ),
+        (Some("GET"), _) => ("404 Not Found", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use util::metrics::{Counter, MetricName};
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serves_the_default_registry() {
+        let counter = Arc::new(Counter::new(
+            MetricName {
+                name: "algod_test_exported_total",
+                description: "Exported by the test",
+            },
+            vec![],
+        ));
+        counter.add(5);
+        util::metrics::default_registry().register(counter);
+
+        let exporter = MetricsExporter::start("127.0.0.1:0").unwrap();
+        let response = get(exporter.local_addr(), METRICS_PATH);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("# TYPE algod_test_exported_total counter\n"));
+        assert!(response.contains("\nalgod_test_exported_total 5\n"));
+
+        for path in ["/", "/metrics/ledger", "/debug"] {
+            let response = get(exporter.local_addr(), path);
+            assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        }
+    }
+
+    #[test]
+    fn idle_clients_time_out() {
+        let exporter = MetricsExporter::listen("127.0.0.1:0", Duration::from_millis(50)).unwrap();
+        // A client that never sends its request is dropped, and the
+        // requests behind it are served.
+        let mut idle = TcpStream::connect(exporter.local_addr()).unwrap();
+        let response = get(exporter.local_addr(), METRICS_PATH);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let mut rest = vec![];
+        assert_eq!(idle.read_to_end(&mut rest).unwrap(), 0);
+    }
+}
@@ -1,10 +1,17 @@
 mod api;
+mod exporter;
+
 use config::Local;
 use std::path::{Path, PathBuf};
-use tokio::sync::oneshot;
+use tokio::{signal, sync::oneshot};
 
 use data::bookkeeping;
-use node::AlgorandFullNode;
+use node::{AlgorandFullNode, NodeError, NodeResult};
+use util::logging::{self, LogOptions};
+use util::rng::NodeRng;
+use util::telemetry::{self, TelemetryConfig, TelemetryEvent};
+
+pub use exporter::{MetricsExporter, METRICS_PATH};
 
 pub struct Server {
     pub root_path: PathBuf,
@@ -15,6 +22,8 @@ pub struct Server {
     pub node: node::AlgorandFullNode,
     pub stopping: oneshot::Receiver<()>,
     pub router_stop_sender: oneshot::Sender<()>,
+    /// Serves the metrics with `enable_metric_reporting`.
+    pub metrics_exporter: Option<MetricsExporter>,
 }
 
 pub struct ServerInit {
@@ -22,21 +31,150 @@ pub struct ServerInit {
     pub genesis: bookkeeping::genesis::Genesis,
     pub genesis_text: String,
     pub cfg: Local,
+    pub log_to_stdout: bool,
+    pub telemetry: TelemetryConfig,
+    /// The randomness of the node, seeded for reproducible runs.
+    pub rng: NodeRng,
 }
 
 impl Server {
-    pub fn new(server_init: ServerInit) {
+    pub fn new(server_init: ServerInit) -> NodeResult<Self> {
         let ServerInit {
             root_path,
             genesis,
             genesis_text,
             cfg,
+            log_to_stdout,
+            telemetry,
+            rng,
         } = server_init;
         api::server::set_genesis_text(genesis_text);
 
-        let _live_log = Path::join(&root_path, "node.log");
-        let _archive = Path::join(&root_path, &cfg.log_archive_name);
-        println!("logging to: {:?}", _live_log);
-        let node = AlgorandFullNode::new(root_path.clone(), cfg.clone(), &genesis);
+        let live_log = Path::join(&root_path, "node.log");
+        let max_age = match cfg.log_archive_max_age.as_str() {
+            "" => None,
+            age => Some(logging::parse_duration(age).ok_or_else(|| {
+                NodeError::Logging(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("invalid log_archive_max_age {:?}", age),
+                ))
+            })?),
+        };
+        let options = LogOptions {
+            live: live_log.clone(),
+            archive: Path::join(&root_path, &cfg.log_archive_name),
+            size_limit: cfg.log_size_limit.into(),
+            max_age,
+            level: logging::level_filter(cfg.base_logger_debug_level),
+            to_stdout: log_to_stdout,
+        };
+        logging::init(&options).map_err(NodeError::Logging)?;
+        if !log_to_stdout {
+            tracing::info!("logging to: {:?}", live_log);
+        }
+        telemetry::init(&telemetry).map_err(NodeError::Telemetry)?;
+        tracing::info!("loading ledger");
+        let mut node = AlgorandFullNode::new(root_path.clone(), cfg.clone(), &genesis, rng)?;
+        node.start()?;
+        telemetry::event(TelemetryEvent::Startup {
+            version: config::version::current_version().to_string(),
+            genesis_id: genesis.id(),
+        });
+        if cfg.enable_runtime_metrics {
+            util::metrics::register_runtime_metrics();
+        }
+        let metrics_exporter = if cfg.enable_metric_reporting {
+            let address = &cfg.node_exporter_listen_address;
+            let exporter =
+                MetricsExporter::start(address).map_err(|source| NodeError::Metrics {
+                    address: address.clone(),
+                    source,
+                })?;
+            tracing::info!(
+                "serving metrics on {}{}",
+                exporter.local_addr(),
+                METRICS_PATH
+            );
+            Some(exporter)
+        } else {
+            None
+        };
+        // Stopping the router resolves `stopping`.
+        let (router_stop_sender, stopping) = oneshot::channel();
+        Ok(Self {
+            pid_file: Path::join(&root_path, "algod.pid"),
+            net_file: Path::join(&root_path, "algod.net"),
+            net_listen_file: Path::join(&root_path, "algod-listen.net"),
+            root_path,
+            genesis,
+            node,
+            stopping,
+            router_stop_sender,
+            metrics_exporter,
+        })
+    }
+
+    /// Runs the node until the process receives SIGINT or SIGTERM, or the
+    /// router stops, then stops it. The caller flushes the telemetry.
+    pub fn run(mut self) -> NodeResult<()> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let reason = runtime.block_on(wait_for_shutdown(&mut self.stopping))?;
+        tracing::info!("shutting down: {}", reason);
+        self.stop();
+        Ok(())
+    }
+
+    /// Stops the background services of the node.
+    pub fn stop(&mut self) {
+        self.node.stop();
+        telemetry::event(TelemetryEvent::Shutdown);
+    }
+}
+
+/// Waits for SIGINT or SIGTERM, or for the router to stop, and tells which.
+async fn wait_for_shutdown(stopping: &mut oneshot::Receiver<()>) -> std::io::Result<&'static str> {
+    #[cfg(unix)]
+    let mut terminate = signal::unix::signal(signal::unix::SignalKind::terminate())?;
+    #[cfg(unix)]
+    let terminated = terminate.recv();
+    #[cfg(not(unix))]
+    let terminated = std::future::pending::<Option<()>>();
+    tokio::select! {
+        interrupted = signal::ctrl_c() => interrupted.map(|()| "SIGINT"),
+        _ = terminated => Ok("SIGTERM"),
+        _ = stopping => Ok("router stopped"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn router_stop_ends_the_wait() {
+        let (stop, mut stopping) = oneshot::channel();
+        stop.send(()).unwrap();
+        assert_eq!(
+            wait_for_shutdown(&mut stopping).await.unwrap(),
+            "router stopped"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn sigterm_ends_the_wait() {
+        let (_stop, mut stopping) = oneshot::channel();
+        let wait = tokio::spawn(async move { wait_for_shutdown(&mut stopping).await });
+        // The wait installs its handlers once it runs, while this task sleeps.
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let pid = std::process::id().to_string();
+        let killed = std::process::Command::new("kill")
+            .args(["-TERM", &pid])
+            .status()
+            .unwrap();
+        assert!(killed.success());
+        assert_eq!(wait.await.unwrap().unwrap(), "SIGTERM");
     }
 }
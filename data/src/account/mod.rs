@@ -0,0 +1,5 @@
+mod participation;
+mod registry;
+
+pub use participation::*;
+pub use registry::*;
@@ -0,0 +1,197 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crypto::onetimesig::{OneTimeSignature, OneTimeSignatureSecrets, OneTimeSignatureVerifier};
+use crypto::util::{hash_obj, HashDigest, MsgpHashable};
+use crypto::vrf::{self, VRFSecrets, VRFVerifier};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::basics::{self, Address, Round};
+
+/// ParticipationError is an error generating, storing or loading
+/// participation keys.
+#[derive(Debug, thiserror::Error)]
+pub enum ParticipationError {
+    #[error("last valid round {last_valid} is before first valid round {first_valid}")]
+    InvalidInterval {
+        first_valid: Round,
+        last_valid: Round,
+    },
+    #[error("key dilution must be positive")]
+    ZeroKeyDilution,
+    #[error("cannot access participation keys {path:?}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("cannot decode participation keys {path:?}")]
+    Decode {
+        path: PathBuf,
+        source: msgp::DecodeError,
+    },
+}
+
+pub type ParticipationResult<T> = Result<T, ParticipationError>;
+
+/// PARTKEY_FILE_EXTENSION is the extension of participation key files that
+/// the node picks up from the genesis directory.
+pub const PARTKEY_FILE_EXTENSION: &str = "partkey";
+
+/// ParticipationId identifies a single set of participation keys.
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParticipationId(pub HashDigest);
+
+impl std::fmt::Display for ParticipationId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(self.0 .0))
+    }
+}
+
+/// ParticipationKeyIdentity is the public part of a participation key set;
+/// its hash is the participation id.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ParticipationKeyIdentity {
+    #[serde(rename = "addr")]
+    pub parent: Address,
+    #[serde(rename = "sel")]
+    pub selection_id: VRFVerifier,
+    #[serde(rename = "vote")]
+    pub vote_id: OneTimeSignatureVerifier,
+    #[serde(rename = "fv")]
+    pub first_valid: Round,
+    #[serde(rename = "lv")]
+    pub last_valid: Round,
+    #[serde(rename = "kd")]
+    pub key_dilution: u64,
+}
+
+impl MsgpHashable for ParticipationKeyIdentity {
+    fn hash_id(&self) -> protocol::HashId {
+        protocol::PARTICIPATION_KEYS
+    }
+}
+
+/// A Participation encapsulates a set of secrets which allows a root to
+/// participate in consensus. All such accounts are associated with a parent
+/// root account via the Address.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Participation {
+    pub parent: Address,
+    pub vrf: VRFSecrets,
+    pub voting: OneTimeSignatureSecrets,
+    pub first_valid: Round,
+    pub last_valid: Round,
+    pub key_dilution: u64,
+}
+
+impl Participation {
+    /// Generates fresh participation keys for `address`, valid from
+    /// `first_valid` to `last_valid` inclusive. A voting subkey batch is
+    /// created for every `key_dilution` rounds of the validity interval.
+    pub fn generate(
+        address: Address,
+        first_valid: Round,
+        last_valid: Round,
+        key_dilution: u64,
+        rng: &mut dyn RngCore,
+    ) -> ParticipationResult<Self> {
+        if last_valid < first_valid {
+            return Err(ParticipationError::InvalidInterval {
+                first_valid,
+                last_valid,
+            });
+        }
+        if key_dilution == 0 {
+            return Err(ParticipationError::ZeroKeyDilution);
+        }
+        let first_id = basics::one_time_id_for_round(first_valid, key_dilution);
+        let last_id = basics::one_time_id_for_round(last_valid, key_dilution);
+        let voting = OneTimeSignatureSecrets::generate(
+            first_id.batch,
+            last_id.batch - first_id.batch + 1,
+            rng,
+        );
+        let vrf = vrf::generate_vrf_secrets(rng);
+        Ok(Self {
+            parent: address,
+            vrf,
+            voting,
+            first_valid,
+            last_valid,
+            key_dilution,
+        })
+    }
+
+    pub fn identity(&self) -> ParticipationKeyIdentity {
+        ParticipationKeyIdentity {
+            parent: self.parent,
+            selection_id: self.vrf.pk,
+            vote_id: self.voting.verifier,
+            first_valid: self.first_valid,
+            last_valid: self.last_valid,
+            key_dilution: self.key_dilution,
+        }
+    }
+
+    pub fn id(&self) -> ParticipationId {
+        ParticipationId(hash_obj(&self.identity()))
+    }
+
+    /// Deletes the voting keys of every round before `round`.
+    pub fn delete_old_keys(&mut self, round: Round) {
+        self.voting.delete_before_fine_grained(
+            basics::one_time_id_for_round(round, self.key_dilution),
+            self.key_dilution,
+        );
+    }
+
+    /// Signs `msg` with the voting key of `round`, as a participant of a
+    /// compact certificate. Returns `None` if that key is not available.
+    pub fn sign_compact_cert(
+        &self,
+        round: Round,
+        msg: &impl MsgpHashable,
+    ) -> Option<OneTimeSignature> {
+        if round < self.first_valid || round > self.last_valid {
+            return None;
+        }
+        self.voting
+            .sign(basics::one_time_id_for_round(round, self.key_dilution), msg)
+    }
+
+    /// Returns true if the keys are valid for some round in the interval.
+    pub fn overlaps_interval(&self, first: Round, last: Round) -> bool {
+        !(last < self.first_valid || first > self.last_valid)
+    }
+
+    /// The conventional file name of these keys in the genesis directory.
+    pub fn file_name(&self) -> String {
+        format!(
+            "{}.{}.{}.{}",
+            self.parent.string(),
+            self.first_valid,
+            self.last_valid,
+            PARTKEY_FILE_EXTENSION
+        )
+    }
+
+    pub fn persist<P: AsRef<Path>>(&self, path: P) -> ParticipationResult<()> {
+        fs::write(&path, protocol::encode(self)).map_err(|source| ParticipationError::Io {
+            path: path.as_ref().to_path_buf(),
+            source,
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> ParticipationResult<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|source| ParticipationError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        protocol::decode(&bytes).map_err(|source| ParticipationError::Decode {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
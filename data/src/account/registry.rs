@@ -0,0 +1,428 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crypto::onetimesig::OneTimeSignatureSecrets;
+use crypto::util::HashDigest;
+use crypto::vrf::VRFSecrets;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::participation::{Participation, ParticipationId};
+use crate::basics::{self, Address, Round};
+
+/// RegistryError is an error of the participation registry.
+#[derive(Debug, thiserror::Error)]
+pub enum RegistryError {
+    #[error("participation key {0} already exists")]
+    AlreadyExists(ParticipationId),
+    #[error("participation key {0} not found")]
+    NotFound(ParticipationId),
+    #[error("cannot register participation key {id} at round {round}: key expires at round {last_valid}")]
+    Expired {
+        id: ParticipationId,
+        round: Round,
+        last_valid: Round,
+    },
+    #[error("no active participation key for {} at round {round}", account.string())]
+    NoActiveKey { account: Address, round: Round },
+    #[error("participation registry database error")]
+    Database(#[from] rusqlite::Error),
+    #[error("malformed registry entry of length {0}")]
+    Malformed(usize),
+    #[error("cannot decode registry entry")]
+    Decode(#[from] msgp::DecodeError),
+}
+
+pub type RegistryResult<T> = Result<T, RegistryError>;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS Keysets (
+    pk INTEGER PRIMARY KEY,
+    participationID BLOB NOT NULL UNIQUE,
+    account BLOB NOT NULL,
+    firstValidRound INTEGER NOT NULL DEFAULT 0,
+    lastValidRound INTEGER NOT NULL DEFAULT 0,
+    keyDilution INTEGER NOT NULL DEFAULT 0,
+    vrf BLOB
+);
+CREATE TABLE IF NOT EXISTS Rolling (
+    pk INTEGER PRIMARY KEY,
+    lastVoteRound INTEGER NOT NULL DEFAULT 0,
+    lastBlockProposalRound INTEGER NOT NULL DEFAULT 0,
+    effectiveFirstRound INTEGER NOT NULL DEFAULT 0,
+    effectiveLastRound INTEGER NOT NULL DEFAULT 0,
+    voting BLOB
+);";
+
+/// ParticipationAction is used when recording participation actions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParticipationAction {
+    Vote,
+    BlockProposal,
+}
+
+/// ParticipationRecord contains all metadata relating to a set of participation
+/// keys, together with the secrets needed to participate.
+#[derive(Debug, Clone, Default)]
+pub struct ParticipationRecord {
+    pub participation_id: ParticipationId,
+    pub account: Address,
+    pub first_valid: Round,
+    pub last_valid: Round,
+    pub key_dilution: u64,
+    pub last_vote: Round,
+    pub last_block_proposal: Round,
+    pub effective_first: Round,
+    pub effective_last: Round,
+    pub vrf: Option<VRFSecrets>,
+    pub voting: Option<OneTimeSignatureSecrets>,
+}
+
+impl ParticipationRecord {
+    /// Returns true if the keys were registered and are effective at `round`.
+    pub fn is_active(&self, round: Round) -> bool {
+        self.effective_first != 0 && self.effective_first <= round && round <= self.effective_last
+    }
+}
+
+/// ParticipationRegistry tracks installed participation keys. Records are
+/// cached in memory; rolling fields such as the last vote round are written
+/// back to the database on `flush`.
+pub struct ParticipationRegistry {
+    conn: Connection,
+    cache: HashMap<ParticipationId, ParticipationRecord>,
+    dirty: HashSet<ParticipationId>,
+}
+
+impl std::fmt::Debug for ParticipationRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParticipationRegistry")
+            .field("keys", &self.cache.len())
+            .field("dirty", &self.dirty.len())
+            .finish()
+    }
+}
+
+impl ParticipationRegistry {
+    pub fn open<P: AsRef<Path>>(path: P) -> RegistryResult<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> RegistryResult<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> RegistryResult<Self> {
+        conn.execute_batch(SCHEMA)?;
+        let mut registry = Self {
+            conn,
+            cache: HashMap::new(),
+            dirty: HashSet::new(),
+        };
+        registry.reload()?;
+        Ok(registry)
+    }
+
+    /// Replaces the cache with the records stored on disk. Unflushed rolling
+    /// updates are written first so they are not lost.
+    pub fn reload(&mut self) -> RegistryResult<()> {
+        self.flush()?;
+        let mut stmt = self.conn.prepare(
+            "SELECT participationID, account, firstValidRound, lastValidRound, keyDilution, vrf,
+                    lastVoteRound, lastBlockProposalRound, effectiveFirstRound, effectiveLastRound, voting
+             FROM Keysets INNER JOIN Rolling ON Keysets.pk = Rolling.pk",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, Vec<u8>>(0)?,
+                row.get::<_, Vec<u8>>(1)?,
+                [
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, i64>(4)?,
+                    row.get::<_, i64>(6)?,
+                    row.get::<_, i64>(7)?,
+                    row.get::<_, i64>(8)?,
+                    row.get::<_, i64>(9)?,
+                ],
+                row.get::<_, Option<Vec<u8>>>(5)?,
+                row.get::<_, Option<Vec<u8>>>(10)?,
+            ))
+        })?;
+        let mut cache = HashMap::new();
+        for row in rows {
+            let (id, account, rounds, vrf, voting) = row?;
+            let record = ParticipationRecord {
+                participation_id: ParticipationId(HashDigest(to_digest(&id)?)),
+                account: Address::from(to_digest(&account)?),
+                first_valid: rounds[0] as Round,
+                last_valid: rounds[1] as Round,
+                key_dilution: rounds[2] as u64,
+                last_vote: rounds[3] as Round,
+                last_block_proposal: rounds[4] as Round,
+                effective_first: rounds[5] as Round,
+                effective_last: rounds[6] as Round,
+                vrf: vrf.map(|b| protocol::decode(&b)).transpose()?,
+                voting: voting.map(|b| protocol::decode(&b)).transpose()?,
+            };
+            cache.insert(record.participation_id, record);
+        }
+        self.cache = cache;
+        Ok(())
+    }
+
+    /// Adds a new set of participation keys to the registry.
+    pub fn insert(&mut self, part: &Participation) -> RegistryResult<ParticipationId> {
+        let id = part.id();
+        if self.cache.contains_key(&id) {
+            return Err(RegistryError::AlreadyExists(id));
+        }
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO Keysets (participationID, account, firstValidRound, lastValidRound, keyDilution, vrf)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                &id.0 .0[..],
+                &part.parent.as_bytes()[..],
+                part.first_valid as i64,
+                part.last_valid as i64,
+                part.key_dilution as i64,
+                protocol::encode(&part.vrf),
+            ],
+        )?;
+        let pk = tx.last_insert_rowid();
+        tx.execute(
+            "INSERT INTO Rolling (pk, voting) VALUES (?1, ?2)",
+            params![pk, protocol::encode(&part.voting)],
+        )?;
+        tx.commit()?;
+        self.cache.insert(
+            id,
+            ParticipationRecord {
+                participation_id: id,
+                account: part.parent,
+                first_valid: part.first_valid,
+                last_valid: part.last_valid,
+                key_dilution: part.key_dilution,
+                vrf: Some(part.vrf.clone()),
+                voting: Some(part.voting.clone()),
+                ..Default::default()
+            },
+        );
+        Ok(id)
+    }
+
+    pub fn delete(&mut self, id: ParticipationId) -> RegistryResult<()> {
+        if self.cache.remove(&id).is_none() {
+            return Err(RegistryError::NotFound(id));
+        }
+        self.dirty.remove(&id);
+        let tx = self.conn.transaction()?;
+        let pk: Option<i64> = tx
+            .query_row(
+                "SELECT pk FROM Keysets WHERE participationID = ?1",
+                params![&id.0 .0[..]],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(pk) = pk {
+            tx.execute("DELETE FROM Keysets WHERE pk = ?1", params![pk])?;
+            tx.execute("DELETE FROM Rolling WHERE pk = ?1", params![pk])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Removes every key whose validity ended before `latest_round`.
+    pub fn delete_expired(&mut self, latest_round: Round) -> RegistryResult<()> {
+        let expired: Vec<_> = self
+            .cache
+            .values()
+            .filter(|r| r.last_valid < latest_round)
+            .map(|r| r.participation_id)
+            .collect();
+        for id in expired {
+            self.delete(id)?;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, id: &ParticipationId) -> Option<&ParticipationRecord> {
+        self.cache.get(id)
+    }
+
+    pub fn get_all(&self) -> Vec<ParticipationRecord> {
+        self.cache.values().cloned().collect()
+    }
+
+    /// Returns the keys of `account` that are registered for `round`.
+    pub fn get_for_round(&self, account: &Address, round: Round) -> Option<&ParticipationRecord> {
+        self.cache
+            .values()
+            .find(|r| r.account == *account && r.is_active(round))
+    }
+
+    /// Marks the keys as effective starting at round `on`. Any other key of
+    /// the same account that is effective at that point is retired the round
+    /// before.
+    pub fn register(&mut self, id: ParticipationId, on: Round) -> RegistryResult<()> {
+        let account = match self.cache.get(&id) {
+            Some(record) => {
+                if on > record.last_valid {
+                    return Err(RegistryError::Expired {
+                        id,
+                        round: on,
+                        last_valid: record.last_valid,
+                    });
+                }
+                record.account
+            }
+            None => return Err(RegistryError::NotFound(id)),
+        };
+        for record in self.cache.values_mut() {
+            if record.participation_id == id {
+                record.effective_first = on;
+                record.effective_last = record.last_valid;
+                self.dirty.insert(id);
+            } else if record.account == account && record.is_active(on) {
+                record.effective_last = on.saturating_sub(1);
+                self.dirty.insert(record.participation_id);
+            }
+        }
+        self.flush()
+    }
+
+    /// Records that the keys of `account` were used at `round`.
+    pub fn record(
+        &mut self,
+        account: &Address,
+        round: Round,
+        action: ParticipationAction,
+    ) -> RegistryResult<()> {
+        let record = self
+            .cache
+            .values_mut()
+            .find(|r| r.account == *account && r.is_active(round))
+            .ok_or(RegistryError::NoActiveKey {
+                account: *account,
+                round,
+            })?;
+        match action {
+            ParticipationAction::Vote => record.last_vote = round,
+            ParticipationAction::BlockProposal => record.last_block_proposal = round,
+        }
+        self.dirty.insert(record.participation_id);
+        Ok(())
+    }
+
+    /// Deletes the voting keys of `account` for every round before `round`.
+    /// The deletion is persisted on the next `flush`.
+    pub fn delete_old_keys(&mut self, account: &Address, round: Round) {
+        for record in self.cache.values_mut() {
+            if record.account != *account || record.key_dilution == 0 {
+                continue;
+            }
+            if let Some(voting) = record.voting.as_mut() {
+                voting.delete_before_fine_grained(
+                    basics::one_time_id_for_round(round, record.key_dilution),
+                    record.key_dilution,
+                );
+                self.dirty.insert(record.participation_id);
+            }
+        }
+    }
+
+    /// Writes all pending rolling updates to disk.
+    pub fn flush(&mut self) -> RegistryResult<()> {
+        if self.dirty.is_empty() {
+            return Ok(());
+        }
+        let tx = self.conn.transaction()?;
+        for id in self.dirty.iter() {
+            let record = match self.cache.get(id) {
+                Some(r) => r,
+                None => continue,
+            };
+            tx.execute(
+                "UPDATE Rolling SET lastVoteRound = ?1, lastBlockProposalRound = ?2,
+                        effectiveFirstRound = ?3, effectiveLastRound = ?4, voting = ?5
+                 WHERE pk = (SELECT pk FROM Keysets WHERE participationID = ?6)",
+                params![
+                    record.last_vote as i64,
+                    record.last_block_proposal as i64,
+                    record.effective_first as i64,
+                    record.effective_last as i64,
+                    record.voting.as_ref().map(protocol::encode),
+                    &id.0 .0[..],
+                ],
+            )?;
+        }
+        tx.commit()?;
+        self.dirty.clear();
+        Ok(())
+    }
+}
+
+fn to_digest(bytes: &[u8]) -> RegistryResult<[u8; 32]> {
+    bytes
+        .try_into()
+        .map_err(|_| RegistryError::Malformed(bytes.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn insert_register_and_reload() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let dir = std::env::temp_dir().join(format!("partregistry-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(config::PARTICIPATION_REGISTRY_FILENAME);
+        let _ = std::fs::remove_file(&path);
+
+        let account = Address::from([7u8; 32]);
+        let old = Participation::generate(account, 1, 1000, 100, &mut rng).unwrap();
+        let new = Participation::generate(account, 500, 3000, 100, &mut rng).unwrap();
+        let mut registry = ParticipationRegistry::open(&path).unwrap();
+        let old_id = registry.insert(&old).unwrap();
+        let new_id = registry.insert(&new).unwrap();
+        assert!(matches!(
+            registry.insert(&old),
+            Err(RegistryError::AlreadyExists(id)) if id == old.id()
+        ));
+
+        registry.register(old_id, 10).unwrap();
+        registry.register(new_id, 600).unwrap();
+        registry
+            .record(&account, 700, ParticipationAction::Vote)
+            .unwrap();
+        registry.flush().unwrap();
+
+        let mut reopened = ParticipationRegistry::open(&path).unwrap();
+        assert_eq!(reopened.get(&old_id).unwrap().effective_last, 599);
+        assert_eq!(reopened.get(&new_id).unwrap().last_vote, 700);
+        assert_eq!(
+            reopened
+                .get_for_round(&account, 700)
+                .unwrap()
+                .participation_id,
+            new_id
+        );
+        assert_eq!(
+            reopened
+                .get(&new_id)
+                .unwrap()
+                .voting
+                .as_ref()
+                .unwrap()
+                .verifier,
+            new.voting.verifier
+        );
+
+        reopened.delete_expired(1001).unwrap();
+        assert!(reopened.get(&old_id).is_none());
+        assert_eq!(reopened.get_all().len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
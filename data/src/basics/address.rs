@@ -3,7 +3,9 @@ use serde::{Deserialize, Serialize};
 
 const CHECKSUM_LENGTH: usize = 4;
 
-#[derive(Debug, Default, Hash, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[derive(
+    Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize,
+)]
 pub struct Address(HashDigest);
 
 impl Address {
@@ -22,17 +24,51 @@ impl Address {
         self.0 .0.clone_from_slice(slice);
     }
 
+    pub fn as_bytes(&self) -> &[u8; DIGEST_SIZE] {
+        &self.0 .0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 .0 == [0u8; DIGEST_SIZE]
+    }
+
     pub fn string(&self) -> String {
         let mut addr_with_checksum = [0u8; DIGEST_SIZE + CHECKSUM_LENGTH];
         addr_with_checksum[..DIGEST_SIZE].copy_from_slice(&self.0 .0);
         let short_addr_hash = hash(&self.0 .0);
         addr_with_checksum[DIGEST_SIZE..]
             .copy_from_slice(&short_addr_hash.0[short_addr_hash.len() - CHECKSUM_LENGTH..]);
-        String::from_utf8(addr_with_checksum.to_vec())
-            .expect("failed to make string from address bytes")
+        base32::encode(
+            base32::Alphabet::RFC4648 { padding: false },
+            &addr_with_checksum,
+        )
+    }
+}
+
+impl From<HashDigest> for Address {
+    fn from(digest: HashDigest) -> Self {
+        Self(digest)
     }
 }
-pub type AddressResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+impl From<[u8; DIGEST_SIZE]> for Address {
+    fn from(bytes: [u8; DIGEST_SIZE]) -> Self {
+        Self(HashDigest(bytes))
+    }
+}
+
+/// AddressError is an error decoding the checksummed form of an address.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AddressError {
+    #[error("failed to decode address {0} to base 32")]
+    Base32(String),
+    #[error("decoded bad addr: {0}")]
+    TooShort(String),
+    #[error("address {0} is malformed, checksum verification failed")]
+    Checksum(String),
+}
+
+pub type AddressResult<T> = Result<T, AddressError>;
 
 pub fn unmarshal_checksum_address(address: &String) -> AddressResult<Address> {
     if let Some(decoded) = base32::decode(
@@ -41,20 +77,16 @@ pub fn unmarshal_checksum_address(address: &String) -> AddressResult<Address> {
     ) {
         let mut short = Address::default();
         if decoded.len() < short.len() {
-            return Err(format!("decoded bad addr: {}", address).into());
+            return Err(AddressError::TooShort(address.clone()));
         }
         short.clone_from_slice(&decoded[..short.len()]);
         let incoming_checksum = &decoded[decoded.len() - CHECKSUM_LENGTH..];
         let calculated_checksum = short.get_checksum();
         if !calculated_checksum.eq(incoming_checksum) {
-            return Err(format!(
-                "address {} is malformed, checksum verification failed",
-                address
-            )
-            .into());
+            return Err(AddressError::Checksum(address.clone()));
         }
         Ok(short)
     } else {
-        Err(format!("failed to decode address {} to base 32", address).into())
+        Err(AddressError::Base32(address.clone()))
     }
 }
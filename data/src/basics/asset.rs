@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+
+use super::Address;
+
+/// AssetIndex is the unique integer index of an asset that can be used to
+/// look up the creator of the asset, whose balance record contains the
+/// AssetParams.
+pub type AssetIndex = u64;
+
+/// AssetParams describes the parameters of an asset.
+#[skip_serializing_default]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssetParams {
+    /// The total number of units of this asset created.
+    #[serde(rename = "t")]
+    pub total: u64,
+    /// The number of digits to display after the decimal place.
+    #[serde(rename = "dc")]
+    pub decimals: u32,
+    /// Whether holdings of this asset are frozen by default.
+    #[serde(rename = "df")]
+    pub default_frozen: bool,
+    /// A hint for the name of a unit of this asset.
+    #[serde(rename = "un")]
+    pub unit_name: String,
+    /// A hint for the name of the asset.
+    #[serde(rename = "an")]
+    pub asset_name: String,
+    /// A URL where more information about the asset can be retrieved.
+    #[serde(rename = "au")]
+    pub url: String,
+    /// A commitment to some unspecified asset metadata.
+    #[serde(rename = "am")]
+    pub metadata_hash: [u8; 32],
+    /// The account that may reconfigure or destroy the asset.
+    #[serde(rename = "m")]
+    pub manager: Address,
+    /// The account holding the reserve (non-minted) units of the asset.
+    #[serde(rename = "r")]
+    pub reserve: Address,
+    /// The account that may freeze or unfreeze holdings of the asset.
+    #[serde(rename = "f")]
+    pub freeze: Address,
+    /// The account that may claw back holdings of the asset.
+    #[serde(rename = "c")]
+    pub clawback: Address,
+}
+
+/// AssetHolding describes an asset held by an account.
+#[skip_serializing_default]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssetHolding {
+    #[serde(rename = "a")]
+    pub amount: u64,
+    #[serde(rename = "f")]
+    pub frozen: bool,
+}
+
+/// AssetResource is the per-account record of an asset, stored apart from
+/// the AccountData since `enable_account_data_resource_separation`: the
+/// account's holding of the asset and, for its creator, its parameters.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AssetResource {
+    pub holding: Option<AssetHolding>,
+    pub params: Option<AssetParams>,
+}
+
+impl AssetResource {
+    pub fn is_empty(&self) -> bool {
+        self.holding.is_none() && self.params.is_none()
+    }
+}
@@ -1,7 +1,11 @@
 mod address;
+mod asset;
+mod teal;
 mod units;
 mod user_balance;
 
 pub use address::*;
+pub use asset::*;
+pub use teal::*;
 pub use units::*;
 pub use user_balance::*;
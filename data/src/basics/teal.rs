@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use config::consensus::ConsensusParams;
+
+use super::{Address, MicroAlgos};
+
+/// AppIndex is the unique integer index of an application that can be used
+/// to look up the creator of the application, whose balance record contains
+/// the AppParams.
+pub type AppIndex = u64;
+
+/// Returns the address of the account controlled by application `app`.
+pub fn application_address(app: AppIndex) -> Address {
+    let mut to_hash = protocol::APP_INDEX.as_bytes().to_vec();
+    to_hash.extend_from_slice(&app.to_be_bytes());
+    Address::from(crypto::util::hash(&to_hash))
+}
+
+/// TealType is an enum of the types in a TEAL program: Bytes and Uint.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u64)]
+pub enum TealType {
+    #[default]
+    Bytes = 1,
+    Uint = 2,
+}
+
+/// TealValue contains type information and a value, representing a value
+/// in a TEAL program.
+#[skip_serializing_default]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TealValue {
+    #[serde(rename = "tt")]
+    #[serialize_always]
+    pub tt: TealType,
+    #[serde(rename = "tb")]
+    pub bytes: Vec<u8>,
+    #[serde(rename = "ui")]
+    pub uint: u64,
+}
+
+impl TealValue {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self {
+            tt: TealType::Bytes,
+            bytes,
+            uint: 0,
+        }
+    }
+
+    pub fn from_uint(uint: u64) -> Self {
+        Self {
+            tt: TealType::Uint,
+            bytes: vec![],
+            uint,
+        }
+    }
+}
+
+/// TealKeyValue represents a key/value store for use in an application's
+/// LocalState or GlobalState.
+pub type TealKeyValue = BTreeMap<Vec<u8>, TealValue>;
+
+/// StateSchema sets maximums on the number of each type that may be stored.
+#[skip_serializing_default]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StateSchema {
+    #[serde(rename = "nui")]
+    pub num_uint: u64,
+    #[serde(rename = "nbs")]
+    pub num_byte_slice: u64,
+}
+
+/// StateSchemas is a thin wrapper around the LocalStateSchema and the
+/// GlobalStateSchema, since they are often needed together.
+#[skip_serializing_default]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StateSchemas {
+    #[serde(rename = "lsch")]
+    pub local_state_schema: StateSchema,
+    #[serde(rename = "gsch")]
+    pub global_state_schema: StateSchema,
+}
+
+/// AppParams stores the global information associated with an application.
+#[skip_serializing_default]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppParams {
+    #[serde(rename = "approv")]
+    pub approval_program: Vec<u8>,
+    #[serde(rename = "clearp")]
+    pub clear_state_program: Vec<u8>,
+    #[serde(rename = "gs")]
+    pub global_state: TealKeyValue,
+    #[serde(flatten)]
+    pub state_schemas: StateSchemas,
+    #[serde(rename = "epp")]
+    pub extra_program_pages: u32,
+}
+
+/// AppLocalState stores the LocalState associated with an application.
+#[skip_serializing_default]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppLocalState {
+    #[serde(rename = "hsch")]
+    pub schema: StateSchema,
+    #[serde(rename = "tkv")]
+    pub key_value: TealKeyValue,
+}
+
+impl StateSchema {
+    pub fn num_entries(&self) -> u64 {
+        self.num_uint.saturating_add(self.num_byte_slice)
+    }
+
+    pub fn add_schema(&self, other: &StateSchema) -> StateSchema {
+        StateSchema {
+            num_uint: self.num_uint.saturating_add(other.num_uint),
+            num_byte_slice: self.num_byte_slice.saturating_add(other.num_byte_slice),
+        }
+    }
+
+    pub fn sub_schema(&self, other: &StateSchema) -> StateSchema {
+        StateSchema {
+            num_uint: self.num_uint.saturating_sub(other.num_uint),
+            num_byte_slice: self.num_byte_slice.saturating_sub(other.num_byte_slice),
+        }
+    }
+
+    /// Computes the minimum balance requirements of the schema.
+    pub fn min_balance(&self, proto: &ConsensusParams) -> MicroAlgos {
+        let flat = proto
+            .schema_min_balance_per_entry
+            .saturating_mul(self.num_entries());
+        let uints = proto.schema_uint_min_balance.saturating_mul(self.num_uint);
+        let bytes = proto
+            .schema_bytes_min_balance
+            .saturating_mul(self.num_byte_slice);
+        MicroAlgos(flat.saturating_add(uints).saturating_add(bytes))
+    }
+
+    /// Returns the schema used by the values of `kv`.
+    pub fn of(kv: &TealKeyValue) -> StateSchema {
+        let num_uint = kv.values().filter(|v| v.tt == TealType::Uint).count() as u64;
+        StateSchema {
+            num_uint,
+            num_byte_slice: kv.len() as u64 - num_uint,
+        }
+    }
+
+    /// Checks that `kv` fits in the schema.
+    pub fn allows(&self, kv: &TealKeyValue) -> Result<(), String> {
+        let used = StateSchema::of(kv);
+        if used.num_uint > self.num_uint {
+            return Err(format!(
+                "store integer count {} exceeds schema integer count {}",
+                used.num_uint, self.num_uint
+            ));
+        }
+        if used.num_byte_slice > self.num_byte_slice {
+            return Err(format!(
+                "store bytes count {} exceeds schema bytes count {}",
+                used.num_byte_slice, self.num_byte_slice
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// DeltaAction is an enum of actions that may be performed when applying a
+/// delta to a TEAL key/value store.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u64)]
+pub enum DeltaAction {
+    /// SetBytesAction indicates that a TEAL byte slice should be stored at
+    /// a key.
+    #[default]
+    SetBytesAction = 1,
+    /// SetUintAction indicates that a Uint should be stored at a key.
+    SetUintAction = 2,
+    /// DeleteAction indicates that the value for a particular key should be
+    /// deleted.
+    DeleteAction = 3,
+}
+
+/// ValueDelta links a DeltaAction with a value to be set.
+#[skip_serializing_default]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValueDelta {
+    #[serde(rename = "at")]
+    #[serialize_always]
+    pub action: DeltaAction,
+    #[serde(rename = "bs")]
+    pub bytes: Vec<u8>,
+    #[serde(rename = "ui")]
+    pub uint: u64,
+}
+
+impl ValueDelta {
+    /// Returns the delta turning `old` into `new`, if they differ.
+    pub fn between(old: Option<&TealValue>, new: Option<&TealValue>) -> Option<ValueDelta> {
+        if old == new {
+            return None;
+        }
+        Some(match new {
+            None => ValueDelta {
+                action: DeltaAction::DeleteAction,
+                ..Default::default()
+            },
+            Some(value) if value.tt == TealType::Uint => ValueDelta {
+                action: DeltaAction::SetUintAction,
+                uint: value.uint,
+                ..Default::default()
+            },
+            Some(value) => ValueDelta {
+                action: DeltaAction::SetBytesAction,
+                bytes: value.bytes.clone(),
+                ..Default::default()
+            },
+        })
+    }
+}
+
+/// StateDelta is a map from key/value store keys to ValueDeltas, indicating
+/// what should happen for that key.
+pub type StateDelta = BTreeMap<Vec<u8>, ValueDelta>;
@@ -1,6 +1,15 @@
+use crypto::onetimesig::OneTimeSignatureIdentifier;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
 pub struct MicroAlgos(pub u64);
 
 pub type Round = u64;
+
+/// Maps a round to the identifier of the one-time key that signs for it.
+pub fn one_time_id_for_round(round: Round, key_dilution: u64) -> OneTimeSignatureIdentifier {
+    OneTimeSignatureIdentifier {
+        batch: round / key_dilution,
+        offset: round % key_dilution,
+    }
+}
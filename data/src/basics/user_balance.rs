@@ -1,7 +1,12 @@
+use std::collections::BTreeMap;
+
+use config::consensus::ConsensusParams;
 use macros::*;
 use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
 
-use super::units;
+use super::{units, AppIndex, AppLocalState, AppParams, StateSchema};
+use crypto::merklearray::GenericDigest;
 use crypto::{onetimesig, vrf};
 
 #[skip_serializing_default]
@@ -9,7 +14,7 @@ use crypto::{onetimesig, vrf};
 #[serde(default)]
 pub struct AccountData {
     #[serde(rename = "onl")]
-    pub status: u8,
+    pub status: Status,
     #[serde(rename = "algo")]
     pub microalgos: units::MicroAlgos,
     #[serde(rename = "ebase")]
@@ -26,27 +31,152 @@ pub struct AccountData {
     pub vote_last_valid: units::Round,
     #[serde(rename = "voteKD")]
     pub vote_key_dilution: u64,
+    /// The commitment to the state proof keys of the account.
+    #[serde(rename = "stprf")]
+    pub state_proof_id: GenericDigest,
+    /// The local states of the applications this account has opted in to.
+    #[serde(rename = "appl")]
+    pub app_local_states: BTreeMap<AppIndex, AppLocalState>,
+    /// The parameters of the applications created by this account.
+    #[serde(rename = "appp")]
+    pub app_params: BTreeMap<AppIndex, AppParams>,
+    /// The sum of the local schemas of the opted in applications and the
+    /// global schemas of the created applications.
+    #[serde(rename = "tsch")]
+    pub total_app_schema: StateSchema,
+    /// The extra program pages of the created applications.
+    #[serde(rename = "teap")]
+    pub total_extra_app_pages: u32,
+    /// The number of assets held by this account, including the ones it
+    /// created. The holdings themselves are stored as AssetResources.
+    #[serde(rename = "tas")]
+    pub total_assets: u64,
+    /// The number of assets created by this account.
+    #[serde(rename = "tasp")]
+    pub total_asset_params: u64,
+    /// The address authorized to sign for this account, if it was rekeyed.
+    /// Zero when the account is authorized by its own key.
+    #[serde(rename = "spend")]
+    pub auth_addr: super::Address,
+}
+
+impl AccountData {
+    /// Computes the minimum balance requirements for the account, given
+    /// the assets it holds and the apps it created and opted in to.
+    pub fn min_balance(&self, proto: &ConsensusParams) -> units::MicroAlgos {
+        let mut min = proto.min_balance;
+        min = min.saturating_add(proto.min_balance.saturating_mul(self.total_assets));
+        min = min.saturating_add(self.total_app_schema.min_balance(proto).0);
+        min = min.saturating_add(
+            proto
+                .app_flat_params_min_balance
+                .saturating_mul(self.app_params.len() as u64),
+        );
+        min = min.saturating_add(
+            proto
+                .app_flat_opt_in_min_balance
+                .saturating_mul(self.app_local_states.len() as u64),
+        );
+        min = min.saturating_add(
+            proto
+                .app_flat_params_min_balance
+                .saturating_mul(self.total_extra_app_pages as u64),
+        );
+        units::MicroAlgos(min)
+    }
+
+    /// Whether the account is online with participation keys that are no
+    /// longer valid in `round`.
+    pub fn is_expired(&self, round: units::Round) -> bool {
+        self.status == Status::Online && self.vote_last_valid != 0 && round > self.vote_last_valid
+    }
+
+    /// Takes the account offline, forgetting its participation keys.
+    pub fn clear_online_state(&mut self) {
+        self.status = Status::Offline;
+        self.vote_id = Default::default();
+        self.selection_id = Default::default();
+        self.state_proof_id = Default::default();
+        self.vote_first_valid = 0;
+        self.vote_last_valid = 0;
+        self.vote_key_dilution = 0;
+    }
+
+    /// Whether the account holds nothing, so it may be removed.
+    pub fn is_zero(&self) -> bool {
+        *self == AccountData::default()
+    }
 }
 
+/// CreatableType is an enum representing whether or not a given creatable
+/// is an application or an asset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CreatableType {
+    AssetCreatable,
+    AppCreatable,
+}
+
+/// Keys are written as byte arrays, and read from those or from the base64
+/// strings of genesis files.
 mod base64_bytes {
-    use serde::{Deserialize, Serialize};
+    use serde::de::{self, SeqAccess, Visitor};
+    use serde::Serialize;
     use serde::{Deserializer, Serializer};
+
     pub fn serialize<S: Serializer>(v: &[u8; 32], s: S) -> Result<S::Ok, S::Error> {
         v.serialize(s)
     }
+
     pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[u8; 32], D::Error> {
-        let b = String::deserialize(d)?;
-        let b = base64::decode(b.as_bytes()).unwrap().try_into().unwrap();
-        Ok(b)
+        d.deserialize_any(KeyVisitor)
+    }
+
+    struct KeyVisitor;
+
+    impl<'de> Visitor<'de> for KeyVisitor {
+        type Value = [u8; 32];
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("32 bytes, or their base64 encoding")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            let bytes = base64::decode(v).map_err(E::custom)?;
+            self.visit_bytes(&bytes)
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            v.try_into().map_err(|_| E::invalid_length(v.len(), &self))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut key = [0; 32];
+            for (i, byte) in key.iter_mut().enumerate() {
+                *byte = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+            }
+            if seq.next_element::<u8>()?.is_some() {
+                return Err(de::Error::invalid_length(33, &self));
+            }
+            Ok(key)
+        }
     }
 }
 
-#[derive(Debug, Default)]
+/// Status is the participation status of an account in consensus.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
 pub enum Status {
+    /// Offline accounts hold money but do not participate in consensus.
     #[default]
-    Offline,
-    Online,
-    NotParticipating,
+    Offline = 0,
+    /// Online accounts participate in consensus with their registered
+    /// participation keys.
+    Online = 1,
+    /// NotParticipating accounts neither participate nor earn rewards, and
+    /// can not change their status.
+    NotParticipating = 2,
 }
 
 #[derive(Debug, Default)]
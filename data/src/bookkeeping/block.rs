@@ -1,23 +1,34 @@
+use std::collections::BTreeMap;
+
 use crate::{basics, committee, transactions};
+use config::consensus::{ConsensusParams, PaysetCommitType};
+use crypto::util::{hash_obj, MsgpHashable};
 use serde::{Deserialize, Serialize};
 
+use super::txn_merkle::root_digest;
+
 pub type BlockHash = crypto::util::HashDigest;
 
+pub type BlockResult<T> = Result<T, Box<dyn std::error::Error>>;
+
 #[skip_serializing_default]
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TxnCommitments {
     pub native_sha512_256_commitment: crypto::util::HashDigest,
     pub sha256_commitment: crypto::util::HashDigest,
 }
 
 #[skip_serializing_default]
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ParticipationUpdates {
-    expired_participation_accounts: Vec<basics::Address>,
+    pub expired_participation_accounts: Vec<basics::Address>,
 }
 
 #[skip_serializing_default]
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RewardsState {
     pub fee_sink: basics::Address,
     pub rewards_pool: basics::Address,
@@ -28,25 +39,69 @@ pub struct RewardsState {
 }
 
 #[skip_serializing_default]
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct UpgradeVote {
-    upgrade_propose: protocol::ConsensusVersion,
-    upgrade_delay: basics::Round,
-    upgrade_approve: bool,
+    pub upgrade_propose: protocol::ConsensusVersion,
+    pub upgrade_delay: basics::Round,
+    pub upgrade_approve: bool,
+}
+
+impl UpgradeVote {
+    /// The vote of the proposer of the block following `prev`, whose
+    /// protocol is `proto`: when no upgrade is in progress it proposes one
+    /// of the approved upgrades, otherwise it approves the pending upgrade
+    /// while the vote is open, if this node approves it as well.
+    pub fn make(prev: &BlockHeader, proto: &ConsensusParams) -> UpgradeVote {
+        let state = &prev.upgrade_state;
+        let mut vote = UpgradeVote::default();
+        if state.next_protocol.is_empty() {
+            // Prefer the same proposal across nodes over the map order.
+            if let Some((version, delay)) = proto.approved_upgrades.iter().min() {
+                vote.upgrade_propose = version.clone();
+                vote.upgrade_delay = *delay;
+                vote.upgrade_approve = true;
+            }
+        } else if prev.round + 1 < state.next_protcol_vote_before {
+            vote.upgrade_approve = proto.approved_upgrades.contains_key(&state.next_protocol);
+        }
+        vote
+    }
 }
 
 #[skip_serializing_default]
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct UpgradeState {
-    current_protocol: protocol::ConsensusVersion,
-    next_protocol: protocol::ConsensusVersion,
-    next_protocol_approvals: u64,
-    next_protcol_vote_before: basics::Round,
-    next_protocol_switch_on: basics::Round,
+    pub current_protocol: protocol::ConsensusVersion,
+    pub next_protocol: protocol::ConsensusVersion,
+    pub next_protocol_approvals: u64,
+    pub next_protcol_vote_before: basics::Round,
+    pub next_protocol_switch_on: basics::Round,
 }
 
+/// CompactCertState tracks the state of compact certificates.
 #[skip_serializing_default]
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompactCertState {
+    /// The root of a Merkle tree containing the online accounts that will
+    /// help sign a compact certificate.
+    #[serde(rename = "v")]
+    pub compact_cert_voters: crypto::merklearray::GenericDigest,
+    /// The total number of microalgos held by the accounts in
+    /// `compact_cert_voters`.
+    #[serde(rename = "t")]
+    pub compact_cert_voters_total: basics::MicroAlgos,
+    /// The next round for which we will accept a compact certificate
+    /// transaction.
+    #[serde(rename = "n")]
+    pub compact_cert_next_round: basics::Round,
+}
+
+#[skip_serializing_default]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BlockHeader {
     #[serde(rename="rnd")]
     pub round: basics::Round,
@@ -70,12 +125,27 @@ pub struct BlockHeader {
     pub upgrade_vote: UpgradeVote,
     #[serde(rename="tc")]
     pub txn_counter: u64,
+    #[serde(rename="cc")]
+    pub compact_cert: BTreeMap<protocol::CompactCertType, CompactCertState>,
     #[serde(flatten)]
     pub participation_updates: ParticipationUpdates,
 }
 
+impl MsgpHashable for BlockHeader {
+    fn hash_id(&self) -> protocol::HashId {
+        protocol::BLOCK_HEADER
+    }
+}
+
+impl BlockHeader {
+    pub fn hash(&self) -> BlockHash {
+        hash_obj(self)
+    }
+}
+
 #[skip_serializing_default]
 #[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Block {
     #[serde(flatten)]
     pub header: BlockHeader,
@@ -83,3 +153,47 @@ pub struct Block {
     #[serialize_always]
     pub payset: transactions::payset::PaySet,
 }
+
+impl Block {
+    /// Makes an empty block following `prev`, in the same protocol.
+    pub fn make(prev: &BlockHeader) -> Block {
+        Block {
+            header: BlockHeader {
+                round: prev.round + 1,
+                branch: prev.hash(),
+                timestamp: prev.timestamp,
+                genesis_id: prev.genesis_id.clone(),
+                genesis_hash: prev.genesis_hash,
+                rewards_state: prev.rewards_state.clone(),
+                upgrade_state: prev.upgrade_state.clone(),
+                txn_counter: prev.txn_counter,
+                ..Default::default()
+            },
+            payset: Default::default(),
+        }
+    }
+
+    /// Computes the commitments to the payset of this block, as selected by
+    /// the consensus parameters of its protocol.
+    pub fn payset_commit(&self) -> BlockResult<TxnCommitments> {
+        let proto = &self.header.upgrade_state.current_protocol;
+        let params = config::consensus::get_params(proto)
+            .ok_or_else(|| format!("unsupported protocol {}", proto))?;
+        let native_sha512_256_commitment = match params.payset_commit {
+            PaysetCommitType::PaysetCommitFlat => self.payset.commit(false),
+            PaysetCommitType::PaysetCommitMerkle => root_digest(self.txn_merkle_tree()?.root())?,
+            PaysetCommitType::PaysetCommitUnsupported => {
+                return Err(format!("unsupported payset commit type for protocol {}", proto).into())
+            }
+        };
+        let sha256_commitment = if params.enable_sha256_txn_commitment_header {
+            root_digest(self.txn_merkle_tree_sha256()?.root())?
+        } else {
+            Default::default()
+        };
+        Ok(TxnCommitments {
+            native_sha512_256_commitment,
+            sha256_commitment,
+        })
+    }
+}
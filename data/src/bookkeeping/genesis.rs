@@ -1,14 +1,38 @@
-use std::{collections::HashMap, error::Error};
+use std::collections::HashMap;
 
 use crate::{
-    basics::{self, AccountData, Address},
+    basics::{self, AccountData, Address, AddressError},
     bookkeeping::block::{self, RewardsState},
+    transactions,
 };
 use crypto::util::HashDigest;
 use protocol::{ConsensusVersion, NetworkId};
 use serde::{Deserialize, Serialize};
 
-pub type GenesisResult<T> = Result<T, Box<dyn std::error::Error>>;
+/// GenesisError is an error in the genesis of a network: its allocation
+/// or the protocol it starts with.
+#[derive(Debug, thiserror::Error)]
+pub enum GenesisError {
+    /// One of the addresses of the genesis does not decode. `role` names
+    /// it: an allocation, the fee sink or the rewards pool.
+    #[error("cannot parse {role} addr {address:?}")]
+    Address {
+        role: &'static str,
+        address: String,
+        source: AddressError,
+    },
+    #[error("repeated allocation to {0:?}")]
+    RepeatedAllocation(Address),
+    /// The rewards pool must be allocated to set the initial rewards rate.
+    #[error("rewards pool {0:?} has no allocation")]
+    MissingRewardsPool(Address),
+    #[error("unable to get protocol map")]
+    ConsensusUnavailable,
+    #[error("unsupported protocol {0}")]
+    UnsupportedProtocol(ConsensusVersion),
+}
+
+pub type GenesisResult<T> = Result<T, GenesisError>;
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 #[serde(default)]
@@ -36,31 +60,15 @@ impl Genesis {
     pub fn balances(&self) -> GenesisResult<GenesisBalances> {
         let mut gen_alloc = HashMap::new();
         for entry in self.allocation.clone().into_iter() {
-            let addr = basics::unmarshal_checksum_address(&entry.address).map_err(
-                |err| -> Box<dyn Error> {
-                    format!("cannot parse genesis addr {}: {}", &entry.address, err).into()
-                },
-            )?;
+            let addr = parse_address("genesis", &entry.address)?;
             let is_presesent = gen_alloc.get(&addr).is_some();
             if is_presesent {
-                return Err(format!("repeated allocation to {:?}", &addr).into());
+                return Err(GenesisError::RepeatedAllocation(addr));
             }
             gen_alloc.insert(addr, entry.state);
         }
-        let fee_sink = basics::unmarshal_checksum_address(&self.fee_sink).map_err(
-            |err| -> Box<dyn Error> {
-                format!("cannot parse fee sink addr {:?}: {}", &self.fee_sink, err).into()
-            },
-        )?;
-        let rewards_pool = basics::unmarshal_checksum_address(&self.rewards_pool).map_err(
-            |err| -> Box<dyn Error> {
-                format!(
-                    "cannot parse rewards pool addr {:?}: {}",
-                    &self.fee_sink, err
-                )
-                .into()
-            },
-        )?;
+        let fee_sink = parse_address("fee sink", &self.fee_sink)?;
+        let rewards_pool = parse_address("rewards pool", &self.rewards_pool)?;
         Ok(GenesisBalances::new_with_timestamp(
             gen_alloc,
             fee_sink,
@@ -74,6 +82,14 @@ impl Genesis {
     }
 }
 
+fn parse_address(role: &'static str, address: &String) -> GenesisResult<Address> {
+    basics::unmarshal_checksum_address(address).map_err(|source| GenesisError::Address {
+        role,
+        address: address.clone(),
+        source,
+    })
+}
+
 impl crypto::util::MsgpHashable for Genesis {
     fn hash_id(&self) -> protocol::HashId {
         protocol::GENESIS
@@ -119,13 +135,13 @@ pub fn make_genesis_block(
     genesis_id: String,
     genesis_hash: HashDigest,
 ) -> GenesisResult<block::Block> {
-    let consensus = match config::consensus::CONSENSUS.get().unwrap().read() {
-        Ok(p) => p,
-        Err(e) => return Err(format!("unable to get protocol map {:?}", e).into()),
+    let consensus = match config::consensus::CONSENSUS.get().map(|c| c.read()) {
+        Some(Ok(p)) => p,
+        _ => return Err(GenesisError::ConsensusUnavailable),
     };
     let params = match consensus.get(&proto) {
         Some(p) => p,
-        None => return Err(format!("unsupported protocol {}", proto).into()),
+        None => return Err(GenesisError::UnsupportedProtocol(proto)),
     };
 
     let mut genesis_rewards_state = RewardsState {
@@ -137,7 +153,7 @@ pub fn make_genesis_block(
     let initial_rewards = genesis_bal
         .balances
         .get(&genesis_bal.rewards_pool)
-        .unwrap()
+        .ok_or(GenesisError::MissingRewardsPool(genesis_bal.rewards_pool))?
         .microalgos
         .0;
     if params.initial_rewards_rate_calculation {
@@ -148,20 +164,19 @@ pub fn make_genesis_block(
     }
     let mut blk = block::Block {
         header: block::BlockHeader {
-            //seed: committee::Seed::from(genesis_hash),
-            //    txn_commitments: block::TxnCommitments{
-            //        native_sha512_256_commitment:
-            //    },
+            seed: genesis_hash.0,
+            txn_commitments: block::TxnCommitments {
+                native_sha512_256_commitment: transactions::payset::PaySet::default()
+                    .commit_genesis(),
+                ..Default::default()
+            },
             timestamp: genesis_bal.timestamp,
             genesis_id,
-            //    genesis_hash: (),
             rewards_state: genesis_rewards_state,
-            //    upgrade_state: (),
-            //    upgrade_vote: (),
-            //    txn_counter: (),
-            //    participation_updates: (),
-            //    ..Default::default()
-            //},
+            upgrade_state: block::UpgradeState {
+                current_protocol: proto,
+                ..Default::default()
+            },
             ..Default::default()
         },
         ..Default::default()
@@ -187,4 +202,33 @@ mod test {
             "83a461646472a0a7636f6d6d656e74a0a5737461746580"
         );
     }
+
+    #[test]
+    fn balances_report_bad_addresses() {
+        let sink = Address::from([1; 32]).string();
+        let mut genesis = Genesis {
+            fee_sink: sink.clone(),
+            rewards_pool: sink.clone(),
+            allocation: vec![GenesisAllocation {
+                address: "not an address".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert!(matches!(
+            genesis.balances(),
+            Err(GenesisError::Address {
+                role: "genesis",
+                source: AddressError::Base32(_),
+                ..
+            })
+        ));
+
+        genesis.allocation[0].address = sink.clone();
+        genesis.allocation.push(genesis.allocation[0].clone());
+        assert!(matches!(
+            genesis.balances(),
+            Err(GenesisError::RepeatedAllocation(_))
+        ));
+    }
 }
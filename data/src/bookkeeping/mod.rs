@@ -1,2 +1,3 @@
 pub mod genesis;
 pub mod block;
+pub mod txn_merkle;
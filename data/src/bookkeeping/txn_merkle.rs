@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+
+use crypto::merklearray::{self, Array, MerkleResult, Proof, Tree};
+use crypto::util::{HashDigest, HashType};
+use serde::{Deserialize, Serialize};
+
+use super::block::{Block, BlockHeader, BlockResult};
+use crate::transactions::{signedtxn::SignedTxnInBlock, transaction::Transaction};
+
+/// TxnMerkleElem represents a leaf in the Merkle tree of all transactions
+/// in a block. The leaf commits to the transaction id as well as to the
+/// hash of the entire SignedTxnInBlock.
+#[derive(Debug, Clone, Copy)]
+pub struct TxnMerkleElem {
+    pub txid: HashDigest,
+    pub stib_hash: HashDigest,
+}
+
+impl TxnMerkleElem {
+    pub fn new(txn: &Transaction, stib_hash: HashDigest, hash_type: HashType) -> Self {
+        let txid = match hash_type {
+            HashType::Sha512_256 => txn.id(),
+            HashType::Sha256 => txn.id_sha256(),
+        };
+        Self { txid, stib_hash }
+    }
+
+    pub fn hash_rep(&self) -> Vec<u8> {
+        let prefix = protocol::TXN_MERKLE_LEAF.as_bytes();
+        let mut buf = Vec::with_capacity(prefix.len() + 2 * crypto::util::DIGEST_SIZE);
+        buf.extend_from_slice(prefix);
+        buf.extend_from_slice(&self.txid.0);
+        buf.extend_from_slice(&self.stib_hash.0);
+        buf
+    }
+}
+
+fn stib_hash(stib: &SignedTxnInBlock, hash_type: HashType) -> HashDigest {
+    match hash_type {
+        HashType::Sha512_256 => stib.hash(),
+        HashType::Sha256 => HashType::Sha256.hash(&crypto::util::hash_rep(stib)),
+    }
+}
+
+/// TxnMerkleArray is an `Array` over the payset of a block.
+struct TxnMerkleArray<'a> {
+    block: &'a Block,
+    hash_type: HashType,
+}
+
+impl Array for TxnMerkleArray<'_> {
+    fn length(&self) -> u64 {
+        self.block.payset.0.len() as u64
+    }
+
+    fn marshal(&self, pos: u64) -> MerkleResult<Vec<u8>> {
+        let stib = self
+            .block
+            .payset
+            .0
+            .get(pos as usize)
+            .ok_or_else(|| format!("txn merkle position {} is out of bound", pos))?;
+        let txn = &stib.sigend_txn_with_ad.signed_txn.txn;
+        Ok(TxnMerkleElem::new(txn, stib_hash(stib, self.hash_type), self.hash_type).hash_rep())
+    }
+}
+
+/// TxnInclusionProof proves that a transaction is part of the payset
+/// committed to by a block header.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TxnInclusionProof {
+    #[serde(rename = "idx")]
+    pub idx: u64,
+    #[serde(rename = "stibhash")]
+    pub stib_hash: HashDigest,
+    #[serde(rename = "proof")]
+    pub proof: Proof,
+}
+
+pub(super) fn root_digest(root: merklearray::GenericDigest) -> BlockResult<HashDigest> {
+    if root.is_empty() {
+        return Ok(HashDigest::default());
+    }
+    let bytes: [u8; crypto::util::DIGEST_SIZE] = root
+        .try_into()
+        .map_err(|_| "merkle root has an unexpected length")?;
+    Ok(HashDigest(bytes))
+}
+
+impl Block {
+    /// Returns a Merkle tree of the transactions in this block, using
+    /// SHA-512/256.
+    pub fn txn_merkle_tree(&self) -> BlockResult<Tree> {
+        Tree::build(
+            &TxnMerkleArray {
+                block: self,
+                hash_type: HashType::Sha512_256,
+            },
+            HashType::Sha512_256,
+        )
+    }
+
+    /// Returns a vector commitment of the transactions in this block, using
+    /// SHA-256.
+    pub fn txn_merkle_tree_sha256(&self) -> BlockResult<Tree> {
+        Tree::build_vector_commitment(
+            &TxnMerkleArray {
+                block: self,
+                hash_type: HashType::Sha256,
+            },
+            HashType::Sha256,
+        )
+    }
+
+    /// Proves that the transaction at `idx` is part of the commitment of
+    /// type `hash_type` in the header of this block.
+    pub fn txn_inclusion_proof(
+        &self,
+        idx: u64,
+        hash_type: HashType,
+    ) -> BlockResult<TxnInclusionProof> {
+        let tree = match hash_type {
+            HashType::Sha512_256 => self.txn_merkle_tree()?,
+            HashType::Sha256 => self.txn_merkle_tree_sha256()?,
+        };
+        let stib = self
+            .payset
+            .0
+            .get(idx as usize)
+            .ok_or_else(|| format!("transaction index {} is out of bound", idx))?;
+        Ok(TxnInclusionProof {
+            idx,
+            stib_hash: stib_hash(stib, hash_type),
+            proof: tree.prove_single_leaf(idx)?,
+        })
+    }
+}
+
+impl BlockHeader {
+    /// Verifies that `txn` is included in the block of this header.
+    pub fn verify_txn_inclusion(
+        &self,
+        txn: &Transaction,
+        proof: &TxnInclusionProof,
+    ) -> BlockResult<()> {
+        let hash_type = proof.proof.hash_type;
+        let elem = TxnMerkleElem::new(txn, proof.stib_hash, hash_type);
+        let elems = BTreeMap::from([(proof.idx, elem.hash_rep())]);
+        match hash_type {
+            HashType::Sha512_256 => merklearray::verify(
+                &self.txn_commitments.native_sha512_256_commitment.0,
+                &elems,
+                &proof.proof,
+            ),
+            HashType::Sha256 => merklearray::verify_vector_commitment(
+                &self.txn_commitments.sha256_commitment.0,
+                &elems,
+                &proof.proof,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transactions::payset::PaySet;
+
+    fn commit(block: &mut Block) {
+        block.header.txn_commitments.native_sha512_256_commitment =
+            root_digest(block.txn_merkle_tree().unwrap().root()).unwrap();
+        block.header.txn_commitments.sha256_commitment =
+            root_digest(block.txn_merkle_tree_sha256().unwrap().root()).unwrap();
+    }
+
+    #[test]
+    fn txn_inclusion_proofs() {
+        let txns: Vec<Transaction> = (0..5)
+            .map(|i| {
+                let mut txn = Transaction::default();
+                txn.header.first_valid = i;
+                txn.header.note = vec![i as u8];
+                txn
+            })
+            .collect();
+        let payset = txns
+            .iter()
+            .enumerate()
+            .map(|(i, txn)| {
+                let mut stib = SignedTxnInBlock {
+                    has_genesis_id: i % 2 == 0,
+                    has_genesis_hash: i % 3 == 0,
+                    ..Default::default()
+                };
+                stib.sigend_txn_with_ad.signed_txn.txn = txn.clone();
+                stib
+            })
+            .collect();
+        let mut block = Block {
+            payset: PaySet(payset),
+            ..Default::default()
+        };
+        commit(&mut block);
+
+        for hash_type in [HashType::Sha512_256, HashType::Sha256] {
+            for idx in 0..5 {
+                let proof = block.txn_inclusion_proof(idx, hash_type).unwrap();
+                let txn = &txns[idx as usize];
+                assert!(block.header.verify_txn_inclusion(txn, &proof).is_ok());
+
+                // The proof holds only for the transaction at its position.
+                let next = &txns[(idx as usize + 1) % 5];
+                assert!(block.header.verify_txn_inclusion(next, &proof).is_err());
+                let mut bad = proof.clone();
+                bad.idx = (idx + 1) % 5;
+                assert!(block.header.verify_txn_inclusion(txn, &bad).is_err());
+            }
+        }
+        assert!(block.txn_inclusion_proof(5, HashType::Sha256).is_err());
+
+        // Both commitments depend on the order of the payset.
+        let mut swapped = Block {
+            payset: PaySet(block.payset.0.clone()),
+            ..Default::default()
+        };
+        swapped.payset.0.swap(1, 2);
+        commit(&mut swapped);
+        let (ours, theirs) = (
+            &block.header.txn_commitments,
+            &swapped.header.txn_commitments,
+        );
+        assert_ne!(
+            ours.native_sha512_256_commitment,
+            theirs.native_sha512_256_commitment
+        );
+        assert_ne!(ours.sha256_commitment, theirs.sha256_commitment);
+        let proof = swapped.txn_inclusion_proof(1, HashType::Sha256).unwrap();
+        assert!(swapped
+            .header
+            .verify_txn_inclusion(&txns[2], &proof)
+            .is_ok());
+        assert!(block.header.verify_txn_inclusion(&txns[2], &proof).is_err());
+    }
+}
@@ -0,0 +1,514 @@
+use std::collections::BTreeMap;
+
+use super::{apply, asset, ApplyResult, Balances};
+use crate::basics::{
+    application_address, AccountData, Address, AppIndex, AppLocalState, AppParams, AssetHolding,
+    AssetIndex, AssetParams, CreatableType, MicroAlgos, Round, StateSchemas, TealValue, ValueDelta,
+};
+use crate::bookkeeping::block::BlockHeader;
+use crate::transactions::application::OnCompletion;
+use crate::transactions::logic::{
+    self, EvalParams, GroupPools, LedgerForLogic, LogicResult, MAX_APP_CALL_DEPTH,
+};
+use crate::transactions::signedtxn::SignedTxnWithAD;
+use crate::transactions::teal::EvalDelta;
+use crate::transactions::transaction::Transaction;
+
+/// Returns the parameters and the creator of application `app`.
+fn app_params(balances: &dyn Balances, app: AppIndex) -> Option<(AppParams, Address)> {
+    let creator = balances.get_creator(app, CreatableType::AppCreatable)?;
+    let params = balances.get(&creator).app_params.get(&app)?.clone();
+    Some((params, creator))
+}
+
+/// Applies an application call: creates, opts in to, calls, closes out of,
+/// clears, updates or deletes an application, running its programs.
+pub(super) fn application_call(
+    balances: &mut dyn Balances,
+    group: &mut [SignedTxnWithAD],
+    group_index: usize,
+    call_stack: &[AppIndex],
+    pools: &GroupPools,
+) -> ApplyResult<()> {
+    let txn = group[group_index].signed_txn.txn.clone();
+    let sender = txn.sender();
+    let ac = &txn.application_call_txn_fields;
+
+    let mut app_id = ac.application_id;
+    if app_id == 0 {
+        app_id = create_application(balances, &txn)?;
+        group[group_index].apply_data.application_id = app_id;
+    }
+
+    if call_stack.len() >= MAX_APP_CALL_DEPTH {
+        return Err(format!("appl depth ({}) exceeded", call_stack.len()).into());
+    }
+    if call_stack.contains(&app_id) {
+        return Err(format!("attempt to re-enter {}", app_id).into());
+    }
+
+    let params = app_params(balances, app_id);
+    if params.is_none() && ac.on_completion != OnCompletion::ClearState {
+        return Err("only clearing out is supported for applications that do not exist".into());
+    }
+
+    if ac.on_completion == OnCompletion::ClearState {
+        if !balances.get(&sender).app_local_states.contains_key(&app_id) {
+            return Err(format!(
+                "cannot clear state: {} is not currently opted in to app {}",
+                sender.string(),
+                app_id
+            )
+            .into());
+        }
+        if let Some((params, _)) = params {
+            // The clear state program may fail; its changes only apply if
+            // it approves, but the local state is cleared either way.
+            let checkpoint = balances.checkpoint();
+            match eval_program(
+                balances,
+                group,
+                group_index,
+                app_id,
+                &params.clear_state_program,
+                call_stack,
+                pools,
+            ) {
+                Ok(Some(delta)) => group[group_index].apply_data.eval_delta = delta,
+                Ok(None) | Err(_) => balances.rollback(checkpoint),
+            }
+        }
+        return close_out_application(balances, &sender, app_id);
+    }
+
+    let (params, creator) = params.unwrap_or_default();
+    if !call_stack.is_empty() {
+        let version = logic::program_version(&params.approval_program)?;
+        let min = balances.proto().min_inner_appl_version;
+        if version < min {
+            return Err(format!("inner app call with version v{} < v{}", version, min).into());
+        }
+    }
+
+    if ac.on_completion == OnCompletion::OptIn {
+        opt_in_application(balances, &sender, app_id, &params)?;
+    }
+
+    let delta = eval_program(
+        balances,
+        group,
+        group_index,
+        app_id,
+        &params.approval_program,
+        call_stack,
+        pools,
+    )?
+    .ok_or("transaction rejected by ApprovalProgram")?;
+    group[group_index].apply_data.eval_delta = delta;
+
+    match ac.on_completion {
+        OnCompletion::NoOp | OnCompletion::OptIn | OnCompletion::ClearState => Ok(()),
+        OnCompletion::CloseOut => close_out_application(balances, &sender, app_id),
+        OnCompletion::DeleteApplication => delete_application(balances, &creator, app_id),
+        OnCompletion::UpdateApplication => {
+            let mut record = balances.get(&creator);
+            let stored = record
+                .app_params
+                .get_mut(&app_id)
+                .ok_or("application was deleted")?;
+            stored.approval_program = ac.approval_program.clone();
+            stored.clear_state_program = ac.clear_state_program.clone();
+            balances.put(&creator, record);
+            Ok(())
+        }
+    }
+}
+
+fn create_application(balances: &mut dyn Balances, txn: &Transaction) -> ApplyResult<AppIndex> {
+    let sender = txn.sender();
+    let ac = &txn.application_call_txn_fields;
+    let mut record = balances.get(&sender);
+    let max_apps_created = balances.proto().max_apps_created;
+    if max_apps_created > 0 && record.app_params.len() >= max_apps_created as usize {
+        return Err(format!(
+            "cannot create app for {}: max created apps per acct is {}",
+            sender.string(),
+            max_apps_created
+        )
+        .into());
+    }
+
+    let app_id = balances.txn_counter() + 1;
+    record.app_params.insert(
+        app_id,
+        AppParams {
+            approval_program: ac.approval_program.clone(),
+            clear_state_program: ac.clear_state_program.clone(),
+            global_state: Default::default(),
+            state_schemas: StateSchemas {
+                local_state_schema: ac.local_state_schema,
+                global_state_schema: ac.global_state_schema,
+            },
+            extra_program_pages: ac.extra_program_pages,
+        },
+    );
+    record.total_app_schema = record.total_app_schema.add_schema(&ac.global_state_schema);
+    record.total_extra_app_pages = record
+        .total_extra_app_pages
+        .saturating_add(ac.extra_program_pages);
+    balances.put(&sender, record);
+    balances.set_creator(app_id, CreatableType::AppCreatable, Some(sender));
+    Ok(app_id)
+}
+
+fn delete_application(
+    balances: &mut dyn Balances,
+    creator: &Address,
+    app_id: AppIndex,
+) -> ApplyResult<()> {
+    let mut record = balances.get(creator);
+    let params = record
+        .app_params
+        .remove(&app_id)
+        .ok_or("application was already deleted")?;
+    record.total_app_schema = record
+        .total_app_schema
+        .sub_schema(&params.state_schemas.global_state_schema);
+    record.total_extra_app_pages = record
+        .total_extra_app_pages
+        .saturating_sub(params.extra_program_pages);
+    balances.put(creator, record);
+    balances.set_creator(app_id, CreatableType::AppCreatable, None);
+    Ok(())
+}
+
+fn opt_in_application(
+    balances: &mut dyn Balances,
+    sender: &Address,
+    app_id: AppIndex,
+    params: &AppParams,
+) -> ApplyResult<()> {
+    let mut record = balances.get(sender);
+    if record.app_local_states.contains_key(&app_id) {
+        return Err(format!(
+            "account {} has already opted in to app {}",
+            sender.string(),
+            app_id
+        )
+        .into());
+    }
+    let max_apps_opted_in = balances.proto().max_apps_opted_in;
+    if max_apps_opted_in > 0 && record.app_local_states.len() >= max_apps_opted_in as usize {
+        return Err(format!(
+            "cannot opt in app {} for {}: max opted-in apps per acct is {}",
+            app_id,
+            sender.string(),
+            max_apps_opted_in
+        )
+        .into());
+    }
+    let schema = params.state_schemas.local_state_schema;
+    record.app_local_states.insert(
+        app_id,
+        AppLocalState {
+            schema,
+            key_value: Default::default(),
+        },
+    );
+    record.total_app_schema = record.total_app_schema.add_schema(&schema);
+    balances.put(sender, record);
+    Ok(())
+}
+
+fn close_out_application(
+    balances: &mut dyn Balances,
+    sender: &Address,
+    app_id: AppIndex,
+) -> ApplyResult<()> {
+    let mut record = balances.get(sender);
+    let local_state = record.app_local_states.remove(&app_id).ok_or_else(|| {
+        format!(
+            "account {} is not opted in to app {}",
+            sender.string(),
+            app_id
+        )
+    })?;
+    record.total_app_schema = record.total_app_schema.sub_schema(&local_state.schema);
+    balances.put(sender, record);
+    Ok(())
+}
+
+/// Runs `program` for the application call at `group_index`. Returns the
+/// state changes, logs and inner transactions of the evaluation if the
+/// program approved, and None if it rejected.
+fn eval_program(
+    balances: &mut dyn Balances,
+    group: &[SignedTxnWithAD],
+    group_index: usize,
+    app_id: AppIndex,
+    program: &[u8],
+    call_stack: &[AppIndex],
+    pools: &GroupPools,
+) -> ApplyResult<Option<EvalDelta>> {
+    let proto = balances.proto().clone();
+    let mut params = EvalParams::new(&proto, group);
+    params.caller_app_id = call_stack.last().copied().unwrap_or_default();
+    params.pools = Some(pools);
+
+    let mut call_stack = call_stack.to_vec();
+    call_stack.push(app_id);
+    let mut ledger = AppLedger {
+        balances,
+        app_id,
+        call_stack,
+        pools,
+        originals: BTreeMap::new(),
+    };
+    let result = logic::eval_contract(program, group_index, app_id, &params, &mut ledger)?;
+    if !result.pass {
+        return Ok(None);
+    }
+
+    let mut delta = EvalDelta {
+        logs: result.logs,
+        inner_txns: result.inner_txns,
+        ..Default::default()
+    };
+    let txn = &group[group_index].signed_txn.txn;
+    for ((addr, key), original) in &ledger.originals {
+        let current = match addr {
+            None => ledger.get_global(app_id, key),
+            Some(addr) => ledger.get_local(addr, app_id, key),
+        };
+        let Some(value_delta) = ValueDelta::between(original.as_ref(), current.as_ref()) else {
+            continue;
+        };
+        match addr {
+            None => {
+                delta.global_delta.insert(key.clone(), value_delta);
+            }
+            Some(addr) => {
+                let index = if *addr == txn.sender() {
+                    0
+                } else {
+                    let accounts = &txn.application_call_txn_fields.accounts;
+                    let pos = accounts.iter().position(|a| a == addr).ok_or_else(|| {
+                        format!(
+                            "local state of {} changed but it is not in Accounts",
+                            addr.string()
+                        )
+                    })?;
+                    pos as u64 + 1
+                };
+                delta
+                    .local_deltas
+                    .entry(index)
+                    .or_default()
+                    .insert(key.clone(), value_delta);
+            }
+        }
+    }
+    Ok(Some(delta))
+}
+
+/// AppLedger is the ledger seen by the programs of an application call. It
+/// writes through to the balances, remembering the original value of each
+/// key it writes to compute the EvalDelta.
+struct AppLedger<'b> {
+    balances: &'b mut dyn Balances,
+    app_id: AppIndex,
+    /// The applications whose calls led to this one, ending with it.
+    call_stack: Vec<AppIndex>,
+    pools: &'b GroupPools,
+    /// The values of the keys written, before the first write, by account
+    /// for local state and None for global state.
+    originals: BTreeMap<(Option<Address>, Vec<u8>), Option<TealValue>>,
+}
+
+impl AppLedger<'_> {
+    fn remember(&mut self, addr: Option<Address>, key: &[u8]) {
+        if self.originals.contains_key(&(addr, key.to_vec())) {
+            return;
+        }
+        let original = match addr {
+            None => self.get_global(self.app_id, key),
+            Some(addr) => self.get_local(&addr, self.app_id, key),
+        };
+        self.originals.insert((addr, key.to_vec()), original);
+    }
+
+    /// Modifies the local state of `addr` for the current application.
+    fn update_local(
+        &mut self,
+        addr: &Address,
+        key: &[u8],
+        update: impl FnOnce(&mut AppLocalState),
+    ) -> LogicResult<()> {
+        self.remember(Some(*addr), key);
+        let mut record = self.balances.get(addr);
+        let local_state = record
+            .app_local_states
+            .get_mut(&self.app_id)
+            .ok_or_else(|| format!("{} has not opted in to app {}", addr.string(), self.app_id))?;
+        update(local_state);
+        local_state.schema.allows(&local_state.key_value)?;
+        self.balances.put(addr, record);
+        Ok(())
+    }
+
+    /// Modifies the global state of the current application.
+    fn update_global(
+        &mut self,
+        key: &[u8],
+        update: impl FnOnce(&mut AppParams),
+    ) -> LogicResult<()> {
+        self.remember(None, key);
+        let creator = self
+            .balances
+            .get_creator(self.app_id, CreatableType::AppCreatable)
+            .ok_or_else(|| format!("app {} does not exist", self.app_id))?;
+        let mut record = self.balances.get(&creator);
+        let params = record
+            .app_params
+            .get_mut(&self.app_id)
+            .ok_or_else(|| format!("app {} does not exist", self.app_id))?;
+        update(params);
+        params
+            .state_schemas
+            .global_state_schema
+            .allows(&params.global_state)?;
+        self.balances.put(&creator, record);
+        Ok(())
+    }
+}
+
+impl LedgerForLogic for AppLedger<'_> {
+    fn round(&self) -> Round {
+        self.balances.round()
+    }
+
+    fn latest_timestamp(&self) -> i64 {
+        self.balances.prev_timestamp()
+    }
+
+    fn block_header(&self, round: Round) -> Option<BlockHeader> {
+        self.balances.block_header(round)
+    }
+
+    fn balance(&self, addr: &Address) -> MicroAlgos {
+        self.balances.get(addr).microalgos
+    }
+
+    fn min_balance(&self, addr: &Address) -> MicroAlgos {
+        self.balances.get(addr).min_balance(self.balances.proto())
+    }
+
+    fn auth_addr(&self, addr: &Address) -> Address {
+        self.balances.get(addr).auth_addr
+    }
+
+    fn app_params(&self, app: AppIndex) -> Option<(AppParams, Address)> {
+        app_params(&*self.balances, app)
+    }
+
+    fn asset_holding(&self, addr: &Address, asset: AssetIndex) -> Option<AssetHolding> {
+        self.balances.get_asset(addr, asset).holding
+    }
+
+    fn asset_params(&self, asset: AssetIndex) -> Option<(AssetParams, Address)> {
+        asset::asset_params(&*self.balances, asset)
+    }
+
+    fn opted_in(&self, addr: &Address, app: AppIndex) -> bool {
+        self.balances.get(addr).app_local_states.contains_key(&app)
+    }
+
+    fn get_local(&self, addr: &Address, app: AppIndex, key: &[u8]) -> Option<TealValue> {
+        let record: AccountData = self.balances.get(addr);
+        record
+            .app_local_states
+            .get(&app)?
+            .key_value
+            .get(key)
+            .cloned()
+    }
+
+    fn set_local(
+        &mut self,
+        addr: &Address,
+        app: AppIndex,
+        key: &[u8],
+        value: TealValue,
+    ) -> LogicResult<()> {
+        if app != self.app_id {
+            return Err(format!("cannot write local state of app {}", app).into());
+        }
+        self.update_local(addr, key, |state| {
+            state.key_value.insert(key.to_vec(), value);
+        })
+    }
+
+    fn del_local(&mut self, addr: &Address, app: AppIndex, key: &[u8]) -> LogicResult<()> {
+        if app != self.app_id {
+            return Err(format!("cannot write local state of app {}", app).into());
+        }
+        self.update_local(addr, key, |state| {
+            state.key_value.remove(key);
+        })
+    }
+
+    fn get_global(&self, app: AppIndex, key: &[u8]) -> Option<TealValue> {
+        let (params, _) = app_params(&*self.balances, app)?;
+        params.global_state.get(key).cloned()
+    }
+
+    fn set_global(&mut self, app: AppIndex, key: &[u8], value: TealValue) -> LogicResult<()> {
+        if app != self.app_id {
+            return Err(format!("cannot write global state of app {}", app).into());
+        }
+        self.update_global(key, |params| {
+            params.global_state.insert(key.to_vec(), value);
+        })
+    }
+
+    fn del_global(&mut self, app: AppIndex, key: &[u8]) -> LogicResult<()> {
+        if app != self.app_id {
+            return Err(format!("cannot write global state of app {}", app).into());
+        }
+        self.update_global(key, |params| {
+            params.global_state.remove(key);
+        })
+    }
+
+    fn perform(&mut self, group: &mut [SignedTxnWithAD]) -> LogicResult<()> {
+        let app_address = application_address(self.app_id);
+        for group_index in 0..group.len() {
+            // The application authorizes its own account, unless rekeyed
+            // away, and the accounts rekeyed to it.
+            let txn = &group[group_index].signed_txn.txn;
+            let auth_addr = self.auth_addr(&txn.sender());
+            let authorizer = if auth_addr.is_zero() {
+                txn.sender()
+            } else {
+                auth_addr
+            };
+            if authorizer != app_address {
+                return Err(format!(
+                    "unauthorized inner transaction from {} by app {}",
+                    txn.sender().string(),
+                    self.app_id
+                )
+                .into());
+            }
+            txn.well_formed(self.balances.proto())?;
+            apply(
+                self.balances,
+                group,
+                group_index,
+                &self.call_stack,
+                self.pools,
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,328 @@
+use super::{ApplyResult, Balances};
+use crate::basics::{Address, AssetHolding, AssetIndex, AssetParams, CreatableType};
+use crate::transactions::transaction::{ApplyData, Transaction};
+
+/// Returns the parameters and the creator of `asset`.
+pub(super) fn asset_params(
+    balances: &dyn Balances,
+    asset: AssetIndex,
+) -> Option<(AssetParams, Address)> {
+    let creator = balances.get_creator(asset, CreatableType::AssetCreatable)?;
+    let params = balances.get_asset(&creator, asset).params?;
+    Some((params, creator))
+}
+
+fn get_params(balances: &dyn Balances, asset: AssetIndex) -> ApplyResult<(AssetParams, Address)> {
+    asset_params(balances, asset)
+        .ok_or_else(|| format!("asset {} does not exist or has been deleted", asset).into())
+}
+
+/// Allocates a holding of `asset` in `addr`, counting it in the account's
+/// totals.
+fn allocate_holding(
+    balances: &mut dyn Balances,
+    addr: &Address,
+    asset: AssetIndex,
+    holding: AssetHolding,
+) -> ApplyResult<()> {
+    let mut record = balances.get(addr);
+    let max = balances.proto().max_assets_per_account;
+    if max > 0 && record.total_assets >= max as u64 {
+        return Err(format!(
+            "too many assets in account: {} >= {}",
+            record.total_assets, max
+        )
+        .into());
+    }
+    record.total_assets += 1;
+    balances.put(addr, record);
+
+    let mut resource = balances.get_asset(addr, asset);
+    resource.holding = Some(holding);
+    balances.put_asset(addr, asset, resource);
+    Ok(())
+}
+
+fn deallocate_holding(balances: &mut dyn Balances, addr: &Address, asset: AssetIndex) {
+    let mut record = balances.get(addr);
+    record.total_assets = record.total_assets.saturating_sub(1);
+    balances.put(addr, record);
+
+    let mut resource = balances.get_asset(addr, asset);
+    resource.holding = None;
+    balances.put_asset(addr, asset, resource);
+}
+
+/// Applies an asset configuration: creates, re-configures or destroys an
+/// asset.
+pub(super) fn asset_config(
+    balances: &mut dyn Balances,
+    txn: &Transaction,
+    ad: &mut ApplyData,
+) -> ApplyResult<()> {
+    let sender = txn.sender();
+    let cc = &txn.asset_config_txn_fields;
+
+    if cc.config_asset == 0 {
+        // Allocating an asset; the creator holds every unit.
+        let asset = balances.txn_counter() + 1;
+        if balances
+            .get_creator(asset, CreatableType::AssetCreatable)
+            .is_some()
+        {
+            return Err(format!("already found asset with index {}", asset).into());
+        }
+        let holding = AssetHolding {
+            amount: cc.asset_params.total,
+            frozen: false,
+        };
+        allocate_holding(balances, &sender, asset, holding)?;
+
+        let mut record = balances.get(&sender);
+        record.total_asset_params += 1;
+        balances.put(&sender, record);
+        let mut resource = balances.get_asset(&sender, asset);
+        resource.params = Some(cc.asset_params.clone());
+        balances.put_asset(&sender, asset, resource);
+
+        balances.set_creator(asset, CreatableType::AssetCreatable, Some(sender));
+        ad.config_asset = asset;
+        return Ok(());
+    }
+
+    // Re-configuration and destroying must be done by the manager key.
+    let (mut params, creator) = get_params(balances, cc.config_asset)?;
+    if params.manager.is_zero() || sender != params.manager {
+        return Err(format!(
+            "this transaction should be issued by the manager. It is issued by {}, manager key {}",
+            sender.string(),
+            params.manager.string()
+        )
+        .into());
+    }
+
+    let mut resource = balances.get_asset(&creator, cc.config_asset);
+    if cc.asset_params == AssetParams::default() {
+        // Destroying an asset: the creator must hold every unit.
+        let held = resource
+            .holding
+            .map(|holding| holding.amount)
+            .unwrap_or_default();
+        if held != params.total {
+            return Err(format!(
+                "cannot destroy asset: creator is holding only {}/{}",
+                held, params.total
+            )
+            .into());
+        }
+        resource.params = None;
+        balances.put_asset(&creator, cc.config_asset, resource);
+        deallocate_holding(balances, &creator, cc.config_asset);
+
+        let mut record = balances.get(&creator);
+        record.total_asset_params = record.total_asset_params.saturating_sub(1);
+        balances.put(&creator, record);
+        balances.set_creator(cc.config_asset, CreatableType::AssetCreatable, None);
+    } else {
+        // Changing keys in an asset. Cleared keys can not be set again.
+        if !params.manager.is_zero() {
+            params.manager = cc.asset_params.manager;
+        }
+        if !params.reserve.is_zero() {
+            params.reserve = cc.asset_params.reserve;
+        }
+        if !params.freeze.is_zero() {
+            params.freeze = cc.asset_params.freeze;
+        }
+        if !params.clawback.is_zero() {
+            params.clawback = cc.asset_params.clawback;
+        }
+        resource.params = Some(params);
+        balances.put_asset(&creator, cc.config_asset, resource);
+    }
+    Ok(())
+}
+
+fn take_out(
+    balances: &mut dyn Balances,
+    addr: &Address,
+    asset: AssetIndex,
+    amount: u64,
+    bypass_freeze: bool,
+) -> ApplyResult<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let mut resource = balances.get_asset(addr, asset);
+    let holding = resource
+        .holding
+        .as_mut()
+        .ok_or_else(|| format!("asset {} missing from {}", asset, addr.string()))?;
+    if holding.frozen && !bypass_freeze {
+        return Err(format!("asset {} frozen in {}", asset, addr.string()).into());
+    }
+    holding.amount = holding.amount.checked_sub(amount).ok_or_else(|| {
+        format!(
+            "underflow on subtracting {} from sender amount {}",
+            amount, holding.amount
+        )
+    })?;
+    balances.put_asset(addr, asset, resource);
+    Ok(())
+}
+
+fn put_in(
+    balances: &mut dyn Balances,
+    addr: &Address,
+    asset: AssetIndex,
+    amount: u64,
+    bypass_freeze: bool,
+) -> ApplyResult<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let mut resource = balances.get_asset(addr, asset);
+    let holding = resource
+        .holding
+        .as_mut()
+        .ok_or_else(|| format!("asset {} missing from {}", asset, addr.string()))?;
+    if holding.frozen && !bypass_freeze {
+        return Err(format!("asset frozen in recipient {}", addr.string()).into());
+    }
+    holding.amount = holding.amount.checked_add(amount).ok_or_else(|| {
+        format!(
+            "overflow on adding {} to receiver amount {}",
+            amount, holding.amount
+        )
+    })?;
+    balances.put_asset(addr, asset, resource);
+    Ok(())
+}
+
+/// Applies an asset transfer: opts in to, transfers, claws back or closes
+/// out of an asset.
+pub(super) fn asset_transfer(
+    balances: &mut dyn Balances,
+    txn: &Transaction,
+    ad: &mut ApplyData,
+) -> ApplyResult<()> {
+    let sender = txn.sender();
+    let ct = &txn.asset_transfer_txn_fields;
+
+    // Transfers are sent from the sender, unless the sender claws the
+    // asset back from `asset_sender`.
+    let mut source = sender;
+    let clawback = !ct.asset_sender.is_zero();
+    if clawback {
+        let (params, _) = get_params(balances, ct.xfer_asset)?;
+        if params.clawback.is_zero() || sender != params.clawback {
+            return Err(format!(
+                "clawback not allowed: sender {}, clawback {}",
+                sender.string(),
+                params.clawback.string()
+            )
+            .into());
+        }
+        source = ct.asset_sender;
+    }
+
+    // A zero transfer to self opts in to the asset.
+    let opt_in = ct.asset_amount == 0 && ct.asset_receiver == source && !clawback;
+    if opt_in && balances.get_asset(&source, ct.xfer_asset).holding.is_none() {
+        let (params, _) = get_params(balances, ct.xfer_asset)?;
+        let holding = AssetHolding {
+            amount: 0,
+            frozen: params.default_frozen,
+        };
+        allocate_holding(balances, &source, ct.xfer_asset, holding)?;
+    }
+
+    // Zero transfers succeed without looking at the accounts, so a zero
+    // transfer to the zero address may be used to just close out.
+    take_out(balances, &source, ct.xfer_asset, ct.asset_amount, clawback)?;
+    put_in(
+        balances,
+        &ct.asset_receiver,
+        ct.xfer_asset,
+        ct.asset_amount,
+        clawback,
+    )?;
+
+    if !ct.asset_close_to.is_zero() {
+        if clawback {
+            return Err("cannot close asset by clawback".into());
+        }
+        let resource = balances.get_asset(&source, ct.xfer_asset);
+        // The creator can not close its holding of the asset.
+        if resource.params.is_some() {
+            return Err("cannot close asset ID in allocating account".into());
+        }
+        let holding = resource.holding.ok_or_else(|| {
+            format!(
+                "asset {} not present in account {}",
+                ct.xfer_asset,
+                source.string()
+            )
+        })?;
+
+        // Closing out to the creator is allowed even when frozen.
+        let bypass_freeze = balances
+            .get_asset(&ct.asset_close_to, ct.xfer_asset)
+            .params
+            .is_some();
+        if balances.proto().enable_asset_close_amount {
+            ad.asset_closing_amount = holding.amount;
+        }
+        take_out(
+            balances,
+            &source,
+            ct.xfer_asset,
+            holding.amount,
+            bypass_freeze,
+        )?;
+        put_in(
+            balances,
+            &ct.asset_close_to,
+            ct.xfer_asset,
+            holding.amount,
+            bypass_freeze,
+        )?;
+        let left = balances
+            .get_asset(&source, ct.xfer_asset)
+            .holding
+            .unwrap_or_default()
+            .amount;
+        if left != 0 {
+            return Err(
+                format!("asset {} not zero ({}) after closing", ct.xfer_asset, left).into(),
+            );
+        }
+        deallocate_holding(balances, &source, ct.xfer_asset);
+    }
+    Ok(())
+}
+
+/// Applies an asset freeze, which only the freeze address of the asset may
+/// send.
+pub(super) fn asset_freeze(balances: &mut dyn Balances, txn: &Transaction) -> ApplyResult<()> {
+    let sender = txn.sender();
+    let cf = &txn.asset_freeze_txn_fields;
+    let (params, _) = get_params(balances, cf.freeze_asset)?;
+    if params.freeze.is_zero() || sender != params.freeze {
+        return Err(format!(
+            "freeze not allowed: sender {}, freeze {}",
+            sender.string(),
+            params.freeze.string()
+        )
+        .into());
+    }
+
+    let mut resource = balances.get_asset(&cf.freeze_account, cf.freeze_asset);
+    let holding = resource
+        .holding
+        .as_mut()
+        .ok_or("asset not found in account")?;
+    holding.frozen = cf.asset_frozen;
+    balances.put_asset(&cf.freeze_account, cf.freeze_asset, resource);
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use super::{ApplyResult, Balances};
+use crate::basics::Status;
+use crate::transactions::transaction::Transaction;
+
+/// Registers the participation keys of the sender, bringing it online, or
+/// takes it offline or marks it non-participating if the keys are empty.
+pub(super) fn keyreg(balances: &mut dyn Balances, txn: &Transaction) -> ApplyResult<()> {
+    let sender = txn.sender();
+    if sender == balances.fee_sink() {
+        return Err(format!(
+            "cannot register participation key for fee sink's address {}",
+            sender.string()
+        )
+        .into());
+    }
+    let keyreg = &txn.keyreg_txn_fields;
+    let mut record = balances.get(&sender);
+    if record.status == Status::NotParticipating {
+        return Err(format!(
+            "cannot change online/offline status of non-participating account {}",
+            sender.string()
+        )
+        .into());
+    }
+
+    let proto = balances.proto();
+    record.vote_id = keyreg.vote_pk;
+    record.selection_id = keyreg.selection_pk;
+    if proto.enable_state_proof_keyreg_check {
+        record.state_proof_id = keyreg.state_proof_pk.clone();
+    }
+    if keyreg.vote_pk == [0; 32] || keyreg.selection_pk == [0; 32] {
+        record.status = if keyreg.nonparticipation {
+            if !proto.support_become_non_participating_transactions {
+                return Err("transaction tries to mark an account as nonparticipating, but that transaction is not supported".into());
+            }
+            Status::NotParticipating
+        } else {
+            Status::Offline
+        };
+        record.vote_first_valid = 0;
+        record.vote_last_valid = 0;
+        record.vote_key_dilution = 0;
+    } else {
+        if proto.enable_keyreg_coherency_check {
+            let round = balances.round();
+            if keyreg.vote_last <= round {
+                return Err("transaction tries to mark an account as online with last voting round in the past".into());
+            }
+            if keyreg.vote_first > round + 1 {
+                return Err("transaction tries to mark an account as online with first voting round beyond the next voting round".into());
+            }
+        }
+        record.status = Status::Online;
+        record.vote_first_valid = keyreg.vote_first;
+        record.vote_last_valid = keyreg.vote_last;
+        record.vote_key_dilution = keyreg.vote_key_dilution;
+    }
+    balances.put(&sender, record);
+    Ok(())
+}
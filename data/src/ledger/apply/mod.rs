@@ -0,0 +1,148 @@
+//! Package apply applies the effects of individual transactions to the
+//! balances of a round.
+
+mod application;
+mod asset;
+mod keyreg;
+mod payment;
+
+use config::consensus::ConsensusParams;
+use protocol::TxType;
+
+use crate::basics::{
+    AccountData, Address, AppIndex, AssetIndex, AssetResource, CreatableType, MicroAlgos, Round,
+};
+use crate::bookkeeping::block::BlockHeader;
+use crate::transactions::logic::GroupPools;
+use crate::transactions::signedtxn::SignedTxnWithAD;
+use crate::transactions::transaction::ApplyData;
+
+pub type ApplyResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// Balances allows the apply functions to read and write the accounts of
+/// the round being evaluated.
+pub trait Balances {
+    fn proto(&self) -> &ConsensusParams;
+    /// The round being evaluated.
+    fn round(&self) -> Round;
+    /// The timestamp of the previous block.
+    fn prev_timestamp(&self) -> i64;
+    fn block_header(&self, round: Round) -> Option<BlockHeader>;
+    /// The account collecting transaction fees.
+    fn fee_sink(&self) -> Address;
+
+    fn get(&self, addr: &Address) -> AccountData;
+    fn put(&mut self, addr: &Address, data: AccountData);
+
+    /// Returns the holding and, for its creator, the parameters of `asset`
+    /// in the account `addr`.
+    fn get_asset(&self, addr: &Address, asset: AssetIndex) -> AssetResource;
+    /// Replaces the asset resource. The totals of the AccountData are
+    /// maintained by the caller.
+    fn put_asset(&mut self, addr: &Address, asset: AssetIndex, resource: AssetResource);
+
+    /// Returns the creator of an asset or application.
+    fn get_creator(&self, index: u64, ctype: CreatableType) -> Option<Address>;
+    /// Records the creation (`Some`) or deletion (`None`) of an asset or
+    /// application.
+    fn set_creator(&mut self, index: u64, ctype: CreatableType, creator: Option<Address>);
+
+    /// The number of transactions applied before the current one, counting
+    /// those of earlier blocks. New creatables are numbered after it.
+    fn txn_counter(&self) -> u64;
+    fn increment_txn_counter(&mut self);
+
+    /// Returns a checkpoint that the state can be rolled back to.
+    fn checkpoint(&self) -> usize;
+    /// Undoes every change made since `checkpoint`.
+    fn rollback(&mut self, checkpoint: usize);
+}
+
+/// Moves `amount` from `from` to `to`.
+pub fn move_algos(
+    balances: &mut dyn Balances,
+    from: &Address,
+    to: &Address,
+    amount: MicroAlgos,
+) -> ApplyResult<()> {
+    let mut from_data = balances.get(from);
+    from_data.microalgos.0 = from_data
+        .microalgos
+        .0
+        .checked_sub(amount.0)
+        .ok_or_else(|| {
+            format!(
+                "overspend (account {}, balance {}, tried to spend {})",
+                from.string(),
+                from_data.microalgos.0,
+                amount.0
+            )
+        })?;
+    balances.put(from, from_data);
+
+    let mut to_data = balances.get(to);
+    to_data.microalgos.0 = to_data
+        .microalgos
+        .0
+        .checked_add(amount.0)
+        .ok_or_else(|| format!("balance overflow (account {})", to.string()))?;
+    balances.put(to, to_data);
+    Ok(())
+}
+
+/// Applies the transaction at `group_index` of `group` to `balances`: it
+/// pays the fee and performs the transaction, recording the effects in its
+/// ApplyData. `pools` are shared by the whole group.
+pub fn transaction(
+    balances: &mut dyn Balances,
+    group: &mut [SignedTxnWithAD],
+    group_index: usize,
+    pools: &GroupPools,
+) -> ApplyResult<()> {
+    apply(balances, group, group_index, &[], pools)
+}
+
+/// Applies a transaction on behalf of the applications in `call_stack`,
+/// which is empty for top level transactions.
+fn apply(
+    balances: &mut dyn Balances,
+    group: &mut [SignedTxnWithAD],
+    group_index: usize,
+    call_stack: &[AppIndex],
+    pools: &GroupPools,
+) -> ApplyResult<()> {
+    let txn = group[group_index].signed_txn.txn.clone();
+    let fee_sink = balances.fee_sink();
+    move_algos(balances, &txn.sender(), &fee_sink, txn.header.fee)?;
+
+    // Rekeying to the sender itself restores its own key.
+    let rekey_to = txn.header.rekey_to;
+    if !rekey_to.is_zero() {
+        let mut record = balances.get(&txn.sender());
+        record.auth_addr = if rekey_to == txn.sender() {
+            Address::default()
+        } else {
+            rekey_to
+        };
+        balances.put(&txn.sender(), record);
+    }
+
+    group[group_index].apply_data = ApplyData::default();
+    match txn.tx_type {
+        TxType::PaymentTx => payment::payment(balances, &txn, &mut group[group_index].apply_data)?,
+        TxType::KeyRegistrationTx => keyreg::keyreg(balances, &txn)?,
+        TxType::AssetConfigTx => {
+            asset::asset_config(balances, &txn, &mut group[group_index].apply_data)?
+        }
+        TxType::AssetTransferTx => {
+            asset::asset_transfer(balances, &txn, &mut group[group_index].apply_data)?
+        }
+        TxType::AssetFreezeTx => asset::asset_freeze(balances, &txn)?,
+        TxType::ApplicationCallTx => {
+            application::application_call(balances, group, group_index, call_stack, pools)?
+        }
+        other => return Err(format!("unknown transaction type {:?}", other).into()),
+    }
+    balances.increment_txn_counter();
+    Ok(())
+}
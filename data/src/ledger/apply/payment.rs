@@ -0,0 +1,48 @@
+use super::{move_algos, ApplyResult, Balances};
+use crate::basics::AccountData;
+use crate::transactions::transaction::{ApplyData, Transaction};
+
+/// Applies a payment, closing the sender if `close_remainder_to` is set.
+pub(super) fn payment(
+    balances: &mut dyn Balances,
+    txn: &Transaction,
+    ad: &mut ApplyData,
+) -> ApplyResult<()> {
+    let sender = txn.sender();
+    let pay = &txn.payment_txn_fields;
+    if pay.amount.0 != 0 || !pay.receiver.is_zero() {
+        move_algos(balances, &sender, &pay.receiver, pay.amount)?;
+    }
+
+    if !pay.close_remainder_to.is_zero() {
+        let record = balances.get(&sender);
+        if record.total_assets > 0 {
+            return Err(format!("cannot close: {} outstanding assets", record.total_assets).into());
+        }
+        if !record.app_local_states.is_empty() {
+            return Err(format!(
+                "cannot close account with {} outstanding applications opted in. Please opt out or clear them",
+                record.app_local_states.len()
+            )
+            .into());
+        }
+        if !record.app_params.is_empty() {
+            return Err(format!(
+                "cannot close account with {} outstanding created applications",
+                record.app_params.len()
+            )
+            .into());
+        }
+        ad.closing_amount = record.microalgos;
+        move_algos(
+            balances,
+            &sender,
+            &pay.close_remainder_to,
+            record.microalgos,
+        )?;
+
+        // Clear out the entire account record, so it can be deleted.
+        balances.put(&sender, AccountData::default());
+    }
+    Ok(())
+}
@@ -0,0 +1,289 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use config::consensus::ConsensusParams;
+
+use super::apply::Balances;
+use super::eval::{EvalError, EvalResult};
+use super::totals::AccountTotals;
+use super::txtail::Txlease;
+use crate::basics::{AccountData, Address, AssetIndex, AssetResource, CreatableType, Round};
+use crate::bookkeeping::block::BlockHeader;
+use crate::transactions::transaction::{Transaction, Txid};
+
+/// LedgerForCowBase is the committed state the changes of a round are made
+/// on top of. It is shared by the evaluators of the pool and the validator.
+pub trait LedgerForCowBase: Send + Sync {
+    fn lookup(&self, addr: &Address) -> AccountData;
+    fn lookup_asset(&self, addr: &Address, asset: AssetIndex) -> AssetResource;
+    fn get_creator(&self, index: u64, ctype: CreatableType) -> Option<Address>;
+    fn block_header(&self, round: Round) -> Option<BlockHeader>;
+    /// The totals of the accounts as of the latest round.
+    fn latest_totals(&self) -> AccountTotals;
+    /// Checks that a transaction was not confirmed in an earlier round and
+    /// that its lease is not held, as done by `TxTail::check_dup`.
+    fn check_dup(
+        &self,
+        proto: &ConsensusParams,
+        current: Round,
+        first_valid: Round,
+        last_valid: Round,
+        txid: &Txid,
+        txl: &Txlease,
+    ) -> EvalResult<()>;
+}
+
+/// An undo record: the value an entry had before it was changed.
+enum JournalEntry {
+    Account(Address, Option<AccountData>),
+    Asset((Address, AssetIndex), Option<AssetResource>),
+    Creatable((u64, CreatableType), Option<Option<Address>>),
+    TxnCounter(u64),
+    Txn(Txid, Txlease),
+}
+
+/// RoundCowState holds the changes made by the transactions of a round on
+/// top of the committed ledger. Every change is journaled so that the
+/// changes of a failed transaction group can be rolled back.
+pub struct RoundCowState {
+    base: Arc<dyn LedgerForCowBase>,
+    proto: ConsensusParams,
+    /// The header of the block being evaluated.
+    header: BlockHeader,
+    prev_timestamp: i64,
+    accounts: BTreeMap<Address, AccountData>,
+    /// The totals of the accounts, including the changes of the round.
+    totals: AccountTotals,
+    assets: BTreeMap<(Address, AssetIndex), AssetResource>,
+    creatables: BTreeMap<(u64, CreatableType), Option<Address>>,
+    txn_counter: u64,
+    /// The transactions of the round and the leases they acquired, with
+    /// the last round they are valid in.
+    txids: BTreeMap<Txid, Round>,
+    txleases: BTreeMap<Txlease, Round>,
+    journal: Vec<JournalEntry>,
+}
+
+impl RoundCowState {
+    /// Starts the state of the round of `header`. The transaction counter
+    /// continues from `prev_header`.
+    pub fn new(
+        base: Arc<dyn LedgerForCowBase>,
+        proto: ConsensusParams,
+        header: BlockHeader,
+        prev_header: &BlockHeader,
+    ) -> Self {
+        Self {
+            totals: base.latest_totals(),
+            base,
+            proto,
+            header,
+            prev_timestamp: prev_header.timestamp as i64,
+            accounts: BTreeMap::new(),
+            assets: BTreeMap::new(),
+            creatables: BTreeMap::new(),
+            txn_counter: prev_header.txn_counter,
+            txids: BTreeMap::new(),
+            txleases: BTreeMap::new(),
+            journal: vec![],
+        }
+    }
+
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    pub(crate) fn header_mut(&mut self) -> &mut BlockHeader {
+        &mut self.header
+    }
+
+    /// The totals of the accounts after the changes made so far.
+    pub fn totals(&self) -> AccountTotals {
+        self.totals
+    }
+
+    /// Replaces the state of the account `addr`, keeping the totals.
+    fn set_account(&mut self, addr: Address, data: Option<AccountData>) -> Option<AccountData> {
+        self.totals.del_account(&self.get(&addr));
+        let previous = match data {
+            Some(data) => self.accounts.insert(addr, data),
+            None => self.accounts.remove(&addr),
+        };
+        self.totals.add_account(&self.get(&addr));
+        previous
+    }
+
+    /// The accounts changed in the round, with their new state.
+    pub fn modified_accounts(&self) -> &BTreeMap<Address, AccountData> {
+        &self.accounts
+    }
+
+    /// Checks that `txn` was not confirmed yet and that its lease is not
+    /// held, in this round or an earlier one.
+    pub fn check_dup(&self, txn: &Transaction) -> EvalResult<()> {
+        let txid = txn.id();
+        if self.txids.contains_key(&txid) {
+            return Err(EvalError::AlreadyInLedger(txid));
+        }
+        let txl = Txlease::of(txn);
+        if self.proto.support_transaction_leases && !txl.is_empty() {
+            let held = self.txleases.get(&txl);
+            if held.is_some_and(|&expires| self.header.round <= expires) {
+                return Err(EvalError::LeaseInLedger { txid, lease: txl });
+            }
+        }
+        let header = &txn.header;
+        self.base.check_dup(
+            &self.proto,
+            self.header.round,
+            header.first_valid,
+            header.last_valid,
+            &txid,
+            &txl,
+        )
+    }
+
+    /// Records that `txn` is confirmed in this round, acquiring its lease.
+    pub fn add_tx(&mut self, txn: &Transaction) {
+        let txid = txn.id();
+        let txl = Txlease::of(txn);
+        self.txids.insert(txid, txn.header.last_valid);
+        if !txl.is_empty() {
+            self.txleases.insert(txl, txn.header.last_valid);
+        }
+        self.journal.push(JournalEntry::Txn(txid, txl));
+    }
+
+    /// The transactions confirmed in this round.
+    pub fn txids(&self) -> &BTreeMap<Txid, Round> {
+        &self.txids
+    }
+
+    /// The assets and applications created (`Some`) or deleted (`None`) in
+    /// the round.
+    pub fn modified_creatables(&self) -> &BTreeMap<(u64, CreatableType), Option<Address>> {
+        &self.creatables
+    }
+
+    /// The asset resources changed in the round, with their new state.
+    pub fn modified_assets(&self) -> &BTreeMap<(Address, AssetIndex), AssetResource> {
+        &self.assets
+    }
+
+    /// The accounts changed since `checkpoint`.
+    pub fn modified_since(&self, checkpoint: usize) -> BTreeSet<Address> {
+        self.journal[checkpoint..]
+            .iter()
+            .filter_map(|entry| match entry {
+                JournalEntry::Account(addr, _) => Some(*addr),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl Balances for RoundCowState {
+    fn proto(&self) -> &ConsensusParams {
+        &self.proto
+    }
+
+    fn round(&self) -> Round {
+        self.header.round
+    }
+
+    fn prev_timestamp(&self) -> i64 {
+        self.prev_timestamp
+    }
+
+    fn block_header(&self, round: Round) -> Option<BlockHeader> {
+        self.base.block_header(round)
+    }
+
+    fn fee_sink(&self) -> Address {
+        self.header.rewards_state.fee_sink
+    }
+
+    fn get(&self, addr: &Address) -> AccountData {
+        match self.accounts.get(addr) {
+            Some(data) => data.clone(),
+            None => self.base.lookup(addr),
+        }
+    }
+
+    fn put(&mut self, addr: &Address, data: AccountData) {
+        let previous = self.set_account(*addr, Some(data));
+        self.journal.push(JournalEntry::Account(*addr, previous));
+    }
+
+    fn get_asset(&self, addr: &Address, asset: AssetIndex) -> AssetResource {
+        match self.assets.get(&(*addr, asset)) {
+            Some(resource) => resource.clone(),
+            None => self.base.lookup_asset(addr, asset),
+        }
+    }
+
+    fn put_asset(&mut self, addr: &Address, asset: AssetIndex, resource: AssetResource) {
+        let previous = self.assets.insert((*addr, asset), resource);
+        self.journal
+            .push(JournalEntry::Asset((*addr, asset), previous));
+    }
+
+    fn get_creator(&self, index: u64, ctype: CreatableType) -> Option<Address> {
+        match self.creatables.get(&(index, ctype)) {
+            Some(creator) => *creator,
+            None => self.base.get_creator(index, ctype),
+        }
+    }
+
+    fn set_creator(&mut self, index: u64, ctype: CreatableType, creator: Option<Address>) {
+        let previous = self.creatables.insert((index, ctype), creator);
+        self.journal
+            .push(JournalEntry::Creatable((index, ctype), previous));
+    }
+
+    fn txn_counter(&self) -> u64 {
+        self.txn_counter
+    }
+
+    fn increment_txn_counter(&mut self) {
+        self.journal
+            .push(JournalEntry::TxnCounter(self.txn_counter));
+        self.txn_counter += 1;
+    }
+
+    fn checkpoint(&self) -> usize {
+        self.journal.len()
+    }
+
+    fn rollback(&mut self, checkpoint: usize) {
+        while self.journal.len() > checkpoint {
+            match self.journal.pop() {
+                Some(JournalEntry::Account(addr, previous)) => {
+                    self.set_account(addr, previous);
+                }
+                Some(JournalEntry::Asset(key, previous)) => match previous {
+                    Some(resource) => {
+                        self.assets.insert(key, resource);
+                    }
+                    None => {
+                        self.assets.remove(&key);
+                    }
+                },
+                Some(JournalEntry::Creatable(key, previous)) => match previous {
+                    Some(creator) => {
+                        self.creatables.insert(key, creator);
+                    }
+                    None => {
+                        self.creatables.remove(&key);
+                    }
+                },
+                Some(JournalEntry::TxnCounter(counter)) => self.txn_counter = counter,
+                Some(JournalEntry::Txn(txid, txl)) => {
+                    self.txids.remove(&txid);
+                    self.txleases.remove(&txl);
+                }
+                None => break,
+            }
+        }
+    }
+}
@@ -0,0 +1,962 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use config::consensus::ConsensusParams;
+use crypto::util::HashDigest;
+
+use super::apply::{self, Balances};
+use super::cow::{LedgerForCowBase, RoundCowState};
+use crate::basics::{Address, Round};
+use crate::bookkeeping::block::{Block, BlockHeader};
+use crate::transactions::logic::GroupPools;
+use crate::transactions::signedtxn::{SignedTxn, SignedTxnInBlock, SignedTxnWithAD};
+use crate::transactions::transaction::{compute_group_id, txid_string, Transaction, Txid};
+use crate::transactions::verified_cache::VerifiedTxnCache;
+use crate::transactions::verify::{self, GroupVerifier, VerifyError};
+use protocol::ConsensusVersion;
+
+use super::txtail::Txlease;
+
+/// EvalError is the reason a transaction group or a block does not apply
+/// to the ledger.
+#[derive(Debug, thiserror::Error)]
+pub enum EvalError {
+    #[error("block round {round} does not follow round {prev}")]
+    RoundMismatch { round: Round, prev: Round },
+    #[error("unsupported protocol {0}")]
+    UnsupportedProtocol(ConsensusVersion),
+    #[error("txn root wrong in block {0}")]
+    TxnRoot(Round),
+    #[error("transaction {}: applydata mismatch", txid_string(.0))]
+    ApplyDataMismatch(Txid),
+    #[error("length of expired accounts ({len}) was greater than expected ({max})")]
+    TooManyExpiredAccounts { len: usize, max: i64 },
+    #[error("duplicate address found: {}", .0.string())]
+    DuplicateExpiredAccount(Address),
+    #[error("expired account {} is not actually expired", .0.string())]
+    NotExpired(Address),
+    #[error("group size {size} exceeds maximum {max}")]
+    GroupTooLarge { size: usize, max: i32 },
+    #[error(
+        "transactionGroup: [{group_index}] had zero Group but was submitted in a group of {size}"
+    )]
+    ZeroGroup { group_index: usize, size: usize },
+    #[error("transactionGroup: incomplete group")]
+    IncompleteGroup,
+    #[error("transaction already in ledger: {}", txid_string(.0))]
+    AlreadyInLedger(Txid),
+    #[error(
+        "transaction {} using an overlapping lease (sender, lease):({}, {})",
+        txid_string(txid),
+        lease.sender.string(),
+        hex::encode(lease.lease)
+    )]
+    LeaseInLedger { txid: Txid, lease: Txlease },
+    #[error(
+        "transaction {}: should have been authorized by {} but was actually authorized by {}",
+        txid_string(txid),
+        expected.string(),
+        actual.string()
+    )]
+    WrongAuthorizer {
+        txid: Txid,
+        expected: Address,
+        actual: Address,
+    },
+    #[error(
+        "account {} balance {balance} below min {min} ({created_apps} created apps, {opted_in_apps} opted in apps)",
+        account.string()
+    )]
+    BelowMinBalance {
+        account: Address,
+        balance: u64,
+        min: u64,
+        created_apps: usize,
+        opted_in_apps: usize,
+    },
+    #[error(
+        "account {} would use too much space after this transaction. Minimum balance requirements would be {min} (greater than max {max})",
+        account.string()
+    )]
+    MinBalanceTooLarge {
+        account: Address,
+        min: u64,
+        max: u64,
+    },
+    #[error(transparent)]
+    Verify(#[from] VerifyError),
+    /// The transaction is malformed or not valid in the round.
+    #[error(transparent)]
+    Txn(Box<dyn std::error::Error>),
+    /// The transaction does not apply to the accounts it changes.
+    #[error(transparent)]
+    Apply(Box<dyn std::error::Error>),
+    /// The commitments of the block could not be computed.
+    #[error(transparent)]
+    Block(Box<dyn std::error::Error>),
+}
+
+pub type EvalResult<T> = Result<T, EvalError>;
+
+/// BlockEvaluator evaluates the transactions of a block in order, on top
+/// of the ledger it extends.
+pub struct BlockEvaluator {
+    state: RoundCowState,
+    payset: Vec<SignedTxnWithAD>,
+}
+
+impl BlockEvaluator {
+    /// Starts evaluating the block of `header`, which follows `prev_header`,
+    /// the latest block of `base`.
+    pub fn new(
+        base: Arc<dyn LedgerForCowBase>,
+        header: BlockHeader,
+        prev_header: &BlockHeader,
+    ) -> EvalResult<Self> {
+        if header.round != prev_header.round + 1 {
+            return Err(EvalError::RoundMismatch {
+                round: header.round,
+                prev: prev_header.round,
+            });
+        }
+        let version = &header.upgrade_state.current_protocol;
+        let proto = config::consensus::get_params(version)
+            .ok_or_else(|| EvalError::UnsupportedProtocol(version.clone()))?;
+        Ok(Self {
+            state: RoundCowState::new(base, proto, header, prev_header),
+            payset: vec![],
+        })
+    }
+
+    pub fn round(&self) -> Round {
+        self.state.round()
+    }
+
+    /// The state of the ledger after the transactions evaluated so far.
+    pub fn state(&self) -> &RoundCowState {
+        &self.state
+    }
+
+    /// The transactions added to the block, with their ApplyData.
+    pub fn payset(&self) -> &[SignedTxnWithAD] {
+        &self.payset
+    }
+
+    /// Evaluates `group` and adds it to the block. If any transaction of
+    /// the group fails, the block is left unchanged.
+    pub fn transaction_group(&mut self, group: &[SignedTxn]) -> EvalResult<()> {
+        let mut group: Vec<SignedTxnWithAD> = group
+            .iter()
+            .map(|stxn| SignedTxnWithAD {
+                signed_txn: stxn.clone(),
+                apply_data: Default::default(),
+            })
+            .collect();
+        transaction_group(&mut self.state, &mut group)?;
+        self.payset.extend(group);
+        Ok(())
+    }
+
+    /// The online accounts modified in the block whose participation keys
+    /// have expired, up to the number a block may take offline.
+    pub fn expired_online_accounts(&self) -> Vec<Address> {
+        let round = self.round();
+        let max = self
+            .state
+            .proto()
+            .max_proposed_expired_online_accounts
+            .max(0) as usize;
+        self.state
+            .modified_accounts()
+            .iter()
+            .filter(|(_, data)| data.is_expired(round))
+            .map(|(addr, _)| *addr)
+            .take(max)
+            .collect()
+    }
+
+    /// Ends the evaluation of the block by taking its expired participation
+    /// accounts offline. When `generate` is set the block is being
+    /// assembled and the accounts are chosen among the modified ones;
+    /// otherwise the accounts listed in the header are validated.
+    pub fn end_of_block(&mut self, generate: bool) -> EvalResult<()> {
+        if generate {
+            let expired = self.expired_online_accounts();
+            self.state
+                .header_mut()
+                .participation_updates
+                .expired_participation_accounts = expired;
+        } else {
+            self.validate_expired_online_accounts()?;
+        }
+        let expired = self
+            .state
+            .header()
+            .participation_updates
+            .expired_participation_accounts
+            .clone();
+        for addr in expired {
+            let mut data = self.state.get(&addr);
+            data.clear_online_state();
+            self.state.put(&addr, data);
+        }
+        Ok(())
+    }
+
+    fn validate_expired_online_accounts(&self) -> EvalResult<()> {
+        let expired = &self
+            .state
+            .header()
+            .participation_updates
+            .expired_participation_accounts;
+        let max = self.state.proto().max_proposed_expired_online_accounts;
+        if expired.len() > max.max(0) as usize {
+            return Err(EvalError::TooManyExpiredAccounts {
+                len: expired.len(),
+                max: max.into(),
+            });
+        }
+        let mut seen = BTreeSet::new();
+        for addr in expired {
+            if !seen.insert(addr) {
+                return Err(EvalError::DuplicateExpiredAccount(*addr));
+            }
+            if !self.state.get(addr).is_expired(self.round()) {
+                return Err(EvalError::NotExpired(*addr));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Validates `block`, which follows `prev`, the latest block of `base`: its
+/// commitments, the signatures of its groups, skipping those in `cache`,
+/// and the evaluation of its groups and expired participation accounts.
+/// The signatures are checked by `verifier` when given. Returns the
+/// evaluator holding the state after the block.
+pub fn validate_block(
+    base: Arc<dyn LedgerForCowBase>,
+    block: &Block,
+    prev: &BlockHeader,
+    cache: &VerifiedTxnCache,
+    verifier: Option<&GroupVerifier>,
+) -> EvalResult<BlockEvaluator> {
+    let started = Instant::now();
+    if block.payset_commit().map_err(EvalError::Block)? != block.header.txn_commitments {
+        return Err(EvalError::TxnRoot(block.header.round));
+    }
+    let mut eval = BlockEvaluator::new(base, block.header.clone(), prev)?;
+    let version = block.header.upgrade_state.current_protocol.clone();
+    let stxns: Vec<SignedTxnWithAD> = block
+        .payset
+        .0
+        .iter()
+        .map(|stxn| from_block(stxn, &block.header))
+        .collect();
+    for group in stxns.chunk_by(|a, b| {
+        let group = a.signed_txn.txn.header.group;
+        group != HashDigest::default() && group == b.signed_txn.txn.header.group
+    }) {
+        let proto = eval.state.proto();
+        match cache.verify_group(group, &version, proto, verifier) {
+            // A block is not dropped for load: when the backlog is full,
+            // its groups are verified on this thread.
+            Err(VerifyError::Backlog(e)) => {
+                tracing::debug!("verifying block {} inline: {}", block.header.round, e);
+                cache.verify_group(group, &version, proto, None)?;
+            }
+            result => result?,
+        }
+        // The block must carry the ApplyData of its transactions.
+        let mut applied = group.to_vec();
+        for stxn in applied.iter_mut() {
+            stxn.apply_data = Default::default();
+        }
+        transaction_group(&mut eval.state, &mut applied)?;
+        for (stxn, expected) in applied.iter().zip(group) {
+            if stxn.apply_data != expected.apply_data {
+                return Err(EvalError::ApplyDataMismatch(stxn.signed_txn.txn.id()));
+            }
+        }
+        eval.payset.extend(applied);
+    }
+    eval.end_of_block(false)?;
+    super::ledger_metrics()
+        .validation
+        .observe(started.elapsed());
+    Ok(eval)
+}
+
+/// Restores the genesis of a transaction of the block of `header`, which
+/// blocks leave out.
+pub(crate) fn from_block(stxn: &SignedTxnInBlock, header: &BlockHeader) -> SignedTxnWithAD {
+    let mut stxn = stxn.clone();
+    let txn = &mut stxn.sigend_txn_with_ad.signed_txn.txn;
+    if stxn.has_genesis_id {
+        txn.header.genesis_id = header.genesis_id.clone();
+    }
+    if stxn.has_genesis_hash {
+        txn.header.genesis_hash = header.genesis_hash;
+    }
+    stxn.sigend_txn_with_ad
+}
+
+/// Applies a transaction group to `cow`. Either every transaction applies
+/// and every account it modified keeps its minimum balance, or the group
+/// has no effect.
+pub fn transaction_group(cow: &mut RoundCowState, group: &mut [SignedTxnWithAD]) -> EvalResult<()> {
+    let checkpoint = cow.checkpoint();
+    let result = apply_group(cow, group, checkpoint);
+    if result.is_err() {
+        cow.rollback(checkpoint);
+    }
+    result
+}
+
+/// Checks that `group` is no larger than allowed, that its transactions
+/// agree on a group id, which must be the id of the whole group, and that
+/// they pay enough fees.
+pub fn check_group(group: &[SignedTxnWithAD], proto: &ConsensusParams) -> EvalResult<()> {
+    if group.len() > proto.max_tx_group_size.max(0) as usize {
+        return Err(EvalError::GroupTooLarge {
+            size: group.len(),
+            max: proto.max_tx_group_size,
+        });
+    }
+    let txns: Vec<Transaction> = group
+        .iter()
+        .map(|stxn| stxn.signed_txn.txn.clone())
+        .collect();
+    let mut grouped = false;
+    for (group_index, txn) in txns.iter().enumerate() {
+        if txn.header.group != HashDigest::default() {
+            grouped = true;
+        } else if txns.len() > 1 {
+            return Err(EvalError::ZeroGroup {
+                group_index,
+                size: txns.len(),
+            });
+        }
+    }
+    if grouped {
+        let group_id = compute_group_id(&txns).map_err(EvalError::Txn)?;
+        if txns.iter().any(|txn| txn.header.group != group_id) {
+            return Err(EvalError::IncompleteGroup);
+        }
+    }
+    Ok(verify::txn_group_fees(group, proto)?)
+}
+
+fn apply_group(
+    cow: &mut RoundCowState,
+    group: &mut [SignedTxnWithAD],
+    checkpoint: usize,
+) -> EvalResult<()> {
+    check_group(group, cow.proto())?;
+    let round = cow.round();
+    let pools = GroupPools::new(cow.proto(), group);
+    for group_index in 0..group.len() {
+        let txn = &group[group_index].signed_txn.txn;
+        txn.well_formed(cow.proto()).map_err(EvalError::Txn)?;
+        txn.alive(round).map_err(EvalError::Txn)?;
+        cow.check_dup(txn)?;
+        check_authorizer(cow, &group[group_index].signed_txn)?;
+        apply::transaction(cow, group, group_index, &pools).map_err(EvalError::Apply)?;
+        cow.add_tx(&group[group_index].signed_txn.txn);
+    }
+    check_min_balance(cow, checkpoint)
+}
+
+/// Checks that `stxn` is authorized by the current authorizer of its sender
+/// account, which may have been rekeyed.
+fn check_authorizer(cow: &RoundCowState, stxn: &SignedTxn) -> EvalResult<()> {
+    let sender = stxn.txn.sender();
+    let auth_addr = cow.get(&sender).auth_addr;
+    let correct = if auth_addr.is_zero() {
+        sender
+    } else {
+        auth_addr
+    };
+    if stxn.authorizer() != correct {
+        return Err(EvalError::WrongAuthorizer {
+            txid: stxn.txn.id(),
+            expected: correct,
+            actual: stxn.authorizer(),
+        });
+    }
+    Ok(())
+}
+
+/// Checks that the accounts modified since `checkpoint` hold at least
+/// their minimum balance.
+fn check_min_balance(cow: &RoundCowState, checkpoint: usize) -> EvalResult<()> {
+    let proto = cow.proto();
+    let rewards = &cow.header().rewards_state;
+    for addr in cow.modified_since(checkpoint) {
+        if addr == rewards.fee_sink || addr == rewards.rewards_pool {
+            continue;
+        }
+        let data = cow.get(&addr);
+        // Accounts may always become empty, so they can be deleted.
+        if data.is_zero() {
+            continue;
+        }
+        let min = data.min_balance(proto);
+        if data.microalgos.0 < min.0 {
+            return Err(EvalError::BelowMinBalance {
+                account: addr,
+                balance: data.microalgos.0,
+                min: min.0,
+                created_apps: data.app_params.len(),
+                opted_in_apps: data.app_local_states.len(),
+            });
+        }
+        if proto.maximum_minimum_balance != 0 && min.0 > proto.maximum_minimum_balance {
+            return Err(EvalError::MinBalanceTooLarge {
+                account: addr,
+                min: min.0,
+                max: proto.maximum_minimum_balance,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use protocol::TxType;
+
+    use super::*;
+    use crate::basics::{
+        application_address, Address, AppIndex, AssetIndex, AssetParams, CreatableType, MicroAlgos,
+        StateSchema, Status, TealValue,
+    };
+    use crate::bookkeeping::block::Block;
+    use crate::ledger::testing::MemBase;
+    use crate::transactions::application::OnCompletion;
+    use crate::transactions::asset::{AssetConfigTxnFields, AssetFreezeTxnFields};
+    use crate::transactions::transaction::Transaction;
+
+    fn first_header() -> BlockHeader {
+        BlockHeader {
+            round: 1,
+            ..Default::default()
+        }
+    }
+
+    fn appl(sender: Address, app: AppIndex, on_completion: OnCompletion) -> SignedTxnWithAD {
+        let mut stxn = SignedTxnWithAD::default();
+        let txn: &mut Transaction = &mut stxn.signed_txn.txn;
+        txn.tx_type = TxType::ApplicationCallTx;
+        txn.header.sender = sender;
+        txn.header.fee = MicroAlgos(1000);
+        txn.header.last_valid = 10;
+        txn.application_call_txn_fields.application_id = app;
+        txn.application_call_txn_fields.on_completion = on_completion;
+        stxn
+    }
+
+    fn pay(sender: Address, receiver: Address, amount: u64) -> SignedTxnWithAD {
+        let mut stxn = SignedTxnWithAD::default();
+        let txn = &mut stxn.signed_txn.txn;
+        txn.tx_type = TxType::PaymentTx;
+        txn.header.sender = sender;
+        txn.header.fee = MicroAlgos(1000);
+        txn.header.last_valid = 10;
+        txn.payment_txn_fields.receiver = receiver;
+        txn.payment_txn_fields.amount = MicroAlgos(amount);
+        stxn
+    }
+
+    fn axfer(
+        sender: Address,
+        asset: AssetIndex,
+        receiver: Address,
+        amount: u64,
+    ) -> SignedTxnWithAD {
+        let mut stxn = SignedTxnWithAD::default();
+        let txn = &mut stxn.signed_txn.txn;
+        txn.tx_type = TxType::AssetTransferTx;
+        txn.header.sender = sender;
+        txn.header.fee = MicroAlgos(1000);
+        txn.header.last_valid = 10;
+        txn.asset_transfer_txn_fields.xfer_asset = asset;
+        txn.asset_transfer_txn_fields.asset_receiver = receiver;
+        txn.asset_transfer_txn_fields.asset_amount = amount;
+        stxn
+    }
+
+    /// Sets the group id of the transactions of `group`.
+    fn grouped(group: &mut [SignedTxnWithAD]) {
+        let txns: Vec<Transaction> = group
+            .iter()
+            .map(|stxn| stxn.signed_txn.txn.clone())
+            .collect();
+        let group_id = compute_group_id(&txns).unwrap();
+        for stxn in group.iter_mut() {
+            stxn.signed_txn.txn.header.group = group_id;
+        }
+    }
+
+    // txn ApplicationID; bz done; itxn_begin; int pay; itxn_field TypeEnum;
+    // txn Sender; itxn_field Receiver; int 5000; itxn_field Amount;
+    // itxn_submit; done: int 1
+    const PAY_SENDER: [u8; 23] = [
+        0x06, 0x31, 0x18, 0x41, 0x00, 0x0f, 0xb1, 0x81, 0x01, 0xb2, 0x10, 0x31, 0x00, 0xb2, 0x07,
+        0x81, 0x88, 0x27, 0xb2, 0x08, 0xb3, 0x81, 0x01,
+    ];
+
+    // txn OnCompletion; int OptIn; ==; bz done; int 0; byte "l"; int 9;
+    // app_local_put; done: int 1
+    const OPT_IN_LOCAL: [u8; 19] = [
+        0x06, 0x31, 0x19, 0x81, 0x01, 0x12, 0x41, 0x00, 0x08, 0x81, 0x00, 0x80, 0x01, b'l', 0x81,
+        0x09, 0x66, 0x81, 0x01,
+    ];
+
+    #[test]
+    fn applications_and_inner_transactions() {
+        config::consensus::init();
+        let proto = config::consensus::get_params(protocol::CONSENSUS_V31).unwrap();
+        let creator = Address::from([1; 32]);
+        let user = Address::from([2; 32]);
+        let base = Arc::new(MemBase::funded(&[creator, user]));
+        let mut cow = RoundCowState::new(
+            base.clone(),
+            proto.clone(),
+            first_header(),
+            &BlockHeader::default(),
+        );
+
+        // Create an application that stores local state on opt in.
+        let mut create = appl(creator, 0, OnCompletion::NoOp);
+        let fields = &mut create.signed_txn.txn.application_call_txn_fields;
+        fields.approval_program = OPT_IN_LOCAL.to_vec();
+        fields.clear_state_program = vec![0x06, 0x00];
+        fields.local_state_schema = StateSchema {
+            num_uint: 1,
+            num_byte_slice: 0,
+        };
+        let mut group = [create];
+        transaction_group(&mut cow, &mut group).unwrap();
+        let app = group[0].apply_data.application_id;
+        assert_eq!(app, 1);
+        assert_eq!(cow.get(&creator).min_balance(&proto).0, 200_000);
+
+        // Opting in allocates local state, written by the program.
+        let mut group = [appl(user, app, OnCompletion::OptIn)];
+        transaction_group(&mut cow, &mut group).unwrap();
+        let local = &cow.get(&user).app_local_states[&app];
+        assert_eq!(local.key_value[b"l".as_slice()], TealValue::from_uint(9));
+        assert_eq!(group[0].apply_data.eval_delta.local_deltas[&0].len(), 1);
+        assert_eq!(
+            cow.get(&user).min_balance(&proto).0,
+            100_000 + 100_000 + 25_000 + 3_500
+        );
+
+        // Clearing state always succeeds, even though the clear state
+        // program fails.
+        let mut group = [appl(user, app, OnCompletion::ClearState)];
+        transaction_group(&mut cow, &mut group).unwrap();
+        assert!(cow.get(&user).app_local_states.is_empty());
+        assert_eq!(cow.get(&user).total_app_schema, StateSchema::default());
+
+        // An application paying its caller with an inner transaction.
+        let mut create = appl(creator, 0, OnCompletion::NoOp);
+        create
+            .signed_txn
+            .txn
+            .application_call_txn_fields
+            .approval_program = PAY_SENDER.to_vec();
+        create
+            .signed_txn
+            .txn
+            .application_call_txn_fields
+            .clear_state_program = vec![0x06, 0x81, 0x01];
+        let mut group = [create];
+        transaction_group(&mut cow, &mut group).unwrap();
+        let payer = group[0].apply_data.application_id;
+        let payer_addr = application_address(payer);
+
+        // Without funds for the inner payment, the group has no effect.
+        let before = cow.get(&user);
+        let mut group = [
+            pay(user, payer_addr, 100_000),
+            appl(user, payer, OnCompletion::NoOp),
+        ];
+        group[1].signed_txn.txn.header.note = b"underfunded".to_vec();
+        let mut call = group[1].clone();
+        group[0].signed_txn.txn.payment_txn_fields.amount = MicroAlgos(100_000 + 4_000);
+        grouped(&mut group);
+        assert!(transaction_group(&mut cow, &mut group).is_err());
+        assert_eq!(cow.get(&user), before);
+        assert!(cow.get(&payer_addr).is_zero());
+
+        let mut group = [pay(user, payer_addr, 200_000)];
+        transaction_group(&mut cow, &mut group).unwrap();
+        let mut group = [call.clone()];
+        transaction_group(&mut cow, &mut group).unwrap();
+        let inner = &group[0].apply_data.eval_delta.inner_txns;
+        assert_eq!(inner.len(), 1);
+        assert_eq!(inner[0].signed_txn.txn.payment_txn_fields.receiver, user);
+        assert_eq!(cow.get(&payer_addr).microalgos.0, 200_000 - 5_000 - 1_000);
+
+        // Inner transactions count towards the transaction counter.
+        call.signed_txn.txn.header.note = b"again".to_vec();
+        let counter = cow.txn_counter();
+        transaction_group(&mut cow, &mut [call.clone()]).unwrap();
+        assert_eq!(cow.txn_counter(), counter + 2);
+
+        // Overpaying the outer fee pays for the inner transaction.
+        call.signed_txn.txn.header.note = b"credit".to_vec();
+        call.signed_txn.txn.header.fee = MicroAlgos(2000);
+        let before = cow.get(&payer_addr).microalgos.0;
+        let mut group = [call];
+        transaction_group(&mut cow, &mut group).unwrap();
+        let inner = &group[0].apply_data.eval_delta.inner_txns;
+        assert_eq!(inner[0].signed_txn.txn.header.fee, MicroAlgos(0));
+        assert_eq!(cow.get(&payer_addr).microalgos.0, before - 5_000);
+    }
+
+    #[test]
+    fn asset_lifecycle() {
+        config::consensus::init();
+        let proto = config::consensus::get_params(protocol::CONSENSUS_V32).unwrap();
+        let creator = Address::from([1; 32]);
+        let user = Address::from([2; 32]);
+        let base = Arc::new(MemBase::funded(&[creator, user]));
+        let mut cow = RoundCowState::new(
+            base.clone(),
+            proto.clone(),
+            first_header(),
+            &BlockHeader::default(),
+        );
+        let apply = |cow: &mut RoundCowState, stxn: SignedTxnWithAD| {
+            let mut group = [stxn];
+            transaction_group(cow, &mut group).map(|_| group[0].apply_data.clone())
+        };
+
+        let mut create = SignedTxnWithAD::default();
+        let txn = &mut create.signed_txn.txn;
+        txn.tx_type = TxType::AssetConfigTx;
+        txn.header.sender = creator;
+        txn.header.fee = MicroAlgos(1000);
+        txn.header.last_valid = 10;
+        txn.asset_config_txn_fields.asset_params = AssetParams {
+            total: 100,
+            unit_name: "tok".into(),
+            manager: creator,
+            freeze: creator,
+            clawback: creator,
+            ..Default::default()
+        };
+        let asset = apply(&mut cow, create.clone()).unwrap().config_asset;
+        assert_eq!(asset, 1);
+        assert_eq!(cow.get(&creator).total_assets, 1);
+        assert_eq!(cow.get(&creator).min_balance(&proto).0, 200_000);
+
+        // Receiving requires opting in first.
+        assert!(apply(&mut cow, axfer(creator, asset, user, 10)).is_err());
+        apply(&mut cow, axfer(user, asset, user, 0)).unwrap();
+        apply(&mut cow, axfer(creator, asset, user, 10)).unwrap();
+        assert_eq!(cow.get_asset(&user, asset).holding.unwrap().amount, 10);
+
+        // Frozen holdings can only be clawed back.
+        let mut freeze = SignedTxnWithAD::default();
+        let txn = &mut freeze.signed_txn.txn;
+        txn.tx_type = TxType::AssetFreezeTx;
+        txn.header.sender = creator;
+        txn.header.fee = MicroAlgos(1000);
+        txn.header.last_valid = 10;
+        txn.asset_freeze_txn_fields = AssetFreezeTxnFields {
+            freeze_account: user,
+            freeze_asset: asset,
+            asset_frozen: true,
+        };
+        apply(&mut cow, freeze).unwrap();
+        assert!(apply(&mut cow, axfer(user, asset, creator, 1)).is_err());
+        let mut clawback = axfer(creator, asset, creator, 4);
+        clawback
+            .signed_txn
+            .txn
+            .asset_transfer_txn_fields
+            .asset_sender = user;
+        apply(&mut cow, clawback).unwrap();
+
+        // The creator can not be destroyed while units are outstanding,
+        // and closing out to the creator is allowed even when frozen.
+        let mut destroy = create;
+        destroy.signed_txn.txn.asset_config_txn_fields = AssetConfigTxnFields {
+            config_asset: asset,
+            ..Default::default()
+        };
+        assert!(apply(&mut cow, destroy.clone()).is_err());
+        let mut close = axfer(user, asset, creator, 0);
+        close
+            .signed_txn
+            .txn
+            .asset_transfer_txn_fields
+            .asset_close_to = creator;
+        assert_eq!(apply(&mut cow, close).unwrap().asset_closing_amount, 6);
+        assert!(cow.get_asset(&user, asset).is_empty());
+        assert_eq!(cow.get(&user).total_assets, 0);
+
+        apply(&mut cow, destroy).unwrap();
+        assert!(cow.get_asset(&creator, asset).is_empty());
+        assert_eq!(cow.get(&creator).total_asset_params, 0);
+        assert_eq!(cow.get_creator(asset, CreatableType::AssetCreatable), None);
+    }
+
+    #[test]
+    fn groups_and_leases() {
+        config::consensus::init();
+        let proto = config::consensus::get_params(protocol::CONSENSUS_V32).unwrap();
+        let alice = Address::from([1; 32]);
+        let bob = Address::from([2; 32]);
+        let base = Arc::new(MemBase::funded(&[alice, bob]));
+        let mut cow =
+            RoundCowState::new(base.clone(), proto, first_header(), &BlockHeader::default());
+
+        let mut group = [pay(alice, bob, 10), pay(bob, alice, 20)];
+        assert!(transaction_group(&mut cow, &mut group.clone()).is_err());
+        grouped(&mut group);
+        assert!(transaction_group(&mut cow, &mut group[..1].to_vec()).is_err());
+
+        // A failing transaction undoes the whole group, which can then be
+        // confirmed exactly once.
+        let mut failing = group.to_vec();
+        failing[1].signed_txn.txn.payment_txn_fields.amount = MicroAlgos(2_000_000);
+        assert!(transaction_group(&mut cow, &mut failing).is_err());
+        assert_eq!(cow.get(&alice).microalgos.0, 1_000_000);
+        transaction_group(&mut cow, &mut group.clone()).unwrap();
+        assert_eq!(cow.get(&alice).microalgos.0, 1_000_000 - 10 - 1000 + 20);
+        assert!(transaction_group(&mut cow, &mut group).is_err());
+
+        // A lease is held by the first transaction acquiring it.
+        let mut leased = pay(alice, bob, 1);
+        leased.signed_txn.txn.header.lease = [7; 32];
+        transaction_group(&mut cow, &mut [leased.clone()]).unwrap();
+        leased.signed_txn.txn.payment_txn_fields.amount = MicroAlgos(2);
+        assert!(transaction_group(&mut cow, &mut [leased]).is_err());
+    }
+
+    // int 0; loop: int 1; +; dup; int 150; <; bnz loop
+    // Costs 1 + 150 * 6 = 901.
+    const BURN: [u8; 14] = [
+        0x05, 0x81, 0x00, 0x81, 0x01, 0x08, 0x49, 0x81, 0x96, 0x01, 0x0c, 0x40, 0xff, 0xf5,
+    ];
+
+    #[test]
+    fn fee_and_budget_pooling() {
+        config::consensus::init();
+        let proto = config::consensus::get_params(protocol::CONSENSUS_V31).unwrap();
+        let alice = Address::from([1; 32]);
+        let bob = Address::from([2; 32]);
+        let base = Arc::new(MemBase::funded(&[alice, bob]));
+        let eval = |proto: &ConsensusParams, group: &mut [SignedTxnWithAD]| {
+            let mut cow = RoundCowState::new(
+                base.clone(),
+                proto.clone(),
+                first_header(),
+                &BlockHeader::default(),
+            );
+            grouped(group);
+            transaction_group(&mut cow, group)
+        };
+
+        // One transaction may pay the fee of another, as long as the
+        // group pays the minimum fee of each.
+        let mut group = [pay(alice, bob, 1), pay(bob, alice, 1)];
+        group[0].signed_txn.txn.header.fee = MicroAlgos(0);
+        let err = eval(&proto, &mut group.clone()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "txgroup had 1000 in fees, which is less than the minimum 2 * 1000"
+        );
+        group[1].signed_txn.txn.header.fee = MicroAlgos(2000);
+        eval(&proto, &mut group.clone()).unwrap();
+        let unpooled = ConsensusParams {
+            enable_fee_pooling: false,
+            ..proto.clone()
+        };
+        assert!(eval(&unpooled, &mut group).is_err());
+
+        // An application call costing more than its own budget borrows
+        // the budget of the other calls of the group.
+        let mut create = appl(alice, 0, OnCompletion::NoOp);
+        let fields = &mut create.signed_txn.txn.application_call_txn_fields;
+        fields.approval_program = BURN.to_vec();
+        fields.clear_state_program = vec![0x05, 0x81, 0x01];
+        let err = eval(&proto, &mut [create.clone()]).unwrap_err();
+        assert!(err.to_string().contains("dynamic cost budget exceeded"));
+        let mut noop = create.clone();
+        noop.signed_txn
+            .txn
+            .application_call_txn_fields
+            .approval_program = vec![0x05, 0x81, 0x01];
+        let mut group = [create, noop];
+        eval(&proto, &mut group.clone()).unwrap();
+        let unpooled = ConsensusParams {
+            enable_app_cost_pooling: false,
+            ..proto
+        };
+        assert!(eval(&unpooled, &mut group).is_err());
+    }
+
+    #[test]
+    fn rekeyed_accounts() {
+        config::consensus::init();
+        let proto = config::consensus::get_params(protocol::CONSENSUS_V31).unwrap();
+        let owner = Address::from([1; 32]);
+        let hot = Address::from([2; 32]);
+        let base = Arc::new(MemBase::funded(&[owner, hot]));
+        let mut cow =
+            RoundCowState::new(base.clone(), proto, first_header(), &BlockHeader::default());
+
+        let mut rekey = pay(owner, hot, 0);
+        rekey.signed_txn.txn.header.rekey_to = hot;
+        transaction_group(&mut cow, &mut [rekey]).unwrap();
+        assert_eq!(cow.get(&owner).auth_addr, hot);
+
+        // Only the new authorizer may sign for the account.
+        let mut spend = pay(owner, hot, 1);
+        let err = transaction_group(&mut cow, &mut [spend.clone()]).unwrap_err();
+        assert!(err.to_string().contains("should have been authorized by"));
+        spend.signed_txn.auth_addr = hot;
+        transaction_group(&mut cow, &mut [spend]).unwrap();
+
+        // Rekeying to the account itself restores its own key.
+        let mut back = pay(owner, hot, 2);
+        back.signed_txn.auth_addr = hot;
+        back.signed_txn.txn.header.rekey_to = owner;
+        transaction_group(&mut cow, &mut [back]).unwrap();
+        assert!(cow.get(&owner).auth_addr.is_zero());
+    }
+
+    fn keyreg(sender: Address, online: bool) -> SignedTxnWithAD {
+        let mut stxn = SignedTxnWithAD::default();
+        let txn = &mut stxn.signed_txn.txn;
+        txn.tx_type = TxType::KeyRegistrationTx;
+        txn.header.sender = sender;
+        txn.header.fee = MicroAlgos(1000);
+        txn.header.last_valid = 10;
+        if online {
+            let keys = &mut txn.keyreg_txn_fields;
+            keys.vote_pk = [7; 32];
+            keys.selection_pk = [8; 32];
+            keys.vote_first = 1;
+            keys.vote_last = 1000;
+            keys.vote_key_dilution = 100;
+            keys.state_proof_pk = vec![9; 64];
+        }
+        stxn
+    }
+
+    #[test]
+    fn keyreg_status_transitions() {
+        config::consensus::init();
+        let proto = config::consensus::get_params(protocol::CONSENSUS_V31).unwrap();
+        let voter = Address::from([1; 32]);
+        let base = Arc::new(MemBase::funded(&[voter]));
+        let mut cow = RoundCowState::new(
+            base.clone(),
+            proto.clone(),
+            first_header(),
+            &BlockHeader::default(),
+        );
+        assert_eq!(cow.totals().online, MicroAlgos(0));
+
+        let online = keyreg(voter, true);
+        online.signed_txn.txn.well_formed(&proto).unwrap();
+        transaction_group(&mut cow, &mut [online]).unwrap();
+        assert_eq!(cow.get(&voter).status, Status::Online);
+        assert_eq!(cow.get(&voter).state_proof_id, vec![9; 64]);
+        assert_eq!(cow.totals().online, MicroAlgos(1_000_000 - 1000));
+
+        transaction_group(&mut cow, &mut [keyreg(voter, false)]).unwrap();
+        assert_eq!(cow.get(&voter).status, Status::Offline);
+        assert_eq!(cow.totals().online, MicroAlgos(0));
+
+        // Keys must be registered together.
+        let mut incoherent = keyreg(voter, true);
+        incoherent
+            .signed_txn
+            .txn
+            .keyreg_txn_fields
+            .vote_key_dilution = 0;
+        let err = incoherent.signed_txn.txn.well_formed(&proto).unwrap_err();
+        assert!(err.to_string().contains("need to be clear/set together"));
+
+        // A non-participating account can not come back online.
+        let mut retire = keyreg(voter, false);
+        retire.signed_txn.txn.keyreg_txn_fields.nonparticipation = true;
+        transaction_group(&mut cow, &mut [retire]).unwrap();
+        assert_eq!(cow.get(&voter).status, Status::NotParticipating);
+        assert!(transaction_group(&mut cow, &mut [keyreg(voter, true)]).is_err());
+
+        // The fees moved to the fee sink, so no money was lost.
+        assert_eq!(cow.totals().all(), MicroAlgos(1_000_000));
+    }
+
+    #[test]
+    fn expired_participation_accounts() {
+        config::consensus::init();
+        let voter = Address::from([1; 32]);
+        let idle = Address::from([2; 32]);
+        let base = Arc::new(MemBase::funded(&[voter, idle]));
+        let mut genesis = BlockHeader::default();
+        genesis.upgrade_state.current_protocol = protocol::CONSENSUS_V31.into();
+
+        // The voter registers keys valid until round 2.
+        let mut online = keyreg(voter, true);
+        online.signed_txn.txn.keyreg_txn_fields.vote_last = 2;
+        let first = Block::make(&genesis).header;
+        let mut eval = BlockEvaluator::new(base.clone(), first.clone(), &genesis).unwrap();
+        eval.transaction_group(&[online.signed_txn.clone()])
+            .unwrap();
+        eval.end_of_block(true).unwrap();
+        assert!(eval
+            .state()
+            .header()
+            .participation_updates
+            .expired_participation_accounts
+            .is_empty());
+        base.commit(eval.state(), &[online.signed_txn.txn]);
+
+        // In round 3 the keys have expired, and the proposer takes the
+        // voter offline once the block touches it.
+        let mut prev = first;
+        prev.round = 2;
+        let header = Block::make(&prev).header;
+        let mut eval = BlockEvaluator::new(base.clone(), header.clone(), &prev).unwrap();
+        eval.transaction_group(&[pay(voter, idle, 1).signed_txn])
+            .unwrap();
+        assert_eq!(eval.state().totals().online, MicroAlgos(1_000_000 - 2001));
+        eval.end_of_block(true).unwrap();
+        let expired = &eval
+            .state()
+            .header()
+            .participation_updates
+            .expired_participation_accounts;
+        assert_eq!(expired, &vec![voter]);
+        assert_eq!(eval.state().get(&voter).status, Status::Offline);
+        assert_eq!(eval.state().totals().online, MicroAlgos(0));
+
+        // Validators accept only accounts that did expire.
+        let mut proposed = header.clone();
+        proposed
+            .participation_updates
+            .expired_participation_accounts = vec![voter];
+        let mut eval = BlockEvaluator::new(base.clone(), proposed, &prev).unwrap();
+        eval.end_of_block(false).unwrap();
+        assert_eq!(eval.state().get(&voter).status, Status::Offline);
+
+        let mut proposed = header;
+        proposed
+            .participation_updates
+            .expired_participation_accounts = vec![idle];
+        let mut eval = BlockEvaluator::new(base.clone(), proposed, &prev).unwrap();
+        let err = eval.end_of_block(false).unwrap_err();
+        assert!(err.to_string().contains("is not actually expired"));
+    }
+}
@@ -0,0 +1,61 @@
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use util::metrics::{default_registry, Gauge, Histogram, Metric, MetricName, Sample};
+
+use crate::basics::Round;
+
+const ROUND: MetricName = MetricName {
+    name: "algod_ledger_round",
+    description: "The latest round of the ledger",
+};
+const COMMIT: MetricName = MetricName {
+    name: "algod_ledger_commit_seconds",
+    description: "Time to add a block to the ledger",
+};
+const VALIDATION: MetricName = MetricName {
+    name: "algod_ledger_validate_block_seconds",
+    description: "Time to validate a block before adding it",
+};
+
+/// The metrics of the ledger of the node.
+#[derive(Debug)]
+pub struct LedgerMetrics {
+    pub round: Gauge,
+    pub commit: Histogram,
+    pub validation: Histogram,
+}
+
+impl LedgerMetrics {
+    fn new() -> Self {
+        Self {
+            round: Gauge::new(ROUND, vec![]),
+            commit: Histogram::new(COMMIT, vec![]),
+            validation: Histogram::new(VALIDATION, vec![]),
+        }
+    }
+
+    /// Block `round` was added to the ledger in `elapsed`.
+    pub fn block_committed(&self, round: Round, elapsed: Duration) {
+        self.round.set(round as i64);
+        self.commit.observe(elapsed);
+    }
+}
+
+impl Metric for LedgerMetrics {
+    fn collect(&self, out: &mut Vec<Sample>) {
+        self.round.collect(out);
+        self.commit.collect(out);
+        self.validation.collect(out);
+    }
+}
+
+/// The ledger metrics, registered in the default registry on first use.
+pub fn ledger_metrics() -> &'static LedgerMetrics {
+    static METRICS: OnceLock<Arc<LedgerMetrics>> = OnceLock::new();
+    METRICS.get_or_init(|| {
+        let metrics = Arc::new(LedgerMetrics::new());
+        default_registry().register(metrics.clone());
+        metrics
+    })
+}
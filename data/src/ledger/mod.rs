@@ -0,0 +1,729 @@
+pub mod apply;
+pub mod cow;
+pub mod eval;
+mod metrics;
+mod store;
+mod totals;
+pub mod txtail;
+mod voters;
+
+#[cfg(test)]
+pub(crate) mod testing;
+
+pub use metrics::*;
+pub use totals::*;
+pub use voters::*;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::Instant;
+
+use config::consensus::ConsensusParams;
+use config::Local;
+use crypto::util::HashDigest;
+use ledger::BlockListener;
+use parking_lot::{Mutex, RwLock};
+use protocol::ConsensusVersion;
+use rusqlite::Connection;
+
+use self::apply::Balances;
+use self::cow::LedgerForCowBase;
+use self::eval::{BlockEvaluator, EvalError, EvalResult};
+use self::txtail::{TxTail, Txlease};
+use crate::{
+    basics::{AccountData, Address, AssetIndex, AssetResource, CreatableType, Round},
+    bookkeeping::{
+        block::{Block, BlockHeader},
+        genesis,
+    },
+    transactions::{
+        transaction::{Transaction, Txid},
+        verified_cache::VerifiedTxnCache,
+        verify::GroupVerifier,
+    },
+};
+
+/// LedgerError is an error opening the ledger of the node or adding blocks
+/// to it.
+#[derive(Debug, thiserror::Error)]
+pub enum LedgerError {
+    /// The ledger could not be initialized with the genesis block.
+    #[error("cannot make the genesis block")]
+    Genesis(#[from] genesis::GenesisError),
+    #[error("ledger database error")]
+    Database(#[from] rusqlite::Error),
+    #[error("cannot decode block {round}")]
+    Decode {
+        round: Round,
+        source: msgp::DecodeError,
+    },
+    #[error("cannot decode account {}", .addr.string())]
+    DecodeAccount {
+        addr: Address,
+        source: msgp::DecodeError,
+    },
+    #[error("malformed address of length {0}")]
+    MalformedAddress(usize),
+    #[error("unknown account resource type {0}")]
+    MalformedResource(i64),
+    /// The ledger database was made for another genesis.
+    #[error("ledger database holds genesis block {found}, not {expected}")]
+    GenesisMismatch { expected: String, found: String },
+    #[error("unsupported protocol {0}")]
+    UnsupportedProtocol(ConsensusVersion),
+    #[error("block {0} is not in the ledger")]
+    MissingBlock(Round),
+    /// The block does not apply to the latest round of the ledger.
+    #[error("invalid block")]
+    Eval(#[from] EvalError),
+}
+
+pub type LedgerResult<T> = Result<T, LedgerError>;
+
+/// The suffix of the ledger database after the ledger filename prefix.
+pub const LEDGER_DB_SUFFIX: &str = ".sqlite";
+
+/// The state of the ledger as of its latest block.
+struct LedgerState {
+    latest: BlockHeader,
+    accounts: store::Accounts,
+    totals: AccountTotals,
+    tail: TxTail,
+}
+
+/// Ledger holds the blocks of the node, from the genesis block on, and the
+/// state of the accounts they lead to. Both are kept in the ledger
+/// database; the accounts and the recent transactions are also kept in
+/// memory, where the evaluators read them.
+pub struct Ledger {
+    db: Mutex<Connection>,
+    state: RwLock<LedgerState>,
+    /// Held while a block is validated and committed, so that it is
+    /// validated on the state it is committed on.
+    writer: Mutex<()>,
+    pub genesis_id: String,
+    pub genesis_hash: HashDigest,
+}
+
+impl std::fmt::Debug for Ledger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Ledger")
+            .field("genesis_id", &self.genesis_id)
+            .field("latest", &self.latest())
+            .finish()
+    }
+}
+
+impl Ledger {
+    /// The latest round of the ledger.
+    pub fn latest(&self) -> Round {
+        self.state.read().latest.round
+    }
+
+    /// The header of the latest block of the ledger.
+    pub fn latest_header(&self) -> BlockHeader {
+        self.state.read().latest.clone()
+    }
+
+    pub fn block(&self, round: Round) -> LedgerResult<Block> {
+        store::get_block(&self.db.lock(), round)
+    }
+
+    pub fn block_hdr(&self, round: Round) -> LedgerResult<BlockHeader> {
+        Ok(self.block(round)?.header)
+    }
+
+    /// Validates `block`, which must follow the latest block, and adds it
+    /// to the ledger. The signatures of the groups in `cache` are not
+    /// verified again; the others are verified by `verifier` when given.
+    pub fn add_block(
+        self: &Arc<Self>,
+        block: &Block,
+        cache: &VerifiedTxnCache,
+        verifier: Option<&GroupVerifier>,
+    ) -> LedgerResult<()> {
+        let started = Instant::now();
+        let _writer = self.writer.lock();
+        let prev = self.latest_header();
+        let eval = eval::validate_block(self.clone(), block, &prev, cache, verifier)?;
+        self.commit(block, &eval)?;
+        ledger_metrics().block_committed(block.header.round, started.elapsed());
+        Ok(())
+    }
+
+    /// Writes `block` and the changes of its evaluation `eval`, then makes
+    /// them the latest state.
+    fn commit(&self, block: &Block, eval: &BlockEvaluator) -> LedgerResult<()> {
+        let cow = eval.state();
+        let separated = cow.proto().enable_account_data_resource_separation;
+        let mut db = self.db.lock();
+        let tx = db.transaction()?;
+        store::put_block(&tx, block)?;
+        {
+            let state = self.state.read();
+            let touched: BTreeSet<Address> = cow
+                .modified_accounts()
+                .keys()
+                .chain(cow.modified_assets().keys().map(|(addr, _)| addr))
+                .copied()
+                .collect();
+            for addr in &touched {
+                let mut assets: BTreeMap<AssetIndex, AssetResource> = state
+                    .accounts
+                    .assets_of(addr)
+                    .map(|(asset, resource)| (asset, resource.clone()))
+                    .collect();
+                for ((_, asset), resource) in cow
+                    .modified_assets()
+                    .range((*addr, AssetIndex::MIN)..=(*addr, AssetIndex::MAX))
+                {
+                    assets.insert(*asset, resource.clone());
+                }
+                store::put_account(
+                    &tx,
+                    addr,
+                    &cow.get(addr),
+                    assets.iter().map(|(a, r)| (*a, r)),
+                    separated,
+                )?;
+            }
+            for ((index, ctype), creator) in cow.modified_creatables() {
+                store::put_creator(&tx, *index, *ctype, creator.as_ref())?;
+            }
+        }
+        tx.commit()?;
+
+        let mut state = self.state.write();
+        let accounts = &mut state.accounts;
+        for (addr, data) in cow.modified_accounts() {
+            if data.is_zero() {
+                accounts.accounts.remove(addr);
+            } else {
+                accounts.accounts.insert(*addr, data.clone());
+            }
+        }
+        for (key, resource) in cow.modified_assets() {
+            if resource.is_empty() {
+                accounts.assets.remove(key);
+            } else {
+                accounts.assets.insert(*key, resource.clone());
+            }
+        }
+        for (key, creator) in cow.modified_creatables() {
+            match creator {
+                Some(creator) => accounts.creators.insert(*key, *creator),
+                None => accounts.creators.remove(key),
+            };
+        }
+        let txns: Vec<Transaction> = eval
+            .payset()
+            .iter()
+            .map(|stxn| stxn.signed_txn.txn.clone())
+            .collect();
+        state.tail.new_block(block.header.round, &txns, cow.proto());
+        state.totals = cow.totals();
+        state.latest = block.header.clone();
+        Ok(())
+    }
+}
+
+impl LedgerForCowBase for Ledger {
+    fn lookup(&self, addr: &Address) -> AccountData {
+        let state = self.state.read();
+        state
+            .accounts
+            .accounts
+            .get(addr)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn lookup_asset(&self, addr: &Address, asset: AssetIndex) -> AssetResource {
+        let state = self.state.read();
+        let assets = &state.accounts.assets;
+        assets.get(&(*addr, asset)).cloned().unwrap_or_default()
+    }
+
+    fn get_creator(&self, index: u64, ctype: CreatableType) -> Option<Address> {
+        let state = self.state.read();
+        state.accounts.creators.get(&(index, ctype)).copied()
+    }
+
+    fn block_header(&self, round: Round) -> Option<BlockHeader> {
+        match self.block_hdr(round) {
+            Ok(header) => Some(header),
+            Err(LedgerError::MissingBlock(_)) => None,
+            Err(e) => {
+                tracing::error!("cannot read block {}: {}", round, e);
+                None
+            }
+        }
+    }
+
+    fn latest_totals(&self) -> AccountTotals {
+        self.state.read().totals
+    }
+
+    fn check_dup(
+        &self,
+        proto: &ConsensusParams,
+        current: Round,
+        first_valid: Round,
+        last_valid: Round,
+        txid: &Txid,
+        txl: &Txlease,
+    ) -> EvalResult<()> {
+        let state = self.state.read();
+        state
+            .tail
+            .check_dup(proto, current, first_valid, last_valid, txid, txl)
+    }
+}
+
+/// Opens the ledger at `db_filename_prefix`, or in memory with `memory`.
+/// An empty ledger is initialized with the genesis block and the genesis
+/// balances.
+#[allow(clippy::too_many_arguments)]
+pub fn load_ledger(
+    db_filename_prefix: String,
+    memory: bool,
+    genesis_proto: ConsensusVersion,
+    genesis_bal: genesis::GenesisBalances,
+    genesis_id: String,
+    genesis_hash: HashDigest,
+    _block_listeners: Vec<Box<dyn BlockListener>>,
+    _config: Local,
+) -> LedgerResult<Ledger> {
+    let balances = genesis_bal.balances.clone();
+    let separated = config::consensus::get_params(&genesis_proto)
+        .is_some_and(|proto| proto.enable_account_data_resource_separation);
+    let genesis =
+        genesis::make_genesis_block(genesis_proto, genesis_bal, genesis_id.clone(), genesis_hash)?;
+    let gen_hash = genesis.header.hash();
+    let mut conn = if memory {
+        Connection::open_in_memory()?
+    } else {
+        Connection::open(format!("{}{}", db_filename_prefix, LEDGER_DB_SUFFIX))?
+    };
+    store::create_tables(&conn)?;
+    let latest = match store::latest_round(&conn)? {
+        None => {
+            let tx = conn.transaction()?;
+            store::put_block(&tx, &genesis)?;
+            for (addr, data) in &balances {
+                store::put_account(&tx, addr, data, std::iter::empty(), separated)?;
+            }
+            tx.commit()?;
+            0
+        }
+        Some(latest) => {
+            let found = store::get_block(&conn, 0)?.header.hash();
+            if found != gen_hash {
+                return Err(LedgerError::GenesisMismatch {
+                    expected: hex::encode(gen_hash.0),
+                    found: hex::encode(found.0),
+                });
+            }
+            latest
+        }
+    };
+    let latest = store::get_block(&conn, latest)?.header;
+    let accounts = store::load_accounts(&conn)?;
+    let mut totals = AccountTotals::default();
+    for data in accounts.accounts.values() {
+        totals.add_account(data);
+    }
+    let tail = load_tail(&conn, &latest)?;
+
+    ledger_metrics().round.set(latest.round as i64);
+    tracing::info!(
+        genesis_block = hex::encode(gen_hash.0).as_str(),
+        latest = latest.round,
+        "loaded ledger"
+    );
+    Ok(Ledger {
+        db: Mutex::new(conn),
+        state: RwLock::new(LedgerState {
+            latest,
+            accounts,
+            totals,
+            tail,
+        }),
+        writer: Mutex::new(()),
+        genesis_id,
+        genesis_hash,
+    })
+}
+
+/// Rebuilds the TxTail from the blocks up to `latest` whose transactions
+/// may still be valid.
+fn load_tail(conn: &Connection, latest: &BlockHeader) -> LedgerResult<TxTail> {
+    let params = |version: &ConsensusVersion| {
+        config::consensus::get_params(version)
+            .ok_or_else(|| LedgerError::UnsupportedProtocol(version.clone()))
+    };
+    let proto = params(&latest.upgrade_state.current_protocol)?;
+    let mut tail = TxTail::default();
+    for round in latest.round.saturating_sub(proto.max_txn_life)..=latest.round {
+        let block = store::get_block(conn, round)?;
+        let txns: Vec<Transaction> = block
+            .payset
+            .0
+            .iter()
+            .map(|stxn| eval::from_block(stxn, &block.header).signed_txn.txn)
+            .collect();
+        tail.new_block(
+            round,
+            &txns,
+            &params(&block.header.upgrade_state.current_protocol)?,
+        );
+    }
+    Ok(tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use protocol::TxType;
+
+    use super::*;
+    use crate::basics::{AssetParams, MicroAlgos, StateSchema, Status, TealValue};
+    use crate::pools::TransactionPool;
+    use crate::transactions::application::OnCompletion;
+    use crate::transactions::signedtxn::SignedTxn;
+    use crypto::curve25519::SignatureSecrets;
+
+    /// The funded accounts of the test genesis.
+    struct Funded {
+        alice: SignatureSecrets,
+        sink: Address,
+    }
+
+    impl Funded {
+        fn new() -> Self {
+            Self {
+                alice: SignatureSecrets::generate(&[1; 32]),
+                sink: Address::from([9; 32]),
+            }
+        }
+
+        fn alice(&self) -> Address {
+            Address::from(self.alice.signature_verifier)
+        }
+    }
+
+    fn open(
+        prefix: &str,
+        memory: bool,
+        genesis_id: &str,
+        proto: &str,
+    ) -> LedgerResult<Arc<Ledger>> {
+        let funded = Funded::new();
+        let data = |microalgos| AccountData {
+            microalgos: MicroAlgos(microalgos),
+            vote_id: [3; 32],
+            ..Default::default()
+        };
+        let balances = genesis::GenesisBalances::new_with_timestamp(
+            [
+                (funded.alice(), data(10_000_000)),
+                (funded.sink, data(1_000_000)),
+            ]
+            .into(),
+            funded.sink,
+            funded.sink,
+            0,
+        );
+        load_ledger(
+            prefix.to_string(),
+            memory,
+            proto.to_string(),
+            balances,
+            genesis_id.to_string(),
+            HashDigest::default(),
+            vec![],
+            Local::default(),
+        )
+        .map(Arc::new)
+    }
+
+    /// Adds a block with the groups `groups` to `ledger`, going through a
+    /// transaction pool as the node does.
+    fn add(ledger: &Arc<Ledger>, groups: &[Vec<SignedTxn>]) -> LedgerResult<Block> {
+        let cache = Arc::new(VerifiedTxnCache::new(16));
+        let config = config::default_local();
+        let latest = ledger.latest_header();
+        let mut pool =
+            TransactionPool::new(ledger.clone(), &latest, &config, cache.clone(), None).unwrap();
+        for group in groups {
+            pool.verify_and_remember(group).unwrap();
+        }
+        let block = pool.assemble_block().unwrap().block;
+        ledger.add_block(&block, &cache, None)?;
+        Ok(block)
+    }
+
+    fn pay(secrets: &SignatureSecrets, receiver: Address, amount: u64) -> SignedTxn {
+        let mut stxn = SignedTxn::default();
+        let txn = &mut stxn.txn;
+        txn.tx_type = TxType::PaymentTx;
+        txn.header.sender = Address::from(secrets.signature_verifier);
+        txn.header.fee = MicroAlgos(1000);
+        txn.header.last_valid = 100;
+        txn.payment_txn_fields.receiver = receiver;
+        txn.payment_txn_fields.amount = MicroAlgos(amount);
+        stxn.sig = secrets.sign(&stxn.txn);
+        stxn
+    }
+
+    #[test]
+    fn blocks_and_accounts_persist() {
+        config::consensus::init();
+        let dir = std::env::temp_dir().join(format!("ledger-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let prefix = dir.join("ledger");
+        let prefix = prefix.to_str().unwrap();
+        let funded = Funded::new();
+        let bob = Address::from([2; 32]);
+
+        let ledger = open(prefix, false, "test-v1", protocol::CONSENSUS_V31).unwrap();
+        assert_eq!(ledger.latest(), 0);
+        let total = ledger.latest_totals().all();
+        let payment = pay(&funded.alice, bob, 200_000);
+        let block = add(&ledger, &[vec![payment.clone()]]).unwrap();
+        assert_eq!(ledger.latest(), 1);
+        assert_eq!(
+            ledger.block_hdr(1).unwrap().branch,
+            ledger.block_hdr(0).unwrap().hash()
+        );
+        assert_eq!(ledger.lookup(&bob).microalgos, MicroAlgos(200_000));
+        assert!(matches!(
+            ledger.add_block(&block, &VerifiedTxnCache::new(16), None),
+            Err(LedgerError::Eval(EvalError::RoundMismatch {
+                round: 1,
+                prev: 1
+            }))
+        ));
+        assert!(matches!(ledger.block(2), Err(LedgerError::MissingBlock(2))));
+        drop(ledger);
+
+        // The accounts and the recent transactions are loaded back, so the
+        // payment can not be confirmed again.
+        let ledger = open(prefix, false, "test-v1", protocol::CONSENSUS_V31).unwrap();
+        assert_eq!(ledger.latest(), 1);
+        assert_eq!(ledger.lookup(&bob).microalgos, MicroAlgos(200_000));
+        assert_eq!(ledger.lookup(&funded.alice()).vote_id, [3; 32]);
+        assert_eq!(ledger.latest_totals().all(), total);
+        let txn = &payment.txn;
+        let (first, last) = (txn.header.first_valid, txn.header.last_valid);
+        let proto = config::consensus::get_params(protocol::CONSENSUS_V31).unwrap();
+        assert!(matches!(
+            ledger.check_dup(&proto, 2, first, last, &txn.id(), &Txlease::of(txn)),
+            Err(EvalError::AlreadyInLedger(_))
+        ));
+        drop(ledger);
+
+        assert!(matches!(
+            open(prefix, false, "test-v2", protocol::CONSENSUS_V31),
+            Err(LedgerError::GenesisMismatch { .. })
+        ));
+        let fresh = open(prefix, true, "test-v2", protocol::CONSENSUS_V31).unwrap();
+        assert_eq!(fresh.latest(), 0);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    fn appl(secrets: &SignatureSecrets, app: u64, on_completion: OnCompletion) -> SignedTxn {
+        let mut stxn = SignedTxn::default();
+        let txn = &mut stxn.txn;
+        txn.tx_type = TxType::ApplicationCallTx;
+        txn.header.sender = Address::from(secrets.signature_verifier);
+        txn.header.fee = MicroAlgos(1000);
+        txn.header.last_valid = 100;
+        let fields = &mut txn.application_call_txn_fields;
+        fields.application_id = app;
+        fields.on_completion = on_completion;
+        if app == 0 {
+            fields.approval_program = GLOBAL_AND_LOCAL.to_vec();
+            fields.clear_state_program = vec![0x06, 0x81, 0x01];
+            let one_uint = StateSchema {
+                num_uint: 1,
+                num_byte_slice: 0,
+            };
+            fields.global_state_schema = one_uint;
+            fields.local_state_schema = one_uint;
+        }
+        stxn.sig = secrets.sign(&stxn.txn);
+        stxn
+    }
+
+    // byte "g"; int 7; app_global_put; txn OnCompletion; int OptIn; ==;
+    // bz done; int 0; byte "l"; int 9; app_local_put; done: int 1
+    const GLOBAL_AND_LOCAL: [u8; 25] = [
+        0x06, 0x80, 0x01, b'g', 0x81, 0x07, 0x67, 0x31, 0x19, 0x81, 0x01, 0x12, 0x41, 0x00, 0x08,
+        0x81, 0x00, 0x80, 0x01, b'l', 0x81, 0x09, 0x66, 0x81, 0x01,
+    ];
+
+    #[test]
+    fn application_state_persists() {
+        config::consensus::init();
+        let dir = std::env::temp_dir().join(format!("ledger-app-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let prefix = dir.join("ledger");
+        let prefix = prefix.to_str().unwrap();
+        let funded = Funded::new();
+        let alice = funded.alice();
+
+        let ledger = open(prefix, false, "test-v1", protocol::CONSENSUS_V31).unwrap();
+        let create = add(&ledger, &[vec![appl(&funded.alice, 0, OnCompletion::NoOp)]]).unwrap();
+        let app = create.payset.0[0]
+            .sigend_txn_with_ad
+            .apply_data
+            .application_id;
+        add(
+            &ledger,
+            &[vec![appl(&funded.alice, app, OnCompletion::OptIn)]],
+        )
+        .unwrap();
+        drop(ledger);
+
+        // The application, its global state and the local state of the
+        // account that opted in are read back from the database.
+        let ledger = open(prefix, false, "test-v1", protocol::CONSENSUS_V31).unwrap();
+        assert_eq!(
+            ledger.get_creator(app, CreatableType::AppCreatable),
+            Some(alice)
+        );
+        let data = ledger.lookup(&alice);
+        let global = &data.app_params[&app].global_state;
+        assert_eq!(global[b"g".as_slice()], TealValue::from_uint(7));
+        let local = &data.app_local_states[&app].key_value;
+        assert_eq!(local[b"l".as_slice()], TealValue::from_uint(9));
+        assert_eq!(data.total_app_schema.num_uint, 2);
+
+        // Closing out frees the local state in the database as well.
+        add(
+            &ledger,
+            &[vec![appl(&funded.alice, app, OnCompletion::CloseOut)]],
+        )
+        .unwrap();
+        drop(ledger);
+        let ledger = open(prefix, false, "test-v1", protocol::CONSENSUS_V31).unwrap();
+        assert!(ledger.lookup(&alice).app_local_states.is_empty());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    fn acfg(secrets: &SignatureSecrets) -> SignedTxn {
+        let mut stxn = SignedTxn::default();
+        let txn = &mut stxn.txn;
+        txn.tx_type = TxType::AssetConfigTx;
+        txn.header.sender = Address::from(secrets.signature_verifier);
+        txn.header.fee = MicroAlgos(1000);
+        txn.header.last_valid = 100;
+        txn.asset_config_txn_fields.asset_params = AssetParams {
+            total: 100,
+            unit_name: "tok".into(),
+            ..Default::default()
+        };
+        stxn.sig = secrets.sign(&stxn.txn);
+        stxn
+    }
+
+    #[test]
+    fn resources_follow_the_protocol_layout() {
+        config::consensus::init();
+        let dir = std::env::temp_dir().join(format!("ledger-layout-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let funded = Funded::new();
+        let alice = funded.alice();
+
+        // Before v32 assets and applications are kept in the account
+        // record; from v32 on, each of them is a row of its own.
+        for (proto, rows) in [(protocol::CONSENSUS_V31, 0), (protocol::CONSENSUS_V32, 3)] {
+            let prefix = dir.join(proto.rsplit('/').next().unwrap());
+            let prefix = prefix.to_str().unwrap();
+            let ledger = open(prefix, false, "test-v1", proto).unwrap();
+            let block = add(
+                &ledger,
+                &[
+                    vec![acfg(&funded.alice)],
+                    vec![appl(&funded.alice, 0, OnCompletion::NoOp)],
+                ],
+            )
+            .unwrap();
+            let ad = |i: usize| &block.payset.0[i].sigend_txn_with_ad.apply_data;
+            let (asset, app) = (ad(0).config_asset, ad(1).application_id);
+            let count = |ledger: &Ledger| -> i64 {
+                let db = ledger.db.lock();
+                db.query_row("SELECT COUNT(*) FROM resources", [], |row| row.get(0))
+                    .unwrap()
+            };
+            assert_eq!(count(&ledger), rows, "{proto}");
+            drop(ledger);
+
+            let ledger = open(prefix, false, "test-v1", proto).unwrap();
+            let resource = ledger.lookup_asset(&alice, asset);
+            assert_eq!(resource.holding.unwrap().amount, 100);
+            assert_eq!(resource.params.unwrap().unit_name, "tok");
+            assert_eq!(
+                ledger.get_creator(asset, CreatableType::AssetCreatable),
+                Some(alice)
+            );
+            let data = ledger.lookup(&alice);
+            assert_eq!(data.total_assets, 1);
+            assert_eq!(
+                data.app_params[&app].global_state[b"g".as_slice()],
+                TealValue::from_uint(7)
+            );
+            assert_eq!(count(&ledger), rows, "{proto}");
+        }
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn expired_accounts_go_offline() {
+        config::consensus::init();
+        let dir = std::env::temp_dir().join(format!("ledger-expiry-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let prefix = dir.join("ledger");
+        let prefix = prefix.to_str().unwrap();
+        let funded = Funded::new();
+        let alice = funded.alice();
+
+        // Alice registers keys valid until round 2.
+        let ledger = open(prefix, false, "test-v1", protocol::CONSENSUS_V31).unwrap();
+        let mut online = SignedTxn::default();
+        let txn = &mut online.txn;
+        txn.tx_type = TxType::KeyRegistrationTx;
+        txn.header.sender = alice;
+        txn.header.fee = MicroAlgos(1000);
+        txn.header.last_valid = 100;
+        let keys = &mut txn.keyreg_txn_fields;
+        keys.vote_pk = [7; 32];
+        keys.selection_pk = [8; 32];
+        keys.vote_first = 1;
+        keys.vote_last = 2;
+        keys.vote_key_dilution = 100;
+        keys.state_proof_pk = vec![9; 64];
+        online.sig = funded.alice.sign(&online.txn);
+        add(&ledger, &[vec![online]]).unwrap();
+        assert_eq!(ledger.lookup(&alice).status, Status::Online);
+        add(&ledger, &[]).unwrap();
+
+        // The block of round 3 touches her, so it takes her offline.
+        let block = add(&ledger, &[vec![pay(&funded.alice, funded.sink, 1)]]).unwrap();
+        assert_eq!(
+            block
+                .header
+                .participation_updates
+                .expired_participation_accounts,
+            vec![alice]
+        );
+        drop(ledger);
+
+        let ledger = open(prefix, false, "test-v1", protocol::CONSENSUS_V31).unwrap();
+        let data = ledger.lookup(&alice);
+        assert_eq!(data.status, Status::Offline);
+        assert_eq!(data.vote_last_valid, 0);
+        assert_eq!(ledger.latest_totals().online, MicroAlgos(0));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,275 @@
+//! The tables the ledger keeps its blocks and the state of its accounts in.
+//!
+//! Accounts are stored in the layout of the protocol of the block that
+//! last changed them. Before `enable_account_data_resource_separation`, an
+//! account is a single `accountbase` record holding its assets and
+//! applications. From then on, the record only holds the AccountData
+//! proper, and each asset holding, asset, local state and application is a
+//! row of `resources`.
+
+use std::collections::BTreeMap;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use super::{LedgerError, LedgerResult};
+use crate::basics::{
+    AccountData, Address, AppLocalState, AppParams, AssetHolding, AssetIndex, AssetParams,
+    AssetResource, CreatableType, Round,
+};
+use crate::bookkeeping::block::Block;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS blocks (
+    rnd INTEGER PRIMARY KEY,
+    blk BLOB NOT NULL
+);
+CREATE TABLE IF NOT EXISTS accountbase (
+    address BLOB PRIMARY KEY,
+    data BLOB NOT NULL
+);
+CREATE TABLE IF NOT EXISTS resources (
+    address BLOB NOT NULL,
+    aidx INTEGER NOT NULL,
+    rtype INTEGER NOT NULL,
+    data BLOB NOT NULL,
+    PRIMARY KEY (address, aidx, rtype)
+);
+CREATE TABLE IF NOT EXISTS assetcreators (
+    asset INTEGER NOT NULL,
+    ctype INTEGER NOT NULL,
+    creator BLOB NOT NULL,
+    PRIMARY KEY (asset, ctype)
+);";
+
+/// The kinds of rows of `resources`.
+const ASSET_HOLDING: i64 = 1;
+const ASSET_PARAMS: i64 = 2;
+const APP_LOCAL_STATE: i64 = 3;
+const APP_PARAMS: i64 = 4;
+
+/// AccountRecord is an account as stored in `accountbase`: its AccountData
+/// with, before resources were separated, the holdings and the parameters
+/// of its assets.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AccountRecord {
+    #[serde(flatten)]
+    data: AccountData,
+    #[serde(rename = "asset", default, skip_serializing_if = "BTreeMap::is_empty")]
+    holdings: BTreeMap<AssetIndex, AssetHolding>,
+    #[serde(rename = "apar", default, skip_serializing_if = "BTreeMap::is_empty")]
+    params: BTreeMap<AssetIndex, AssetParams>,
+}
+
+/// The state of the accounts, as committed by the latest block.
+#[derive(Debug, Default)]
+pub(super) struct Accounts {
+    pub(super) accounts: BTreeMap<Address, AccountData>,
+    pub(super) assets: BTreeMap<(Address, AssetIndex), AssetResource>,
+    pub(super) creators: BTreeMap<(u64, CreatableType), Address>,
+}
+
+impl Accounts {
+    /// The assets of `addr`.
+    pub(super) fn assets_of(
+        &self,
+        addr: &Address,
+    ) -> impl Iterator<Item = (AssetIndex, &AssetResource)> {
+        self.assets
+            .range((*addr, AssetIndex::MIN)..=(*addr, AssetIndex::MAX))
+            .map(|((_, asset), resource)| (*asset, resource))
+    }
+}
+
+pub(super) fn create_tables(conn: &Connection) -> LedgerResult<()> {
+    Ok(conn.execute_batch(SCHEMA)?)
+}
+
+pub(super) fn latest_round(conn: &Connection) -> LedgerResult<Option<Round>> {
+    let latest: Option<i64> =
+        conn.query_row("SELECT MAX(rnd) FROM blocks", [], |row| row.get(0))?;
+    Ok(latest.map(|round| round as Round))
+}
+
+pub(super) fn get_block(conn: &Connection, round: Round) -> LedgerResult<Block> {
+    let bytes: Vec<u8> = conn
+        .query_row(
+            "SELECT blk FROM blocks WHERE rnd = ?1",
+            params![round as i64],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or(LedgerError::MissingBlock(round))?;
+    protocol::decode(&bytes).map_err(|source| LedgerError::Decode { round, source })
+}
+
+pub(super) fn put_block(conn: &Connection, block: &Block) -> LedgerResult<()> {
+    conn.execute(
+        "INSERT INTO blocks (rnd, blk) VALUES (?1, ?2)",
+        params![block.header.round as i64, protocol::encode(block)],
+    )?;
+    Ok(())
+}
+
+/// Writes the state of the account `addr`, holding `assets`, in the
+/// combined or the `separated` layout, deleting the account once it holds
+/// nothing.
+pub(super) fn put_account<'r>(
+    conn: &Connection,
+    addr: &Address,
+    data: &AccountData,
+    assets: impl Iterator<Item = (AssetIndex, &'r AssetResource)>,
+    separated: bool,
+) -> LedgerResult<()> {
+    conn.execute(
+        "DELETE FROM resources WHERE address = ?1",
+        params![addr.as_bytes()],
+    )?;
+    let assets: Vec<_> = assets.collect();
+    if data.is_zero() && assets.is_empty() {
+        conn.execute(
+            "DELETE FROM accountbase WHERE address = ?1",
+            params![addr.as_bytes()],
+        )?;
+        return Ok(());
+    }
+
+    let mut record = AccountRecord {
+        data: data.clone(),
+        ..Default::default()
+    };
+    if separated {
+        let put = |aidx: u64, rtype: i64, bytes: Vec<u8>| {
+            conn.execute(
+                "INSERT INTO resources (address, aidx, rtype, data) VALUES (?1, ?2, ?3, ?4)",
+                params![addr.as_bytes(), aidx as i64, rtype, bytes],
+            )
+        };
+        for (asset, resource) in assets {
+            if let Some(holding) = &resource.holding {
+                put(asset, ASSET_HOLDING, protocol::encode(holding))?;
+            }
+            if let Some(params) = &resource.params {
+                put(asset, ASSET_PARAMS, protocol::encode(params))?;
+            }
+        }
+        for (app, local) in std::mem::take(&mut record.data.app_local_states) {
+            put(app, APP_LOCAL_STATE, protocol::encode(&local))?;
+        }
+        for (app, params) in std::mem::take(&mut record.data.app_params) {
+            put(app, APP_PARAMS, protocol::encode(&params))?;
+        }
+    } else {
+        for (asset, resource) in assets {
+            if let Some(holding) = resource.holding {
+                record.holdings.insert(asset, holding);
+            }
+            if let Some(params) = &resource.params {
+                record.params.insert(asset, params.clone());
+            }
+        }
+    }
+    conn.execute(
+        "INSERT OR REPLACE INTO accountbase (address, data) VALUES (?1, ?2)",
+        params![addr.as_bytes(), protocol::encode(&record)],
+    )?;
+    Ok(())
+}
+
+pub(super) fn put_creator(
+    conn: &Connection,
+    index: u64,
+    ctype: CreatableType,
+    creator: Option<&Address>,
+) -> LedgerResult<()> {
+    match creator {
+        Some(creator) => conn.execute(
+            "INSERT OR REPLACE INTO assetcreators (asset, ctype, creator) VALUES (?1, ?2, ?3)",
+            params![index as i64, ctype_code(ctype), creator.as_bytes()],
+        )?,
+        None => conn.execute(
+            "DELETE FROM assetcreators WHERE asset = ?1 AND ctype = ?2",
+            params![index as i64, ctype_code(ctype)],
+        )?,
+    };
+    Ok(())
+}
+
+/// Reads the state of all accounts.
+pub(super) fn load_accounts(conn: &Connection) -> LedgerResult<Accounts> {
+    let mut state = Accounts::default();
+    let mut stmt = conn.prepare("SELECT address, data FROM accountbase")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let addr = address(row.get(0)?)?;
+        let bytes: Vec<u8> = row.get(1)?;
+        let record: AccountRecord = protocol::decode(&bytes)
+            .map_err(|source| LedgerError::DecodeAccount { addr, source })?;
+        for (asset, holding) in record.holdings {
+            let resource = state.assets.entry((addr, asset)).or_default();
+            resource.holding = Some(holding);
+        }
+        for (asset, params) in record.params {
+            let resource = state.assets.entry((addr, asset)).or_default();
+            resource.params = Some(params);
+        }
+        state.accounts.insert(addr, record.data);
+    }
+
+    let mut stmt = conn.prepare("SELECT address, aidx, rtype, data FROM resources")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let addr = address(row.get(0)?)?;
+        let aidx = row.get::<_, i64>(1)? as u64;
+        let bytes: Vec<u8> = row.get(3)?;
+        let decode_error = |source| LedgerError::DecodeAccount { addr, source };
+        match row.get(2)? {
+            ASSET_HOLDING => {
+                let holding: AssetHolding = protocol::decode(&bytes).map_err(decode_error)?;
+                state.assets.entry((addr, aidx)).or_default().holding = Some(holding);
+            }
+            ASSET_PARAMS => {
+                let params: AssetParams = protocol::decode(&bytes).map_err(decode_error)?;
+                state.assets.entry((addr, aidx)).or_default().params = Some(params);
+            }
+            APP_LOCAL_STATE => {
+                let local: AppLocalState = protocol::decode(&bytes).map_err(decode_error)?;
+                let data = state.accounts.entry(addr).or_default();
+                data.app_local_states.insert(aidx, local);
+            }
+            APP_PARAMS => {
+                let params: AppParams = protocol::decode(&bytes).map_err(decode_error)?;
+                let data = state.accounts.entry(addr).or_default();
+                data.app_params.insert(aidx, params);
+            }
+            rtype => return Err(LedgerError::MalformedResource(rtype)),
+        }
+    }
+
+    let mut stmt = conn.prepare("SELECT asset, ctype, creator FROM assetcreators")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let index: i64 = row.get(0)?;
+        let ctype = match row.get::<_, i64>(1)? {
+            0 => CreatableType::AssetCreatable,
+            _ => CreatableType::AppCreatable,
+        };
+        let creator = address(row.get(2)?)?;
+        state.creators.insert((index as u64, ctype), creator);
+    }
+    Ok(state)
+}
+
+fn ctype_code(ctype: CreatableType) -> i64 {
+    match ctype {
+        CreatableType::AssetCreatable => 0,
+        CreatableType::AppCreatable => 1,
+    }
+}
+
+fn address(bytes: Vec<u8>) -> LedgerResult<Address> {
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|bytes: Vec<u8>| LedgerError::MalformedAddress(bytes.len()))?;
+    Ok(Address::from(bytes))
+}
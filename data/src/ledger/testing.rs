@@ -0,0 +1,98 @@
+//! An in-memory ledger for tests.
+
+use std::collections::BTreeMap;
+
+use config::consensus::ConsensusParams;
+use parking_lot::Mutex;
+
+use super::apply::Balances;
+use super::cow::{LedgerForCowBase, RoundCowState};
+use super::eval::EvalResult;
+use super::totals::AccountTotals;
+use super::txtail::{TxTail, Txlease};
+use crate::basics::{
+    AccountData, Address, AssetIndex, AssetResource, CreatableType, MicroAlgos, Round,
+};
+use crate::bookkeeping::block::BlockHeader;
+use crate::transactions::transaction::{Transaction, Txid};
+
+#[derive(Default)]
+pub(crate) struct MemBase {
+    accounts: Mutex<BTreeMap<Address, AccountData>>,
+    assets: Mutex<BTreeMap<(Address, AssetIndex), AssetResource>>,
+    creators: Mutex<BTreeMap<(u64, CreatableType), Address>>,
+    tail: Mutex<TxTail>,
+}
+
+impl MemBase {
+    /// A ledger where each of `addrs` holds 1 Algo.
+    pub(crate) fn funded(addrs: &[Address]) -> Self {
+        let data = AccountData {
+            microalgos: MicroAlgos(1_000_000),
+            ..Default::default()
+        };
+        let base = Self::default();
+        base.accounts
+            .lock()
+            .extend(addrs.iter().map(|addr| (*addr, data.clone())));
+        base
+    }
+
+    /// Commits the changes of the round evaluated in `state`.
+    pub(crate) fn commit(&self, state: &RoundCowState, txns: &[Transaction]) {
+        self.accounts
+            .lock()
+            .extend(state.modified_accounts().clone());
+        self.assets.lock().extend(state.modified_assets().clone());
+        let mut creators = self.creators.lock();
+        for (key, creator) in state.modified_creatables() {
+            match creator {
+                Some(creator) => creators.insert(*key, *creator),
+                None => creators.remove(key),
+            };
+        }
+        self.tail
+            .lock()
+            .new_block(state.round(), txns, state.proto());
+    }
+}
+
+impl LedgerForCowBase for MemBase {
+    fn lookup(&self, addr: &Address) -> AccountData {
+        self.accounts.lock().get(addr).cloned().unwrap_or_default()
+    }
+
+    fn lookup_asset(&self, addr: &Address, asset: AssetIndex) -> AssetResource {
+        let assets = self.assets.lock();
+        assets.get(&(*addr, asset)).cloned().unwrap_or_default()
+    }
+
+    fn get_creator(&self, index: u64, ctype: CreatableType) -> Option<Address> {
+        self.creators.lock().get(&(index, ctype)).copied()
+    }
+
+    fn block_header(&self, _round: Round) -> Option<BlockHeader> {
+        None
+    }
+
+    fn latest_totals(&self) -> AccountTotals {
+        let mut totals = AccountTotals::default();
+        for data in self.accounts.lock().values() {
+            totals.add_account(data);
+        }
+        totals
+    }
+
+    fn check_dup(
+        &self,
+        proto: &ConsensusParams,
+        current: Round,
+        first_valid: Round,
+        last_valid: Round,
+        txid: &Txid,
+        txl: &Txlease,
+    ) -> EvalResult<()> {
+        let tail = self.tail.lock();
+        tail.check_dup(proto, current, first_valid, last_valid, txid, txl)
+    }
+}
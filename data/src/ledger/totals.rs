@@ -0,0 +1,37 @@
+use crate::basics::{AccountData, MicroAlgos, Status};
+
+/// AccountTotals is the money held by all accounts, by participation
+/// status. The online money is the stake that votes in consensus.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AccountTotals {
+    pub online: MicroAlgos,
+    pub offline: MicroAlgos,
+    pub not_participating: MicroAlgos,
+}
+
+impl AccountTotals {
+    fn status_mut(&mut self, status: Status) -> &mut MicroAlgos {
+        match status {
+            Status::Online => &mut self.online,
+            Status::Offline => &mut self.offline,
+            Status::NotParticipating => &mut self.not_participating,
+        }
+    }
+
+    /// Counts the money of an account in the totals.
+    pub fn add_account(&mut self, data: &AccountData) {
+        let total = self.status_mut(data.status);
+        total.0 = total.0.saturating_add(data.microalgos.0);
+    }
+
+    /// Removes the money of an account from the totals.
+    pub fn del_account(&mut self, data: &AccountData) {
+        let total = self.status_mut(data.status);
+        total.0 = total.0.saturating_sub(data.microalgos.0);
+    }
+
+    /// The money held by all accounts.
+    pub fn all(&self) -> MicroAlgos {
+        MicroAlgos(self.online.0 + self.offline.0 + self.not_participating.0)
+    }
+}
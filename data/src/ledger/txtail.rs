@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+
+use config::consensus::ConsensusParams;
+
+use super::eval::{EvalError, EvalResult};
+use crate::basics::{Address, Round};
+use crate::transactions::transaction::{Transaction, Txid};
+
+/// Txlease is the combination of a sender and a lease value. While a
+/// transaction holding it is valid, no other transaction with the same
+/// Txlease can be confirmed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Txlease {
+    pub sender: Address,
+    pub lease: [u8; 32],
+}
+
+impl Txlease {
+    pub fn of(txn: &Transaction) -> Self {
+        Self {
+            sender: txn.sender(),
+            lease: txn.header.lease,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lease == [0; 32]
+    }
+}
+
+/// The transactions confirmed in a round, and the leases they hold, each
+/// with the last round it is valid in.
+#[derive(Debug, Default)]
+struct RoundTxMembers {
+    txids: BTreeMap<Txid, Round>,
+    txleases: BTreeMap<Txlease, Round>,
+}
+
+/// TxTail remembers the transactions of the last `max_txn_life` rounds, so
+/// that a transaction can not be confirmed twice and a lease can not be
+/// acquired while it is held.
+#[derive(Debug, Default)]
+pub struct TxTail {
+    recent: BTreeMap<Round, RoundTxMembers>,
+}
+
+impl TxTail {
+    /// Records the transactions confirmed in `round`, forgetting the rounds
+    /// whose transactions can no longer be valid.
+    pub fn new_block(&mut self, round: Round, txns: &[Transaction], proto: &ConsensusParams) {
+        let members = self.recent.entry(round).or_default();
+        for txn in txns {
+            members.txids.insert(txn.id(), txn.header.last_valid);
+            let txl = Txlease::of(txn);
+            if !txl.is_empty() {
+                members.txleases.insert(txl, txn.header.last_valid);
+            }
+        }
+        let oldest = round.saturating_sub(proto.max_txn_life);
+        self.recent = self.recent.split_off(&oldest);
+    }
+
+    /// Checks that transaction `txid`, valid from `first_valid` to
+    /// `last_valid`, was not confirmed yet and that its lease `txl` is not
+    /// held, for inclusion in the block of round `current`.
+    pub fn check_dup(
+        &self,
+        proto: &ConsensusParams,
+        current: Round,
+        first_valid: Round,
+        last_valid: Round,
+        txid: &Txid,
+        txl: &Txlease,
+    ) -> EvalResult<()> {
+        if proto.support_transaction_leases && !txl.is_empty() {
+            // Leases used to be checked over the window of the new
+            // transaction only, missing earlier holders still valid.
+            let (first_checked, last_checked) = if proto.fix_transaction_leases {
+                (current.saturating_sub(proto.max_txn_life), current)
+            } else {
+                (first_valid, last_valid)
+            };
+            for members in self
+                .recent
+                .range(first_checked..=last_checked)
+                .map(|(_, m)| m)
+            {
+                if members
+                    .txleases
+                    .get(txl)
+                    .is_some_and(|&expires| current <= expires)
+                {
+                    return Err(EvalError::LeaseInLedger {
+                        txid: *txid,
+                        lease: *txl,
+                    });
+                }
+            }
+        }
+        for members in self.recent.range(first_valid..=last_valid).map(|(_, m)| m) {
+            if members.txids.contains_key(txid) {
+                return Err(EvalError::AlreadyInLedger(*txid));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::basics::MicroAlgos;
+
+    #[test]
+    fn leases_are_held_until_last_valid() {
+        let proto = ConsensusParams {
+            max_txn_life: 1000,
+            support_transaction_leases: true,
+            fix_transaction_leases: true,
+            ..Default::default()
+        };
+        let mut txn = Transaction::default();
+        txn.header.sender = Address::from([1; 32]);
+        txn.header.first_valid = 1;
+        txn.header.last_valid = 10;
+        txn.header.lease = [7; 32];
+        let mut tail = TxTail::default();
+        tail.new_block(5, std::slice::from_ref(&txn), &proto);
+
+        // A different transaction with the same lease conflicts until the
+        // first one expires, even if its own window starts later.
+        let mut other = txn.clone();
+        other.header.fee = MicroAlgos(1);
+        other.header.first_valid = 8;
+        other.header.last_valid = 20;
+        let check = |proto: &ConsensusParams, current, txn: &Transaction| {
+            let (first, last) = (txn.header.first_valid, txn.header.last_valid);
+            tail.check_dup(proto, current, first, last, &txn.id(), &Txlease::of(txn))
+        };
+        assert!(check(&proto, 9, &other).is_err());
+        assert!(check(&proto, 11, &other).is_ok());
+        assert!(check(&proto, 6, &txn).is_err());
+
+        // Without the fix, only the window of the new transaction counts.
+        let unfixed = ConsensusParams {
+            fix_transaction_leases: false,
+            ..proto.clone()
+        };
+        other.header.first_valid = 6;
+        assert!(check(&unfixed, 9, &other).is_ok());
+    }
+}
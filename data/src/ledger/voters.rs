@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use config::consensus::ConsensusParams;
+use crypto::compactcert::{Participant, ParticipantsArray};
+use crypto::merklearray::{GenericDigest, Tree};
+use crypto::util::HashType;
+
+use crate::basics::{AccountData, Address, MicroAlgos, Round, Status};
+use crate::bookkeeping::block::CompactCertState;
+
+pub type VotersResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// VotersForRound tracks the top online voting accounts as of a particular
+/// round, along with a Merkle tree commitment to those voting accounts.
+#[derive(Debug, Clone)]
+pub struct VotersForRound {
+    /// The top online accounts, sorted by decreasing stake, as participants
+    /// of a compact certificate.
+    pub participants: Vec<Participant>,
+    /// Maps the address of every voter to its position in `participants`.
+    pub addr_to_pos: HashMap<Address, u64>,
+    /// The Merkle tree over `participants`.
+    pub tree: Tree,
+    /// The sum of the weights of `participants`.
+    pub total_weight: MicroAlgos,
+}
+
+impl VotersForRound {
+    /// Selects the top `compact_cert_top_voters` accounts that are online
+    /// at `round` and commits to them.
+    pub fn new<'a>(
+        accounts: impl IntoIterator<Item = (&'a Address, &'a AccountData)>,
+        round: Round,
+        params: &ConsensusParams,
+    ) -> VotersResult<Self> {
+        let mut top: Vec<(&Address, &AccountData)> = accounts
+            .into_iter()
+            .filter(|(_, data)| {
+                data.status == Status::Online
+                    && data.microalgos.0 > 0
+                    && data.vote_key_dilution > 0
+                    && data.vote_first_valid <= round
+                    && round <= data.vote_last_valid
+            })
+            .collect();
+        top.sort_by(|(a_addr, a), (b_addr, b)| {
+            b.microalgos
+                .0
+                .cmp(&a.microalgos.0)
+                .then_with(|| a_addr.as_bytes().cmp(b_addr.as_bytes()))
+        });
+        top.truncate(params.compact_cert_top_voters as usize);
+
+        let mut participants = Vec::with_capacity(top.len());
+        let mut addr_to_pos = HashMap::with_capacity(top.len());
+        let mut total_weight = 0u64;
+        for (pos, (addr, data)) in top.into_iter().enumerate() {
+            total_weight = total_weight
+                .checked_add(data.microalgos.0)
+                .ok_or("overflow computing the total weight of the voters")?;
+            participants.push(Participant {
+                pk: data.vote_id,
+                weight: data.microalgos.0,
+                key_dilution: data.vote_key_dilution,
+            });
+            addr_to_pos.insert(*addr, pos as u64);
+        }
+        let tree = Tree::build(&ParticipantsArray(&participants), HashType::Sha512_256)?;
+        Ok(Self {
+            participants,
+            addr_to_pos,
+            tree,
+            total_weight: MicroAlgos(total_weight),
+        })
+    }
+
+    /// The commitment to the voters, as stored in block headers.
+    pub fn commitment(&self) -> GenericDigest {
+        self.tree.root()
+    }
+
+    /// The weight a compact certificate has to exceed, given the weight
+    /// threshold of `params` (a fraction of 2^32).
+    pub fn proven_weight(&self, params: &ConsensusParams) -> u64 {
+        ((self.total_weight.0 as u128 * params.compact_cert_weight_threshold as u128) >> 32) as u64
+    }
+
+    /// The compact cert state that a header at a voters round commits to.
+    pub fn compact_cert_state(&self, next_round: Round) -> CompactCertState {
+        CompactCertState {
+            compact_cert_voters: self.commitment(),
+            compact_cert_voters_total: self.total_weight,
+            compact_cert_next_round: next_round,
+        }
+    }
+}
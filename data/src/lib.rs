@@ -1,7 +1,9 @@
+pub mod account;
 pub mod basics;
 pub mod bookkeeping;
 pub mod committee;
 pub mod ledger;
+pub mod pools;
 pub mod transactions;
 
 #[macro_use]
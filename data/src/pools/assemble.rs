@@ -0,0 +1,249 @@
+//! Block assembly: the proposer packs the pending groups of the pool into
+//! a block, the best paying first, within the size of a block and the time
+//! budget of a proposal.
+
+use std::cmp::Ordering;
+use std::time::{Duration, Instant};
+
+use msgp::Marshaler;
+
+use super::{PoolError, PoolResult, TransactionPool};
+use crate::bookkeeping::block::{Block, BlockHeader, UpgradeVote};
+use crate::ledger::apply::Balances;
+use crate::ledger::eval::BlockEvaluator;
+use crate::transactions::payset::PaySet;
+use crate::transactions::signedtxn::{SignedTxn, SignedTxnInBlock, SignedTxnWithAD};
+
+/// Why the assembly of a block stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssembleStopReason {
+    /// Every pending group was considered.
+    Empty,
+    /// The next group would exceed `max_txn_bytes_per_block`.
+    BlockFull,
+    /// The `proposal_assembly_time` budget was spent.
+    Timeout,
+}
+
+/// AssembleBlockStats describes the assembly of a block, for the operators
+/// to tune the pool. It is only collected with `enable_assemble_stats`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembleBlockStats {
+    /// The number of pending transactions when the assembly started.
+    pub starting_count: usize,
+    /// The number of transactions included in the block.
+    pub included_count: usize,
+    /// The number of pending transactions that failed to evaluate.
+    pub invalid_count: usize,
+    pub min_fee: u64,
+    pub max_fee: u64,
+    pub average_fee: u64,
+    /// The encoded length of the included transactions.
+    pub total_length: usize,
+    pub stop_reason: AssembleStopReason,
+    pub elapsed: Duration,
+}
+
+/// AssembledBlock is a block proposal, with the statistics of its
+/// assembly when enabled.
+#[derive(Debug)]
+pub struct AssembledBlock {
+    pub block: Block,
+    pub stats: Option<AssembleBlockStats>,
+}
+
+/// A pending group with the encoded length and the fees it pays.
+struct Candidate<'g> {
+    group: &'g [SignedTxn],
+    length: usize,
+    fee: u64,
+}
+
+impl Candidate<'_> {
+    /// Orders candidates by decreasing fee per byte, comparing
+    /// `fee / length` without dividing.
+    fn priority(&self, other: &Self) -> Ordering {
+        let this = self.fee as u128 * other.length as u128;
+        let that = other.fee as u128 * self.length as u128;
+        that.cmp(&this)
+    }
+}
+
+impl TransactionPool {
+    /// Assembles a proposal for the block following the latest one. The
+    /// pending groups are evaluated anew in fee priority order; those that
+    /// no longer apply are skipped. The assembly stops when the next group
+    /// does not fit in the block or once `proposal_assembly_time` has
+    /// elapsed. The block votes on protocol upgrades, takes the expired
+    /// participation accounts offline and commits to its payset.
+    pub fn assemble_block(&self) -> PoolResult<AssembledBlock> {
+        let started = Instant::now();
+        let deadline = started + self.assembly_time;
+
+        let mut candidates: Vec<Candidate> = self
+            .pending
+            .iter()
+            .map(|group| Candidate {
+                group,
+                length: group.iter().map(encoded_length).sum(),
+                fee: group
+                    .iter()
+                    .map(|stxn| stxn.txn.header.fee.0)
+                    .fold(0, u64::saturating_add),
+            })
+            .collect();
+        candidates.sort_by(Candidate::priority);
+
+        let mut header = Block::make(&self.latest).header;
+        let proto = self.evaluator.state().proto().clone();
+        header.upgrade_vote = UpgradeVote::make(&self.latest, &proto);
+        let mut evaluator = BlockEvaluator::new(self.base.clone(), header, &self.latest)?;
+
+        let max_length = proto.max_txn_bytes_per_block.max(0) as usize;
+        let mut total_length = 0;
+        let mut invalid_count = 0;
+        let mut stop_reason = AssembleStopReason::Empty;
+        for candidate in &candidates {
+            if Instant::now() >= deadline {
+                stop_reason = AssembleStopReason::Timeout;
+                break;
+            }
+            if total_length + candidate.length > max_length {
+                stop_reason = AssembleStopReason::BlockFull;
+                break;
+            }
+            match evaluator.transaction_group(candidate.group) {
+                Ok(()) => total_length += candidate.length,
+                Err(_) => invalid_count += candidate.group.len(),
+            }
+        }
+        evaluator.end_of_block(true)?;
+
+        let mut block = Block {
+            header: evaluator.state().header().clone(),
+            payset: PaySet(
+                evaluator
+                    .payset()
+                    .iter()
+                    .map(|stxn| in_block(stxn, evaluator.state().header()))
+                    .collect(),
+            ),
+        };
+        block.header.txn_counter = evaluator.state().txn_counter();
+        block.header.txn_commitments = block.payset_commit().map_err(PoolError::Block)?;
+
+        let stats = self.assemble_stats.then(|| {
+            let fees: Vec<u64> = evaluator
+                .payset()
+                .iter()
+                .map(|stxn| stxn.signed_txn.txn.header.fee.0)
+                .collect();
+            AssembleBlockStats {
+                starting_count: self.pending_count(),
+                included_count: fees.len(),
+                invalid_count,
+                min_fee: fees.iter().copied().min().unwrap_or_default(),
+                max_fee: fees.iter().copied().max().unwrap_or_default(),
+                average_fee: fees.iter().sum::<u64>() / fees.len().max(1) as u64,
+                total_length,
+                stop_reason,
+                elapsed: started.elapsed(),
+            }
+        });
+        Ok(AssembledBlock { block, stats })
+    }
+}
+
+fn encoded_length(stxn: &SignedTxn) -> usize {
+    let mut buffer = vec![];
+    stxn.marshal_msg(&mut buffer);
+    buffer.len()
+}
+
+/// Encodes `stxn` for the block of header `block`, leaving out the genesis
+/// of the transaction, which the block already names.
+fn in_block(stxn: &SignedTxnWithAD, block: &BlockHeader) -> SignedTxnInBlock {
+    let mut stxn = stxn.clone();
+    let header = &mut stxn.signed_txn.txn.header;
+    let has_genesis_id = !header.genesis_id.is_empty() && header.genesis_id == block.genesis_id;
+    if has_genesis_id {
+        header.genesis_id.clear();
+    }
+    let has_genesis_hash =
+        header.genesis_hash != Default::default() && header.genesis_hash == block.genesis_hash;
+    if has_genesis_hash {
+        header.genesis_hash = Default::default();
+    }
+    SignedTxnInBlock {
+        sigend_txn_with_ad: stxn,
+        has_genesis_id,
+        has_genesis_hash,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use protocol::TxType;
+
+    use super::*;
+    use crate::basics::{Address, MicroAlgos};
+    use crate::ledger::testing::MemBase;
+    use crate::transactions::verified_cache::VerifiedTxnCache;
+
+    fn pay(sender: Address, fee: u64) -> SignedTxn {
+        let mut stxn = SignedTxn::default();
+        let txn = &mut stxn.txn;
+        txn.tx_type = TxType::PaymentTx;
+        txn.header.sender = sender;
+        txn.header.fee = MicroAlgos(fee);
+        txn.header.last_valid = 10;
+        txn.payment_txn_fields.receiver = sender;
+        stxn
+    }
+
+    #[test]
+    fn blocks_take_the_best_paying_groups_first() {
+        config::consensus::init();
+        let senders = [Address::from([1; 32]), Address::from([2; 32])];
+        let base = Arc::new(MemBase::funded(&senders));
+        let mut genesis = BlockHeader::default();
+        genesis.upgrade_state.current_protocol = protocol::CONSENSUS_V32.into();
+        let mut config = config::default_local();
+        config.enable_assemble_stats = true;
+        let verified = Arc::new(VerifiedTxnCache::new(16));
+        let mut pool =
+            TransactionPool::new(base.clone(), &genesis, &config, verified.clone(), None).unwrap();
+        pool.remember(&[pay(senders[0], 1000)]).unwrap();
+        pool.remember(&[pay(senders[1], 5000)]).unwrap();
+
+        let assembled = pool.assemble_block().unwrap();
+        let fees: Vec<u64> = assembled
+            .block
+            .payset
+            .0
+            .iter()
+            .map(|stxn| stxn.sigend_txn_with_ad.signed_txn.txn.header.fee.0)
+            .collect();
+        assert_eq!(fees, vec![5000, 1000]);
+        assert_eq!(assembled.block.header.round, 1);
+        assert_eq!(assembled.block.header.txn_counter, 2);
+        let stats = assembled.stats.unwrap();
+        assert_eq!(stats.included_count, 2);
+        assert_eq!(stats.average_fee, 3000);
+        assert_eq!(stats.stop_reason, AssembleStopReason::Empty);
+
+        // Without time to assemble, the proposal is empty.
+        config.proposal_assembly_time = Duration::ZERO;
+        let mut pool =
+            TransactionPool::new(base.clone(), &genesis, &config, verified.clone(), None).unwrap();
+        pool.remember(&[pay(senders[0], 1000)]).unwrap();
+        let assembled = pool.assemble_block().unwrap();
+        assert!(assembled.block.payset.0.is_empty());
+        assert_eq!(
+            assembled.stats.unwrap().stop_reason,
+            AssembleStopReason::Timeout
+        );
+    }
+}
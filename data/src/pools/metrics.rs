@@ -0,0 +1,94 @@
+use std::sync::{Arc, OnceLock};
+
+use util::metrics::{default_registry, Counter, Gauge, Metric, MetricName, Sample};
+
+const COUNT: MetricName = MetricName {
+    name: "algod_tx_pool_count",
+    description: "Transactions pending in the pool",
+};
+const REJECTED: MetricName = MetricName {
+    name: "algod_tx_pool_rejected_total",
+    description: "Transaction groups the pool refused, by reason",
+};
+const EVICTED: MetricName = MetricName {
+    name: "algod_tx_pool_evicted_total",
+    description: "Pending transaction groups that no longer applied after a new block",
+};
+
+/// Why the pool refused a transaction group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    /// The pool is full.
+    Capacity,
+    /// A transaction of the group is already pending.
+    Duplicate,
+    /// The group does not evaluate on top of the pending groups.
+    Invalid,
+    /// The signatures of the group do not verify.
+    Unverified,
+}
+
+impl RejectReason {
+    const ALL: [RejectReason; 4] = [
+        RejectReason::Capacity,
+        RejectReason::Duplicate,
+        RejectReason::Invalid,
+        RejectReason::Unverified,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RejectReason::Capacity => "capacity",
+            RejectReason::Duplicate => "duplicate",
+            RejectReason::Invalid => "invalid",
+            RejectReason::Unverified => "unverified",
+        }
+    }
+}
+
+/// The metrics of the transaction pool of the node.
+#[derive(Debug)]
+pub struct PoolMetrics {
+    pub count: Gauge,
+    rejected: [Counter; RejectReason::ALL.len()],
+    pub evicted: Counter,
+}
+
+impl PoolMetrics {
+    fn new() -> Self {
+        Self {
+            count: Gauge::new(COUNT, vec![]),
+            rejected: RejectReason::ALL.map(|reason| {
+                Counter::new(
+                    REJECTED,
+                    vec![("reason".to_string(), reason.label().to_string())],
+                )
+            }),
+            evicted: Counter::new(EVICTED, vec![]),
+        }
+    }
+
+    pub fn rejected(&self, reason: RejectReason) -> &Counter {
+        &self.rejected[reason as usize]
+    }
+}
+
+impl Metric for PoolMetrics {
+    fn collect(&self, out: &mut Vec<Sample>) {
+        self.count.collect(out);
+        for rejected in &self.rejected {
+            rejected.collect(out);
+        }
+        self.evicted.collect(out);
+    }
+}
+
+/// The pool metrics, registered in the default registry on first use.
+pub fn pool_metrics() -> &'static PoolMetrics {
+    static METRICS: OnceLock<Arc<PoolMetrics>> = OnceLock::new();
+    METRICS.get_or_init(|| {
+        let metrics = Arc::new(PoolMetrics::new());
+        default_registry().register(metrics.clone());
+        metrics
+    })
+}
@@ -0,0 +1,379 @@
+//! The transaction pool holds the transaction groups waiting to be added to
+//! a block, keeping them valid on top of the latest round of the ledger.
+
+mod assemble;
+mod metrics;
+
+pub use assemble::*;
+pub use metrics::*;
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use config::Local;
+use util::execpool::SpawnError;
+
+use crate::bookkeeping::block::{Block, BlockHeader};
+use crate::ledger::apply::Balances;
+use crate::ledger::cow::LedgerForCowBase;
+use crate::ledger::eval::{BlockEvaluator, EvalError};
+use crate::transactions::signedtxn::{SignedTxn, SignedTxnWithAD};
+use crate::transactions::transaction::{txid_string, Txid};
+use crate::transactions::verified_cache::VerifiedTxnCache;
+use crate::transactions::verify::{GroupVerifier, VerifyError};
+
+/// PoolError is the reason the pool refused a transaction group, or could
+/// not assemble a block.
+#[derive(Debug, thiserror::Error)]
+pub enum PoolError {
+    #[error("transaction pool has reached capacity")]
+    Full,
+    #[error("transaction {} already in the pool", txid_string(.0))]
+    Duplicate(Txid),
+    #[error(transparent)]
+    Eval(#[from] EvalError),
+    #[error(transparent)]
+    Verify(#[from] VerifyError),
+    /// The group could not be verified for lack of room in the
+    /// verification backlog; it may be submitted again later.
+    #[error("transaction pool is busy: {0}")]
+    Busy(SpawnError),
+    /// The commitments of the assembled block could not be computed.
+    #[error(transparent)]
+    Block(Box<dyn std::error::Error>),
+}
+
+impl PoolError {
+    /// The reason a group refused with this error is counted under.
+    pub fn reject_reason(&self) -> RejectReason {
+        match self {
+            PoolError::Full | PoolError::Busy(_) => RejectReason::Capacity,
+            PoolError::Duplicate(_) => RejectReason::Duplicate,
+            PoolError::Verify(_) => RejectReason::Unverified,
+            PoolError::Eval(_) | PoolError::Block(_) => RejectReason::Invalid,
+        }
+    }
+}
+
+pub type PoolResult<T> = Result<T, PoolError>;
+
+/// TransactionPool evaluates the groups it remembers on top of the latest
+/// block, so that every pending group can be added to the next block.
+/// Groups are expected to have been verified before they are remembered.
+pub struct TransactionPool {
+    base: Arc<dyn LedgerForCowBase>,
+    /// The maximum number of pending transactions.
+    size: usize,
+    pending: Vec<Vec<SignedTxn>>,
+    pending_txids: HashSet<Txid>,
+    evaluator: BlockEvaluator,
+    /// The latest block of the ledger, which the pending groups follow.
+    latest: BlockHeader,
+    /// The time budget of assembling a block proposal.
+    assembly_time: Duration,
+    assemble_stats: bool,
+    /// The groups whose signatures were verified, shared with the block
+    /// validator.
+    verified: Arc<VerifiedTxnCache>,
+    /// Verifies the signatures of new groups off the calling thread, when
+    /// set.
+    verifier: Option<Arc<GroupVerifier>>,
+}
+
+impl TransactionPool {
+    /// Makes an empty pool for the block following `latest`, the latest
+    /// block of `base`. The groups the pool verifies, with `verifier` if
+    /// given, are remembered in `verified`, which the node shares with the
+    /// block validator.
+    pub fn new(
+        base: Arc<dyn LedgerForCowBase>,
+        latest: &BlockHeader,
+        config: &Local,
+        verified: Arc<VerifiedTxnCache>,
+        verifier: Option<Arc<GroupVerifier>>,
+    ) -> PoolResult<Self> {
+        let evaluator = BlockEvaluator::new(base.clone(), Block::make(latest).header, latest)?;
+        Ok(Self {
+            base,
+            size: config.tx_pool_size.max(0) as usize,
+            pending: vec![],
+            pending_txids: HashSet::new(),
+            evaluator,
+            latest: latest.clone(),
+            assembly_time: config.proposal_assembly_time,
+            assemble_stats: config.enable_assemble_stats,
+            verified,
+            verifier,
+        })
+    }
+
+    /// The number of pending transactions.
+    pub fn pending_count(&self) -> usize {
+        self.pending_txids.len()
+    }
+
+    /// The pending transaction groups, in the order they were remembered.
+    pub fn pending_txn_groups(&self) -> &[Vec<SignedTxn>] {
+        &self.pending
+    }
+
+    /// The evaluation of the pending groups in the next block.
+    pub fn evaluator(&self) -> &BlockEvaluator {
+        &self.evaluator
+    }
+
+    /// Verifies the signatures of `group`, a group received from gossip or
+    /// submitted locally, and remembers it. The verification is cached, so
+    /// it is not repeated when the group is found in a block.
+    pub fn verify_and_remember(&mut self, group: &[SignedTxn]) -> PoolResult<()> {
+        let with_ad: Vec<SignedTxnWithAD> = group
+            .iter()
+            .map(|stxn| SignedTxnWithAD {
+                signed_txn: stxn.clone(),
+                apply_data: Default::default(),
+            })
+            .collect();
+        let state = self.evaluator.state();
+        let version = &state.header().upgrade_state.current_protocol;
+        let verifier = self.verifier.as_deref();
+        match self
+            .verified
+            .verify_group(&with_ad, version, state.proto(), verifier)
+        {
+            Ok(()) => {}
+            // Shed the group rather than verify it here, under the lock of
+            // the pool, when the backlog is full.
+            Err(VerifyError::Backlog(e)) => {
+                pool_metrics().rejected(RejectReason::Capacity).inc();
+                return Err(PoolError::Busy(e));
+            }
+            Err(e) => {
+                pool_metrics().rejected(RejectReason::Unverified).inc();
+                return Err(e.into());
+            }
+        }
+        self.remember(group)
+    }
+
+    /// Adds `group` to the pool if it fits and evaluates on top of the
+    /// pending groups. A group is remembered either entirely or not at all.
+    pub fn remember(&mut self, group: &[SignedTxn]) -> PoolResult<()> {
+        let metrics = pool_metrics();
+        match self.add(group) {
+            Ok(()) => {
+                metrics.count.set(self.pending_count() as i64);
+                Ok(())
+            }
+            Err(e) => {
+                metrics.rejected(e.reject_reason()).inc();
+                Err(e)
+            }
+        }
+    }
+
+    fn add(&mut self, group: &[SignedTxn]) -> PoolResult<()> {
+        if self.pending_count() + group.len() > self.size {
+            return Err(PoolError::Full);
+        }
+        for stxn in group {
+            let txid = stxn.txn.id();
+            if self.pending_txids.contains(&txid) {
+                return Err(PoolError::Duplicate(txid));
+            }
+        }
+        self.evaluator.transaction_group(group)?;
+        self.pending_txids
+            .extend(group.iter().map(|stxn| stxn.txn.id()));
+        self.pending.push(group.to_vec());
+        Ok(())
+    }
+
+    /// Re-evaluates the pending groups on top of `latest`, the new latest
+    /// block of the ledger, dropping the groups that are no longer valid,
+    /// such as those committed in `latest`.
+    pub fn on_new_block(&mut self, latest: &BlockHeader) -> PoolResult<()> {
+        self.evaluator =
+            BlockEvaluator::new(self.base.clone(), Block::make(latest).header, latest)?;
+        self.latest = latest.clone();
+        self.pending_txids.clear();
+        let metrics = pool_metrics();
+        for group in std::mem::take(&mut self.pending) {
+            // Groups that no longer apply are dropped.
+            if self.add(&group).is_err() {
+                metrics.evicted.inc();
+            }
+        }
+        metrics.count.set(self.pending_count() as i64);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use protocol::TxType;
+
+    use super::*;
+    use crypto::curve25519::SignatureSecrets;
+
+    use crate::basics::{Address, MicroAlgos};
+    use crate::ledger::eval::validate_block;
+    use crate::ledger::testing::MemBase;
+    use crate::transactions::transaction::{compute_group_id, Transaction};
+
+    fn pay(sender: Address, receiver: Address, amount: u64) -> Transaction {
+        let mut stxn = SignedTxn::default();
+        let txn = &mut stxn.txn;
+        txn.tx_type = TxType::PaymentTx;
+        txn.header.sender = sender;
+        txn.header.fee = MicroAlgos(1000);
+        txn.header.last_valid = 10;
+        txn.payment_txn_fields.receiver = receiver;
+        txn.payment_txn_fields.amount = MicroAlgos(amount);
+        stxn.txn
+    }
+
+    fn group(txns: Vec<Transaction>) -> Vec<SignedTxn> {
+        let group_id = compute_group_id(&txns).unwrap();
+        txns.into_iter()
+            .map(|mut txn| {
+                txn.header.group = group_id;
+                SignedTxn {
+                    txn,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pending_groups_follow_the_ledger() {
+        config::consensus::init();
+        let alice = Address::from([1; 32]);
+        let bob = Address::from([2; 32]);
+        let base = Arc::new(MemBase::funded(&[alice, bob]));
+        let mut genesis = BlockHeader::default();
+        genesis.upgrade_state.current_protocol = protocol::CONSENSUS_V32.into();
+        let mut config = config::default_local();
+        config.tx_pool_size = 3;
+        let verified = Arc::new(VerifiedTxnCache::new(16));
+        let mut pool =
+            TransactionPool::new(base.clone(), &genesis, &config, verified, None).unwrap();
+
+        let swap = group(vec![pay(alice, bob, 10), pay(bob, alice, 20)]);
+        pool.remember(&swap).unwrap();
+        assert_eq!(pool.pending_count(), 2);
+        let duplicates = pool_metrics().rejected(RejectReason::Duplicate).get();
+        assert!(matches!(
+            pool.remember(&swap[..1]),
+            Err(PoolError::Duplicate(txid)) if txid == swap[0].txn.id()
+        ));
+        assert!(pool_metrics().rejected(RejectReason::Duplicate).get() > duplicates);
+
+        // Groups that do not apply leave the pool unchanged.
+        let overspend = group(vec![pay(bob, alice, 2_000_000)]);
+        assert!(matches!(pool.remember(&overspend), Err(PoolError::Eval(_))));
+        assert_eq!(pool.evaluator().payset().len(), 2);
+        let refill = group(vec![pay(alice, bob, 1), pay(alice, bob, 2)]);
+        assert!(matches!(pool.remember(&refill), Err(PoolError::Full)));
+
+        // Once committed, the pending transactions leave the pool.
+        let txns: Vec<Transaction> = swap.iter().map(|stxn| stxn.txn.clone()).collect();
+        base.commit(pool.evaluator().state(), &txns);
+        let mut latest = Block::make(&genesis).header;
+        latest.txn_counter = pool.evaluator().state().txn_counter();
+        pool.on_new_block(&latest).unwrap();
+        assert_eq!(pool.pending_count(), 0);
+        pool.remember(&refill).unwrap();
+    }
+
+    #[test]
+    fn verified_groups_are_shared_with_the_validator() {
+        config::consensus::init();
+        let secrets = SignatureSecrets::generate(&[1; 32]);
+        let alice = Address::from(secrets.signature_verifier);
+        let bob = Address::from([2; 32]);
+        let base = Arc::new(MemBase::funded(&[alice]));
+        let mut genesis = BlockHeader::default();
+        genesis.upgrade_state.current_protocol = protocol::CONSENSUS_V32.into();
+        let cache = Arc::new(VerifiedTxnCache::new(16));
+        let config = config::default_local();
+        let mut pool =
+            TransactionPool::new(base.clone(), &genesis, &config, cache.clone(), None).unwrap();
+
+        let mut stxn = SignedTxn {
+            txn: pay(alice, bob, 100_000),
+            ..Default::default()
+        };
+        assert!(matches!(
+            pool.verify_and_remember(&[stxn.clone()]),
+            Err(PoolError::Verify(VerifyError::NoSignature))
+        ));
+        stxn.sig = secrets.sign(&stxn.txn);
+        pool.verify_and_remember(&[stxn]).unwrap();
+        assert_eq!(cache.len(), 1);
+
+        // The validator finds the group of the proposal in the cache.
+        let block = pool.assemble_block().unwrap().block;
+        let eval = validate_block(base.clone(), &block, &genesis, &cache, None).unwrap();
+        assert_eq!(eval.state().get(&bob).microalgos, MicroAlgos(100_000));
+        assert_eq!(cache.len(), 1);
+
+        let mut tampered = block;
+        tampered.header.txn_commitments = Default::default();
+        assert!(validate_block(base.clone(), &tampered, &genesis, &cache, None).is_err());
+    }
+
+    #[test]
+    fn groups_are_verified_on_the_backlog() {
+        use util::execpool::{Backlog, DedicatedExecutor, Priority};
+
+        config::consensus::init();
+        let secrets = SignatureSecrets::generate(&[1; 32]);
+        let alice = Address::from(secrets.signature_verifier);
+        let bob = Address::from([2; 32]);
+        let base = Arc::new(MemBase::funded(&[alice]));
+        let mut genesis = BlockHeader::default();
+        genesis.upgrade_state.current_protocol = protocol::CONSENSUS_V32.into();
+        let exec = DedicatedExecutor::new("Test verification", Some(1));
+        let verifier = Arc::new(GroupVerifier::new(Backlog::new(
+            exec.clone(),
+            Priority::LowPriority,
+        )));
+        let cache = Arc::new(VerifiedTxnCache::new(16));
+        let config = config::default_local();
+        let mut pool = TransactionPool::new(
+            base.clone(),
+            &genesis,
+            &config,
+            cache.clone(),
+            Some(verifier.clone()),
+        )
+        .unwrap();
+
+        let signed = |amount| {
+            let txn = pay(alice, bob, amount);
+            SignedTxn {
+                sig: secrets.sign(&txn),
+                txn,
+                ..Default::default()
+            }
+        };
+        pool.verify_and_remember(&[signed(100_000)]).unwrap();
+        assert_eq!(cache.len(), 1);
+
+        // Groups the backlog can not take are shed, not verified inline.
+        exec.shutdown();
+        assert!(matches!(
+            pool.verify_and_remember(&[signed(200_000)]),
+            Err(PoolError::Busy(SpawnError::Shutdown))
+        ));
+        assert_eq!(pool.pending_count(), 1);
+
+        // Blocks are verified inline instead.
+        let block = pool.assemble_block().unwrap().block;
+        let fresh = VerifiedTxnCache::new(16);
+        validate_block(base.clone(), &block, &genesis, &fresh, Some(&verifier)).unwrap();
+        assert_eq!(fresh.len(), 1);
+    }
+}
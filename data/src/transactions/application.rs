@@ -0,0 +1,191 @@
+use config::consensus::ConsensusParams;
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use super::transaction::TxnResult;
+use crate::basics::{Address, AppIndex, AssetIndex, StateSchema};
+
+/// OnCompletion is an enum representing some layer 1 side effect that an
+/// ApplicationCall transaction will have if it is included in a block.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u64)]
+pub enum OnCompletion {
+    /// NoOp indicates that an application transaction will simply call its
+    /// approval program.
+    #[default]
+    NoOp = 0,
+    /// OptIn indicates that an application transaction will allocate some
+    /// local state for the application in the sender's account.
+    OptIn = 1,
+    /// CloseOut indicates that an application transaction will deallocate
+    /// some local state for the application from the user's account.
+    CloseOut = 2,
+    /// ClearState is similar to CloseOut, but may never fail. This allows
+    /// users to reclaim their minimum balance from an application they no
+    /// longer wish to opt in to.
+    ClearState = 3,
+    /// UpdateApplication indicates that an application transaction will
+    /// update the approval and clear state programs of the application.
+    UpdateApplication = 4,
+    /// DeleteApplication indicates that an application transaction will
+    /// delete the AppParams for the application from the creator's balance
+    /// record.
+    DeleteApplication = 5,
+}
+
+impl TryFrom<u64> for OnCompletion {
+    type Error = String;
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => OnCompletion::NoOp,
+            1 => OnCompletion::OptIn,
+            2 => OnCompletion::CloseOut,
+            3 => OnCompletion::ClearState,
+            4 => OnCompletion::UpdateApplication,
+            5 => OnCompletion::DeleteApplication,
+            _ => return Err(format!("invalid OnCompletion {}", value)),
+        })
+    }
+}
+
+/// ApplicationCallTxnFields captures the transaction fields used for all
+/// interactions with applications.
+#[skip_serializing_default]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApplicationCallTxnFields {
+    #[serde(rename = "apid")]
+    pub application_id: AppIndex,
+    #[serde(rename = "apan")]
+    pub on_completion: OnCompletion,
+    #[serde(rename = "apaa")]
+    pub application_args: Vec<Vec<u8>>,
+    #[serde(rename = "apat")]
+    pub accounts: Vec<Address>,
+    #[serde(rename = "apfa")]
+    pub foreign_apps: Vec<AppIndex>,
+    #[serde(rename = "apas")]
+    pub foreign_assets: Vec<AssetIndex>,
+    #[serde(rename = "apls")]
+    pub local_state_schema: StateSchema,
+    #[serde(rename = "apgs")]
+    pub global_state_schema: StateSchema,
+    #[serde(rename = "apap")]
+    pub approval_program: Vec<u8>,
+    #[serde(rename = "apsu")]
+    pub clear_state_program: Vec<u8>,
+    #[serde(rename = "apep")]
+    pub extra_program_pages: u32,
+}
+
+impl ApplicationCallTxnFields {
+    /// Checks the fields against the application limits of `proto`.
+    pub fn well_formed(&self, proto: &ConsensusParams) -> TxnResult<()> {
+        if !proto.application {
+            return Err("application transaction not supported".into());
+        }
+        let creating = self.application_id == 0;
+        if !creating
+            && self.on_completion != OnCompletion::UpdateApplication
+            && (!self.approval_program.is_empty() || !self.clear_state_program.is_empty())
+        {
+            return Err(
+                "programs may only be specified during application creation or update".into(),
+            );
+        }
+        if !creating {
+            if self.local_state_schema != StateSchema::default()
+                || self.global_state_schema != StateSchema::default()
+            {
+                return Err("local and global state schemas are immutable".into());
+            }
+            if self.extra_program_pages != 0 {
+                return Err("tx.ExtraProgramPages is immutable".into());
+            }
+        }
+        if creating && self.on_completion == OnCompletion::ClearState {
+            return Err("cannot clear state of an application that does not exist yet".into());
+        }
+
+        let limit = |max: i32| max.max(0) as usize;
+        if self.application_args.len() > limit(proto.max_app_args) {
+            return Err(format!("too many application args, max {}", proto.max_app_args).into());
+        }
+        let arg_sum = self
+            .application_args
+            .iter()
+            .fold(0usize, |sum, arg| sum.saturating_add(arg.len()));
+        if arg_sum > limit(proto.max_app_total_arg_len) {
+            return Err(format!(
+                "application args total length too long, max len {} bytes",
+                proto.max_app_total_arg_len
+            )
+            .into());
+        }
+        if self.accounts.len() > limit(proto.max_app_txn_accounts) {
+            return Err(format!(
+                "tx.Accounts too long, max number of accounts is {}",
+                proto.max_app_txn_accounts
+            )
+            .into());
+        }
+        if self.foreign_apps.len() > limit(proto.max_app_txn_foreign_apps) {
+            return Err(format!(
+                "tx.ForeignApps too long, max number of foreign apps is {}",
+                proto.max_app_txn_foreign_apps
+            )
+            .into());
+        }
+        if self.foreign_assets.len() > limit(proto.max_app_txn_foreign_assets) {
+            return Err(format!(
+                "tx.ForeignAssets too long, max number of foreign assets is {}",
+                proto.max_app_txn_foreign_assets
+            )
+            .into());
+        }
+        let references = self.accounts.len() + self.foreign_apps.len() + self.foreign_assets.len();
+        if references > limit(proto.max_app_total_txn_references) {
+            return Err(format!(
+                "tx references exceed MaxAppTotalTxnReferences = {}",
+                proto.max_app_total_txn_references
+            )
+            .into());
+        }
+        if self.extra_program_pages as usize > limit(proto.max_extra_app_program_pages) {
+            return Err(format!(
+                "tx.ExtraProgramPages exceeds MaxExtraAppProgramPages = {}",
+                proto.max_extra_app_program_pages
+            )
+            .into());
+        }
+
+        let pages = 1 + self.extra_program_pages as usize;
+        let max_len = pages * limit(proto.max_app_program_len);
+        if self.approval_program.len() > max_len {
+            return Err(format!("approval program too long. max len {} bytes", max_len).into());
+        }
+        if self.clear_state_program.len() > max_len {
+            return Err(format!("clear state program too long. max len {} bytes", max_len).into());
+        }
+        let max_total = pages * limit(proto.max_app_total_program_len);
+        if self.approval_program.len() + self.clear_state_program.len() > max_total {
+            return Err(format!("app programs too long. max total len {} bytes", max_total).into());
+        }
+        if self.local_state_schema.num_entries() > proto.max_local_schema_entries {
+            return Err(format!(
+                "tx.LocalStateSchema too large, max number of keys is {}",
+                proto.max_local_schema_entries
+            )
+            .into());
+        }
+        if self.global_state_schema.num_entries() > proto.max_global_schema_entries {
+            return Err(format!(
+                "tx.GlobalStateSchema too large, max number of keys is {}",
+                proto.max_global_schema_entries
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use config::consensus::ConsensusParams;
+use serde::{Deserialize, Serialize};
+
+use super::transaction::TxnResult;
+use crate::basics::{Address, AssetIndex, AssetParams};
+
+/// AssetConfigTxnFields captures the fields used for asset allocation,
+/// re-configuration, and destruction.
+#[skip_serializing_default]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssetConfigTxnFields {
+    /// The asset being configured or destroyed. A zero value means
+    /// allocation.
+    #[serde(rename = "caid")]
+    pub config_asset: AssetIndex,
+    /// The parameters for the asset being created or re-configured. A zero
+    /// value means destruction.
+    #[serde(rename = "apar")]
+    pub asset_params: AssetParams,
+}
+
+/// AssetTransferTxnFields captures the fields used for asset transfers.
+#[skip_serializing_default]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssetTransferTxnFields {
+    #[serde(rename = "xaid")]
+    pub xfer_asset: AssetIndex,
+    /// The amount of the asset to transfer. A zero amount transferred to
+    /// self allocates that asset in the account's holdings.
+    #[serde(rename = "aamt")]
+    pub asset_amount: u64,
+    /// The sender of the transfer. If this is not a zero value, the real
+    /// transaction sender must be the Clawback address from the
+    /// AssetParams.
+    #[serde(rename = "asnd")]
+    pub asset_sender: Address,
+    #[serde(rename = "arcv")]
+    pub asset_receiver: Address,
+    /// Indicates that the asset should be removed from the account's
+    /// holdings, and the remaining units transferred to this address.
+    #[serde(rename = "aclose")]
+    pub asset_close_to: Address,
+}
+
+/// AssetFreezeTxnFields captures the fields used for freezing asset
+/// slots.
+#[skip_serializing_default]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssetFreezeTxnFields {
+    /// The address of the account whose asset slot is being frozen or
+    /// un-frozen.
+    #[serde(rename = "fadd")]
+    pub freeze_account: Address,
+    #[serde(rename = "faid")]
+    pub freeze_asset: AssetIndex,
+    /// The new frozen value.
+    #[serde(rename = "afrz")]
+    pub asset_frozen: bool,
+}
+
+impl AssetConfigTxnFields {
+    /// Checks the asset parameters against the limits of `proto`.
+    pub fn well_formed(&self, proto: &ConsensusParams) -> TxnResult<()> {
+        let params = &self.asset_params;
+        if params.asset_name.len() > proto.max_asset_name_bytes.max(0) as usize {
+            return Err(format!(
+                "transaction asset name too big: {} > {}",
+                params.asset_name.len(),
+                proto.max_asset_name_bytes
+            )
+            .into());
+        }
+        if params.unit_name.len() > proto.max_asset_unit_name_bytes.max(0) as usize {
+            return Err(format!(
+                "transaction asset unit name too big: {} > {}",
+                params.unit_name.len(),
+                proto.max_asset_unit_name_bytes
+            )
+            .into());
+        }
+        if params.url.len() > proto.max_asset_url_bytes.max(0) as usize {
+            return Err(format!(
+                "transaction asset url too big: {} > {}",
+                params.url.len(),
+                proto.max_asset_url_bytes
+            )
+            .into());
+        }
+        if params.decimals > proto.max_asset_decimals {
+            return Err(format!(
+                "transaction asset decimals is too high (max is {})",
+                proto.max_asset_decimals
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use config::consensus::ConsensusParams;
+use crypto::merklearray::GenericDigest;
+use crypto::{onetimesig::OneTimeSignatureVerifier, vrf::VRFVerifier};
+use serde::{Deserialize, Serialize};
+
+use super::transaction::TxnResult;
+use crate::basics::Round;
+
+/// KeyregTxnFields captures the fields used for key registration
+/// transactions.
+#[skip_serializing_default]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyregTxnFields {
+    #[serde(rename = "votekey")]
+    pub vote_pk: OneTimeSignatureVerifier,
+    #[serde(rename = "selkey")]
+    pub selection_pk: VRFVerifier,
+    #[serde(rename = "votefst")]
+    pub vote_first: Round,
+    #[serde(rename = "votelst")]
+    pub vote_last: Round,
+    #[serde(rename = "votekd")]
+    pub vote_key_dilution: u64,
+    /// The commitment to the state proof keys, required to go online since
+    /// `enable_state_proof_keyreg_check`.
+    #[serde(rename = "sprfkey")]
+    pub state_proof_pk: GenericDigest,
+    #[serde(rename = "nonpart")]
+    pub nonparticipation: bool,
+}
+
+impl KeyregTxnFields {
+    fn goes_offline(&self) -> bool {
+        self.vote_pk == [0; 32] || self.selection_pk == [0; 32]
+    }
+
+    /// Checks that the fields describe a coherent registration: either
+    /// going online with a complete set of keys, or going offline or
+    /// non-participating without any. `last_valid` is the last round of the
+    /// transaction.
+    pub fn well_formed(&self, proto: &ConsensusParams, last_valid: Round) -> TxnResult<()> {
+        if proto.enable_keyreg_coherency_check {
+            if self.vote_first > self.vote_last {
+                return Err(
+                    "transaction first voting round need to be less than its last voting round"
+                        .into(),
+                );
+            }
+            let all_clear = self.vote_pk == [0; 32]
+                && self.selection_pk == [0; 32]
+                && self.vote_key_dilution == 0;
+            let all_set = self.vote_pk != [0; 32]
+                && self.selection_pk != [0; 32]
+                && self.vote_key_dilution != 0;
+            if !all_clear && !all_set {
+                return Err("the following transaction fields need to be clear/set together : votekey, selkey, votekd".into());
+            }
+            if self.vote_key_dilution == 0 {
+                if self.vote_first != 0 || self.vote_last != 0 {
+                    return Err("on going offline key registration transaction, the vote first and vote last fields should not be set".into());
+                }
+            } else {
+                if self.vote_last == 0 {
+                    return Err(
+                        "transaction tries to register keys to go online, but vote last is set to zero"
+                            .into(),
+                    );
+                }
+                if self.vote_first > last_valid + 1 {
+                    return Err("transaction tries to register keys to go online, but first voting round is beyond the round after last valid round".into());
+                }
+            }
+        }
+
+        // Marking an account non-participating supplies no keys, as when
+        // going offline.
+        if self.nonparticipation {
+            if !proto.support_become_non_participating_transactions {
+                return Err("transaction tries to mark an account as nonparticipating, but that transaction is not supported".into());
+            }
+            if !self.goes_offline() {
+                return Err(
+                    "transaction tries to register keys to go online, but nonparticipatory flag is set"
+                        .into(),
+                );
+            }
+        }
+        self.state_proof_pk_well_formed(proto)
+    }
+
+    fn state_proof_pk_well_formed(&self, proto: &ConsensusParams) -> TxnResult<()> {
+        let empty = self.state_proof_pk.is_empty();
+        if !proto.enable_state_proof_keyreg_check {
+            if !empty {
+                return Err(
+                    "transaction field StateProofPK should be empty in this consensus version"
+                        .into(),
+                );
+            }
+            return Ok(());
+        }
+        if self.vote_key_dilution == 0 {
+            if empty {
+                return Ok(());
+            }
+            if self.nonparticipation {
+                return Err(
+                    "non participation keyreg transactions should contain empty stateProofPK"
+                        .into(),
+                );
+            }
+            return Err("offline keyreg transactions should contain empty stateProofPK".into());
+        }
+        if empty {
+            return Err("online keyreg transaction cannot have empty field StateProofPK".into());
+        }
+        let period = self.vote_last.saturating_sub(self.vote_first);
+        if period > proto.max_keyreg_valid_period {
+            return Err(format!(
+                "validity period for keyreg transaction is too long: {} > {}",
+                period, proto.max_keyreg_valid_period
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,416 @@
+//! Integer and byte-array math.
+
+use std::cmp::Ordering;
+
+use num_bigint::BigUint;
+
+use super::eval::{EvalContext, OpResult, StackValue};
+use super::{MAX_BYTE_MATH_SIZE, MAX_STRING_SIZE};
+
+fn binary(cx: &mut EvalContext, f: impl FnOnce(u64, u64) -> OpResult<u64>) -> OpResult {
+    let b = cx.pop_uint()?;
+    let a = cx.pop_uint()?;
+    cx.push_uint(f(a, b)?);
+    Ok(())
+}
+
+fn compare(cx: &mut EvalContext, f: impl FnOnce(u64, u64) -> bool) -> OpResult {
+    let b = cx.pop_uint()?;
+    let a = cx.pop_uint()?;
+    cx.push_bool(f(a, b));
+    Ok(())
+}
+
+pub(super) fn op_plus(cx: &mut EvalContext) -> OpResult {
+    binary(cx, |a, b| {
+        a.checked_add(b).ok_or_else(|| "+ overflowed".into())
+    })
+}
+
+pub(super) fn op_minus(cx: &mut EvalContext) -> OpResult {
+    binary(cx, |a, b| {
+        a.checked_sub(b)
+            .ok_or_else(|| "- would result negative".into())
+    })
+}
+
+pub(super) fn op_div(cx: &mut EvalContext) -> OpResult {
+    binary(cx, |a, b| a.checked_div(b).ok_or_else(|| "/ 0".into()))
+}
+
+pub(super) fn op_mul(cx: &mut EvalContext) -> OpResult {
+    binary(cx, |a, b| {
+        a.checked_mul(b).ok_or_else(|| "* overflowed".into())
+    })
+}
+
+pub(super) fn op_modulo(cx: &mut EvalContext) -> OpResult {
+    binary(cx, |a, b| a.checked_rem(b).ok_or_else(|| "% 0".into()))
+}
+
+pub(super) fn op_lt(cx: &mut EvalContext) -> OpResult {
+    compare(cx, |a, b| a < b)
+}
+
+pub(super) fn op_gt(cx: &mut EvalContext) -> OpResult {
+    compare(cx, |a, b| a > b)
+}
+
+pub(super) fn op_le(cx: &mut EvalContext) -> OpResult {
+    compare(cx, |a, b| a <= b)
+}
+
+pub(super) fn op_ge(cx: &mut EvalContext) -> OpResult {
+    compare(cx, |a, b| a >= b)
+}
+
+pub(super) fn op_and(cx: &mut EvalContext) -> OpResult {
+    compare(cx, |a, b| a != 0 && b != 0)
+}
+
+pub(super) fn op_or(cx: &mut EvalContext) -> OpResult {
+    compare(cx, |a, b| a != 0 || b != 0)
+}
+
+fn equal(cx: &mut EvalContext) -> OpResult<bool> {
+    let b = cx.pop()?;
+    let a = cx.pop()?;
+    match (&a, &b) {
+        (StackValue::Uint(_), StackValue::Uint(_))
+        | (StackValue::Bytes(_), StackValue::Bytes(_)) => Ok(a == b),
+        _ => Err(format!(
+            "cannot compare ({} to {})",
+            a.type_name(),
+            b.type_name()
+        )),
+    }
+}
+
+pub(super) fn op_eq(cx: &mut EvalContext) -> OpResult {
+    let eq = equal(cx)?;
+    cx.push_bool(eq);
+    Ok(())
+}
+
+pub(super) fn op_neq(cx: &mut EvalContext) -> OpResult {
+    let eq = equal(cx)?;
+    cx.push_bool(!eq);
+    Ok(())
+}
+
+pub(super) fn op_not(cx: &mut EvalContext) -> OpResult {
+    let a = cx.pop_uint()?;
+    cx.push_bool(a == 0);
+    Ok(())
+}
+
+pub(super) fn op_itob(cx: &mut EvalContext) -> OpResult {
+    let a = cx.pop_uint()?;
+    cx.push_bytes(a.to_be_bytes().to_vec())
+}
+
+pub(super) fn op_btoi(cx: &mut EvalContext) -> OpResult {
+    let a = cx.pop_bytes()?;
+    if a.len() > 8 {
+        return Err(format!("btoi arg too long, got [{}]bytes", a.len()));
+    }
+    cx.push_uint(a.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64));
+    Ok(())
+}
+
+pub(super) fn op_bit_or(cx: &mut EvalContext) -> OpResult {
+    binary(cx, |a, b| Ok(a | b))
+}
+
+pub(super) fn op_bit_and(cx: &mut EvalContext) -> OpResult {
+    binary(cx, |a, b| Ok(a & b))
+}
+
+pub(super) fn op_bit_xor(cx: &mut EvalContext) -> OpResult {
+    binary(cx, |a, b| Ok(a ^ b))
+}
+
+pub(super) fn op_bit_not(cx: &mut EvalContext) -> OpResult {
+    let a = cx.pop_uint()?;
+    cx.push_uint(!a);
+    Ok(())
+}
+
+fn push_u128(cx: &mut EvalContext, value: u128) {
+    cx.push_uint((value >> 64) as u64);
+    cx.push_uint(value as u64);
+}
+
+fn pop_u128(cx: &mut EvalContext) -> OpResult<u128> {
+    let lo = cx.pop_uint()?;
+    let hi = cx.pop_uint()?;
+    Ok(((hi as u128) << 64) | lo as u128)
+}
+
+pub(super) fn op_mulw(cx: &mut EvalContext) -> OpResult {
+    let b = cx.pop_uint()?;
+    let a = cx.pop_uint()?;
+    push_u128(cx, a as u128 * b as u128);
+    Ok(())
+}
+
+pub(super) fn op_addw(cx: &mut EvalContext) -> OpResult {
+    let b = cx.pop_uint()?;
+    let a = cx.pop_uint()?;
+    push_u128(cx, a as u128 + b as u128);
+    Ok(())
+}
+
+pub(super) fn op_divmodw(cx: &mut EvalContext) -> OpResult {
+    let divisor = pop_u128(cx)?;
+    let dividend = pop_u128(cx)?;
+    if divisor == 0 {
+        return Err("/ 0".into());
+    }
+    push_u128(cx, dividend / divisor);
+    push_u128(cx, dividend % divisor);
+    Ok(())
+}
+
+pub(super) fn op_divw(cx: &mut EvalContext) -> OpResult {
+    let c = cx.pop_uint()?;
+    let dividend = pop_u128(cx)?;
+    if c == 0 {
+        return Err("/ 0".into());
+    }
+    let quotient = u64::try_from(dividend / c as u128).map_err(|_| "divw overflow")?;
+    cx.push_uint(quotient);
+    Ok(())
+}
+
+pub(super) fn op_shl(cx: &mut EvalContext) -> OpResult {
+    binary(cx, |a, b| {
+        if b > 63 {
+            return Err(format!("shl arg too big, ({} > 63)", b));
+        }
+        Ok(a << b)
+    })
+}
+
+pub(super) fn op_shr(cx: &mut EvalContext) -> OpResult {
+    binary(cx, |a, b| {
+        if b > 63 {
+            return Err(format!("shr arg too big, ({} > 63)", b));
+        }
+        Ok(a >> b)
+    })
+}
+
+/// Returns the integer square root of `a`, rounded down.
+fn isqrt(a: u64) -> u64 {
+    if a < 2 {
+        return a;
+    }
+    let mut x = (a as f64).sqrt() as u64;
+    while x.checked_mul(x).is_none_or(|sq| sq > a) {
+        x -= 1;
+    }
+    while (x + 1).checked_mul(x + 1).is_some_and(|sq| sq <= a) {
+        x += 1;
+    }
+    x
+}
+
+pub(super) fn op_sqrt(cx: &mut EvalContext) -> OpResult {
+    let a = cx.pop_uint()?;
+    cx.push_uint(isqrt(a));
+    Ok(())
+}
+
+pub(super) fn op_bitlen(cx: &mut EvalContext) -> OpResult {
+    let len = match cx.pop()? {
+        StackValue::Uint(a) => 64 - a.leading_zeros() as u64,
+        StackValue::Bytes(b) => match b.iter().position(|&byte| byte != 0) {
+            Some(i) => ((b.len() - i) * 8) as u64 - b[i].leading_zeros() as u64,
+            None => 0,
+        },
+    };
+    cx.push_uint(len);
+    Ok(())
+}
+
+pub(super) fn op_exp(cx: &mut EvalContext) -> OpResult {
+    binary(cx, |a, b| {
+        if a == 0 && b == 0 {
+            return Err("0^0 is undefined".into());
+        }
+        if a < 2 {
+            return Ok(if b == 0 { 1 } else { a });
+        }
+        u32::try_from(b)
+            .ok()
+            .and_then(|b| a.checked_pow(b))
+            .ok_or_else(|| format!("{}^{} overflow", a, b))
+    })
+}
+
+pub(super) fn op_expw(cx: &mut EvalContext) -> OpResult {
+    let b = cx.pop_uint()?;
+    let a = cx.pop_uint()?;
+    if a == 0 && b == 0 {
+        return Err("0^0 is undefined".into());
+    }
+    let result = if a < 2 {
+        if b == 0 {
+            1
+        } else {
+            a as u128
+        }
+    } else {
+        u32::try_from(b)
+            .ok()
+            .and_then(|b| (a as u128).checked_pow(b))
+            .ok_or_else(|| format!("{}^{} overflow", a, b))?
+    };
+    push_u128(cx, result);
+    Ok(())
+}
+
+fn pop_big(cx: &mut EvalContext) -> OpResult<BigUint> {
+    let bytes = cx.pop_bytes()?;
+    if bytes.len() > MAX_BYTE_MATH_SIZE {
+        return Err(format!(
+            "math attempted on large byte-array: {} > {}",
+            bytes.len(),
+            MAX_BYTE_MATH_SIZE
+        ));
+    }
+    Ok(BigUint::from_bytes_be(&bytes))
+}
+
+fn push_big(cx: &mut EvalContext, value: BigUint) -> OpResult {
+    let zero = BigUint::default();
+    if value == zero {
+        return cx.push_bytes(vec![]);
+    }
+    cx.push_bytes(value.to_bytes_be())
+}
+
+fn bytes_binary(
+    cx: &mut EvalContext,
+    f: impl FnOnce(BigUint, BigUint) -> OpResult<BigUint>,
+) -> OpResult {
+    let b = pop_big(cx)?;
+    let a = pop_big(cx)?;
+    let result = f(a, b)?;
+    push_big(cx, result)
+}
+
+fn bytes_compare(cx: &mut EvalContext, f: impl FnOnce(Ordering) -> bool) -> OpResult {
+    let b = pop_big(cx)?;
+    let a = pop_big(cx)?;
+    cx.push_bool(f(a.cmp(&b)));
+    Ok(())
+}
+
+pub(super) fn op_bytes_plus(cx: &mut EvalContext) -> OpResult {
+    bytes_binary(cx, |a, b| Ok(a + b))
+}
+
+pub(super) fn op_bytes_minus(cx: &mut EvalContext) -> OpResult {
+    bytes_binary(cx, |a, b| {
+        if a < b {
+            return Err("byte math would have negative result".into());
+        }
+        Ok(a - b)
+    })
+}
+
+pub(super) fn op_bytes_div(cx: &mut EvalContext) -> OpResult {
+    bytes_binary(cx, |a, b| {
+        if b == BigUint::default() {
+            return Err("division by zero".into());
+        }
+        Ok(a / b)
+    })
+}
+
+pub(super) fn op_bytes_mul(cx: &mut EvalContext) -> OpResult {
+    bytes_binary(cx, |a, b| Ok(a * b))
+}
+
+pub(super) fn op_bytes_modulo(cx: &mut EvalContext) -> OpResult {
+    bytes_binary(cx, |a, b| {
+        if b == BigUint::default() {
+            return Err("modulo by zero".into());
+        }
+        Ok(a % b)
+    })
+}
+
+pub(super) fn op_bytes_lt(cx: &mut EvalContext) -> OpResult {
+    bytes_compare(cx, |o| o == Ordering::Less)
+}
+
+pub(super) fn op_bytes_gt(cx: &mut EvalContext) -> OpResult {
+    bytes_compare(cx, |o| o == Ordering::Greater)
+}
+
+pub(super) fn op_bytes_le(cx: &mut EvalContext) -> OpResult {
+    bytes_compare(cx, |o| o != Ordering::Greater)
+}
+
+pub(super) fn op_bytes_ge(cx: &mut EvalContext) -> OpResult {
+    bytes_compare(cx, |o| o != Ordering::Less)
+}
+
+pub(super) fn op_bytes_eq(cx: &mut EvalContext) -> OpResult {
+    bytes_compare(cx, |o| o == Ordering::Equal)
+}
+
+pub(super) fn op_bytes_neq(cx: &mut EvalContext) -> OpResult {
+    bytes_compare(cx, |o| o != Ordering::Equal)
+}
+
+pub(super) fn op_bsqrt(cx: &mut EvalContext) -> OpResult {
+    let a = pop_big(cx)?;
+    push_big(cx, a.sqrt())
+}
+
+/// Applies `f` bytewise to the operands, left-padded with zeros to the
+/// same length.
+fn bytes_bitwise(cx: &mut EvalContext, f: impl Fn(u8, u8) -> u8) -> OpResult {
+    let b = cx.pop_bytes()?;
+    let a = cx.pop_bytes()?;
+    let len = a.len().max(b.len());
+    let pad = |v: &[u8]| {
+        let mut padded = vec![0u8; len - v.len()];
+        padded.extend_from_slice(v);
+        padded
+    };
+    let (a, b) = (pad(&a), pad(&b));
+    cx.push_bytes(a.iter().zip(b.iter()).map(|(&x, &y)| f(x, y)).collect())
+}
+
+pub(super) fn op_bytes_bit_or(cx: &mut EvalContext) -> OpResult {
+    bytes_bitwise(cx, |a, b| a | b)
+}
+
+pub(super) fn op_bytes_bit_and(cx: &mut EvalContext) -> OpResult {
+    bytes_bitwise(cx, |a, b| a & b)
+}
+
+pub(super) fn op_bytes_bit_xor(cx: &mut EvalContext) -> OpResult {
+    bytes_bitwise(cx, |a, b| a ^ b)
+}
+
+pub(super) fn op_bytes_bit_not(cx: &mut EvalContext) -> OpResult {
+    let a = cx.pop_bytes()?;
+    cx.push_bytes(a.iter().map(|b| !b).collect())
+}
+
+pub(super) fn op_bytes_zero(cx: &mut EvalContext) -> OpResult {
+    let len = cx.pop_uint()?;
+    if len > MAX_STRING_SIZE as u64 {
+        return Err(format!(
+            "bzero attempted to create a too large string: {}",
+            len
+        ));
+    }
+    cx.push_bytes(vec![0u8; len as usize])
+}
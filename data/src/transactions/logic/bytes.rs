@@ -0,0 +1,350 @@
+//! Byte string manipulation, hashing and signature verification.
+
+use crypto::ecdsa::{self, EcdsaCurve};
+use crypto::util::{self, HashType};
+
+use super::eval::{EvalContext, OpResult, StackValue};
+use super::program_hash;
+
+fn hash(cx: &mut EvalContext, f: impl FnOnce(&[u8]) -> util::HashDigest) -> OpResult {
+    let a = cx.pop_bytes()?;
+    cx.push_bytes(f(&a).0.to_vec())
+}
+
+pub(super) fn op_sha256(cx: &mut EvalContext) -> OpResult {
+    hash(cx, |a| HashType::Sha256.hash(a))
+}
+
+pub(super) fn op_keccak256(cx: &mut EvalContext) -> OpResult {
+    hash(cx, util::keccak256)
+}
+
+pub(super) fn op_sha512_256(cx: &mut EvalContext) -> OpResult {
+    hash(cx, util::hash)
+}
+
+pub(super) fn op_sha3_256(cx: &mut EvalContext) -> OpResult {
+    hash(cx, util::sha3_256)
+}
+
+/// Pops the signature and public key of an ed25519 verification.
+fn pop_ed25519(cx: &mut EvalContext) -> OpResult<([u8; 64], [u8; 32])> {
+    let pk = cx.pop_bytes()?;
+    let sig = cx.pop_bytes()?;
+    let pk: [u8; 32] = pk
+        .try_into()
+        .map_err(|pk: Vec<u8>| format!("invalid public key of {} bytes", pk.len()))?;
+    let sig: [u8; 64] = sig
+        .try_into()
+        .map_err(|sig: Vec<u8>| format!("invalid signature of {} bytes", sig.len()))?;
+    Ok((sig, pk))
+}
+
+pub(super) fn op_ed25519verify(cx: &mut EvalContext) -> OpResult {
+    let (sig, pk) = pop_ed25519(cx)?;
+    let data = cx.pop_bytes()?;
+    let mut msg = protocol::PROGRAM_DATA.as_bytes().to_vec();
+    msg.extend_from_slice(&program_hash(cx.program).0);
+    msg.extend_from_slice(&data);
+    cx.push_bool(crypto::curve25519::ed25519_verify(pk, &msg, sig, true));
+    Ok(())
+}
+
+pub(super) fn op_ed25519verify_bare(cx: &mut EvalContext) -> OpResult {
+    let (sig, pk) = pop_ed25519(cx)?;
+    let data = cx.pop_bytes()?;
+    cx.push_bool(crypto::curve25519::ed25519_verify(pk, &data, sig, true));
+    Ok(())
+}
+
+fn curve(cx: &EvalContext) -> OpResult<EcdsaCurve> {
+    match cx.imm(0) {
+        0 => Ok(EcdsaCurve::Secp256k1),
+        1 if cx.version >= 7 => Ok(EcdsaCurve::Secp256r1),
+        other => Err(format!("invalid curve {}", other)),
+    }
+}
+
+pub(super) fn op_ecdsa_verify(cx: &mut EvalContext) -> OpResult {
+    let curve = curve(cx)?;
+    let y = cx.pop_bytes()?;
+    let x = cx.pop_bytes()?;
+    let s = cx.pop_bytes()?;
+    let r = cx.pop_bytes()?;
+    let digest = cx.pop_bytes()?;
+    if digest.len() != 32 {
+        return Err(format!(
+            "data is not a 32 byte hash: {} bytes",
+            digest.len()
+        ));
+    }
+    cx.push_bool(ecdsa::verify(curve, &digest, &r, &s, &x, &y));
+    Ok(())
+}
+
+pub(super) fn op_ecdsa_pk_decompress(cx: &mut EvalContext) -> OpResult {
+    let curve = curve(cx)?;
+    let compressed = cx.pop_bytes()?;
+    let (x, y) = ecdsa::decompress(curve, &compressed).map_err(|e| e.to_string())?;
+    cx.push_bytes(x.to_vec())?;
+    cx.push_bytes(y.to_vec())
+}
+
+pub(super) fn op_ecdsa_pk_recover(cx: &mut EvalContext) -> OpResult {
+    let curve = curve(cx)?;
+    let s = cx.pop_bytes()?;
+    let r = cx.pop_bytes()?;
+    let v = cx.pop_uint()?;
+    let digest = cx.pop_bytes()?;
+    if digest.len() != 32 {
+        return Err(format!(
+            "data is not a 32 byte hash: {} bytes",
+            digest.len()
+        ));
+    }
+    let (x, y) = ecdsa::recover(curve, &digest, v, &r, &s).map_err(|e| e.to_string())?;
+    cx.push_bytes(x.to_vec())?;
+    cx.push_bytes(y.to_vec())
+}
+
+pub(super) fn op_len(cx: &mut EvalContext) -> OpResult {
+    let a = cx.pop_bytes()?;
+    cx.push_uint(a.len() as u64);
+    Ok(())
+}
+
+pub(super) fn op_concat(cx: &mut EvalContext) -> OpResult {
+    let b = cx.pop_bytes()?;
+    let mut a = cx.pop_bytes()?;
+    a.extend_from_slice(&b);
+    cx.push_bytes(a)
+}
+
+fn substring(bytes: &[u8], start: u64, end: u64) -> OpResult<Vec<u8>> {
+    if end < start {
+        return Err("substring end before start".into());
+    }
+    if end > bytes.len() as u64 {
+        return Err("substring range beyond length of string".into());
+    }
+    Ok(bytes[start as usize..end as usize].to_vec())
+}
+
+pub(super) fn op_substring(cx: &mut EvalContext) -> OpResult {
+    let (start, end) = (cx.imm(0) as u64, cx.imm(1) as u64);
+    let a = cx.pop_bytes()?;
+    cx.push_bytes(substring(&a, start, end)?)
+}
+
+pub(super) fn op_substring3(cx: &mut EvalContext) -> OpResult {
+    let end = cx.pop_uint()?;
+    let start = cx.pop_uint()?;
+    let a = cx.pop_bytes()?;
+    cx.push_bytes(substring(&a, start, end)?)
+}
+
+fn extract(bytes: &[u8], start: u64, len: u64) -> OpResult<Vec<u8>> {
+    let end = start
+        .checked_add(len)
+        .filter(|&end| end <= bytes.len() as u64)
+        .ok_or_else(|| {
+            format!(
+                "extraction end {} is beyond length: {}",
+                start.saturating_add(len),
+                bytes.len()
+            )
+        })?;
+    Ok(bytes[start as usize..end as usize].to_vec())
+}
+
+pub(super) fn op_extract(cx: &mut EvalContext) -> OpResult {
+    let (start, len) = (cx.imm(0) as u64, cx.imm(1) as u64);
+    let a = cx.pop_bytes()?;
+    // A length of zero extracts to the end of the string.
+    let len = if len == 0 {
+        (a.len() as u64)
+            .checked_sub(start)
+            .ok_or_else(|| format!("extraction start {} is beyond length: {}", start, a.len()))?
+    } else {
+        len
+    };
+    cx.push_bytes(extract(&a, start, len)?)
+}
+
+pub(super) fn op_extract3(cx: &mut EvalContext) -> OpResult {
+    let len = cx.pop_uint()?;
+    let start = cx.pop_uint()?;
+    let a = cx.pop_bytes()?;
+    cx.push_bytes(extract(&a, start, len)?)
+}
+
+fn extract_uint(cx: &mut EvalContext, size: u64) -> OpResult {
+    let start = cx.pop_uint()?;
+    let a = cx.pop_bytes()?;
+    let bytes = extract(&a, start, size)?;
+    cx.push_uint(bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64));
+    Ok(())
+}
+
+pub(super) fn op_extract_uint16(cx: &mut EvalContext) -> OpResult {
+    extract_uint(cx, 2)
+}
+
+pub(super) fn op_extract_uint32(cx: &mut EvalContext) -> OpResult {
+    extract_uint(cx, 4)
+}
+
+pub(super) fn op_extract_uint64(cx: &mut EvalContext) -> OpResult {
+    extract_uint(cx, 8)
+}
+
+fn replace(mut bytes: Vec<u8>, start: u64, replacement: &[u8]) -> OpResult<Vec<u8>> {
+    let end = start
+        .checked_add(replacement.len() as u64)
+        .filter(|&end| end <= bytes.len() as u64)
+        .ok_or_else(|| format!("replacement end exceeds original length: {}", bytes.len()))?;
+    bytes[start as usize..end as usize].copy_from_slice(replacement);
+    Ok(bytes)
+}
+
+pub(super) fn op_replace2(cx: &mut EvalContext) -> OpResult {
+    let start = cx.imm(0) as u64;
+    let b = cx.pop_bytes()?;
+    let a = cx.pop_bytes()?;
+    cx.push_bytes(replace(a, start, &b)?)
+}
+
+pub(super) fn op_replace3(cx: &mut EvalContext) -> OpResult {
+    let c = cx.pop_bytes()?;
+    let start = cx.pop_uint()?;
+    let a = cx.pop_bytes()?;
+    cx.push_bytes(replace(a, start, &c)?)
+}
+
+pub(super) fn op_getbit(cx: &mut EvalContext) -> OpResult {
+    let bit = cx.pop_uint()?;
+    let value = match cx.pop()? {
+        StackValue::Uint(a) => {
+            if bit > 63 {
+                return Err(format!("getbit index {} beyond 64 bits", bit));
+            }
+            (a >> bit) & 1
+        }
+        StackValue::Bytes(a) => {
+            if bit >= a.len() as u64 * 8 {
+                return Err(format!(
+                    "getbit index {} beyond byteslice of {} bytes",
+                    bit,
+                    a.len()
+                ));
+            }
+            // Bit 0 is the most significant bit of the first byte.
+            ((a[(bit / 8) as usize] >> (7 - bit % 8)) & 1) as u64
+        }
+    };
+    cx.push_uint(value);
+    Ok(())
+}
+
+pub(super) fn op_setbit(cx: &mut EvalContext) -> OpResult {
+    let value = cx.pop_uint()?;
+    let bit = cx.pop_uint()?;
+    if value > 1 {
+        return Err("setbit value > 1".into());
+    }
+    match cx.pop()? {
+        StackValue::Uint(a) => {
+            if bit > 63 {
+                return Err(format!("setbit index {} beyond 64 bits", bit));
+            }
+            cx.push_uint((a & !(1 << bit)) | (value << bit));
+            Ok(())
+        }
+        StackValue::Bytes(mut a) => {
+            if bit >= a.len() as u64 * 8 {
+                return Err(format!(
+                    "setbit index {} beyond byteslice of {} bytes",
+                    bit,
+                    a.len()
+                ));
+            }
+            let mask = 1u8 << (7 - bit % 8);
+            let byte = &mut a[(bit / 8) as usize];
+            *byte = if value == 1 {
+                *byte | mask
+            } else {
+                *byte & !mask
+            };
+            cx.push_bytes(a)
+        }
+    }
+}
+
+pub(super) fn op_getbyte(cx: &mut EvalContext) -> OpResult {
+    let i = cx.pop_uint()?;
+    let a = cx.pop_bytes()?;
+    let byte = a
+        .get(usize::try_from(i).unwrap_or(usize::MAX))
+        .ok_or_else(|| format!("getbyte index {} beyond length {}", i, a.len()))?;
+    cx.push_uint(*byte as u64);
+    Ok(())
+}
+
+pub(super) fn op_setbyte(cx: &mut EvalContext) -> OpResult {
+    let value = cx.pop_uint()?;
+    let i = cx.pop_uint()?;
+    let mut a = cx.pop_bytes()?;
+    let value = u8::try_from(value).map_err(|_| format!("setbyte value {} > 255", value))?;
+    let len = a.len();
+    *a.get_mut(usize::try_from(i).unwrap_or(usize::MAX))
+        .ok_or_else(|| format!("setbyte index {} beyond length {}", i, len))? = value;
+    cx.push_bytes(a)
+}
+
+pub(super) fn op_base64_decode(cx: &mut EvalContext) -> OpResult {
+    let config = match cx.imm(0) {
+        0 => base64::URL_SAFE,
+        1 => base64::STANDARD,
+        other => return Err(format!("invalid base64_decode encoding {}", other)),
+    };
+    let a = cx.pop_bytes()?;
+    cx.add_cost(a.len().div_ceil(16) as u64);
+    // Padding is optional.
+    let trimmed = a
+        .strip_suffix(b"==")
+        .or_else(|| a.strip_suffix(b"="))
+        .unwrap_or(&a);
+    let decoded = base64::decode_config(trimmed, config.pad(false))
+        .map_err(|e| format!("invalid base64: {}", e))?;
+    cx.push_bytes(decoded)
+}
+
+pub(super) fn op_json_ref(cx: &mut EvalContext) -> OpResult {
+    let kind = cx.imm(0);
+    let key = cx.pop_bytes()?;
+    let json = cx.pop_bytes()?;
+    cx.add_cost(24 + 2 * (json.len() / 7) as u64);
+
+    let object: serde_json::Map<String, serde_json::Value> = serde_json::from_slice(&json)
+        .map_err(|e| format!("error while parsing JSON text, invalid json text: {}", e))?;
+    let key = String::from_utf8(key).map_err(|_| "key is not valid utf-8")?;
+    let value = object
+        .get(&key)
+        .ok_or_else(|| format!("key {:?} not found in JSON text", key))?;
+    match (kind, value) {
+        (0, serde_json::Value::String(s)) => cx.push_bytes(s.as_bytes().to_vec()),
+        (1, serde_json::Value::Number(n)) => {
+            let n = n
+                .as_u64()
+                .ok_or_else(|| format!("value {} is not a uint64", n))?;
+            cx.push_uint(n);
+            Ok(())
+        }
+        (2, serde_json::Value::Object(_)) => cx.push_bytes(value.to_string().into_bytes()),
+        (0..=2, _) => Err(format!(
+            "value of key {:?} does not have the requested type",
+            key
+        )),
+        (other, _) => Err(format!("invalid json_ref type {}", other)),
+    }
+}
@@ -0,0 +1,1053 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+
+use config::consensus::ConsensusParams;
+use protocol::TxType;
+
+use super::opcodes::{self, Immediates, Modes, OpSpec};
+use super::state::LedgerForLogic;
+use super::{LOGIC_VERSION, MAX_STACK_DEPTH, MAX_STRING_SIZE, SCRATCH_SIZE};
+use crate::basics::{AppIndex, TealType, TealValue};
+use crate::transactions::signedtxn::SignedTxnWithAD;
+
+pub type LogicResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// The result type of the implementation of a single opcode. The error is
+/// turned into an EvalError carrying the machine state.
+pub(super) type OpResult<T = ()> = Result<T, String>;
+
+/// RunMode distinguishes logic signatures from application programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunMode {
+    /// Signature is the mode of a logic signature; it has no access to the
+    /// ledger.
+    Signature,
+    /// Application is the mode of approval and clear state programs.
+    Application,
+}
+
+impl fmt::Display for RunMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunMode::Signature => f.write_str("Signature"),
+            RunMode::Application => f.write_str("Application"),
+        }
+    }
+}
+
+/// StackValue is a value on the stack or in the scratch space of the AVM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StackValue {
+    Uint(u64),
+    Bytes(Vec<u8>),
+}
+
+impl Default for StackValue {
+    fn default() -> Self {
+        StackValue::Uint(0)
+    }
+}
+
+impl StackValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            StackValue::Uint(_) => "uint64",
+            StackValue::Bytes(_) => "[]byte",
+        }
+    }
+
+    pub fn to_teal_value(&self) -> TealValue {
+        match self {
+            StackValue::Uint(u) => TealValue::from_uint(*u),
+            StackValue::Bytes(b) => TealValue::from_bytes(b.clone()),
+        }
+    }
+}
+
+impl From<&TealValue> for StackValue {
+    fn from(tv: &TealValue) -> Self {
+        match tv.tt {
+            TealType::Uint => StackValue::Uint(tv.uint),
+            TealType::Bytes => StackValue::Bytes(tv.bytes.clone()),
+        }
+    }
+}
+
+impl fmt::Display for StackValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StackValue::Uint(u) => write!(f, "{}", u),
+            StackValue::Bytes(b) => write!(f, "0x{}", hex::encode(b)),
+        }
+    }
+}
+
+/// EvalParams contains data that comes into the evaluation of the programs
+/// of a transaction group.
+pub struct EvalParams<'a> {
+    pub proto: &'a ConsensusParams,
+    pub txn_group: &'a [SignedTxnWithAD],
+    /// The scratch space of every application call of the group evaluated
+    /// so far, for `gload` and friends.
+    pub past_scratch: RefCell<Vec<Option<Vec<StackValue>>>>,
+    /// The application whose inner transactions are the group, or zero for
+    /// a top level group.
+    pub caller_app_id: AppIndex,
+    /// The fee credit and opcode budget shared with the rest of the group.
+    pub pools: Option<&'a GroupPools>,
+}
+
+impl<'a> EvalParams<'a> {
+    pub fn new(proto: &'a ConsensusParams, txn_group: &'a [SignedTxnWithAD]) -> Self {
+        Self {
+            proto,
+            txn_group,
+            past_scratch: RefCell::new(vec![None; txn_group.len()]),
+            caller_app_id: 0,
+            pools: None,
+        }
+    }
+}
+
+/// Returns the fees paid by `group`, saturating, and the number of its
+/// transactions owing the minimum fee: all but compact certificates.
+pub fn group_fees(group: &[SignedTxnWithAD]) -> (u64, u64) {
+    group.iter().fold((0, 0), |(paid, count), stxn| {
+        let txn = &stxn.signed_txn.txn;
+        let owing = (txn.tx_type != TxType::CompactCertTx) as u64;
+        (paid.saturating_add(txn.header.fee.0), count + owing)
+    })
+}
+
+/// GroupPools holds what the transactions of a top level group share, with
+/// the inner transactions they submit: the fees paid beyond the minimum,
+/// which may pay for inner transactions, and the opcode budget of the
+/// application calls.
+#[derive(Debug, Default)]
+pub struct GroupPools {
+    /// The fee credit, since `enable_fee_pooling`.
+    pub fee_credit: Option<Cell<u64>>,
+    /// The opcode budget left to the application calls, since
+    /// `enable_app_cost_pooling`.
+    pub app_budget: Option<Cell<u64>>,
+}
+
+impl GroupPools {
+    pub fn new(proto: &ConsensusParams, group: &[SignedTxnWithAD]) -> Self {
+        let (paid, count) = group_fees(group);
+        let fee_credit = proto
+            .enable_fee_pooling
+            .then(|| Cell::new(paid.saturating_sub(proto.min_txn_fee.saturating_mul(count))));
+        let apps = group
+            .iter()
+            .filter(|stxn| stxn.signed_txn.txn.tx_type == TxType::ApplicationCallTx)
+            .count() as u64;
+        let app_budget = proto
+            .enable_app_cost_pooling
+            .then(|| Cell::new(apps * proto.max_app_program_cost.max(0) as u64));
+        Self {
+            fee_credit,
+            app_budget,
+        }
+    }
+
+    /// Settles fees of `paid` for transactions owing `need`: overpayment is
+    /// added to the fee credit and underpayment taken from it.
+    pub fn pay_fees(&self, paid: u64, need: u64) -> Result<(), String> {
+        let credit = self.fee_credit.as_ref();
+        let available = credit.map(Cell::get).unwrap_or_default();
+        if paid < need && need - paid > available {
+            return Err(format!(
+                "fee too small: paid {}, need {} with {} credit",
+                paid, need, available
+            ));
+        }
+        if let Some(credit) = credit {
+            credit.set(available.saturating_add(paid) - need);
+        }
+        Ok(())
+    }
+}
+
+/// EvalError is the error of a failed program evaluation. Besides the
+/// error itself it reports where the program failed and the state of the
+/// machine at that point, to help debugging smart contracts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalError {
+    pub err: String,
+    pub pc: usize,
+    pub stack: Vec<StackValue>,
+    /// The scratch slots that do not hold the default value.
+    pub scratch: Vec<(usize, StackValue)>,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pc={} {}; stack=[", self.pc, self.err)?;
+        for (i, value) in self.stack.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", value)?;
+        }
+        f.write_str("]; scratch={")?;
+        for (i, (slot, value)) in self.scratch.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}: {}", slot, value)?;
+        }
+        f.write_str("}")
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+impl EvalError {
+    fn new(err: impl Into<String>, pc: usize) -> Self {
+        Self {
+            err: err.into(),
+            pc,
+            stack: vec![],
+            scratch: vec![],
+        }
+    }
+}
+
+/// ContractResult is the outcome of an application program that ran to
+/// completion.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContractResult {
+    /// Whether the program approved the transaction.
+    pub pass: bool,
+    /// The messages emitted by `log`.
+    pub logs: Vec<Vec<u8>>,
+    /// The opcode cost of the evaluation.
+    pub cost: u64,
+    /// The inner transactions submitted by the program, with the effects
+    /// of their evaluation.
+    pub inner_txns: Vec<SignedTxnWithAD>,
+}
+
+/// EvalContext is the execution state of a single program.
+pub(super) struct EvalContext<'a> {
+    pub params: &'a EvalParams<'a>,
+    pub ledger: Option<&'a mut dyn LedgerForLogic>,
+    pub mode: RunMode,
+    pub program: &'a [u8],
+    pub version: u64,
+    pub group_index: usize,
+    pub app_id: AppIndex,
+    pub args: &'a [Vec<u8>],
+
+    pub stack: Vec<StackValue>,
+    pub scratch: Vec<StackValue>,
+    pub call_stack: Vec<usize>,
+    pub intc: Vec<u64>,
+    pub bytec: Vec<Vec<u8>>,
+    pub pc: usize,
+    pub next_pc: usize,
+    pub cost: u64,
+    pub budget: u64,
+    pub logs: Vec<Vec<u8>>,
+
+    /// The inner transaction group being assembled by `itxn_begin`.
+    pub sub_txns: Vec<SignedTxnWithAD>,
+    /// The last inner transaction group submitted, for `itxn` and `gitxn`.
+    pub last_inner_group: Vec<SignedTxnWithAD>,
+    /// All inner transactions submitted so far.
+    pub inner_txns: Vec<SignedTxnWithAD>,
+}
+
+impl<'a> EvalContext<'a> {
+    pub fn txn(&self) -> &'a SignedTxnWithAD {
+        &self.params.txn_group[self.group_index]
+    }
+
+    pub fn ledger(&mut self) -> OpResult<&mut (dyn LedgerForLogic + 'a)> {
+        match self.ledger.as_deref_mut() {
+            Some(ledger) => Ok(ledger),
+            None => Err("no ledger available".into()),
+        }
+    }
+
+    pub fn push(&mut self, value: StackValue) {
+        self.stack.push(value);
+    }
+
+    pub fn push_uint(&mut self, value: u64) {
+        self.stack.push(StackValue::Uint(value));
+    }
+
+    pub fn push_bool(&mut self, value: bool) {
+        self.stack.push(StackValue::Uint(value as u64));
+    }
+
+    pub fn push_bytes(&mut self, value: Vec<u8>) -> OpResult {
+        if value.len() > MAX_STRING_SIZE {
+            return Err(format!(
+                "byte string of {} bytes exceeds {}",
+                value.len(),
+                MAX_STRING_SIZE
+            ));
+        }
+        self.stack.push(StackValue::Bytes(value));
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> OpResult<StackValue> {
+        self.stack
+            .pop()
+            .ok_or_else(|| "stack underflow".to_string())
+    }
+
+    pub fn pop_uint(&mut self) -> OpResult<u64> {
+        match self.pop()? {
+            StackValue::Uint(u) => Ok(u),
+            other => Err(format!("expected uint64 but got {}", other.type_name())),
+        }
+    }
+
+    pub fn pop_bytes(&mut self) -> OpResult<Vec<u8>> {
+        match self.pop()? {
+            StackValue::Bytes(b) => Ok(b),
+            other => Err(format!("expected []byte but got {}", other.type_name())),
+        }
+    }
+
+    /// Returns the `n`th one byte immediate of the current instruction.
+    pub fn imm(&self, n: usize) -> u8 {
+        self.program[self.pc + 1 + n]
+    }
+
+    /// The opcode budget shared by the application calls of the group, if
+    /// they pool their budgets.
+    pub fn pooled_budget(&self) -> Option<&'a Cell<u64>> {
+        if self.mode != RunMode::Application {
+            return None;
+        }
+        self.params.pools?.app_budget.as_ref()
+    }
+
+    /// Charges the cost so far to the pooled budget, before other programs
+    /// draw on it.
+    pub fn settle_budget(&self) {
+        if let Some(pool) = self.pooled_budget() {
+            pool.set(self.budget.saturating_sub(self.cost));
+        }
+    }
+
+    /// Resumes from the pooled budget left by other programs.
+    pub fn refresh_budget(&mut self) {
+        if let Some(pool) = self.pooled_budget() {
+            self.budget = pool.get() + self.cost;
+        }
+    }
+
+    /// Adds the cost of work that depends on the data being processed.
+    pub fn add_cost(&mut self, cost: u64) {
+        self.cost += cost;
+    }
+
+    pub fn require_mode(&self, mode: RunMode, what: &str) -> OpResult {
+        if self.mode != mode {
+            return Err(format!("{} not allowed in current mode", what));
+        }
+        Ok(())
+    }
+
+    fn error(&self, err: impl Into<String>) -> EvalError {
+        EvalError {
+            err: err.into(),
+            pc: self.pc,
+            stack: self.stack.clone(),
+            scratch: self
+                .scratch
+                .iter()
+                .enumerate()
+                .filter(|(_, value)| **value != StackValue::default())
+                .map(|(slot, value)| (slot, value.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// Reads an unsigned LEB128 varint at `pos`, returning its value and
+/// length.
+pub(super) fn read_varuint(program: &[u8], pos: usize) -> OpResult<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in program.iter().skip(pos).take(10).enumerate() {
+        if i == 9 && byte > 1 {
+            return Err("varuint overflows a uint64".into());
+        }
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err("could not decode varuint".into())
+}
+
+/// Returns the size of the immediates of the instruction at `pc`.
+fn immediates_size(spec: &OpSpec, program: &[u8], pc: usize) -> OpResult<usize> {
+    let start = pc + 1;
+    let size = match spec.immediates {
+        Immediates::None => 0,
+        Immediates::Bytes(n) => n,
+        Immediates::Branch => 2,
+        Immediates::Varuint => read_varuint(program, start)?.1,
+        Immediates::VarBytes => {
+            let (len, n) = read_varuint(program, start)?;
+            n.checked_add(len as usize).ok_or("pushbytes too long")?
+        }
+        Immediates::IntcBlock => {
+            let (count, mut n) = read_varuint(program, start)?;
+            for _ in 0..count {
+                n += read_varuint(program, start + n)?.1;
+            }
+            n
+        }
+        Immediates::BytecBlock => {
+            let (count, mut n) = read_varuint(program, start)?;
+            for _ in 0..count {
+                let (len, m) = read_varuint(program, start + n)?;
+                n = n
+                    .checked_add(m)
+                    .and_then(|n| n.checked_add(len as usize))
+                    .ok_or("bytecblock too long")?;
+                if start + n > program.len() {
+                    break;
+                }
+            }
+            n
+        }
+    };
+    if start + size > program.len() {
+        return Err(format!(
+            "{} opcode at pc={} needs more bytes",
+            spec.name, pc
+        ));
+    }
+    Ok(size)
+}
+
+/// Returns the target of the branch instruction at `pc`.
+pub(super) fn branch_target(program: &[u8], pc: usize, version: u64) -> OpResult<usize> {
+    let raw = u16::from_be_bytes([program[pc + 1], program[pc + 2]]);
+    let end = pc as i64 + 3;
+    let target = if version >= 4 {
+        end + raw as i16 as i64
+    } else {
+        end + raw as i64
+    };
+    if target < 0 || target > program.len() as i64 {
+        return Err(format!("branch target {} outside of program", target));
+    }
+    Ok(target as usize)
+}
+
+fn mode_allowed(modes: Modes, mode: RunMode) -> bool {
+    match modes {
+        Modes::Any => true,
+        Modes::Signature => mode == RunMode::Signature,
+        Modes::Application => mode == RunMode::Application,
+    }
+}
+
+fn max_cost(mode: RunMode, proto: &ConsensusParams) -> u64 {
+    match mode {
+        RunMode::Signature => proto.logic_sig_max_cost,
+        RunMode::Application => proto.max_app_program_cost.max(0) as u64,
+    }
+}
+
+/// Statically checks a program: its version, that every instruction is
+/// valid and available in `mode`, that branches land on instructions, and
+/// for versions before dynamic cost accounting, that its total cost is
+/// within the budget. Returns the version of the program.
+fn check(program: &[u8], mode: RunMode, proto: &ConsensusParams) -> Result<u64, EvalError> {
+    let (version, vlen) = read_varuint(program, 0).map_err(|e| EvalError::new(e, 0))?;
+    if version > LOGIC_VERSION || version > proto.logic_sig_version {
+        return Err(EvalError::new(
+            format!(
+                "program version {} greater than protocol supported version {}",
+                version,
+                proto.logic_sig_version.min(LOGIC_VERSION)
+            ),
+            0,
+        ));
+    }
+    if mode == RunMode::Application && version < 2 {
+        return Err(EvalError::new(
+            format!(
+                "program version must be >= 2 for this mode, got {}",
+                version
+            ),
+            0,
+        ));
+    }
+
+    let mut starts = vec![false; program.len() + 1];
+    starts[program.len()] = true;
+    let mut branches = vec![];
+    let mut cost = 0u64;
+    let mut pc = vlen;
+    while pc < program.len() {
+        starts[pc] = true;
+        let spec = opcodes::op_spec(program[pc], version)
+            .ok_or_else(|| EvalError::new(format!("illegal opcode 0x{:02x}", program[pc]), pc))?;
+        if !mode_allowed(spec.modes, mode) {
+            return Err(EvalError::new(
+                format!("{} not allowed in current mode", spec.name),
+                pc,
+            ));
+        }
+        let size = immediates_size(spec, program, pc).map_err(|e| EvalError::new(e, pc))?;
+        if matches!(spec.immediates, Immediates::Branch) {
+            let target = branch_target(program, pc, version).map_err(|e| EvalError::new(e, pc))?;
+            if version < 4 && target <= pc {
+                return Err(EvalError::new("branch target must be after the branch", pc));
+            }
+            branches.push((pc, target));
+        }
+        cost += spec.static_cost(program, pc);
+        pc += 1 + size;
+    }
+    for (pc, target) in branches {
+        if !starts[target] {
+            return Err(EvalError::new(
+                format!("branch target {} is not an aligned instruction", target),
+                pc,
+            ));
+        }
+    }
+    if version < 4 && cost > max_cost(mode, proto) {
+        return Err(EvalError::new(
+            format!(
+                "program too costly for version < 4: {} > {}",
+                cost,
+                max_cost(mode, proto)
+            ),
+            0,
+        ));
+    }
+    Ok(version)
+}
+
+/// Statically checks a logic signature program.
+pub fn check_signature(program: &[u8], params: &EvalParams) -> Result<(), EvalError> {
+    check(program, RunMode::Signature, params.proto).map(|_| ())
+}
+
+/// Statically checks an approval or clear state program.
+pub fn check_contract(program: &[u8], params: &EvalParams) -> Result<(), EvalError> {
+    check(program, RunMode::Application, params.proto).map(|_| ())
+}
+
+fn eval(mut cx: EvalContext) -> Result<(bool, EvalContext), EvalError> {
+    let version = check(cx.program, cx.mode, cx.params.proto)?;
+    cx.version = version;
+    cx.pc = read_varuint(cx.program, 0)
+        .map_err(|e| EvalError::new(e, 0))?
+        .1;
+
+    while cx.pc < cx.program.len() {
+        let spec = opcodes::op_spec(cx.program[cx.pc], version)
+            .ok_or_else(|| cx.error(format!("illegal opcode 0x{:02x}", cx.program[cx.pc])))?;
+        cx.cost += spec.static_cost(cx.program, cx.pc);
+        let size = immediates_size(spec, cx.program, cx.pc).map_err(|e| cx.error(e))?;
+        cx.next_pc = cx.pc + 1 + size;
+        (spec.op)(&mut cx).map_err(|e| cx.error(format!("{}: {}", spec.name, e)))?;
+        if version >= 4 && cx.cost > cx.budget {
+            return Err(cx.error(format!(
+                "dynamic cost budget exceeded, executing {}: local program cost was {}",
+                spec.name, cx.cost
+            )));
+        }
+        if cx.stack.len() > MAX_STACK_DEPTH {
+            return Err(cx.error(format!("stack overflow in {}", spec.name)));
+        }
+        cx.pc = cx.next_pc;
+    }
+
+    if cx.stack.len() != 1 {
+        return Err(cx.error(format!("stack len is {} instead of 1", cx.stack.len())));
+    }
+    match cx.stack[0] {
+        StackValue::Uint(u) => Ok((u != 0, cx)),
+        StackValue::Bytes(_) => Err(cx.error("stack finished with bytes not int")),
+    }
+}
+
+fn context<'a>(
+    program: &'a [u8],
+    mode: RunMode,
+    group_index: usize,
+    params: &'a EvalParams<'a>,
+) -> Result<EvalContext<'a>, EvalError> {
+    if group_index >= params.txn_group.len() {
+        return Err(EvalError::new(
+            format!(
+                "group index {} out of range of {} transactions",
+                group_index,
+                params.txn_group.len()
+            ),
+            0,
+        ));
+    }
+    let mut cx = EvalContext {
+        params,
+        ledger: None,
+        mode,
+        program,
+        version: 0,
+        group_index,
+        app_id: 0,
+        args: &[],
+        stack: Vec::with_capacity(16),
+        scratch: vec![StackValue::default(); SCRATCH_SIZE],
+        call_stack: vec![],
+        intc: vec![],
+        bytec: vec![],
+        pc: 0,
+        next_pc: 0,
+        cost: 0,
+        budget: max_cost(mode, params.proto),
+        logs: vec![],
+        sub_txns: vec![],
+        last_inner_group: vec![],
+        inner_txns: vec![],
+    };
+    cx.refresh_budget();
+    Ok(cx)
+}
+
+/// Evaluates a logic signature `program` with arguments `args` on behalf of
+/// the transaction at `group_index`. Returns whether the program approved
+/// the transaction.
+pub fn eval_signature_program(
+    program: &[u8],
+    args: &[Vec<u8>],
+    group_index: usize,
+    params: &EvalParams,
+) -> Result<bool, EvalError> {
+    let mut cx = context(program, RunMode::Signature, group_index, params)?;
+    cx.args = args;
+    eval(cx).map(|(pass, _)| pass)
+}
+
+/// Evaluates the approval or clear state `program` of application `app_id`
+/// for the transaction at `group_index`. State changes are written to
+/// `ledger`, which is expected to discard them if the program fails or
+/// rejects.
+pub fn eval_contract(
+    program: &[u8],
+    group_index: usize,
+    app_id: AppIndex,
+    params: &EvalParams,
+    ledger: &mut dyn LedgerForLogic,
+) -> Result<ContractResult, EvalError> {
+    let mut cx = context(program, RunMode::Application, group_index, params)?;
+    cx.app_id = app_id;
+    cx.ledger = Some(ledger);
+    let (pass, cx) = eval(cx)?;
+    cx.settle_budget();
+    params.past_scratch.borrow_mut()[group_index] = Some(cx.scratch);
+    Ok(ContractResult {
+        pass,
+        logs: cx.logs,
+        cost: cx.cost,
+        inner_txns: cx.inner_txns,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::basics::{
+        Address, AppParams, AssetHolding, AssetIndex, AssetParams, MicroAlgos, Round,
+    };
+    use crate::bookkeeping::block::BlockHeader;
+
+    fn proto() -> ConsensusParams {
+        ConsensusParams {
+            logic_sig_version: 7,
+            logic_sig_max_cost: 20000,
+            max_app_program_cost: 700,
+            max_app_key_len: 64,
+            max_app_bytes_value_len: 128,
+            max_app_sum_key_value_lens: 128,
+            ..Default::default()
+        }
+    }
+
+    #[derive(Default)]
+    struct GlobalsLedger {
+        globals: BTreeMap<Vec<u8>, TealValue>,
+    }
+
+    impl LedgerForLogic for GlobalsLedger {
+        fn round(&self) -> Round {
+            1
+        }
+        fn latest_timestamp(&self) -> i64 {
+            0
+        }
+        fn block_header(&self, _round: Round) -> Option<BlockHeader> {
+            None
+        }
+        fn balance(&self, _addr: &Address) -> MicroAlgos {
+            MicroAlgos(0)
+        }
+        fn min_balance(&self, _addr: &Address) -> MicroAlgos {
+            MicroAlgos(0)
+        }
+        fn auth_addr(&self, _addr: &Address) -> Address {
+            Address::default()
+        }
+        fn asset_holding(&self, _addr: &Address, _asset: AssetIndex) -> Option<AssetHolding> {
+            None
+        }
+        fn asset_params(&self, _asset: AssetIndex) -> Option<(AssetParams, Address)> {
+            None
+        }
+        fn app_params(&self, _app: AppIndex) -> Option<(AppParams, Address)> {
+            None
+        }
+        fn opted_in(&self, _addr: &Address, _app: AppIndex) -> bool {
+            false
+        }
+        fn get_local(&self, _addr: &Address, _app: AppIndex, _key: &[u8]) -> Option<TealValue> {
+            None
+        }
+        fn set_local(
+            &mut self,
+            _: &Address,
+            _: AppIndex,
+            _: &[u8],
+            _: TealValue,
+        ) -> LogicResult<()> {
+            Err("no local state".into())
+        }
+        fn del_local(&mut self, _: &Address, _: AppIndex, _: &[u8]) -> LogicResult<()> {
+            Err("no local state".into())
+        }
+        fn get_global(&self, _app: AppIndex, key: &[u8]) -> Option<TealValue> {
+            self.globals.get(key).cloned()
+        }
+        fn set_global(&mut self, _app: AppIndex, key: &[u8], value: TealValue) -> LogicResult<()> {
+            self.globals.insert(key.to_vec(), value);
+            Ok(())
+        }
+        fn del_global(&mut self, _app: AppIndex, key: &[u8]) -> LogicResult<()> {
+            self.globals.remove(key);
+            Ok(())
+        }
+        fn perform(&mut self, _group: &mut [SignedTxnWithAD]) -> LogicResult<()> {
+            Err("no inner transactions".into())
+        }
+    }
+
+    fn eval_sig(program: &[u8], proto: &ConsensusParams) -> Result<bool, EvalError> {
+        let group = [SignedTxnWithAD::default()];
+        let params = EvalParams::new(proto, &group);
+        eval_signature_program(program, &[], 0, &params)
+    }
+
+    fn varuint(mut n: u64) -> Vec<u8> {
+        let mut out = vec![];
+        while n >= 0x80 {
+            out.push(n as u8 | 0x80);
+            n >>= 7;
+        }
+        out.push(n as u8);
+        out
+    }
+
+    /// pushint `n`.
+    fn int(n: u64) -> Vec<u8> {
+        let mut op = vec![0x81];
+        op.extend(varuint(n));
+        op
+    }
+
+    /// pushbytes `b`.
+    fn bytes(b: &[u8]) -> Vec<u8> {
+        let mut op = vec![0x80];
+        op.extend(varuint(b.len() as u64));
+        op.extend(b);
+        op
+    }
+
+    fn asm(version: u8, ops: &[&[u8]]) -> Vec<u8> {
+        let mut program = vec![version];
+        for op in ops {
+            program.extend(*op);
+        }
+        program
+    }
+
+    /// Evaluates each program as a logic signature and compares the outcome
+    /// with the expected pass flag or with a substring of the error.
+    fn assert_evals(cases: &[(&str, Vec<u8>, Result<bool, &str>)]) {
+        let proto = proto();
+        for (name, program, want) in cases {
+            match (eval_sig(program, &proto), want) {
+                (Ok(pass), Ok(want)) => assert_eq!(pass, *want, "{}", name),
+                (Err(err), Err(want)) => {
+                    assert!(err.err.contains(want), "{}: {:?}", name, err.err)
+                }
+                (got, want) => panic!("{}: got {:?}, want {:?}", name, got, want),
+            }
+        }
+    }
+
+    #[test]
+    fn arithmetic_and_branches() {
+        // int 1; int 2; +; int 3; ==
+        let program = [0x07, 0x81, 0x01, 0x81, 0x02, 0x08, 0x81, 0x03, 0x12];
+        assert_eq!(eval_sig(&program, &proto()), Ok(true));
+
+        // Counts scratch slot 0 up to 5 with a backward branch.
+        let program = [
+            0x07, 0x81, 0x00, 0x35, 0x00, // int 0; store 0
+            0x34, 0x00, 0x81, 0x01, 0x08, 0x49, 0x35,
+            0x00, // loop: load 0; int 1; +; dup; store 0
+            0x81, 0x05, 0x0c, 0x40, 0xff, 0xf2, // int 5; <; bnz loop
+            0x34, 0x00, 0x81, 0x05, 0x12, // load 0; int 5; ==
+        ];
+        assert_eq!(eval_sig(&program, &proto()), Ok(true));
+
+        // Backward branches are rejected before v4.
+        let mut v3 = program;
+        v3[0] = 0x03;
+        assert!(eval_sig(&v3, &proto()).is_err());
+    }
+
+    #[test]
+    fn errors_report_machine_state() {
+        // int 42; store 3; int 7; err
+        let program = [0x07, 0x81, 0x2a, 0x35, 0x03, 0x81, 0x07, 0x00];
+        let err = eval_sig(&program, &proto()).unwrap_err();
+        assert_eq!(err.pc, 7);
+        assert_eq!(err.stack, vec![StackValue::Uint(7)]);
+        assert_eq!(err.scratch, vec![(3, StackValue::Uint(42))]);
+
+        // Application only opcodes fail the check of logic signatures.
+        let program = [0x07, 0x80, 0x01, b'k', 0x64];
+        assert!(eval_sig(&program, &proto())
+            .unwrap_err()
+            .err
+            .contains("not allowed"));
+    }
+
+    #[test]
+    fn dynamic_cost_budget() {
+        // byte ""; sha256; sha256; len
+        let program = [0x07, 0x80, 0x00, 0x01, 0x01, 0x15];
+        assert_eq!(eval_sig(&program, &proto()), Ok(true));
+
+        let mut tight = proto();
+        tight.logic_sig_max_cost = 40;
+        let err = eval_sig(&program, &tight).unwrap_err();
+        assert!(err.err.contains("dynamic cost budget exceeded"));
+        assert_eq!(err.pc, 4);
+    }
+
+    #[test]
+    fn application_global_state() {
+        // byte "k"; int 5; app_global_put; byte "k"; app_global_get; int 5; ==
+        let program = [
+            0x07, 0x80, 0x01, b'k', 0x81, 0x05, 0x67, 0x80, 0x01, b'k', 0x64, 0x81, 0x05, 0x12,
+        ];
+        let proto = proto();
+        let group = [SignedTxnWithAD::default()];
+        let params = EvalParams::new(&proto, &group);
+        let mut ledger = GlobalsLedger::default();
+        let result = eval_contract(&program, 0, 1, &params, &mut ledger).unwrap();
+        assert!(result.pass);
+        assert_eq!(result.cost, 7);
+        assert_eq!(
+            ledger.globals.get(b"k".as_slice()),
+            Some(&TealValue::from_uint(5))
+        );
+        assert!(params.past_scratch.borrow()[0].is_some());
+
+        // Keys longer than max_app_key_len are rejected.
+        let mut long_key = vec![0x07, 0x80, 65];
+        long_key.extend([b'k'; 65]);
+        long_key.extend([0x81, 0x05, 0x67, 0x81, 0x01]);
+        let err = eval_contract(&long_key, 0, 1, &params, &mut ledger).unwrap_err();
+        assert!(err.err.contains("key too long"));
+    }
+
+    #[test]
+    fn byte_range_failures() {
+        let abc = bytes(b"abc");
+        #[rustfmt::skip]
+        let cases = [
+            ("substring whole", asm(7, &[&abc, &[0x51, 0, 3], &abc, &[0x12]]), Ok(true)),
+            ("substring empty at end", asm(7, &[&abc, &[0x51, 3, 3], &[0x15]]), Ok(false)),
+            ("substring end before start", asm(7, &[&abc, &[0x51, 2, 1]]),
+                Err("substring: substring end before start")),
+            ("substring past end", asm(7, &[&abc, &[0x51, 1, 4]]),
+                Err("substring: substring range beyond length of string")),
+            ("substring3 past end", asm(7, &[&abc, &int(0), &int(4), &[0x52]]),
+                Err("substring3: substring range beyond length of string")),
+            ("substring3 end before start", asm(7, &[&abc, &int(2), &int(1), &[0x52]]),
+                Err("substring3: substring end before start")),
+            ("extract to end", asm(7, &[&abc, &[0x57, 1, 0], &bytes(b"bc"), &[0x12]]), Ok(true)),
+            ("extract start past end", asm(7, &[&abc, &[0x57, 4, 0]]),
+                Err("extract: extraction start 4 is beyond length: 3")),
+            ("extract end past end", asm(7, &[&abc, &[0x57, 1, 3]]),
+                Err("extract: extraction end 4 is beyond length: 3")),
+            ("extract3 huge start", asm(7, &[&abc, &int(u64::MAX), &int(1), &[0x58]]),
+                Err("extract3: extraction end 18446744073709551615 is beyond length: 3")),
+            ("extract3 huge length", asm(7, &[&abc, &int(1), &int(u64::MAX), &[0x58]]),
+                Err("extract3: extraction end 18446744073709551615 is beyond length: 3")),
+            ("extract_uint16", asm(7, &[&bytes(&[1, 2]), &int(0), &[0x59], &int(258), &[0x12]]),
+                Ok(true)),
+            ("extract_uint16 past end", asm(7, &[&bytes(&[1, 2]), &int(1), &[0x59]]),
+                Err("extract_uint16: extraction end 3 is beyond length: 2")),
+            ("extract_uint64 short", asm(7, &[&bytes(&[0; 7]), &int(0), &[0x5b]]),
+                Err("extract_uint64: extraction end 8 is beyond length: 7")),
+            ("replace2 at end", asm(7, &[&abc, &bytes(b"z"), &[0x5c, 2], &bytes(b"abz"), &[0x12]]),
+                Ok(true)),
+            ("replace2 past end", asm(7, &[&abc, &bytes(b"xy"), &[0x5c, 2]]),
+                Err("replace2: replacement end exceeds original length: 3")),
+            ("replace3 past end", asm(7, &[&abc, &int(3), &bytes(b"x"), &[0x5d]]),
+                Err("replace3: replacement end exceeds original length: 3")),
+        ];
+        assert_evals(&cases);
+    }
+
+    #[test]
+    fn shift_and_division_failures() {
+        #[rustfmt::skip]
+        let cases = [
+            ("shl by 63", asm(7, &[&int(1), &int(63), &[0x90], &int(1 << 63), &[0x12]]), Ok(true)),
+            ("shl by 64", asm(7, &[&int(1), &int(64), &[0x90]]),
+                Err("shl: shl arg too big, (64 > 63)")),
+            ("shr by 63", asm(7, &[&int(u64::MAX), &int(63), &[0x91]]), Ok(true)),
+            ("shr by 64", asm(7, &[&int(1), &int(64), &[0x91]]),
+                Err("shr: shr arg too big, (64 > 63)")),
+            ("divw", asm(7, &[&int(1), &int(0), &int(2), &[0x97], &int(1 << 63), &[0x12]]),
+                Ok(true)),
+            ("divw by zero", asm(7, &[&int(0), &int(1), &int(0), &[0x97]]), Err("divw: / 0")),
+            ("divw overflow", asm(7, &[&int(1), &int(0), &int(1), &[0x97]]),
+                Err("divw: divw overflow")),
+            ("divmodw by zero", asm(7, &[&int(0), &int(1), &int(0), &int(0), &[0x1f]]),
+                Err("divmodw: / 0")),
+            ("/ by zero", asm(7, &[&int(1), &int(0), &[0x0a]]), Err("/: / 0")),
+            ("% by zero", asm(7, &[&int(1), &int(0), &[0x18]]), Err("%: % 0")),
+            ("b/ by zero", asm(7, &[&bytes(&[1]), &bytes(&[]), &[0xa2]]),
+                Err("b/: division by zero")),
+        ];
+        assert_evals(&cases);
+    }
+
+    #[test]
+    fn string_size_limit() {
+        #[rustfmt::skip]
+        let cases = [
+            ("bzero at limit", asm(7, &[&int(4096), &[0xaf, 0x15], &int(4096), &[0x12]]),
+                Ok(true)),
+            ("bzero over limit", asm(7, &[&int(4097), &[0xaf]]),
+                Err("bzero: bzero attempted to create a too large string: 4097")),
+            ("concat over limit", asm(7, &[&int(4096), &[0xaf], &bytes(b"a"), &[0x50]]),
+                Err("concat: byte string of 4097 bytes exceeds 4096")),
+            ("replace3 keeps the length", asm(7, &[&int(4096), &[0xaf], &int(0), &bytes(b"a"),
+                &[0x5d, 0x15], &int(4096), &[0x12]]), Ok(true)),
+        ];
+        assert_evals(&cases);
+    }
+
+    #[test]
+    fn check_rejects_bad_branches_and_immediates() {
+        let proto = proto();
+        let group = [SignedTxnWithAD::default()];
+        let params = EvalParams::new(&proto, &group);
+        #[rustfmt::skip]
+        let cases: [(&str, &[u8], bool, &str, usize); 13] = [
+            ("forward past end", &[0x07, 0x42, 0x00, 0x05], false,
+                "branch target 9 outside of program", 1),
+            ("backward before start", &[0x07, 0x42, 0xff, 0xf0], false,
+                "branch target -12 outside of program", 1),
+            ("into an immediate", &[0x07, 0x81, 0x01, 0x40, 0xff, 0xfc], false,
+                "branch target 2 is not an aligned instruction", 3),
+            ("backward before v4", &[0x03, 0x81, 0x01, 0x40, 0xff, 0xfb], false,
+                "branch target 65537 outside of program", 3),
+            ("truncated branch", &[0x07, 0x42, 0x00], false, "b opcode at pc=1 needs more bytes", 1),
+            ("truncated immediate", &[0x07, 0x51, 0x01], false,
+                "substring opcode at pc=1 needs more bytes", 1),
+            ("truncated varuint", &[0x07, 0x81], false, "could not decode varuint", 1),
+            ("truncated pushbytes", &[0x07, 0x80, 0x03, b'a'], false,
+                "pushbytes opcode at pc=1 needs more bytes", 1),
+            ("illegal opcode", &[0x07, 0x81, 0x01, 0x45], false, "illegal opcode 0x45", 3),
+            ("future version", &[0x08, 0x81, 0x01], false,
+                "program version 8 greater than protocol supported version 7", 0),
+            ("contract before v2", &[0x01, 0x81, 0x01], true,
+                "program version must be >= 2 for this mode, got 1", 0),
+            ("arg in a contract", &[0x07, 0x2c, 0x00], true, "arg not allowed in current mode", 1),
+            ("app_global_get in a signature", &[0x07, 0x80, 0x01, b'k', 0x64], false,
+                "app_global_get not allowed in current mode", 4),
+        ];
+        for (name, program, contract, want, pc) in cases {
+            let err = if contract {
+                check_contract(program, &params)
+            } else {
+                check_signature(program, &params)
+            }
+            .unwrap_err();
+            assert!(err.err.contains(want), "{}: {:?}", name, err.err);
+            assert_eq!(err.pc, pc, "{}", name);
+        }
+
+        // Branching to the end of the program is allowed.
+        assert_eq!(
+            check_signature(&[0x07, 0x81, 0x01, 0x42, 0x00, 0x00], &params),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn opcode_availability_and_cost_by_version() {
+        // (opcode, name, first version, cost as of it)
+        let cases = [
+            (0x01, "sha256", 1, 7),
+            (0x1f, "divmodw", 4, 20),
+            (0x90, "shl", 4, 1),
+            (0x57, "extract", 5, 1),
+            (0x97, "divw", 6, 1),
+            (0x5c, "replace2", 7, 1),
+        ];
+        for (opcode, name, version, cost) in cases {
+            assert!(
+                opcodes::op_spec(opcode, version - 1).is_none(),
+                "{} before v{}",
+                name,
+                version
+            );
+            let spec = opcodes::op_spec(opcode, version).unwrap();
+            assert_eq!((spec.name, spec.version), (name, version));
+            assert_eq!(spec.static_cost(&[], 0), cost, "{}", name);
+            assert_eq!(opcodes::op_spec(opcode, LOGIC_VERSION).unwrap().name, name);
+        }
+
+        // sha256 was repriced in v2; ecdsa_verify is priced by curve.
+        assert_eq!(opcodes::op_spec(0x01, 2).unwrap().static_cost(&[], 0), 35);
+        let ecdsa = opcodes::op_spec(0x05, 5).unwrap();
+        assert_eq!(ecdsa.static_cost(&[0x05, 0x00], 0), 1700);
+        assert_eq!(ecdsa.static_cost(&[0x05, 0x01], 0), 2500);
+
+        // Programs can only use the opcodes of their own version.
+        assert_eq!(eval_sig(&asm(3, &[&int(1)]), &proto()), Ok(true));
+        let shl = asm(3, &[&int(1), &int(1), &[0x90]]);
+        assert!(eval_sig(&shl, &proto())
+            .unwrap_err()
+            .err
+            .contains("illegal opcode 0x90"));
+    }
+}
@@ -0,0 +1,606 @@
+//! Transaction, global, application and account fields, and the opcodes
+//! that read transactions of the group and their scratch space.
+
+use protocol::TxType;
+
+use super::eval::{EvalContext, OpResult, RunMode, StackValue};
+use crate::basics::{application_address, Address};
+use crate::transactions::signedtxn::SignedTxnWithAD;
+
+/// Declares a field enumeration numbered from zero, together with the name
+/// and the TEAL version introducing each field.
+macro_rules! fields {
+    ($(#[$meta:meta])* $name:ident { $($field:ident = $version:expr,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[repr(u8)]
+        pub enum $name {
+            $($field,)*
+        }
+
+        impl $name {
+            const ALL: &'static [($name, &'static str, u64)] =
+                &[$(($name::$field, stringify!($field), $version),)*];
+
+            pub fn from_u8(value: u8) -> Option<Self> {
+                Self::ALL.get(value as usize).map(|(field, _, _)| *field)
+            }
+
+            pub fn name(self) -> &'static str {
+                Self::ALL[self as usize].1
+            }
+
+            /// The TEAL version that introduced the field.
+            pub fn version(self) -> u64 {
+                Self::ALL[self as usize].2
+            }
+        }
+    };
+}
+
+fields! {
+    /// TxnField is an enum of the fields of a transaction readable by
+    /// `txn` and related opcodes.
+    TxnField {
+        Sender = 1,
+        Fee = 1,
+        FirstValid = 1,
+        FirstValidTime = 1,
+        LastValid = 1,
+        Note = 1,
+        Lease = 1,
+        Receiver = 1,
+        Amount = 1,
+        CloseRemainderTo = 1,
+        VotePK = 1,
+        SelectionPK = 1,
+        VoteFirst = 1,
+        VoteLast = 1,
+        VoteKeyDilution = 1,
+        Type = 1,
+        TypeEnum = 1,
+        XferAsset = 1,
+        AssetAmount = 1,
+        AssetSender = 1,
+        AssetReceiver = 1,
+        AssetCloseTo = 1,
+        GroupIndex = 1,
+        TxID = 1,
+        ApplicationID = 2,
+        OnCompletion = 2,
+        ApplicationArgs = 2,
+        NumAppArgs = 2,
+        Accounts = 2,
+        NumAccounts = 2,
+        ApprovalProgram = 2,
+        ClearStateProgram = 2,
+        RekeyTo = 2,
+        ConfigAsset = 2,
+        ConfigAssetTotal = 2,
+        ConfigAssetDecimals = 2,
+        ConfigAssetDefaultFrozen = 2,
+        ConfigAssetUnitName = 2,
+        ConfigAssetName = 2,
+        ConfigAssetURL = 2,
+        ConfigAssetMetadataHash = 2,
+        ConfigAssetManager = 2,
+        ConfigAssetReserve = 2,
+        ConfigAssetFreeze = 2,
+        ConfigAssetClawback = 2,
+        FreezeAsset = 2,
+        FreezeAssetAccount = 2,
+        FreezeAssetFrozen = 2,
+        Assets = 3,
+        NumAssets = 3,
+        Applications = 3,
+        NumApplications = 3,
+        GlobalNumUint = 3,
+        GlobalNumByteSlice = 3,
+        LocalNumUint = 3,
+        LocalNumByteSlice = 3,
+        ExtraProgramPages = 4,
+        Nonparticipation = 5,
+        Logs = 5,
+        NumLogs = 5,
+        CreatedAssetID = 5,
+        CreatedApplicationID = 5,
+        LastLog = 6,
+        StateProofPK = 6,
+        ApprovalProgramPages = 7,
+        NumApprovalProgramPages = 7,
+        ClearStateProgramPages = 7,
+        NumClearStateProgramPages = 7,
+    }
+}
+
+impl TxnField {
+    /// Whether the field is an effect of the evaluation of the transaction,
+    /// rather than a field of the transaction itself.
+    pub fn is_effect(self) -> bool {
+        matches!(
+            self,
+            TxnField::Logs
+                | TxnField::NumLogs
+                | TxnField::LastLog
+                | TxnField::CreatedAssetID
+                | TxnField::CreatedApplicationID
+        )
+    }
+
+    /// Whether the field is an array, read with `txna` and related opcodes.
+    pub fn is_array(self) -> bool {
+        matches!(
+            self,
+            TxnField::ApplicationArgs
+                | TxnField::Accounts
+                | TxnField::Assets
+                | TxnField::Applications
+                | TxnField::Logs
+                | TxnField::ApprovalProgramPages
+                | TxnField::ClearStateProgramPages
+        )
+    }
+}
+
+fields! {
+    /// GlobalField is an enum of the values readable by `global`.
+    GlobalField {
+        MinTxnFee = 1,
+        MinBalance = 1,
+        MaxTxnLife = 1,
+        ZeroAddress = 1,
+        GroupSize = 1,
+        LogicSigVersion = 2,
+        Round = 2,
+        LatestTimestamp = 2,
+        CurrentApplicationID = 2,
+        CreatorAddress = 3,
+        CurrentApplicationAddress = 5,
+        GroupID = 5,
+        OpcodeBudget = 6,
+        CallerApplicationID = 6,
+        CallerApplicationAddress = 6,
+    }
+}
+
+impl GlobalField {
+    /// Whether the field is only available to application programs.
+    pub fn application_only(self) -> bool {
+        matches!(
+            self,
+            GlobalField::Round
+                | GlobalField::LatestTimestamp
+                | GlobalField::CurrentApplicationID
+                | GlobalField::CreatorAddress
+                | GlobalField::CurrentApplicationAddress
+                | GlobalField::CallerApplicationID
+                | GlobalField::CallerApplicationAddress
+        )
+    }
+}
+
+fields! {
+    /// AssetHoldingField is an enum of the fields readable by
+    /// `asset_holding_get`.
+    AssetHoldingField {
+        AssetBalance = 2,
+        AssetFrozen = 2,
+    }
+}
+
+fields! {
+    /// AssetParamsField is an enum of the parameters readable by
+    /// `asset_params_get`.
+    AssetParamsField {
+        AssetTotal = 2,
+        AssetDecimals = 2,
+        AssetDefaultFrozen = 2,
+        AssetUnitName = 2,
+        AssetName = 2,
+        AssetURL = 2,
+        AssetMetadataHash = 2,
+        AssetManager = 2,
+        AssetReserve = 2,
+        AssetFreeze = 2,
+        AssetClawback = 2,
+        AssetCreator = 5,
+    }
+}
+
+fields! {
+    /// AppParamsField is an enum of the parameters readable by
+    /// `app_params_get`.
+    AppParamsField {
+        AppApprovalProgram = 5,
+        AppClearStateProgram = 5,
+        AppGlobalNumUint = 5,
+        AppGlobalNumByteSlice = 5,
+        AppLocalNumUint = 5,
+        AppLocalNumByteSlice = 5,
+        AppExtraProgramPages = 5,
+        AppCreator = 5,
+        AppAddress = 5,
+    }
+}
+
+fields! {
+    /// AcctParamsField is an enum of the parameters readable by
+    /// `acct_params_get`.
+    AcctParamsField {
+        AcctBalance = 6,
+        AcctMinBalance = 6,
+        AcctAuthAddr = 6,
+    }
+}
+
+/// Decodes a field immediate, checking it is available in `version`.
+pub(super) fn txn_field(value: u8, version: u64) -> OpResult<TxnField> {
+    TxnField::from_u8(value)
+        .filter(|field| field.version() <= version)
+        .ok_or_else(|| format!("invalid txn field {}", value))
+}
+
+fn type_enum(tx_type: TxType) -> u64 {
+    match tx_type {
+        TxType::Unknown | TxType::CompactCertTx => 0,
+        TxType::PaymentTx => 1,
+        TxType::KeyRegistrationTx => 2,
+        TxType::AssetConfigTx => 3,
+        TxType::AssetTransferTx => 4,
+        TxType::AssetFreezeTx => 5,
+        TxType::ApplicationCallTx => 6,
+    }
+}
+
+fn address(addr: &Address) -> StackValue {
+    StackValue::Bytes(addr.as_bytes().to_vec())
+}
+
+fn bytes(b: &[u8]) -> StackValue {
+    StackValue::Bytes(b.to_vec())
+}
+
+fn array_item<T>(items: &[T], index: u64, field: TxnField) -> OpResult<&T> {
+    items
+        .get(usize::try_from(index).unwrap_or(usize::MAX))
+        .ok_or_else(|| format!("invalid {} index {}", field.name(), index))
+}
+
+/// Splits `program` into pages of `page_size` bytes.
+fn program_pages(program: &[u8], page_size: usize) -> Vec<&[u8]> {
+    program.chunks(page_size.max(1)).collect()
+}
+
+impl EvalContext<'_> {
+    /// Reads `field` of the transaction at `group_index` of the group.
+    /// `array_index` is the index into array fields and must be given
+    /// exactly for them.
+    pub(super) fn txn_field_value(
+        &self,
+        group_index: usize,
+        field: TxnField,
+        array_index: Option<u64>,
+    ) -> OpResult<StackValue> {
+        let stxn = self.params.txn_group.get(group_index).ok_or_else(|| {
+            format!(
+                "txn index {}, len(group) is {}",
+                group_index,
+                self.params.txn_group.len()
+            )
+        })?;
+        // The effects of a transaction are only known once it ran.
+        let effects = self.mode == RunMode::Application && group_index < self.group_index;
+        self.stxn_field_value(stxn, group_index, field, array_index, effects)
+    }
+
+    /// Reads `field` of `stxn`, the transaction at `group_index` of its
+    /// group. Whether its effects, like its logs, may be read is given by
+    /// `effects`.
+    pub(super) fn stxn_field_value(
+        &self,
+        stxn: &SignedTxnWithAD,
+        group_index: usize,
+        field: TxnField,
+        array_index: Option<u64>,
+        effects: bool,
+    ) -> OpResult<StackValue> {
+        if field.is_array() != array_index.is_some() {
+            return Err(if field.is_array() {
+                format!("{} is an array field", field.name())
+            } else {
+                format!("{} is not an array field", field.name())
+            });
+        }
+        if field.is_effect() && !effects {
+            return Err(format!(
+                "{} is only available for earlier transactions of an application call",
+                field.name()
+            ));
+        }
+        let txn = &stxn.signed_txn.txn;
+        let header = &txn.header;
+        let pay = &txn.payment_txn_fields;
+        let keyreg = &txn.keyreg_txn_fields;
+        let app = &txn.application_call_txn_fields;
+        let cc = &txn.asset_config_txn_fields;
+        let ct = &txn.asset_transfer_txn_fields;
+        let cf = &txn.asset_freeze_txn_fields;
+        let logs = &stxn.apply_data.eval_delta.logs;
+        let index = array_index.unwrap_or(0);
+        let page_size = self.params.proto.max_app_program_len.max(0) as usize;
+
+        let value = match field {
+            TxnField::Sender => address(&header.sender),
+            TxnField::Fee => StackValue::Uint(header.fee.0),
+            TxnField::FirstValid => StackValue::Uint(header.first_valid),
+            TxnField::LastValid => StackValue::Uint(header.last_valid),
+            TxnField::Note => bytes(&header.note),
+            TxnField::Lease => bytes(&header.lease),
+            TxnField::Receiver => address(&pay.receiver),
+            TxnField::Amount => StackValue::Uint(pay.amount.0),
+            TxnField::CloseRemainderTo => address(&pay.close_remainder_to),
+            TxnField::VotePK => bytes(&keyreg.vote_pk),
+            TxnField::SelectionPK => bytes(&keyreg.selection_pk),
+            TxnField::VoteFirst => StackValue::Uint(keyreg.vote_first),
+            TxnField::VoteLast => StackValue::Uint(keyreg.vote_last),
+            TxnField::VoteKeyDilution => StackValue::Uint(keyreg.vote_key_dilution),
+            TxnField::Nonparticipation => StackValue::Uint(keyreg.nonparticipation as u64),
+            TxnField::Type => bytes(txn.tx_type.as_str().as_bytes()),
+            TxnField::TypeEnum => StackValue::Uint(type_enum(txn.tx_type)),
+            TxnField::XferAsset => StackValue::Uint(ct.xfer_asset),
+            TxnField::AssetAmount => StackValue::Uint(ct.asset_amount),
+            TxnField::AssetSender => address(&ct.asset_sender),
+            TxnField::AssetReceiver => address(&ct.asset_receiver),
+            TxnField::AssetCloseTo => address(&ct.asset_close_to),
+            TxnField::GroupIndex => StackValue::Uint(group_index as u64),
+            TxnField::TxID => bytes(&txn.id().0),
+            TxnField::ApplicationID => StackValue::Uint(app.application_id),
+            TxnField::OnCompletion => StackValue::Uint(app.on_completion as u64),
+            TxnField::ApplicationArgs => bytes(array_item(&app.application_args, index, field)?),
+            TxnField::NumAppArgs => StackValue::Uint(app.application_args.len() as u64),
+            TxnField::Accounts => match index {
+                0 => address(&header.sender),
+                _ => address(array_item(&app.accounts, index - 1, field)?),
+            },
+            TxnField::NumAccounts => StackValue::Uint(app.accounts.len() as u64),
+            TxnField::Assets => StackValue::Uint(*array_item(&app.foreign_assets, index, field)?),
+            TxnField::NumAssets => StackValue::Uint(app.foreign_assets.len() as u64),
+            TxnField::Applications => match index {
+                0 => StackValue::Uint(app.application_id),
+                _ => StackValue::Uint(*array_item(&app.foreign_apps, index - 1, field)?),
+            },
+            TxnField::NumApplications => StackValue::Uint(app.foreign_apps.len() as u64),
+            TxnField::ConfigAsset => StackValue::Uint(cc.config_asset),
+            TxnField::ConfigAssetTotal => StackValue::Uint(cc.asset_params.total),
+            TxnField::ConfigAssetDecimals => StackValue::Uint(cc.asset_params.decimals as u64),
+            TxnField::ConfigAssetDefaultFrozen => {
+                StackValue::Uint(cc.asset_params.default_frozen as u64)
+            }
+            TxnField::ConfigAssetUnitName => bytes(cc.asset_params.unit_name.as_bytes()),
+            TxnField::ConfigAssetName => bytes(cc.asset_params.asset_name.as_bytes()),
+            TxnField::ConfigAssetURL => bytes(cc.asset_params.url.as_bytes()),
+            TxnField::ConfigAssetMetadataHash => bytes(&cc.asset_params.metadata_hash),
+            TxnField::ConfigAssetManager => address(&cc.asset_params.manager),
+            TxnField::ConfigAssetReserve => address(&cc.asset_params.reserve),
+            TxnField::ConfigAssetFreeze => address(&cc.asset_params.freeze),
+            TxnField::ConfigAssetClawback => address(&cc.asset_params.clawback),
+            TxnField::FreezeAsset => StackValue::Uint(cf.freeze_asset),
+            TxnField::FreezeAssetAccount => address(&cf.freeze_account),
+            TxnField::FreezeAssetFrozen => StackValue::Uint(cf.asset_frozen as u64),
+            TxnField::ApprovalProgram => bytes(&app.approval_program),
+            TxnField::ClearStateProgram => bytes(&app.clear_state_program),
+            TxnField::GlobalNumUint => StackValue::Uint(app.global_state_schema.num_uint),
+            TxnField::GlobalNumByteSlice => {
+                StackValue::Uint(app.global_state_schema.num_byte_slice)
+            }
+            TxnField::LocalNumUint => StackValue::Uint(app.local_state_schema.num_uint),
+            TxnField::LocalNumByteSlice => StackValue::Uint(app.local_state_schema.num_byte_slice),
+            TxnField::ExtraProgramPages => StackValue::Uint(app.extra_program_pages as u64),
+            TxnField::CreatedAssetID => StackValue::Uint(stxn.apply_data.config_asset),
+            TxnField::CreatedApplicationID => StackValue::Uint(stxn.apply_data.application_id),
+            TxnField::Logs => bytes(array_item(logs, index, field)?),
+            TxnField::NumLogs => StackValue::Uint(logs.len() as u64),
+            TxnField::LastLog => bytes(logs.last().map(Vec::as_slice).unwrap_or_default()),
+            TxnField::ApprovalProgramPages => bytes(array_item(
+                &program_pages(&app.approval_program, page_size),
+                index,
+                field,
+            )?),
+            TxnField::NumApprovalProgramPages => {
+                StackValue::Uint(program_pages(&app.approval_program, page_size).len() as u64)
+            }
+            TxnField::ClearStateProgramPages => bytes(array_item(
+                &program_pages(&app.clear_state_program, page_size),
+                index,
+                field,
+            )?),
+            TxnField::NumClearStateProgramPages => {
+                StackValue::Uint(program_pages(&app.clear_state_program, page_size).len() as u64)
+            }
+            TxnField::RekeyTo => address(&header.rekey_to),
+            TxnField::FirstValidTime | TxnField::StateProofPK => {
+                return Err(format!("txn field {} is not supported", field.name()));
+            }
+        };
+        Ok(value)
+    }
+
+    fn push_txn_field(
+        &mut self,
+        group_index: usize,
+        field: u8,
+        array_index: Option<u64>,
+    ) -> OpResult {
+        let field = txn_field(field, self.version)?;
+        let value = self.txn_field_value(group_index, field, array_index)?;
+        self.push(value);
+        Ok(())
+    }
+
+    fn global_field_value(&mut self, field: GlobalField) -> OpResult<StackValue> {
+        if field.application_only() {
+            self.require_mode(RunMode::Application, field.name())?;
+        }
+        let proto = self.params.proto;
+        let value = match field {
+            GlobalField::MinTxnFee => StackValue::Uint(proto.min_txn_fee),
+            GlobalField::MinBalance => StackValue::Uint(proto.min_balance),
+            GlobalField::MaxTxnLife => StackValue::Uint(proto.max_txn_life),
+            GlobalField::ZeroAddress => address(&Address::default()),
+            GlobalField::GroupSize => StackValue::Uint(self.params.txn_group.len() as u64),
+            GlobalField::LogicSigVersion => StackValue::Uint(proto.logic_sig_version),
+            GlobalField::Round => StackValue::Uint(self.ledger()?.round()),
+            GlobalField::LatestTimestamp => {
+                let timestamp = self.ledger()?.latest_timestamp();
+                StackValue::Uint(
+                    u64::try_from(timestamp)
+                        .map_err(|_| format!("latest timestamp {} < 0", timestamp))?,
+                )
+            }
+            GlobalField::CurrentApplicationID => StackValue::Uint(self.app_id),
+            GlobalField::CreatorAddress => {
+                let app_id = self.app_id;
+                let (_, creator) = self
+                    .ledger()?
+                    .app_params(app_id)
+                    .ok_or_else(|| format!("app {} does not exist", app_id))?;
+                address(&creator)
+            }
+            GlobalField::CurrentApplicationAddress => address(&application_address(self.app_id)),
+            GlobalField::GroupID => bytes(&self.txn().signed_txn.txn.header.group.0),
+            GlobalField::OpcodeBudget => StackValue::Uint(self.budget.saturating_sub(self.cost)),
+            GlobalField::CallerApplicationID => StackValue::Uint(self.params.caller_app_id),
+            GlobalField::CallerApplicationAddress => match self.params.caller_app_id {
+                0 => address(&Address::default()),
+                caller => address(&application_address(caller)),
+            },
+        };
+        Ok(value)
+    }
+
+    /// Checks `group_index` refers to an earlier transaction of the group
+    /// and returns its scratch space.
+    fn past_scratch_value(&self, group_index: u64, slot: u64) -> OpResult<StackValue> {
+        if group_index >= self.group_index as u64 {
+            return Err(format!(
+                "can't get scratch of transaction {} from transaction {}",
+                group_index, self.group_index
+            ));
+        }
+        let past = self.params.past_scratch.borrow();
+        let scratch = past[group_index as usize]
+            .as_ref()
+            .ok_or_else(|| format!("transaction {} is not an application call", group_index))?;
+        scratch
+            .get(usize::try_from(slot).unwrap_or(usize::MAX))
+            .cloned()
+            .ok_or_else(|| format!("invalid scratch slot {}", slot))
+    }
+
+    fn created_app_id(&self, group_index: u64) -> OpResult<u64> {
+        if group_index >= self.group_index as u64 {
+            return Err(format!(
+                "can't get creatable ID of transaction {} from transaction {}",
+                group_index, self.group_index
+            ));
+        }
+        let stxn = &self.params.txn_group[group_index as usize];
+        let app = &stxn.signed_txn.txn.application_call_txn_fields;
+        if stxn.signed_txn.txn.tx_type != TxType::ApplicationCallTx || app.application_id != 0 {
+            return Err(format!(
+                "transaction {} did not create an application",
+                group_index
+            ));
+        }
+        Ok(stxn.apply_data.application_id)
+    }
+}
+
+fn to_index(value: u64) -> usize {
+    usize::try_from(value).unwrap_or(usize::MAX)
+}
+
+pub(super) fn op_txn(cx: &mut EvalContext) -> OpResult {
+    cx.push_txn_field(cx.group_index, cx.imm(0), None)
+}
+
+pub(super) fn op_gtxn(cx: &mut EvalContext) -> OpResult {
+    cx.push_txn_field(cx.imm(0) as usize, cx.imm(1), None)
+}
+
+pub(super) fn op_txna(cx: &mut EvalContext) -> OpResult {
+    cx.push_txn_field(cx.group_index, cx.imm(0), Some(cx.imm(1) as u64))
+}
+
+pub(super) fn op_gtxna(cx: &mut EvalContext) -> OpResult {
+    cx.push_txn_field(cx.imm(0) as usize, cx.imm(1), Some(cx.imm(2) as u64))
+}
+
+pub(super) fn op_gtxns(cx: &mut EvalContext) -> OpResult {
+    let group_index = cx.pop_uint()?;
+    cx.push_txn_field(to_index(group_index), cx.imm(0), None)
+}
+
+pub(super) fn op_gtxnsa(cx: &mut EvalContext) -> OpResult {
+    let group_index = cx.pop_uint()?;
+    cx.push_txn_field(to_index(group_index), cx.imm(0), Some(cx.imm(1) as u64))
+}
+
+pub(super) fn op_txnas(cx: &mut EvalContext) -> OpResult {
+    let index = cx.pop_uint()?;
+    cx.push_txn_field(cx.group_index, cx.imm(0), Some(index))
+}
+
+pub(super) fn op_gtxnas(cx: &mut EvalContext) -> OpResult {
+    let index = cx.pop_uint()?;
+    cx.push_txn_field(cx.imm(0) as usize, cx.imm(1), Some(index))
+}
+
+pub(super) fn op_gtxnsas(cx: &mut EvalContext) -> OpResult {
+    let index = cx.pop_uint()?;
+    let group_index = cx.pop_uint()?;
+    cx.push_txn_field(to_index(group_index), cx.imm(0), Some(index))
+}
+
+pub(super) fn op_global(cx: &mut EvalContext) -> OpResult {
+    let field = GlobalField::from_u8(cx.imm(0))
+        .filter(|field| field.version() <= cx.version)
+        .ok_or_else(|| format!("invalid global field {}", cx.imm(0)))?;
+    let value = cx.global_field_value(field)?;
+    cx.push(value);
+    Ok(())
+}
+
+pub(super) fn op_gload(cx: &mut EvalContext) -> OpResult {
+    let value = cx.past_scratch_value(cx.imm(0) as u64, cx.imm(1) as u64)?;
+    cx.push(value);
+    Ok(())
+}
+
+pub(super) fn op_gloads(cx: &mut EvalContext) -> OpResult {
+    let group_index = cx.pop_uint()?;
+    let value = cx.past_scratch_value(group_index, cx.imm(0) as u64)?;
+    cx.push(value);
+    Ok(())
+}
+
+pub(super) fn op_gloadss(cx: &mut EvalContext) -> OpResult {
+    let slot = cx.pop_uint()?;
+    let group_index = cx.pop_uint()?;
+    let value = cx.past_scratch_value(group_index, slot)?;
+    cx.push(value);
+    Ok(())
+}
+
+pub(super) fn op_gaid(cx: &mut EvalContext) -> OpResult {
+    let id = cx.created_app_id(cx.imm(0) as u64)?;
+    cx.push_uint(id);
+    Ok(())
+}
+
+pub(super) fn op_gaids(cx: &mut EvalContext) -> OpResult {
+    let group_index = cx.pop_uint()?;
+    let id = cx.created_app_id(group_index)?;
+    cx.push_uint(id);
+    Ok(())
+}
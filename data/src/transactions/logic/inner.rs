@@ -0,0 +1,384 @@
+//! Inner transactions: transactions assembled and submitted by an
+//! application program, executed on behalf of the application account.
+
+use protocol::TxType;
+
+use super::eval::{group_fees, EvalContext, GroupPools, OpResult, StackValue};
+use super::fields::{txn_field, TxnField};
+use crate::basics::{application_address, Address, MicroAlgos};
+use crate::transactions::application::OnCompletion;
+use crate::transactions::signedtxn::SignedTxnWithAD;
+
+/// The first version allowing inner key registrations and application
+/// calls, and grouped inner transactions.
+const INNER_APPL_VERSION: u64 = 6;
+
+impl EvalContext<'_> {
+    /// Starts a new inner transaction with the defaults of a transaction
+    /// sent by the application account.
+    fn add_inner_txn(&mut self) -> OpResult {
+        let max = self.params.proto.max_inner_transactions.max(0) as usize;
+        if self.inner_txns.len() + self.sub_txns.len() >= max {
+            return Err(format!(
+                "too many inner transactions {} with {} left",
+                self.sub_txns.len() + 1,
+                max.saturating_sub(self.inner_txns.len())
+            ));
+        }
+        let outer = &self.txn().signed_txn.txn.header;
+        let mut stxn = SignedTxnWithAD::default();
+        let header = &mut stxn.signed_txn.txn.header;
+        header.sender = application_address(self.app_id);
+        // The fee credit lowers the default fee, but is only used up when
+        // the group is submitted, as the program may still change the fee.
+        let credit = self
+            .params
+            .pools
+            .and_then(|pools| pools.fee_credit.as_ref())
+            .map(|credit| credit.get())
+            .unwrap_or_default();
+        header.fee = MicroAlgos(self.params.proto.min_txn_fee.saturating_sub(credit));
+        header.first_valid = outer.first_valid;
+        header.last_valid = outer.last_valid;
+        self.sub_txns.push(stxn);
+        Ok(())
+    }
+
+    fn assign_account(&self, value: StackValue) -> OpResult<Address> {
+        match value {
+            StackValue::Bytes(_) => self.account_ref(value),
+            StackValue::Uint(_) => Err("address must be []byte".into()),
+        }
+    }
+
+    fn set_tx_type(&self, tx_type: TxType) -> OpResult<TxType> {
+        let version = match tx_type {
+            TxType::PaymentTx
+            | TxType::AssetConfigTx
+            | TxType::AssetTransferTx
+            | TxType::AssetFreezeTx => 5,
+            TxType::KeyRegistrationTx | TxType::ApplicationCallTx => INNER_APPL_VERSION,
+            _ => {
+                return Err(format!(
+                    "{} is not a valid Type for itxn_field",
+                    tx_type.as_str()
+                ))
+            }
+        };
+        if self.version < version {
+            return Err(format!(
+                "{} is not a valid Type for itxn_field",
+                tx_type.as_str()
+            ));
+        }
+        Ok(tx_type)
+    }
+
+    /// Stores `value` into `field` of the inner transaction being
+    /// assembled. Array fields are appended to.
+    fn set_inner_field(&mut self, field: TxnField, value: StackValue) -> OpResult {
+        let uint = |value: &StackValue| match value {
+            StackValue::Uint(u) => Ok(*u),
+            other => Err(format!(
+                "{} must be uint64 but got {}",
+                field.name(),
+                other.type_name()
+            )),
+        };
+        let bytes = |value: &StackValue| match value {
+            StackValue::Bytes(b) => Ok(b.clone()),
+            other => Err(format!(
+                "{} must be []byte but got {}",
+                field.name(),
+                other.type_name()
+            )),
+        };
+        let key = |value: &StackValue| -> OpResult<[u8; 32]> {
+            bytes(value)?.try_into().map_err(|b: Vec<u8>| {
+                format!("{} must be 32 bytes but is {}", field.name(), b.len())
+            })
+        };
+        let string = |value: &StackValue| {
+            String::from_utf8(bytes(value)?)
+                .map_err(|_| format!("{} must be valid UTF-8", field.name()))
+        };
+        let boolean = |value: &StackValue| match uint(value)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(format!("boolean is neither 1 nor 0: {}", other)),
+        };
+        let proto = self.params.proto;
+
+        // Resolve references before borrowing the transaction.
+        let account = match field {
+            TxnField::Sender
+            | TxnField::Receiver
+            | TxnField::CloseRemainderTo
+            | TxnField::AssetSender
+            | TxnField::AssetReceiver
+            | TxnField::AssetCloseTo
+            | TxnField::FreezeAssetAccount
+            | TxnField::RekeyTo
+            | TxnField::Accounts => Some(self.assign_account(value.clone())?),
+            _ => None,
+        };
+        let tx_type = match field {
+            TxnField::Type => {
+                let name = bytes(&value)?;
+                let tx_type = match name.as_slice() {
+                    b"pay" => TxType::PaymentTx,
+                    b"keyreg" => TxType::KeyRegistrationTx,
+                    b"acfg" => TxType::AssetConfigTx,
+                    b"axfer" => TxType::AssetTransferTx,
+                    b"afrz" => TxType::AssetFreezeTx,
+                    b"appl" => TxType::ApplicationCallTx,
+                    _ => {
+                        return Err(format!(
+                            "{:?} is not a valid Type for itxn_field",
+                            String::from_utf8_lossy(&name)
+                        ))
+                    }
+                };
+                Some(self.set_tx_type(tx_type)?)
+            }
+            TxnField::TypeEnum => {
+                let tx_type = match uint(&value)? {
+                    1 => TxType::PaymentTx,
+                    2 => TxType::KeyRegistrationTx,
+                    3 => TxType::AssetConfigTx,
+                    4 => TxType::AssetTransferTx,
+                    5 => TxType::AssetFreezeTx,
+                    6 => TxType::ApplicationCallTx,
+                    other => {
+                        return Err(format!("{} is not a valid TypeEnum for itxn_field", other))
+                    }
+                };
+                Some(self.set_tx_type(tx_type)?)
+            }
+            _ => None,
+        };
+
+        let txn = &mut self
+            .sub_txns
+            .last_mut()
+            .ok_or("itxn_field without itxn_begin")?
+            .signed_txn
+            .txn;
+        let app = &mut txn.application_call_txn_fields;
+        let asset_params = &mut txn.asset_config_txn_fields.asset_params;
+        let ct = &mut txn.asset_transfer_txn_fields;
+        let cf = &mut txn.asset_freeze_txn_fields;
+        match field {
+            TxnField::Sender => txn.header.sender = account.unwrap_or_default(),
+            TxnField::Fee => txn.header.fee = MicroAlgos(uint(&value)?),
+            TxnField::Note => {
+                let note = bytes(&value)?;
+                if note.len() > proto.max_txn_note_bytes.max(0) as usize {
+                    return Err(format!(
+                        "{} may not exceed {} bytes",
+                        field.name(),
+                        proto.max_txn_note_bytes
+                    ));
+                }
+                txn.header.note = note;
+            }
+            TxnField::RekeyTo if self.version >= INNER_APPL_VERSION => {
+                txn.header.rekey_to = account.unwrap_or_default()
+            }
+            TxnField::Type | TxnField::TypeEnum => txn.tx_type = tx_type.unwrap_or_default(),
+            TxnField::Receiver => txn.payment_txn_fields.receiver = account.unwrap_or_default(),
+            TxnField::Amount => txn.payment_txn_fields.amount = MicroAlgos(uint(&value)?),
+            TxnField::CloseRemainderTo => {
+                txn.payment_txn_fields.close_remainder_to = account.unwrap_or_default()
+            }
+            TxnField::VotePK => txn.keyreg_txn_fields.vote_pk = key(&value)?,
+            TxnField::SelectionPK => txn.keyreg_txn_fields.selection_pk = key(&value)?,
+            TxnField::VoteFirst => txn.keyreg_txn_fields.vote_first = uint(&value)?,
+            TxnField::VoteLast => txn.keyreg_txn_fields.vote_last = uint(&value)?,
+            TxnField::VoteKeyDilution => txn.keyreg_txn_fields.vote_key_dilution = uint(&value)?,
+            TxnField::Nonparticipation => txn.keyreg_txn_fields.nonparticipation = boolean(&value)?,
+            TxnField::XferAsset => ct.xfer_asset = uint(&value)?,
+            TxnField::AssetAmount => ct.asset_amount = uint(&value)?,
+            TxnField::AssetSender => ct.asset_sender = account.unwrap_or_default(),
+            TxnField::AssetReceiver => ct.asset_receiver = account.unwrap_or_default(),
+            TxnField::AssetCloseTo => ct.asset_close_to = account.unwrap_or_default(),
+            TxnField::ConfigAsset => txn.asset_config_txn_fields.config_asset = uint(&value)?,
+            TxnField::ConfigAssetTotal => asset_params.total = uint(&value)?,
+            TxnField::ConfigAssetDecimals => {
+                asset_params.decimals = u32::try_from(uint(&value)?)
+                    .map_err(|_| format!("{} must be a uint32", field.name()))?
+            }
+            TxnField::ConfigAssetDefaultFrozen => asset_params.default_frozen = boolean(&value)?,
+            TxnField::ConfigAssetUnitName => asset_params.unit_name = string(&value)?,
+            TxnField::ConfigAssetName => asset_params.asset_name = string(&value)?,
+            TxnField::ConfigAssetURL => asset_params.url = string(&value)?,
+            TxnField::ConfigAssetMetadataHash => asset_params.metadata_hash = key(&value)?,
+            TxnField::ConfigAssetManager => asset_params.manager = Address::from(key(&value)?),
+            TxnField::ConfigAssetReserve => asset_params.reserve = Address::from(key(&value)?),
+            TxnField::ConfigAssetFreeze => asset_params.freeze = Address::from(key(&value)?),
+            TxnField::ConfigAssetClawback => asset_params.clawback = Address::from(key(&value)?),
+            TxnField::FreezeAsset => cf.freeze_asset = uint(&value)?,
+            TxnField::FreezeAssetAccount => cf.freeze_account = account.unwrap_or_default(),
+            TxnField::FreezeAssetFrozen => cf.asset_frozen = boolean(&value)?,
+            TxnField::ApplicationID => app.application_id = uint(&value)?,
+            TxnField::OnCompletion => app.on_completion = OnCompletion::try_from(uint(&value)?)?,
+            TxnField::ApplicationArgs => {
+                if app.application_args.len() >= proto.max_app_args.max(0) as usize {
+                    return Err("too many application args".into());
+                }
+                app.application_args.push(bytes(&value)?);
+            }
+            TxnField::Accounts => {
+                if app.accounts.len() >= proto.max_app_txn_accounts.max(0) as usize {
+                    return Err("too many foreign accounts".into());
+                }
+                app.accounts.push(account.unwrap_or_default());
+            }
+            TxnField::Applications => {
+                if app.foreign_apps.len() >= proto.max_app_txn_foreign_apps.max(0) as usize {
+                    return Err("too many foreign apps".into());
+                }
+                app.foreign_apps.push(uint(&value)?);
+            }
+            TxnField::Assets => {
+                if app.foreign_assets.len() >= proto.max_app_txn_foreign_assets.max(0) as usize {
+                    return Err("too many foreign assets".into());
+                }
+                app.foreign_assets.push(uint(&value)?);
+            }
+            TxnField::ApprovalProgram => app.approval_program = bytes(&value)?,
+            TxnField::ClearStateProgram => app.clear_state_program = bytes(&value)?,
+            TxnField::ApprovalProgramPages => app.approval_program.extend(bytes(&value)?),
+            TxnField::ClearStateProgramPages => app.clear_state_program.extend(bytes(&value)?),
+            TxnField::GlobalNumUint => app.global_state_schema.num_uint = uint(&value)?,
+            TxnField::GlobalNumByteSlice => app.global_state_schema.num_byte_slice = uint(&value)?,
+            TxnField::LocalNumUint => app.local_state_schema.num_uint = uint(&value)?,
+            TxnField::LocalNumByteSlice => app.local_state_schema.num_byte_slice = uint(&value)?,
+            TxnField::ExtraProgramPages => {
+                let pages = uint(&value)?;
+                if pages > proto.max_extra_app_program_pages.max(0) as u64 {
+                    return Err(format!("too many extra program pages {}", pages));
+                }
+                app.extra_program_pages = pages as u32;
+            }
+            _ => return Err(format!("invalid itxn_field {}", field.name())),
+        }
+        Ok(())
+    }
+
+    /// Reads `field` of transaction `group_index` of the last submitted
+    /// inner group, or its last transaction.
+    fn push_inner_field(
+        &mut self,
+        group_index: Option<usize>,
+        field: u8,
+        array_index: Option<u64>,
+    ) -> OpResult {
+        let field = txn_field(field, self.version)?;
+        if self.last_inner_group.is_empty() {
+            return Err("no inner transaction available".into());
+        }
+        let group_index = group_index.unwrap_or(self.last_inner_group.len() - 1);
+        let stxn = self.last_inner_group.get(group_index).ok_or_else(|| {
+            format!(
+                "txn index {}, len(group) is {}",
+                group_index,
+                self.last_inner_group.len()
+            )
+        })?;
+        let value = self.stxn_field_value(stxn, group_index, field, array_index, true)?;
+        self.push(value);
+        Ok(())
+    }
+}
+
+pub(super) fn op_itxn_begin(cx: &mut EvalContext) -> OpResult {
+    if !cx.sub_txns.is_empty() {
+        return Err("itxn_begin without itxn_submit".into());
+    }
+    cx.add_inner_txn()
+}
+
+pub(super) fn op_itxn_next(cx: &mut EvalContext) -> OpResult {
+    if cx.sub_txns.is_empty() {
+        return Err("itxn_next without itxn_begin".into());
+    }
+    cx.add_inner_txn()
+}
+
+pub(super) fn op_itxn_field(cx: &mut EvalContext) -> OpResult {
+    let field = txn_field(cx.imm(0), cx.version)?;
+    let value = cx.pop()?;
+    cx.set_inner_field(field, value)
+}
+
+pub(super) fn op_itxn_submit(cx: &mut EvalContext) -> OpResult {
+    if cx.sub_txns.is_empty() {
+        return Err("itxn_submit without itxn_begin".into());
+    }
+    let mut group = std::mem::take(&mut cx.sub_txns);
+    for stxn in &group {
+        if stxn.signed_txn.txn.tx_type == TxType::Unknown {
+            return Err("inner transaction has no Type".into());
+        }
+    }
+
+    // Inner transactions owe the minimum fee, which may be paid from the
+    // fee credit of the group: for each transaction, or for the whole
+    // inner group since `enable_inner_transaction_pooling`.
+    let proto = cx.params.proto;
+    let no_pools = GroupPools::default();
+    let pools = cx.params.pools.unwrap_or(&no_pools);
+    if proto.enable_inner_transaction_pooling {
+        let (paid, count) = group_fees(&group);
+        pools.pay_fees(paid, proto.min_txn_fee.saturating_mul(count))?;
+    } else {
+        for stxn in &group {
+            pools.pay_fees(stxn.signed_txn.txn.header.fee.0, proto.min_txn_fee)?;
+        }
+    }
+
+    // Inner application calls add to the pooled budget, which the cost of
+    // this program so far is charged to before they draw on it.
+    if cx.pooled_budget().is_some() {
+        let apps = group
+            .iter()
+            .filter(|stxn| stxn.signed_txn.txn.tx_type == TxType::ApplicationCallTx)
+            .count() as u64;
+        cx.budget += apps * proto.max_app_program_cost.max(0) as u64;
+    }
+    cx.settle_budget();
+    cx.ledger()?
+        .perform(&mut group)
+        .map_err(|e| e.to_string())?;
+    cx.refresh_budget();
+    cx.inner_txns.extend(group.iter().cloned());
+    cx.last_inner_group = group;
+    Ok(())
+}
+
+pub(super) fn op_itxn(cx: &mut EvalContext) -> OpResult {
+    cx.push_inner_field(None, cx.imm(0), None)
+}
+
+pub(super) fn op_itxna(cx: &mut EvalContext) -> OpResult {
+    cx.push_inner_field(None, cx.imm(0), Some(cx.imm(1) as u64))
+}
+
+pub(super) fn op_itxnas(cx: &mut EvalContext) -> OpResult {
+    let index = cx.pop_uint()?;
+    cx.push_inner_field(None, cx.imm(0), Some(index))
+}
+
+pub(super) fn op_gitxn(cx: &mut EvalContext) -> OpResult {
+    cx.push_inner_field(Some(cx.imm(0) as usize), cx.imm(1), None)
+}
+
+pub(super) fn op_gitxna(cx: &mut EvalContext) -> OpResult {
+    cx.push_inner_field(Some(cx.imm(0) as usize), cx.imm(1), Some(cx.imm(2) as u64))
+}
+
+pub(super) fn op_gitxnas(cx: &mut EvalContext) -> OpResult {
+    let index = cx.pop_uint()?;
+    cx.push_inner_field(Some(cx.imm(0) as usize), cx.imm(1), Some(index))
+}
@@ -0,0 +1,54 @@
+//! Package logic implements the Algorand Virtual Machine (AVM), which
+//! evaluates TEAL bytecode either as a logic signature (stateless mode) or
+//! as the approval or clear state program of an application (stateful
+//! mode).
+
+mod arith;
+mod bytes;
+mod eval;
+mod fields;
+mod inner;
+mod opcodes;
+mod stack;
+mod state;
+
+pub use eval::*;
+pub use fields::{
+    AcctParamsField, AppParamsField, AssetHoldingField, AssetParamsField, GlobalField, TxnField,
+};
+pub use state::LedgerForLogic;
+
+pub use crypto::logicsig::program_hash;
+
+/// LOGIC_VERSION defines the maximum supported version of TEAL.
+pub const LOGIC_VERSION: u64 = 7;
+
+/// MAX_STRING_SIZE is the limit of byte string length in an AVM value.
+pub const MAX_STRING_SIZE: usize = 4096;
+
+/// MAX_BYTE_MATH_SIZE is the limit of byte strings supplied as input to
+/// byte math opcodes.
+pub const MAX_BYTE_MATH_SIZE: usize = 64;
+
+/// MAX_LOG_SIZE is the limit of total log size from n log calls in a
+/// program.
+pub const MAX_LOG_SIZE: usize = 1024;
+
+/// MAX_LOG_CALLS is the limit of total log calls during a program
+/// execution.
+pub const MAX_LOG_CALLS: usize = 32;
+
+/// MAX_STACK_DEPTH is the limit on the number of values on the stack.
+pub const MAX_STACK_DEPTH: usize = 1000;
+
+/// MAX_APP_CALL_DEPTH is the limit on the nesting of inner application
+/// calls.
+pub const MAX_APP_CALL_DEPTH: usize = 8;
+
+/// The number of scratch space slots.
+pub const SCRATCH_SIZE: usize = 256;
+
+/// Returns the TEAL version a program is written in.
+pub fn program_version(program: &[u8]) -> Result<u64, String> {
+    eval::read_varuint(program, 0).map(|(version, _)| version)
+}
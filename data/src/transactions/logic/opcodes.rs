@@ -0,0 +1,325 @@
+use std::sync::OnceLock;
+
+use super::eval::{EvalContext, OpResult};
+use super::{arith, bytes, fields, inner, stack, state, LOGIC_VERSION};
+
+/// Immediates describes the bytes following an opcode in the program.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Immediates {
+    None,
+    /// A fixed number of one byte immediates.
+    Bytes(usize),
+    /// A two byte branch offset.
+    Branch,
+    /// A varuint (`pushint`).
+    Varuint,
+    /// A varuint length followed by that many bytes (`pushbytes`).
+    VarBytes,
+    IntcBlock,
+    BytecBlock,
+}
+
+/// Modes lists the run modes an opcode is available in.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Modes {
+    Any,
+    Signature,
+    Application,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Cost {
+    Fixed(u64),
+    /// The cost of an ECDSA opcode, depending on its curve immediate.
+    ByCurve {
+        secp256k1: u64,
+        secp256r1: u64,
+    },
+}
+
+type OpFn = fn(&mut EvalContext<'_>) -> OpResult;
+
+/// OpSpec defines an opcode as of some version; an opcode that changed
+/// over time has an OpSpec per change.
+pub(super) struct OpSpec {
+    pub opcode: u8,
+    pub name: &'static str,
+    /// The version the spec was introduced in.
+    pub version: u64,
+    pub op: OpFn,
+    pub immediates: Immediates,
+    pub modes: Modes,
+    pub cost: Cost,
+}
+
+impl OpSpec {
+    /// The cost of the instruction at `pc`, not counting costs that depend
+    /// on the data processed.
+    pub fn static_cost(&self, program: &[u8], pc: usize) -> u64 {
+        match self.cost {
+            Cost::Fixed(cost) => cost,
+            Cost::ByCurve {
+                secp256k1,
+                secp256r1,
+            } => match program.get(pc + 1) {
+                Some(1) => secp256r1,
+                _ => secp256k1,
+            },
+        }
+    }
+
+    const fn imm(self, immediates: Immediates) -> Self {
+        Self { immediates, ..self }
+    }
+
+    const fn cost(self, cost: u64) -> Self {
+        Self {
+            cost: Cost::Fixed(cost),
+            ..self
+        }
+    }
+
+    const fn curve_cost(self, secp256k1: u64, secp256r1: u64) -> Self {
+        Self {
+            cost: Cost::ByCurve {
+                secp256k1,
+                secp256r1,
+            },
+            ..self
+        }
+    }
+
+    const fn only(self, modes: Modes) -> Self {
+        Self { modes, ..self }
+    }
+}
+
+const fn op(opcode: u8, name: &'static str, version: u64, op: OpFn) -> OpSpec {
+    OpSpec {
+        opcode,
+        name,
+        version,
+        op,
+        immediates: Immediates::None,
+        modes: Modes::Any,
+        cost: Cost::Fixed(1),
+    }
+}
+
+const APP: Modes = Modes::Application;
+const SIG: Modes = Modes::Signature;
+const fn one(n: usize) -> Immediates {
+    Immediates::Bytes(n)
+}
+
+static OP_SPECS: &[OpSpec] = &[
+    op(0x00, "err", 1, stack::op_err),
+    op(0x01, "sha256", 1, bytes::op_sha256).cost(7),
+    op(0x01, "sha256", 2, bytes::op_sha256).cost(35),
+    op(0x02, "keccak256", 1, bytes::op_keccak256).cost(26),
+    op(0x02, "keccak256", 2, bytes::op_keccak256).cost(130),
+    op(0x03, "sha512_256", 1, bytes::op_sha512_256).cost(9),
+    op(0x03, "sha512_256", 2, bytes::op_sha512_256).cost(45),
+    op(0x04, "ed25519verify", 1, bytes::op_ed25519verify)
+        .cost(1900)
+        .only(SIG),
+    op(0x04, "ed25519verify", 5, bytes::op_ed25519verify).cost(1900),
+    op(0x05, "ecdsa_verify", 5, bytes::op_ecdsa_verify)
+        .imm(one(1))
+        .curve_cost(1700, 2500),
+    op(
+        0x06,
+        "ecdsa_pk_decompress",
+        5,
+        bytes::op_ecdsa_pk_decompress,
+    )
+    .imm(one(1))
+    .curve_cost(650, 2400),
+    op(0x07, "ecdsa_pk_recover", 5, bytes::op_ecdsa_pk_recover)
+        .imm(one(1))
+        .cost(2000),
+    op(0x08, "+", 1, arith::op_plus),
+    op(0x09, "-", 1, arith::op_minus),
+    op(0x0a, "/", 1, arith::op_div),
+    op(0x0b, "*", 1, arith::op_mul),
+    op(0x0c, "<", 1, arith::op_lt),
+    op(0x0d, ">", 1, arith::op_gt),
+    op(0x0e, "<=", 1, arith::op_le),
+    op(0x0f, ">=", 1, arith::op_ge),
+    op(0x10, "&&", 1, arith::op_and),
+    op(0x11, "||", 1, arith::op_or),
+    op(0x12, "==", 1, arith::op_eq),
+    op(0x13, "!=", 1, arith::op_neq),
+    op(0x14, "!", 1, arith::op_not),
+    op(0x15, "len", 1, bytes::op_len),
+    op(0x16, "itob", 1, arith::op_itob),
+    op(0x17, "btoi", 1, arith::op_btoi),
+    op(0x18, "%", 1, arith::op_modulo),
+    op(0x19, "|", 1, arith::op_bit_or),
+    op(0x1a, "&", 1, arith::op_bit_and),
+    op(0x1b, "^", 1, arith::op_bit_xor),
+    op(0x1c, "~", 1, arith::op_bit_not),
+    op(0x1d, "mulw", 1, arith::op_mulw),
+    op(0x1e, "addw", 2, arith::op_addw),
+    op(0x1f, "divmodw", 4, arith::op_divmodw).cost(20),
+    op(0x20, "intcblock", 1, stack::op_intcblock).imm(Immediates::IntcBlock),
+    op(0x21, "intc", 1, stack::op_intc).imm(one(1)),
+    op(0x22, "intc_0", 1, stack::op_intc_0),
+    op(0x23, "intc_1", 1, stack::op_intc_1),
+    op(0x24, "intc_2", 1, stack::op_intc_2),
+    op(0x25, "intc_3", 1, stack::op_intc_3),
+    op(0x26, "bytecblock", 1, stack::op_bytecblock).imm(Immediates::BytecBlock),
+    op(0x27, "bytec", 1, stack::op_bytec).imm(one(1)),
+    op(0x28, "bytec_0", 1, stack::op_bytec_0),
+    op(0x29, "bytec_1", 1, stack::op_bytec_1),
+    op(0x2a, "bytec_2", 1, stack::op_bytec_2),
+    op(0x2b, "bytec_3", 1, stack::op_bytec_3),
+    op(0x2c, "arg", 1, stack::op_arg).imm(one(1)).only(SIG),
+    op(0x2d, "arg_0", 1, stack::op_arg_0).only(SIG),
+    op(0x2e, "arg_1", 1, stack::op_arg_1).only(SIG),
+    op(0x2f, "arg_2", 1, stack::op_arg_2).only(SIG),
+    op(0x30, "arg_3", 1, stack::op_arg_3).only(SIG),
+    op(0x31, "txn", 1, fields::op_txn).imm(one(1)),
+    op(0x32, "global", 1, fields::op_global).imm(one(1)),
+    op(0x33, "gtxn", 1, fields::op_gtxn).imm(one(2)),
+    op(0x34, "load", 1, stack::op_load).imm(one(1)),
+    op(0x35, "store", 1, stack::op_store).imm(one(1)),
+    op(0x36, "txna", 2, fields::op_txna).imm(one(2)),
+    op(0x37, "gtxna", 2, fields::op_gtxna).imm(one(3)),
+    op(0x38, "gtxns", 3, fields::op_gtxns).imm(one(1)),
+    op(0x39, "gtxnsa", 3, fields::op_gtxnsa).imm(one(2)),
+    op(0x3a, "gload", 4, fields::op_gload).imm(one(2)).only(APP),
+    op(0x3b, "gloads", 4, fields::op_gloads)
+        .imm(one(1))
+        .only(APP),
+    op(0x3c, "gaid", 4, fields::op_gaid).imm(one(1)).only(APP),
+    op(0x3d, "gaids", 4, fields::op_gaids).only(APP),
+    op(0x3e, "loads", 5, stack::op_loads),
+    op(0x3f, "stores", 5, stack::op_stores),
+    op(0x40, "bnz", 1, stack::op_bnz).imm(Immediates::Branch),
+    op(0x41, "bz", 2, stack::op_bz).imm(Immediates::Branch),
+    op(0x42, "b", 2, stack::op_b).imm(Immediates::Branch),
+    op(0x43, "return", 2, stack::op_return),
+    op(0x44, "assert", 3, stack::op_assert),
+    op(0x48, "pop", 1, stack::op_pop),
+    op(0x49, "dup", 1, stack::op_dup),
+    op(0x4a, "dup2", 2, stack::op_dup2),
+    op(0x4b, "dig", 3, stack::op_dig).imm(one(1)),
+    op(0x4c, "swap", 3, stack::op_swap),
+    op(0x4d, "select", 3, stack::op_select),
+    op(0x4e, "cover", 5, stack::op_cover).imm(one(1)),
+    op(0x4f, "uncover", 5, stack::op_uncover).imm(one(1)),
+    op(0x50, "concat", 2, bytes::op_concat),
+    op(0x51, "substring", 2, bytes::op_substring).imm(one(2)),
+    op(0x52, "substring3", 2, bytes::op_substring3),
+    op(0x53, "getbit", 3, bytes::op_getbit),
+    op(0x54, "setbit", 3, bytes::op_setbit),
+    op(0x55, "getbyte", 3, bytes::op_getbyte),
+    op(0x56, "setbyte", 3, bytes::op_setbyte),
+    op(0x57, "extract", 5, bytes::op_extract).imm(one(2)),
+    op(0x58, "extract3", 5, bytes::op_extract3),
+    op(0x59, "extract_uint16", 5, bytes::op_extract_uint16),
+    op(0x5a, "extract_uint32", 5, bytes::op_extract_uint32),
+    op(0x5b, "extract_uint64", 5, bytes::op_extract_uint64),
+    op(0x5c, "replace2", 7, bytes::op_replace2).imm(one(1)),
+    op(0x5d, "replace3", 7, bytes::op_replace3),
+    op(0x5e, "base64_decode", 7, bytes::op_base64_decode).imm(one(1)),
+    op(0x5f, "json_ref", 7, bytes::op_json_ref).imm(one(1)),
+    op(0x60, "balance", 2, state::op_balance).only(APP),
+    op(0x61, "app_opted_in", 2, state::op_app_opted_in).only(APP),
+    op(0x62, "app_local_get", 2, state::op_app_local_get).only(APP),
+    op(0x63, "app_local_get_ex", 2, state::op_app_local_get_ex).only(APP),
+    op(0x64, "app_global_get", 2, state::op_app_global_get).only(APP),
+    op(0x65, "app_global_get_ex", 2, state::op_app_global_get_ex).only(APP),
+    op(0x66, "app_local_put", 2, state::op_app_local_put).only(APP),
+    op(0x67, "app_global_put", 2, state::op_app_global_put).only(APP),
+    op(0x68, "app_local_del", 2, state::op_app_local_del).only(APP),
+    op(0x69, "app_global_del", 2, state::op_app_global_del).only(APP),
+    op(0x70, "asset_holding_get", 2, state::op_asset_holding_get)
+        .imm(one(1))
+        .only(APP),
+    op(0x71, "asset_params_get", 2, state::op_asset_params_get)
+        .imm(one(1))
+        .only(APP),
+    op(0x72, "app_params_get", 5, state::op_app_params_get)
+        .imm(one(1))
+        .only(APP),
+    op(0x73, "acct_params_get", 6, state::op_acct_params_get)
+        .imm(one(1))
+        .only(APP),
+    op(0x78, "min_balance", 3, state::op_min_balance).only(APP),
+    op(0x80, "pushbytes", 3, stack::op_pushbytes).imm(Immediates::VarBytes),
+    op(0x81, "pushint", 3, stack::op_pushint).imm(Immediates::Varuint),
+    op(0x84, "ed25519verify_bare", 7, bytes::op_ed25519verify_bare).cost(1900),
+    op(0x88, "callsub", 4, stack::op_callsub).imm(Immediates::Branch),
+    op(0x89, "retsub", 4, stack::op_retsub),
+    op(0x90, "shl", 4, arith::op_shl),
+    op(0x91, "shr", 4, arith::op_shr),
+    op(0x92, "sqrt", 4, arith::op_sqrt).cost(4),
+    op(0x93, "bitlen", 4, arith::op_bitlen),
+    op(0x94, "exp", 4, arith::op_exp),
+    op(0x95, "expw", 4, arith::op_expw).cost(10),
+    op(0x96, "bsqrt", 6, arith::op_bsqrt).cost(40),
+    op(0x97, "divw", 6, arith::op_divw),
+    op(0x98, "sha3_256", 7, bytes::op_sha3_256).cost(130),
+    op(0xa0, "b+", 4, arith::op_bytes_plus).cost(10),
+    op(0xa1, "b-", 4, arith::op_bytes_minus).cost(10),
+    op(0xa2, "b/", 4, arith::op_bytes_div).cost(20),
+    op(0xa3, "b*", 4, arith::op_bytes_mul).cost(20),
+    op(0xa4, "b<", 4, arith::op_bytes_lt),
+    op(0xa5, "b>", 4, arith::op_bytes_gt),
+    op(0xa6, "b<=", 4, arith::op_bytes_le),
+    op(0xa7, "b>=", 4, arith::op_bytes_ge),
+    op(0xa8, "b==", 4, arith::op_bytes_eq),
+    op(0xa9, "b!=", 4, arith::op_bytes_neq),
+    op(0xaa, "b%", 4, arith::op_bytes_modulo).cost(20),
+    op(0xab, "b|", 4, arith::op_bytes_bit_or).cost(6),
+    op(0xac, "b&", 4, arith::op_bytes_bit_and).cost(6),
+    op(0xad, "b^", 4, arith::op_bytes_bit_xor).cost(6),
+    op(0xae, "b~", 4, arith::op_bytes_bit_not).cost(4),
+    op(0xaf, "bzero", 4, arith::op_bytes_zero),
+    op(0xb0, "log", 5, state::op_log).only(APP),
+    op(0xb1, "itxn_begin", 5, inner::op_itxn_begin).only(APP),
+    op(0xb2, "itxn_field", 5, inner::op_itxn_field)
+        .imm(one(1))
+        .only(APP),
+    op(0xb3, "itxn_submit", 5, inner::op_itxn_submit).only(APP),
+    op(0xb4, "itxn", 5, inner::op_itxn).imm(one(1)).only(APP),
+    op(0xb5, "itxna", 5, inner::op_itxna).imm(one(2)).only(APP),
+    op(0xb6, "itxn_next", 6, inner::op_itxn_next).only(APP),
+    op(0xb7, "gitxn", 6, inner::op_gitxn).imm(one(2)).only(APP),
+    op(0xb8, "gitxna", 6, inner::op_gitxna)
+        .imm(one(3))
+        .only(APP),
+    op(0xc0, "txnas", 5, fields::op_txnas).imm(one(1)),
+    op(0xc1, "gtxnas", 5, fields::op_gtxnas).imm(one(2)),
+    op(0xc2, "gtxnsas", 5, fields::op_gtxnsas).imm(one(1)),
+    op(0xc3, "args", 5, stack::op_args).only(SIG),
+    op(0xc4, "gloadss", 6, fields::op_gloadss).only(APP),
+    op(0xc5, "itxnas", 6, inner::op_itxnas)
+        .imm(one(1))
+        .only(APP),
+    op(0xc6, "gitxnas", 6, inner::op_gitxnas)
+        .imm(one(2))
+        .only(APP),
+    op(0xd1, "block", 7, state::op_block).imm(one(1)).only(APP),
+];
+
+type OpsByVersion = Vec<[Option<&'static OpSpec>; 256]>;
+
+/// Returns the spec of `opcode` in programs of `version`, if it exists.
+pub(super) fn op_spec(opcode: u8, version: u64) -> Option<&'static OpSpec> {
+    static OPS: OnceLock<OpsByVersion> = OnceLock::new();
+    let ops = OPS.get_or_init(|| {
+        (0..=LOGIC_VERSION)
+            .map(|v| {
+                let mut ops = [None; 256];
+                for spec in OP_SPECS.iter().filter(|spec| spec.version <= v) {
+                    ops[spec.opcode as usize] = Some(spec);
+                }
+                ops
+            })
+            .collect()
+    });
+    ops.get(version as usize)?[opcode as usize]
+}
@@ -0,0 +1,283 @@
+//! Constants, stack and scratch space manipulation, and flow control.
+
+use super::eval::{branch_target, read_varuint, EvalContext, OpResult};
+use super::SCRATCH_SIZE;
+
+pub(super) fn op_err(_cx: &mut EvalContext) -> OpResult {
+    Err("err opcode executed".into())
+}
+
+pub(super) fn op_intcblock(cx: &mut EvalContext) -> OpResult {
+    let mut pos = cx.pc + 1;
+    let (count, n) = read_varuint(cx.program, pos)?;
+    pos += n;
+    let mut intc = Vec::with_capacity(count.min(256) as usize);
+    for _ in 0..count {
+        let (value, n) = read_varuint(cx.program, pos)?;
+        intc.push(value);
+        pos += n;
+    }
+    cx.intc = intc;
+    Ok(())
+}
+
+fn intc(cx: &mut EvalContext, i: usize) -> OpResult {
+    let value = *cx
+        .intc
+        .get(i)
+        .ok_or_else(|| format!("intc {} beyond {} constants", i, cx.intc.len()))?;
+    cx.push_uint(value);
+    Ok(())
+}
+
+pub(super) fn op_intc(cx: &mut EvalContext) -> OpResult {
+    intc(cx, cx.imm(0) as usize)
+}
+
+pub(super) fn op_intc_0(cx: &mut EvalContext) -> OpResult {
+    intc(cx, 0)
+}
+
+pub(super) fn op_intc_1(cx: &mut EvalContext) -> OpResult {
+    intc(cx, 1)
+}
+
+pub(super) fn op_intc_2(cx: &mut EvalContext) -> OpResult {
+    intc(cx, 2)
+}
+
+pub(super) fn op_intc_3(cx: &mut EvalContext) -> OpResult {
+    intc(cx, 3)
+}
+
+pub(super) fn op_bytecblock(cx: &mut EvalContext) -> OpResult {
+    let mut pos = cx.pc + 1;
+    let (count, n) = read_varuint(cx.program, pos)?;
+    pos += n;
+    let mut bytec = Vec::with_capacity(count.min(256) as usize);
+    for _ in 0..count {
+        let (len, n) = read_varuint(cx.program, pos)?;
+        pos += n;
+        let end = pos
+            .checked_add(len as usize)
+            .filter(|&end| end <= cx.program.len())
+            .ok_or("bytecblock ran past end of program")?;
+        bytec.push(cx.program[pos..end].to_vec());
+        pos = end;
+    }
+    cx.bytec = bytec;
+    Ok(())
+}
+
+fn bytec(cx: &mut EvalContext, i: usize) -> OpResult {
+    let value = cx
+        .bytec
+        .get(i)
+        .ok_or_else(|| format!("bytec {} beyond {} constants", i, cx.bytec.len()))?
+        .clone();
+    cx.push_bytes(value)
+}
+
+pub(super) fn op_bytec(cx: &mut EvalContext) -> OpResult {
+    bytec(cx, cx.imm(0) as usize)
+}
+
+pub(super) fn op_bytec_0(cx: &mut EvalContext) -> OpResult {
+    bytec(cx, 0)
+}
+
+pub(super) fn op_bytec_1(cx: &mut EvalContext) -> OpResult {
+    bytec(cx, 1)
+}
+
+pub(super) fn op_bytec_2(cx: &mut EvalContext) -> OpResult {
+    bytec(cx, 2)
+}
+
+pub(super) fn op_bytec_3(cx: &mut EvalContext) -> OpResult {
+    bytec(cx, 3)
+}
+
+pub(super) fn op_pushint(cx: &mut EvalContext) -> OpResult {
+    let (value, _) = read_varuint(cx.program, cx.pc + 1)?;
+    cx.push_uint(value);
+    Ok(())
+}
+
+pub(super) fn op_pushbytes(cx: &mut EvalContext) -> OpResult {
+    let (len, n) = read_varuint(cx.program, cx.pc + 1)?;
+    let start = cx.pc + 1 + n;
+    cx.push_bytes(cx.program[start..start + len as usize].to_vec())
+}
+
+fn arg(cx: &mut EvalContext, i: usize) -> OpResult {
+    let value = cx
+        .args
+        .get(i)
+        .ok_or_else(|| format!("cannot load arg[{}] of {}", i, cx.args.len()))?
+        .clone();
+    cx.push_bytes(value)
+}
+
+pub(super) fn op_arg(cx: &mut EvalContext) -> OpResult {
+    arg(cx, cx.imm(0) as usize)
+}
+
+pub(super) fn op_arg_0(cx: &mut EvalContext) -> OpResult {
+    arg(cx, 0)
+}
+
+pub(super) fn op_arg_1(cx: &mut EvalContext) -> OpResult {
+    arg(cx, 1)
+}
+
+pub(super) fn op_arg_2(cx: &mut EvalContext) -> OpResult {
+    arg(cx, 2)
+}
+
+pub(super) fn op_arg_3(cx: &mut EvalContext) -> OpResult {
+    arg(cx, 3)
+}
+
+pub(super) fn op_args(cx: &mut EvalContext) -> OpResult {
+    let i = cx.pop_uint()?;
+    arg(cx, usize::try_from(i).unwrap_or(usize::MAX))
+}
+
+fn scratch_slot(i: u64) -> OpResult<usize> {
+    if i >= SCRATCH_SIZE as u64 {
+        return Err(format!("invalid Scratch index {}", i));
+    }
+    Ok(i as usize)
+}
+
+pub(super) fn op_load(cx: &mut EvalContext) -> OpResult {
+    let value = cx.scratch[cx.imm(0) as usize].clone();
+    cx.push(value);
+    Ok(())
+}
+
+pub(super) fn op_store(cx: &mut EvalContext) -> OpResult {
+    let value = cx.pop()?;
+    let slot = cx.imm(0) as usize;
+    cx.scratch[slot] = value;
+    Ok(())
+}
+
+pub(super) fn op_loads(cx: &mut EvalContext) -> OpResult {
+    let slot = scratch_slot(cx.pop_uint()?)?;
+    let value = cx.scratch[slot].clone();
+    cx.push(value);
+    Ok(())
+}
+
+pub(super) fn op_stores(cx: &mut EvalContext) -> OpResult {
+    let value = cx.pop()?;
+    let slot = scratch_slot(cx.pop_uint()?)?;
+    cx.scratch[slot] = value;
+    Ok(())
+}
+
+pub(super) fn op_bnz(cx: &mut EvalContext) -> OpResult {
+    if cx.pop_uint()? != 0 {
+        cx.next_pc = branch_target(cx.program, cx.pc, cx.version)?;
+    }
+    Ok(())
+}
+
+pub(super) fn op_bz(cx: &mut EvalContext) -> OpResult {
+    if cx.pop_uint()? == 0 {
+        cx.next_pc = branch_target(cx.program, cx.pc, cx.version)?;
+    }
+    Ok(())
+}
+
+pub(super) fn op_b(cx: &mut EvalContext) -> OpResult {
+    cx.next_pc = branch_target(cx.program, cx.pc, cx.version)?;
+    Ok(())
+}
+
+pub(super) fn op_callsub(cx: &mut EvalContext) -> OpResult {
+    cx.call_stack.push(cx.next_pc);
+    cx.next_pc = branch_target(cx.program, cx.pc, cx.version)?;
+    Ok(())
+}
+
+pub(super) fn op_retsub(cx: &mut EvalContext) -> OpResult {
+    cx.next_pc = cx.call_stack.pop().ok_or("retsub with empty callstack")?;
+    Ok(())
+}
+
+pub(super) fn op_return(cx: &mut EvalContext) -> OpResult {
+    let value = cx.pop()?;
+    cx.stack = vec![value];
+    cx.next_pc = cx.program.len();
+    Ok(())
+}
+
+pub(super) fn op_assert(cx: &mut EvalContext) -> OpResult {
+    if cx.pop_uint()? == 0 {
+        return Err("assert failed".into());
+    }
+    Ok(())
+}
+
+pub(super) fn op_pop(cx: &mut EvalContext) -> OpResult {
+    cx.pop().map(|_| ())
+}
+
+/// Returns the index of the value `depth` positions below the top.
+fn depth_index(cx: &EvalContext, depth: usize) -> OpResult<usize> {
+    cx.stack
+        .len()
+        .checked_sub(depth + 1)
+        .ok_or_else(|| format!("stack too short for depth {}", depth))
+}
+
+pub(super) fn op_dup(cx: &mut EvalContext) -> OpResult {
+    let value = cx.stack[depth_index(cx, 0)?].clone();
+    cx.push(value);
+    Ok(())
+}
+
+pub(super) fn op_dup2(cx: &mut EvalContext) -> OpResult {
+    let i = depth_index(cx, 1)?;
+    let (a, b) = (cx.stack[i].clone(), cx.stack[i + 1].clone());
+    cx.push(a);
+    cx.push(b);
+    Ok(())
+}
+
+pub(super) fn op_dig(cx: &mut EvalContext) -> OpResult {
+    let value = cx.stack[depth_index(cx, cx.imm(0) as usize)?].clone();
+    cx.push(value);
+    Ok(())
+}
+
+pub(super) fn op_swap(cx: &mut EvalContext) -> OpResult {
+    let i = depth_index(cx, 1)?;
+    cx.stack.swap(i, i + 1);
+    Ok(())
+}
+
+pub(super) fn op_select(cx: &mut EvalContext) -> OpResult {
+    let c = cx.pop_uint()?;
+    let b = cx.pop()?;
+    let a = cx.pop()?;
+    cx.push(if c != 0 { b } else { a });
+    Ok(())
+}
+
+pub(super) fn op_cover(cx: &mut EvalContext) -> OpResult {
+    let i = depth_index(cx, cx.imm(0) as usize)?;
+    let top = cx.pop()?;
+    cx.stack.insert(i, top);
+    Ok(())
+}
+
+pub(super) fn op_uncover(cx: &mut EvalContext) -> OpResult {
+    let i = depth_index(cx, cx.imm(0) as usize)?;
+    let value = cx.stack.remove(i);
+    cx.push(value);
+    Ok(())
+}
@@ -0,0 +1,464 @@
+//! Opcodes that read and write the ledger: balances, application state,
+//! logs and block headers.
+
+use super::eval::{EvalContext, LogicResult, OpResult, StackValue};
+use super::fields::{AcctParamsField, AppParamsField, AssetHoldingField, AssetParamsField};
+use super::{MAX_LOG_CALLS, MAX_LOG_SIZE};
+use crate::basics::{
+    application_address, Address, AppIndex, AppParams, AssetHolding, AssetIndex, AssetParams,
+    MicroAlgos, Round, TealValue,
+};
+use crate::bookkeeping::block::BlockHeader;
+use crate::transactions::signedtxn::SignedTxnWithAD;
+
+/// LedgerForLogic is the view of the ledger available to application
+/// programs. Writes are expected to be buffered by the implementation and
+/// discarded if the program fails.
+pub trait LedgerForLogic {
+    /// The round of the block being evaluated.
+    fn round(&self) -> Round;
+    /// The timestamp of the previous block.
+    fn latest_timestamp(&self) -> i64;
+    fn block_header(&self, round: Round) -> Option<BlockHeader>;
+
+    fn balance(&self, addr: &Address) -> MicroAlgos;
+    fn min_balance(&self, addr: &Address) -> MicroAlgos;
+    /// The address the account `addr` is rekeyed to, or zero.
+    fn auth_addr(&self, addr: &Address) -> Address;
+
+    /// Returns the holding of `asset` in the account `addr`.
+    fn asset_holding(&self, addr: &Address, asset: AssetIndex) -> Option<AssetHolding>;
+    /// Returns the parameters and the creator of asset `asset`.
+    fn asset_params(&self, asset: AssetIndex) -> Option<(AssetParams, Address)>;
+
+    /// Returns the parameters and the creator of application `app`.
+    fn app_params(&self, app: AppIndex) -> Option<(AppParams, Address)>;
+    fn opted_in(&self, addr: &Address, app: AppIndex) -> bool;
+
+    fn get_local(&self, addr: &Address, app: AppIndex, key: &[u8]) -> Option<TealValue>;
+    fn set_local(
+        &mut self,
+        addr: &Address,
+        app: AppIndex,
+        key: &[u8],
+        value: TealValue,
+    ) -> LogicResult<()>;
+    fn del_local(&mut self, addr: &Address, app: AppIndex, key: &[u8]) -> LogicResult<()>;
+
+    fn get_global(&self, app: AppIndex, key: &[u8]) -> Option<TealValue>;
+    fn set_global(&mut self, app: AppIndex, key: &[u8], value: TealValue) -> LogicResult<()>;
+    fn del_global(&mut self, app: AppIndex, key: &[u8]) -> LogicResult<()>;
+
+    /// Executes an inner transaction group submitted by the program,
+    /// filling in the ApplyData of each transaction.
+    fn perform(&mut self, group: &mut [SignedTxnWithAD]) -> LogicResult<()>;
+}
+
+/// The first version referring to accounts and applications directly, by
+/// address and id, rather than by index into the transaction's arrays.
+const DIRECT_REF_VERSION: u64 = 4;
+
+/// The first version allowing the addresses of foreign applications.
+const APP_ADDRESS_REF_VERSION: u64 = 7;
+
+impl EvalContext<'_> {
+    /// Resolves an account reference: an index into the Accounts array or,
+    /// from v4, an address available to the transaction.
+    pub(super) fn account_ref(&self, value: StackValue) -> OpResult<Address> {
+        let txn = &self.txn().signed_txn.txn;
+        let app = &txn.application_call_txn_fields;
+        match value {
+            StackValue::Uint(0) => Ok(txn.sender()),
+            StackValue::Uint(i) => app
+                .accounts
+                .get(usize::try_from(i - 1).unwrap_or(usize::MAX))
+                .copied()
+                .ok_or_else(|| format!("invalid Account reference {}", i)),
+            StackValue::Bytes(b) if self.version >= DIRECT_REF_VERSION => {
+                let bytes: [u8; 32] = b
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| format!("invalid address of {} bytes", b.len()))?;
+                let addr = Address::from(bytes);
+                let available = addr == txn.sender()
+                    || app.accounts.contains(&addr)
+                    || addr == application_address(self.app_id)
+                    || (self.version >= APP_ADDRESS_REF_VERSION
+                        && app
+                            .foreign_apps
+                            .iter()
+                            .any(|&id| addr == application_address(id)));
+                if !available {
+                    return Err(format!("invalid Account reference {}", addr.string()));
+                }
+                Ok(addr)
+            }
+            StackValue::Bytes(_) => Err("expected uint64 but got []byte".into()),
+        }
+    }
+
+    /// Resolves an application reference: an index into the Applications
+    /// array or, from v4, an application id available to the transaction.
+    pub(super) fn app_ref(&self, value: u64) -> OpResult<AppIndex> {
+        let foreign_apps = &self
+            .txn()
+            .signed_txn
+            .txn
+            .application_call_txn_fields
+            .foreign_apps;
+        if value == 0 {
+            return Ok(self.app_id);
+        }
+        if self.version >= DIRECT_REF_VERSION
+            && (value == self.app_id || foreign_apps.contains(&value))
+        {
+            return Ok(value);
+        }
+        foreign_apps
+            .get(usize::try_from(value - 1).unwrap_or(usize::MAX))
+            .copied()
+            .ok_or_else(|| format!("invalid App reference {}", value))
+    }
+
+    /// Resolves an asset reference: an index into the Assets array or,
+    /// from v4, an asset id available to the transaction. Before v4, only
+    /// `foreign` references are indices.
+    pub(super) fn asset_ref(&self, value: u64, foreign: bool) -> OpResult<AssetIndex> {
+        let foreign_assets = &self
+            .txn()
+            .signed_txn
+            .txn
+            .application_call_txn_fields
+            .foreign_assets;
+        let indexed = foreign_assets
+            .get(usize::try_from(value).unwrap_or(usize::MAX))
+            .copied();
+        if self.version >= DIRECT_REF_VERSION {
+            return indexed
+                .or_else(|| foreign_assets.contains(&value).then_some(value))
+                .ok_or_else(|| format!("invalid Asset reference {}", value));
+        }
+        if foreign {
+            return indexed.ok_or_else(|| {
+                format!(
+                    "Asset index {} beyond length of Assets array {}",
+                    value,
+                    foreign_assets.len()
+                )
+            });
+        }
+        Ok(value)
+    }
+
+    fn check_key_value(&self, key: &[u8], value: &StackValue) -> OpResult {
+        let proto = self.params.proto;
+        if key.len() > proto.max_app_key_len.max(0) as usize {
+            return Err(format!(
+                "key too long: length was {}, maximum is {}",
+                key.len(),
+                proto.max_app_key_len
+            ));
+        }
+        if let StackValue::Bytes(b) = value {
+            if b.len() > proto.max_app_bytes_value_len.max(0) as usize {
+                return Err(format!(
+                    "value too long for key 0x{}: length was {}",
+                    hex::encode(key),
+                    b.len()
+                ));
+            }
+            let sum = key.len() + b.len();
+            if sum > proto.max_app_sum_key_value_lens.max(0) as usize {
+                return Err(format!(
+                    "key/value total too long for key 0x{}: sum was {}",
+                    hex::encode(key),
+                    sum
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn push_state(&mut self, value: Option<TealValue>, with_exists: bool) {
+        let exists = value.is_some();
+        self.push(value.as_ref().map(StackValue::from).unwrap_or_default());
+        if with_exists {
+            self.push_bool(exists);
+        }
+    }
+}
+
+pub(super) fn op_balance(cx: &mut EvalContext) -> OpResult {
+    let account = cx.pop()?;
+    let addr = cx.account_ref(account)?;
+    let balance = cx.ledger()?.balance(&addr);
+    cx.push_uint(balance.0);
+    Ok(())
+}
+
+pub(super) fn op_min_balance(cx: &mut EvalContext) -> OpResult {
+    let account = cx.pop()?;
+    let addr = cx.account_ref(account)?;
+    let min_balance = cx.ledger()?.min_balance(&addr);
+    cx.push_uint(min_balance.0);
+    Ok(())
+}
+
+pub(super) fn op_app_opted_in(cx: &mut EvalContext) -> OpResult {
+    let app = cx.pop_uint()?;
+    let account = cx.pop()?;
+    let addr = cx.account_ref(account)?;
+    let app = cx.app_ref(app)?;
+    let opted_in = cx.ledger()?.opted_in(&addr, app);
+    cx.push_bool(opted_in);
+    Ok(())
+}
+
+pub(super) fn op_app_local_get(cx: &mut EvalContext) -> OpResult {
+    let key = cx.pop_bytes()?;
+    let account = cx.pop()?;
+    let addr = cx.account_ref(account)?;
+    let app = cx.app_id;
+    let value = cx.ledger()?.get_local(&addr, app, &key);
+    cx.push_state(value, false);
+    Ok(())
+}
+
+pub(super) fn op_app_local_get_ex(cx: &mut EvalContext) -> OpResult {
+    let key = cx.pop_bytes()?;
+    let app = cx.pop_uint()?;
+    let account = cx.pop()?;
+    let addr = cx.account_ref(account)?;
+    let app = cx.app_ref(app)?;
+    let value = cx.ledger()?.get_local(&addr, app, &key);
+    cx.push_state(value, true);
+    Ok(())
+}
+
+pub(super) fn op_app_global_get(cx: &mut EvalContext) -> OpResult {
+    let key = cx.pop_bytes()?;
+    let app = cx.app_id;
+    let value = cx.ledger()?.get_global(app, &key);
+    cx.push_state(value, false);
+    Ok(())
+}
+
+pub(super) fn op_app_global_get_ex(cx: &mut EvalContext) -> OpResult {
+    let key = cx.pop_bytes()?;
+    let app = cx.pop_uint()?;
+    let app = cx.app_ref(app)?;
+    let value = cx.ledger()?.get_global(app, &key);
+    cx.push_state(value, true);
+    Ok(())
+}
+
+pub(super) fn op_app_local_put(cx: &mut EvalContext) -> OpResult {
+    let value = cx.pop()?;
+    let key = cx.pop_bytes()?;
+    let account = cx.pop()?;
+    let addr = cx.account_ref(account)?;
+    cx.check_key_value(&key, &value)?;
+    let app = cx.app_id;
+    let ledger = cx.ledger()?;
+    if !ledger.opted_in(&addr, app) {
+        return Err(format!("{} has not opted in to app {}", addr.string(), app));
+    }
+    ledger
+        .set_local(&addr, app, &key, value.to_teal_value())
+        .map_err(|e| e.to_string())
+}
+
+pub(super) fn op_app_global_put(cx: &mut EvalContext) -> OpResult {
+    let value = cx.pop()?;
+    let key = cx.pop_bytes()?;
+    cx.check_key_value(&key, &value)?;
+    let app = cx.app_id;
+    cx.ledger()?
+        .set_global(app, &key, value.to_teal_value())
+        .map_err(|e| e.to_string())
+}
+
+pub(super) fn op_app_local_del(cx: &mut EvalContext) -> OpResult {
+    let key = cx.pop_bytes()?;
+    let account = cx.pop()?;
+    let addr = cx.account_ref(account)?;
+    let app = cx.app_id;
+    let ledger = cx.ledger()?;
+    if !ledger.opted_in(&addr, app) {
+        return Err(format!("{} has not opted in to app {}", addr.string(), app));
+    }
+    ledger
+        .del_local(&addr, app, &key)
+        .map_err(|e| e.to_string())
+}
+
+pub(super) fn op_app_global_del(cx: &mut EvalContext) -> OpResult {
+    let key = cx.pop_bytes()?;
+    let app = cx.app_id;
+    cx.ledger()?
+        .del_global(app, &key)
+        .map_err(|e| e.to_string())
+}
+
+pub(super) fn op_asset_holding_get(cx: &mut EvalContext) -> OpResult {
+    let field = AssetHoldingField::from_u8(cx.imm(0))
+        .filter(|field| field.version() <= cx.version)
+        .ok_or_else(|| format!("invalid asset_holding_get field {}", cx.imm(0)))?;
+    let asset = cx.pop_uint()?;
+    let account = cx.pop()?;
+    let addr = cx.account_ref(account)?;
+    let asset = cx.asset_ref(asset, false)?;
+    let Some(holding) = cx.ledger()?.asset_holding(&addr, asset) else {
+        cx.push_uint(0);
+        cx.push_bool(false);
+        return Ok(());
+    };
+    match field {
+        AssetHoldingField::AssetBalance => cx.push_uint(holding.amount),
+        AssetHoldingField::AssetFrozen => cx.push_bool(holding.frozen),
+    }
+    cx.push_bool(true);
+    Ok(())
+}
+
+pub(super) fn op_asset_params_get(cx: &mut EvalContext) -> OpResult {
+    let field = AssetParamsField::from_u8(cx.imm(0))
+        .filter(|field| field.version() <= cx.version)
+        .ok_or_else(|| format!("invalid asset_params_get field {}", cx.imm(0)))?;
+    let asset = cx.pop_uint()?;
+    let asset = cx.asset_ref(asset, true)?;
+    let Some((params, creator)) = cx.ledger()?.asset_params(asset) else {
+        cx.push_uint(0);
+        cx.push_bool(false);
+        return Ok(());
+    };
+    let address = |addr: Address| StackValue::Bytes(addr.as_bytes().to_vec());
+    let value = match field {
+        AssetParamsField::AssetTotal => StackValue::Uint(params.total),
+        AssetParamsField::AssetDecimals => StackValue::Uint(params.decimals as u64),
+        AssetParamsField::AssetDefaultFrozen => StackValue::Uint(params.default_frozen as u64),
+        AssetParamsField::AssetUnitName => StackValue::Bytes(params.unit_name.into_bytes()),
+        AssetParamsField::AssetName => StackValue::Bytes(params.asset_name.into_bytes()),
+        AssetParamsField::AssetURL => StackValue::Bytes(params.url.into_bytes()),
+        AssetParamsField::AssetMetadataHash => StackValue::Bytes(params.metadata_hash.to_vec()),
+        AssetParamsField::AssetManager => address(params.manager),
+        AssetParamsField::AssetReserve => address(params.reserve),
+        AssetParamsField::AssetFreeze => address(params.freeze),
+        AssetParamsField::AssetClawback => address(params.clawback),
+        AssetParamsField::AssetCreator => address(creator),
+    };
+    cx.push(value);
+    cx.push_bool(true);
+    Ok(())
+}
+
+pub(super) fn op_app_params_get(cx: &mut EvalContext) -> OpResult {
+    let field = AppParamsField::from_u8(cx.imm(0))
+        .filter(|field| field.version() <= cx.version)
+        .ok_or_else(|| format!("invalid app_params_get field {}", cx.imm(0)))?;
+    let app = cx.pop_uint()?;
+    let app = cx.app_ref(app)?;
+    let Some((params, creator)) = cx.ledger()?.app_params(app) else {
+        cx.push_uint(0);
+        cx.push_bool(false);
+        return Ok(());
+    };
+    let schemas = &params.state_schemas;
+    let value = match field {
+        AppParamsField::AppApprovalProgram => StackValue::Bytes(params.approval_program),
+        AppParamsField::AppClearStateProgram => StackValue::Bytes(params.clear_state_program),
+        AppParamsField::AppGlobalNumUint => StackValue::Uint(schemas.global_state_schema.num_uint),
+        AppParamsField::AppGlobalNumByteSlice => {
+            StackValue::Uint(schemas.global_state_schema.num_byte_slice)
+        }
+        AppParamsField::AppLocalNumUint => StackValue::Uint(schemas.local_state_schema.num_uint),
+        AppParamsField::AppLocalNumByteSlice => {
+            StackValue::Uint(schemas.local_state_schema.num_byte_slice)
+        }
+        AppParamsField::AppExtraProgramPages => StackValue::Uint(params.extra_program_pages as u64),
+        AppParamsField::AppCreator => StackValue::Bytes(creator.as_bytes().to_vec()),
+        AppParamsField::AppAddress => {
+            StackValue::Bytes(application_address(app).as_bytes().to_vec())
+        }
+    };
+    cx.push(value);
+    cx.push_bool(true);
+    Ok(())
+}
+
+pub(super) fn op_acct_params_get(cx: &mut EvalContext) -> OpResult {
+    let field = AcctParamsField::from_u8(cx.imm(0))
+        .filter(|field| field.version() <= cx.version)
+        .ok_or_else(|| format!("invalid acct_params_get field {}", cx.imm(0)))?;
+    let account = cx.pop()?;
+    let addr = cx.account_ref(account)?;
+    let ledger = cx.ledger()?;
+    let balance = ledger.balance(&addr);
+    let value = match field {
+        AcctParamsField::AcctBalance => StackValue::Uint(balance.0),
+        AcctParamsField::AcctMinBalance => StackValue::Uint(ledger.min_balance(&addr).0),
+        AcctParamsField::AcctAuthAddr => {
+            StackValue::Bytes(ledger.auth_addr(&addr).as_bytes().to_vec())
+        }
+    };
+    cx.push(value);
+    cx.push_bool(balance.0 > 0);
+    Ok(())
+}
+
+pub(super) fn op_log(cx: &mut EvalContext) -> OpResult {
+    let message = cx.pop_bytes()?;
+    if cx.logs.len() >= MAX_LOG_CALLS {
+        return Err(format!(
+            "too many log calls in program. up to {} is allowed",
+            MAX_LOG_CALLS
+        ));
+    }
+    let size: usize = cx.logs.iter().map(Vec::len).sum::<usize>() + message.len();
+    if size > MAX_LOG_SIZE {
+        return Err(format!(
+            "program logs too large. {} bytes > {} bytes limit",
+            size, MAX_LOG_SIZE
+        ));
+    }
+    cx.logs.push(message);
+    Ok(())
+}
+
+pub(super) fn op_block(cx: &mut EvalContext) -> OpResult {
+    let field = cx.imm(0);
+    if field > 1 {
+        return Err(format!("invalid block field {}", field));
+    }
+    let round = cx.pop_uint()?;
+
+    // Only rounds that are certainly committed while the transaction is
+    // valid, and no older than the transaction lifetime, are available.
+    let header = &cx.txn().signed_txn.txn.header;
+    let mut first_avail = header
+        .last_valid
+        .wrapping_sub(cx.params.proto.max_txn_life)
+        .wrapping_sub(1);
+    if first_avail > header.last_valid || first_avail == 0 {
+        first_avail = 1;
+    }
+    let last_avail = header.first_valid.saturating_sub(1);
+    if round < first_avail || round > last_avail {
+        return Err(format!(
+            "round {} is not available. It's outside [{}-{}]",
+            round, first_avail, last_avail
+        ));
+    }
+
+    let header = cx
+        .ledger()?
+        .block_header(round)
+        .ok_or_else(|| format!("no block header for round {}", round))?;
+    match field {
+        0 => cx.push_bytes(header.seed.to_vec()),
+        _ => {
+            cx.push_uint(header.timestamp as u64);
+            Ok(())
+        }
+    }
+}
@@ -1,3 +1,11 @@
-pub mod transaction; 
-pub mod signedtxn;
+pub mod application;
+pub mod asset;
+pub mod keyreg;
+pub mod logic;
+pub mod payment;
 pub mod payset;
+pub mod signedtxn;
+pub mod teal;
+pub mod transaction;
+pub mod verified_cache;
+pub mod verify;
@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+use crate::basics::{Address, MicroAlgos};
+
+/// PaymentTxnFields captures the fields used by payment transactions.
+#[skip_serializing_default]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PaymentTxnFields {
+    #[serde(rename = "rcv")]
+    pub receiver: Address,
+    #[serde(rename = "amt")]
+    pub amount: MicroAlgos,
+    /// When close_remainder_to is set, it indicates that the transaction is
+    /// requesting that the account should be closed, and all remaining
+    /// funds be transferred to this address.
+    #[serde(rename = "close")]
+    pub close_remainder_to: Address,
+}
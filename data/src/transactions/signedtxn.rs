@@ -1,5 +1,10 @@
 use crate::basics;
-use crypto::{curve25519::Signature, logicsig::LogicSig, multisig::MultiSig};
+use crypto::{
+    curve25519::Signature,
+    logicsig::LogicSig,
+    multisig::MultiSig,
+    util::{HashDigest, MsgpHashable},
+};
 use serde::{Deserialize, Serialize};
 
 use super::transaction::{ApplyData, Transaction};
@@ -17,18 +22,42 @@ impl Default for SignatureType {
     }
 }
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+/// SignedTxn wraps a transaction and its authorization: exactly one of a
+/// signature, a multisignature or a logic signature.
+#[skip_serializing_default]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SignedTxn {
+    #[serde(rename = "sig")]
     pub sig: Signature,
+    #[serde(rename = "msig")]
     pub msig: MultiSig,
+    #[serde(rename = "lsig")]
     pub lsig: LogicSig,
+    #[serde(rename = "txn")]
     pub txn: Transaction,
+    #[serde(rename = "sgnr")]
     pub auth_addr: basics::Address,
 }
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+impl SignedTxn {
+    /// The address whose key authorized the transaction: `auth_addr` if
+    /// set, the sender otherwise.
+    pub fn authorizer(&self) -> basics::Address {
+        if self.auth_addr.is_zero() {
+            self.txn.sender()
+        } else {
+            self.auth_addr
+        }
+    }
+}
+
+/// SignedTxnWithAD is a SignedTxn with the ApplyData of its evaluation.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SignedTxnWithAD {
+    #[serde(flatten)]
     pub signed_txn: SignedTxn,
+    #[serde(flatten)]
     pub apply_data: ApplyData,
 }
 
@@ -38,3 +67,15 @@ pub struct SignedTxnInBlock {
     pub has_genesis_id: bool,
     pub has_genesis_hash: bool,
 }
+
+impl MsgpHashable for SignedTxnInBlock {
+    fn hash_id(&self) -> protocol::HashId {
+        protocol::SIGNED_TXN_IN_BLOCK
+    }
+}
+
+impl SignedTxnInBlock {
+    pub fn hash(&self) -> HashDigest {
+        crypto::util::hash_obj(self)
+    }
+}
@@ -0,0 +1,25 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::signedtxn::SignedTxnWithAD;
+use crate::basics::StateDelta;
+
+/// EvalDelta stores StateDeltas for an application's global key/value
+/// store, as well as StateDeltas for some number of accounts holding local
+/// state for that application.
+#[skip_serializing_default]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvalDelta {
+    #[serde(rename = "gd")]
+    pub global_delta: StateDelta,
+    /// The local deltas, keyed by the index of the account in the
+    /// transaction's Accounts array, where 0 is the sender.
+    #[serde(rename = "ld")]
+    pub local_deltas: BTreeMap<u64, StateDelta>,
+    #[serde(rename = "lg")]
+    pub logs: Vec<Vec<u8>>,
+    #[serde(rename = "itx")]
+    pub inner_txns: Vec<SignedTxnWithAD>,
+}
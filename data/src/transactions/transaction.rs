@@ -1,5 +1,239 @@
+use config::consensus::ConsensusParams;
+use crypto::util::{HashDigest, HashType, MsgpHashable};
+use protocol::TxType;
 use serde::{Deserialize, Serialize};
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
-pub struct Transaction {}
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
-pub struct ApplyData {}
+
+use super::application::ApplicationCallTxnFields;
+use super::asset::{AssetConfigTxnFields, AssetFreezeTxnFields, AssetTransferTxnFields};
+use super::keyreg::KeyregTxnFields;
+use super::payment::PaymentTxnFields;
+use super::teal::EvalDelta;
+use crate::basics::{Address, AppIndex, AssetIndex, MicroAlgos, Round};
+
+/// Txid is a hash used to uniquely identify individual transactions.
+pub type Txid = HashDigest;
+
+/// Formats a Txid the way transactions are identified to users: base32,
+/// without padding.
+pub fn txid_string(txid: &Txid) -> String {
+    base32::encode(base32::Alphabet::RFC4648 { padding: false }, &txid.0)
+}
+
+pub type TxnResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// Header captures the fields common to every transaction type.
+#[skip_serializing_default]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Header {
+    #[serde(rename = "snd")]
+    pub sender: Address,
+    #[serde(rename = "fee")]
+    pub fee: MicroAlgos,
+    #[serde(rename = "fv")]
+    pub first_valid: Round,
+    #[serde(rename = "lv")]
+    pub last_valid: Round,
+    #[serde(rename = "note")]
+    pub note: Vec<u8>,
+    #[serde(rename = "gen")]
+    pub genesis_id: String,
+    #[serde(rename = "gh")]
+    pub genesis_hash: HashDigest,
+    /// Group specifies that this transaction is part of a transaction
+    /// group (and, if so, specifies the hash of the group).
+    #[serde(rename = "grp")]
+    pub group: HashDigest,
+    /// Lease enforces mutual exclusion: once this transaction is confirmed,
+    /// no other transaction with the same sender and lease can be confirmed
+    /// until `last_valid`.
+    #[serde(rename = "lx")]
+    pub lease: [u8; 32],
+    /// RekeyTo, if nonzero, sets the sender's auth address to this
+    /// address: from then on, transactions of the sender must be
+    /// authorized by it.
+    #[serde(rename = "rekey")]
+    pub rekey_to: Address,
+}
+
+/// Transaction describes a transaction that can appear in a block.
+#[skip_serializing_default]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transaction {
+    #[serde(rename = "type")]
+    pub tx_type: TxType,
+    #[serde(flatten)]
+    pub header: Header,
+    #[serde(flatten)]
+    pub keyreg_txn_fields: KeyregTxnFields,
+    #[serde(flatten)]
+    pub payment_txn_fields: PaymentTxnFields,
+    #[serde(flatten)]
+    pub asset_config_txn_fields: AssetConfigTxnFields,
+    #[serde(flatten)]
+    pub asset_transfer_txn_fields: AssetTransferTxnFields,
+    #[serde(flatten)]
+    pub asset_freeze_txn_fields: AssetFreezeTxnFields,
+    #[serde(flatten)]
+    pub application_call_txn_fields: ApplicationCallTxnFields,
+}
+
+impl MsgpHashable for Transaction {
+    fn hash_id(&self) -> protocol::HashId {
+        protocol::TRANSACTION
+    }
+}
+
+impl Transaction {
+    /// Returns the Txid (i.e., hash) of the transaction.
+    pub fn id(&self) -> Txid {
+        crypto::util::hash_obj(self)
+    }
+
+    /// Returns the SHA-256 hash of the transaction, used by the SHA-256 txn
+    /// commitment.
+    pub fn id_sha256(&self) -> HashDigest {
+        HashType::Sha256.hash(&crypto::util::hash_rep(self))
+    }
+
+    pub fn sender(&self) -> Address {
+        self.header.sender
+    }
+
+    /// Whether the transaction may be included in the block of `round`.
+    pub fn alive(&self, round: Round) -> TxnResult<()> {
+        let header = &self.header;
+        if round < header.first_valid || round > header.last_valid {
+            return Err(format!(
+                "txn dead: round {} outside of {}--{}",
+                round, header.first_valid, header.last_valid
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Checks that the transaction looks valid on its own, without looking
+    /// at the ledger.
+    pub fn well_formed(&self, proto: &ConsensusParams) -> TxnResult<()> {
+        let header = &self.header;
+        if header.last_valid < header.first_valid {
+            return Err(format!(
+                "transaction invalid range ({}--{})",
+                header.first_valid, header.last_valid
+            )
+            .into());
+        }
+        if header.last_valid - header.first_valid > proto.max_txn_life {
+            return Err(format!(
+                "transaction window size excessive ({}--{})",
+                header.first_valid, header.last_valid
+            )
+            .into());
+        }
+        if !proto.enable_fee_pooling
+            && self.tx_type != TxType::CompactCertTx
+            && header.fee.0 < proto.min_txn_fee
+        {
+            return Err(format!(
+                "transaction had fee {}, which is less than the minimum {}",
+                header.fee.0, proto.min_txn_fee
+            )
+            .into());
+        }
+        if !proto.support_tx_groups && header.group != HashDigest::default() {
+            return Err("transaction groups not supported".into());
+        }
+        if !proto.support_rekeying && !header.rekey_to.is_zero() {
+            return Err("transaction tries to rekey, but rekeying is not supported".into());
+        }
+        if !proto.support_transaction_leases && header.lease != [0; 32] {
+            return Err(format!(
+                "transaction tried to acquire lease {} but protocol does not support transaction leases",
+                hex::encode(header.lease)
+            )
+            .into());
+        }
+
+        match self.tx_type {
+            TxType::PaymentTx => {
+                let close = self.payment_txn_fields.close_remainder_to;
+                if !close.is_zero() && close == self.header.sender {
+                    return Err("transaction cannot close account to its sender".into());
+                }
+                Ok(())
+            }
+            TxType::KeyRegistrationTx => self
+                .keyreg_txn_fields
+                .well_formed(proto, self.header.last_valid),
+            TxType::AssetConfigTx if proto.asset => self.asset_config_txn_fields.well_formed(proto),
+            TxType::AssetTransferTx | TxType::AssetFreezeTx if proto.asset => Ok(()),
+            TxType::AssetConfigTx | TxType::AssetTransferTx | TxType::AssetFreezeTx => {
+                Err("asset transaction not supported".into())
+            }
+            TxType::ApplicationCallTx => self.application_call_txn_fields.well_formed(proto),
+            other => Err(format!("unknown tx type {:?}", other).into()),
+        }
+    }
+}
+
+/// TxGroup describes a group of transactions that must appear together in
+/// a specific order in a block.
+#[skip_serializing_default]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TxGroup {
+    /// The ids of the transactions of the group, computed with their
+    /// `group` field zeroed.
+    #[serde(rename = "txlist")]
+    pub tx_group_hashes: Vec<HashDigest>,
+}
+
+impl MsgpHashable for TxGroup {
+    fn hash_id(&self) -> protocol::HashId {
+        protocol::TX_GROUP
+    }
+}
+
+/// Computes the group id of `txns`, to be stored in the `group` field of
+/// each of them.
+pub fn compute_group_id(txns: &[Transaction]) -> TxnResult<HashDigest> {
+    let max = config::consensus::max_tx_group_size();
+    if txns.len() > max {
+        return Err(format!("group size {} exceeds maximum {}", txns.len(), max).into());
+    }
+    let tx_group_hashes = txns
+        .iter()
+        .map(|txn| {
+            let mut txn = txn.clone();
+            txn.header.group = HashDigest::default();
+            txn.id()
+        })
+        .collect();
+    Ok(crypto::util::hash_obj(&TxGroup { tx_group_hashes }))
+}
+
+/// ApplyData contains information about the transaction's execution.
+#[skip_serializing_default]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApplyData {
+    /// The amount sent to `close_remainder_to` when closing the sender.
+    #[serde(rename = "ca")]
+    pub closing_amount: MicroAlgos,
+    /// The amount of the asset sent to `asset_close_to` when closing the
+    /// holding, recorded since `enable_asset_close_amount`.
+    #[serde(rename = "aca")]
+    pub asset_closing_amount: u64,
+    /// The state changes, logs and inner transactions of an application
+    /// call.
+    #[serde(rename = "dt")]
+    pub eval_delta: EvalDelta,
+    /// The id of the asset created by this transaction, if any.
+    #[serde(rename = "caid")]
+    pub config_asset: AssetIndex,
+    /// The id of the application created by this transaction, if any.
+    #[serde(rename = "apid")]
+    pub application_id: AppIndex,
+}
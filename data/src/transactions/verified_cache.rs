@@ -0,0 +1,143 @@
+//! The cache of transaction groups whose signatures were verified, so
+//! that a group seen in gossip, then remembered by the pool and finally
+//! found in a block, is only verified once.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use config::consensus::ConsensusParams;
+use crypto::util::HashDigest;
+use msgp::Marshaler;
+use protocol::ConsensusVersion;
+
+use super::signedtxn::SignedTxnWithAD;
+use super::verify::{self, GroupVerifier, VerifyResult};
+
+/// VerifiedTxnCache remembers the groups that passed `verify::txn_group`,
+/// by the hash of the signed transactions and the consensus version they
+/// were verified under. It holds up to `size` groups, forgetting the
+/// oldest half once full. The cache is shared between threads.
+pub struct VerifiedTxnCache {
+    size: usize,
+    groups: Mutex<Generations>,
+}
+
+/// The cached groups: new groups enter `current`, which replaces
+/// `previous` when it holds half the size of the cache.
+#[derive(Default)]
+struct Generations {
+    current: HashSet<(HashDigest, ConsensusVersion)>,
+    previous: HashSet<(HashDigest, ConsensusVersion)>,
+}
+
+impl VerifiedTxnCache {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            groups: Mutex::new(Generations::default()),
+        }
+    }
+
+    /// The number of cached groups.
+    pub fn len(&self) -> usize {
+        let groups = self.groups.lock().unwrap();
+        groups.current.len() + groups.previous.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the signatures of `group` were verified under `version`.
+    pub fn contains(&self, group: &[SignedTxnWithAD], version: &ConsensusVersion) -> bool {
+        let key = (group_hash(group), version.clone());
+        let groups = self.groups.lock().unwrap();
+        groups.current.contains(&key) || groups.previous.contains(&key)
+    }
+
+    /// Remembers that the signatures of `group` are valid under `version`.
+    pub fn add(&self, group: &[SignedTxnWithAD], version: &ConsensusVersion) {
+        if self.size == 0 {
+            return;
+        }
+        let key = (group_hash(group), version.clone());
+        let mut groups = self.groups.lock().unwrap();
+        if groups.previous.contains(&key) {
+            return;
+        }
+        if groups.current.len() >= self.size.div_ceil(2) {
+            groups.previous = std::mem::take(&mut groups.current);
+        }
+        groups.current.insert(key);
+    }
+
+    /// Verifies the signatures of `group` under `version`, whose parameters
+    /// are `proto`, unless they were verified before. The signatures are
+    /// checked by `verifier` when given, otherwise on the calling thread.
+    pub fn verify_group(
+        &self,
+        group: &[SignedTxnWithAD],
+        version: &ConsensusVersion,
+        proto: &ConsensusParams,
+        verifier: Option<&GroupVerifier>,
+    ) -> VerifyResult<()> {
+        if self.contains(group, version) {
+            return Ok(());
+        }
+        match verifier {
+            Some(verifier) => verifier.txn_group(group, proto)?,
+            None => verify::txn_group(group, proto)?,
+        }
+        self.add(group, version);
+        Ok(())
+    }
+}
+
+/// Hashes the signed transactions of `group`, without their ApplyData.
+fn group_hash(group: &[SignedTxnWithAD]) -> HashDigest {
+    let mut buffer = vec![];
+    for stxn in group {
+        stxn.signed_txn.marshal_msg(&mut buffer);
+    }
+    crypto::util::hash(&buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::basics::{Address, MicroAlgos};
+
+    fn group(fee: u64) -> Vec<SignedTxnWithAD> {
+        let mut stxn = SignedTxnWithAD::default();
+        stxn.signed_txn.txn.header.sender = Address::from([1; 32]);
+        stxn.signed_txn.txn.header.fee = MicroAlgos(fee);
+        vec![stxn]
+    }
+
+    #[test]
+    fn groups_are_cached_per_version() {
+        let v31: ConsensusVersion = protocol::CONSENSUS_V31.into();
+        let v32: ConsensusVersion = protocol::CONSENSUS_V32.into();
+        let cache = VerifiedTxnCache::new(4);
+        cache.add(&group(1), &v31);
+        assert!(cache.contains(&group(1), &v31));
+        assert!(!cache.contains(&group(1), &v32));
+
+        // Unsigned groups never enter the cache.
+        let proto = ConsensusParams {
+            max_tx_group_size: 16,
+            ..Default::default()
+        };
+        assert!(cache.verify_group(&group(2), &v31, &proto, None).is_err());
+        assert!(!cache.contains(&group(2), &v31));
+        cache.verify_group(&group(1), &v31, &proto, None).unwrap();
+
+        // The oldest groups are forgotten once the cache is full.
+        for fee in 2..6 {
+            cache.add(&group(fee), &v31);
+        }
+        assert!(cache.len() <= 4);
+        assert!(!cache.contains(&group(1), &v31));
+        assert!(cache.contains(&group(5), &v31));
+    }
+}
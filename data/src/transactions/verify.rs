@@ -0,0 +1,321 @@
+//! Stateless verification of the authorization of transactions.
+
+use config::consensus::ConsensusParams;
+use crypto::curve25519::{self, BLANK_SIGNATURE};
+use crypto::util::HashDigest;
+use util::execpool::{Backlog, BatchBacklog, Priority, SpawnError, VERIFICATION_BATCH_SIZE};
+
+use super::logic::{self, EvalParams};
+use super::signedtxn::{SignedTxn, SignedTxnWithAD};
+
+/// VerifyError is the reason a transaction group is not authorized, or
+/// does not pay enough fees.
+#[derive(Debug, thiserror::Error)]
+pub enum VerifyError {
+    #[error("group size {size} exceeds maximum {max}")]
+    GroupTooLarge { size: usize, max: i32 },
+    #[error("txgroup fee requirement overflow")]
+    FeeOverflow,
+    #[error("txgroup had {paid} in fees, which is less than the minimum {count} * {min_fee}")]
+    InsufficientFees { paid: u64, count: u64, min_fee: u64 },
+    #[error("signedtxn has no sig")]
+    NoSignature,
+    #[error("signedtxn should only have one of Sig or Msig or LogicSig")]
+    MultipleSignatures,
+    #[error("signature validation failed for txn {0}")]
+    BadSignature(usize),
+    #[error("multisig validation failed for txn {group_index}")]
+    BadMultisig {
+        group_index: usize,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("LogicSig not enabled")]
+    LogicSigDisabled,
+    #[error("LogicSig.Logic empty")]
+    EmptyLogic,
+    #[error("LogicSig.Logic too long: {len} > {max}")]
+    LogicTooLong { len: usize, max: u64 },
+    #[error(transparent)]
+    Logic(#[from] logic::EvalError),
+    /// The logic signature is not delegated by the authorizer.
+    #[error(transparent)]
+    Delegation(Box<dyn std::error::Error + Send + Sync>),
+    #[error("transaction {0} rejected by logic")]
+    Rejected(usize),
+    /// The group could not be queued for verification.
+    #[error(transparent)]
+    Backlog(#[from] SpawnError),
+}
+
+pub type VerifyResult<T> = Result<T, VerifyError>;
+
+/// Checks the authorization of every transaction of `group`.
+pub fn txn_group(group: &[SignedTxnWithAD], proto: &ConsensusParams) -> VerifyResult<()> {
+    if group.len() > proto.max_tx_group_size.max(0) as usize {
+        return Err(VerifyError::GroupTooLarge {
+            size: group.len(),
+            max: proto.max_tx_group_size,
+        });
+    }
+    txn_group_fees(group, proto)?;
+    for group_index in 0..group.len() {
+        txn(group, group_index, proto)?;
+    }
+    Ok(())
+}
+
+/// A group to verify, with the parameters of its protocol.
+type Item = (Vec<SignedTxnWithAD>, ConsensusParams);
+
+/// GroupVerifier verifies transaction groups on the CPU workers of a
+/// [`Backlog`], at the priority of the backlog, up to
+/// [`VERIFICATION_BATCH_SIZE`] groups to a task.
+pub struct GroupVerifier {
+    priority: Priority,
+    batches: BatchBacklog<Item, VerifyResult<()>>,
+}
+
+impl GroupVerifier {
+    pub fn new(backlog: Backlog) -> Self {
+        Self {
+            priority: backlog.priority(),
+            batches: BatchBacklog::new(backlog, VERIFICATION_BATCH_SIZE, |groups: Vec<Item>| {
+                groups
+                    .iter()
+                    .map(|(group, proto)| txn_group(group, proto))
+                    .collect()
+            }),
+        }
+    }
+
+    /// Checks the authorization of every transaction of `group` on the
+    /// workers, waiting for the result; it must not be called from an
+    /// async task. Fails with [`VerifyError::Backlog`] when the backlog can
+    /// not take the group, leaving the caller to shed the group or verify
+    /// it itself.
+    pub fn txn_group(
+        &self,
+        group: &[SignedTxnWithAD],
+        proto: &ConsensusParams,
+    ) -> VerifyResult<()> {
+        let result = self.batches.push((group.to_vec(), proto.clone()))?;
+        // The batch of the group is dropped when the CPU queue is full.
+        result
+            .blocking_recv()
+            .map_err(|_| SpawnError::BacklogFull(self.priority))?
+    }
+}
+
+/// Checks that the fees paid by `group` cover the minimum fee of each of
+/// its transactions. Since `enable_fee_pooling`, transactions may pay the
+/// fees of others; before, each transaction is checked by `well_formed`.
+pub fn txn_group_fees(group: &[SignedTxnWithAD], proto: &ConsensusParams) -> VerifyResult<()> {
+    if !proto.enable_fee_pooling {
+        return Ok(());
+    }
+    let (paid, count) = logic::group_fees(group);
+    let needed = proto
+        .min_txn_fee
+        .checked_mul(count)
+        .ok_or(VerifyError::FeeOverflow)?;
+    if paid < needed {
+        return Err(VerifyError::InsufficientFees {
+            paid,
+            count,
+            min_fee: proto.min_txn_fee,
+        });
+    }
+    Ok(())
+}
+
+/// Checks the authorization of the transaction at `group_index` of
+/// `group`: its signature, multisignature or logic signature, made by the
+/// authorizer of the transaction. Whether the authorizer is the one of the
+/// sender account is checked on evaluation.
+pub fn txn(
+    group: &[SignedTxnWithAD],
+    group_index: usize,
+    proto: &ConsensusParams,
+) -> VerifyResult<()> {
+    let stxn = &group[group_index].signed_txn;
+    let has_sig = stxn.sig != BLANK_SIGNATURE;
+    let has_msig = !stxn.msig.blank();
+    let has_lsig = !stxn.lsig.blank();
+    match (has_sig, has_msig, has_lsig) {
+        (false, false, false) => Err(VerifyError::NoSignature),
+        (true, false, false) => {
+            let authorizer = stxn.authorizer();
+            if !curve25519::verify(authorizer.as_bytes(), &stxn.txn, &stxn.sig) {
+                return Err(VerifyError::BadSignature(group_index));
+            }
+            Ok(())
+        }
+        (false, true, false) => stxn
+            .msig
+            .verify(&authorizer(stxn), &stxn.txn)
+            .map_err(|source| VerifyError::BadMultisig {
+                group_index,
+                source,
+            }),
+        (false, false, true) => logic_sig(group, group_index, proto),
+        _ => Err(VerifyError::MultipleSignatures),
+    }
+}
+
+fn authorizer(stxn: &SignedTxn) -> HashDigest {
+    HashDigest(*stxn.authorizer().as_bytes())
+}
+
+/// Performs the checks of a logic signature that do not run the program:
+/// that logic signatures are enabled, the size limit, the static program
+/// check, and that the program may authorize the sender.
+pub fn logic_sig_sanity_check(
+    group: &[SignedTxnWithAD],
+    group_index: usize,
+    proto: &ConsensusParams,
+) -> VerifyResult<()> {
+    let stxn = &group[group_index].signed_txn;
+    let lsig = &stxn.lsig;
+    if proto.logic_sig_version == 0 {
+        return Err(VerifyError::LogicSigDisabled);
+    }
+    if lsig.logic.is_empty() {
+        return Err(VerifyError::EmptyLogic);
+    }
+    if lsig.len() as u64 > proto.logic_sig_max_size {
+        return Err(VerifyError::LogicTooLong {
+            len: lsig.len(),
+            max: proto.logic_sig_max_size,
+        });
+    }
+    let params = EvalParams::new(proto, group);
+    logic::check_signature(&lsig.logic, &params)?;
+    lsig.verify_delegation(&authorizer(stxn))
+        .map_err(VerifyError::Delegation)
+}
+
+/// Verifies the logic signature of the transaction at `group_index` by
+/// running its program in stateless mode.
+pub fn logic_sig(
+    group: &[SignedTxnWithAD],
+    group_index: usize,
+    proto: &ConsensusParams,
+) -> VerifyResult<()> {
+    logic_sig_sanity_check(group, group_index, proto)?;
+    let lsig = &group[group_index].signed_txn.lsig;
+    let params = EvalParams::new(proto, group);
+    let pass = logic::eval_signature_program(&lsig.logic, &lsig.args, group_index, &params)?;
+    if !pass {
+        return Err(VerifyError::Rejected(group_index));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::basics::Address;
+    use crypto::curve25519::SignatureSecrets;
+    use crypto::logicsig::LogicSig;
+    use crypto::multisig::{multisig_addr, MultiSig};
+
+    fn proto() -> ConsensusParams {
+        ConsensusParams {
+            logic_sig_version: 7,
+            logic_sig_max_size: 1000,
+            logic_sig_max_cost: 20000,
+            max_tx_group_size: 16,
+            ..Default::default()
+        }
+    }
+
+    fn group_of(sender: Address, lsig: LogicSig) -> Vec<SignedTxnWithAD> {
+        let mut stxn = SignedTxnWithAD::default();
+        stxn.signed_txn.txn.header.sender = sender;
+        stxn.signed_txn.lsig = lsig;
+        vec![stxn]
+    }
+
+    // arg 0; len; int 3; ==
+    const PROGRAM: [u8; 6] = [0x07, 0x2d, 0x15, 0x81, 0x03, 0x12];
+
+    #[test]
+    fn escrow_and_delegated_logic_sigs() {
+        let proto = proto();
+        let lsig = LogicSig::new(PROGRAM.to_vec(), vec![b"abc".to_vec()]);
+
+        // Contract account: the sender is the program hash.
+        let escrow = Address::from(lsig.address());
+        assert!(txn_group(&group_of(escrow, lsig.clone()), &proto).is_ok());
+        assert!(txn_group(&group_of(Address::default(), lsig.clone()), &proto).is_err());
+
+        let mut rejected = lsig.clone();
+        rejected.args = vec![b"ab".to_vec()];
+        assert!(txn_group(&group_of(escrow, rejected), &proto).is_err());
+
+        // Delegation by a single key.
+        let secrets = SignatureSecrets::generate(&[1; 32]);
+        let account = Address::from(secrets.signature_verifier);
+        let mut delegated = lsig.clone();
+        delegated.sign(&secrets);
+        assert!(txn_group(&group_of(account, delegated.clone()), &proto).is_ok());
+        assert!(txn_group(&group_of(escrow, delegated), &proto).is_err());
+
+        // Delegation by a 2-of-2 multisig.
+        let other = SignatureSecrets::generate(&[2; 32]);
+        let pks = [secrets.signature_verifier, other.signature_verifier];
+        let msig_account = Address::from(multisig_addr(1, 2, &pks).unwrap());
+        let mut msig_delegated = lsig.clone();
+        msig_delegated
+            .append_multisig(&secrets, MultiSig::new(1, 2, &pks))
+            .unwrap();
+        assert!(txn_group(&group_of(msig_account, msig_delegated.clone()), &proto).is_err());
+        msig_delegated
+            .append_multisig(&other, MultiSig::default())
+            .unwrap();
+        assert!(txn_group(&group_of(msig_account, msig_delegated), &proto).is_ok());
+
+        // The program and its arguments count against logic_sig_max_size.
+        let mut small = proto.clone();
+        small.logic_sig_max_size = 8;
+        assert!(txn_group(&group_of(escrow, lsig), &small).is_err());
+    }
+
+    #[test]
+    fn signatures_of_rekeyed_accounts() {
+        let proto = proto();
+        let owner = SignatureSecrets::generate(&[1; 32]);
+        let hot = SignatureSecrets::generate(&[2; 32]);
+        let mut stxn = SignedTxnWithAD::default();
+        stxn.signed_txn.txn.header.sender = Address::from(owner.signature_verifier);
+        stxn.signed_txn.sig = hot.sign(&stxn.signed_txn.txn);
+        assert!(txn_group(std::slice::from_ref(&stxn), &proto).is_err());
+
+        // The signature is checked against the authorizer.
+        stxn.signed_txn.auth_addr = Address::from(hot.signature_verifier);
+        assert!(txn_group(std::slice::from_ref(&stxn), &proto).is_ok());
+    }
+
+    #[test]
+    fn groups_of_a_batch_get_their_own_result() {
+        use util::execpool::DedicatedExecutor;
+
+        let proto = proto();
+        let exec = DedicatedExecutor::new("Test GroupVerifier", Some(1));
+        let verifier = GroupVerifier::new(Backlog::new(exec.clone(), Priority::HighPriority));
+        let secrets = SignatureSecrets::generate(&[1; 32]);
+        let mut signed = SignedTxnWithAD::default();
+        signed.signed_txn.txn.header.sender = Address::from(secrets.signature_verifier);
+        signed.signed_txn.sig = secrets.sign(&signed.signed_txn.txn);
+        let mut unsigned = signed.clone();
+        unsigned.signed_txn.sig = BLANK_SIGNATURE;
+
+        std::thread::scope(|s| {
+            let good = s.spawn(|| verifier.txn_group(std::slice::from_ref(&signed), &proto));
+            let bad = s.spawn(|| verifier.txn_group(std::slice::from_ref(&unsigned), &proto));
+            assert!(good.join().unwrap().is_ok());
+            assert!(matches!(bad.join().unwrap(), Err(VerifyError::NoSignature)));
+        });
+        exec.shutdown();
+    }
+}
@@ -7,3 +7,15 @@ impl<T: serde::Serialize> Marshaler for T {
         bytes.extend(rmp_serde::to_vec_named(self).unwrap());
     }
 }
+
+pub type DecodeError = rmp_serde::decode::Error;
+
+pub trait Unmarshaler: Sized {
+    fn unmarshal_msg(bytes: &[u8]) -> Result<Self, DecodeError>;
+}
+
+impl<T: serde::de::DeserializeOwned> Unmarshaler for T {
+    fn unmarshal_msg(bytes: &[u8]) -> Result<Self, DecodeError> {
+        rmp_serde::from_slice(bytes)
+    }
+}
@@ -0,0 +1,103 @@
+//! Metrics of the gossip network: the connected peers and the messages
+//! exchanged with them, by tag.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use util::metrics::{default_registry, Counter, Gauge, Metric, MetricName, Sample};
+
+const PEERS: MetricName = MetricName {
+    name: "algod_network_peers",
+    description: "Peers connected to the node",
+};
+const RECEIVED: MetricName = MetricName {
+    name: "algod_network_received_messages_total",
+    description: "Messages received from peers, by tag",
+};
+const RECEIVED_BYTES: MetricName = MetricName {
+    name: "algod_network_received_bytes_total",
+    description: "Bytes of the messages received from peers, by tag",
+};
+const SENT: MetricName = MetricName {
+    name: "algod_network_sent_messages_total",
+    description: "Messages sent to peers, by tag",
+};
+const SENT_BYTES: MetricName = MetricName {
+    name: "algod_network_sent_bytes_total",
+    description: "Bytes of the messages sent to peers, by tag",
+};
+
+/// The counters of the messages of a tag, in one direction.
+#[derive(Debug)]
+struct TagCounters {
+    messages: Counter,
+    bytes: Counter,
+}
+
+/// The metrics of the network of the node.
+#[derive(Debug)]
+pub struct NetworkMetrics {
+    pub peers: Gauge,
+    received: Mutex<BTreeMap<String, TagCounters>>,
+    sent: Mutex<BTreeMap<String, TagCounters>>,
+}
+
+impl NetworkMetrics {
+    fn new() -> Self {
+        Self {
+            peers: Gauge::new(PEERS, vec![]),
+            received: Mutex::new(BTreeMap::new()),
+            sent: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// A message of `tag` and `len` bytes was received.
+    pub fn message_received(&self, tag: &str, len: usize) {
+        count(&self.received, (RECEIVED, RECEIVED_BYTES), tag, len);
+    }
+
+    /// A message of `tag` and `len` bytes was sent.
+    pub fn message_sent(&self, tag: &str, len: usize) {
+        count(&self.sent, (SENT, SENT_BYTES), tag, len);
+    }
+}
+
+fn count(
+    counters: &Mutex<BTreeMap<String, TagCounters>>,
+    (messages, bytes): (MetricName, MetricName),
+    tag: &str,
+    len: usize,
+) {
+    let mut counters = counters.lock().unwrap();
+    let counters = counters.entry(tag.to_string()).or_insert_with(|| {
+        let labels = vec![("tag".to_string(), tag.to_string())];
+        TagCounters {
+            messages: Counter::new(messages, labels.clone()),
+            bytes: Counter::new(bytes, labels),
+        }
+    });
+    counters.messages.inc();
+    counters.bytes.add(len as u64);
+}
+
+impl Metric for NetworkMetrics {
+    fn collect(&self, out: &mut Vec<Sample>) {
+        self.peers.collect(out);
+        for counters in [&self.received, &self.sent] {
+            for counters in counters.lock().unwrap().values() {
+                counters.messages.collect(out);
+                counters.bytes.collect(out);
+            }
+        }
+    }
+}
+
+/// The network metrics, registered in the default registry on first use.
+pub fn network_metrics() -> &'static NetworkMetrics {
+    static METRICS: OnceLock<Arc<NetworkMetrics>> = OnceLock::new();
+    METRICS.get_or_init(|| {
+        let metrics = Arc::new(NetworkMetrics::new());
+        default_registry().register(metrics.clone());
+        metrics
+    })
+}
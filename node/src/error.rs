@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+
+use data::{
+    account::{ParticipationError, ParticipationId, RegistryError},
+    bookkeeping::genesis::GenesisError,
+    ledger::LedgerError,
+    pools::PoolError,
+};
+
+/// NodeError is an error starting or operating the node.
+#[derive(Debug, thiserror::Error)]
+pub enum NodeError {
+    #[error("unable to create genesis directory {path:?}")]
+    GenesisDir {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid genesis")]
+    Genesis(#[from] GenesisError),
+    #[error("ledger failure")]
+    Ledger(#[from] LedgerError),
+    #[error("transaction pool failure")]
+    Pool(#[from] PoolError),
+    /// Generating or reading participation keys failed.
+    #[error("invalid participation keys")]
+    Participation(#[from] ParticipationError),
+    #[error("participation registry failure")]
+    Registry(#[from] RegistryError),
+    #[error("participation key {0} not found")]
+    ParticipationKeyNotFound(ParticipationId),
+    #[error("participation registry lock poisoned")]
+    RegistryPoisoned,
+    #[error("cannot set up logging")]
+    Logging(#[source] std::io::Error),
+    #[error("cannot start telemetry")]
+    Telemetry(#[source] std::io::Error),
+    #[error("cannot serve metrics on {address}")]
+    Metrics {
+        address: String,
+        source: std::io::Error,
+    },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type NodeResult<T> = Result<T, NodeError>;
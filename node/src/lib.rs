@@ -1,30 +1,83 @@
+mod error;
+mod participation;
 mod top_account_listener;
-use data::bookkeeping;
+use data::{
+    account::ParticipationRegistry,
+    bookkeeping::{
+        self,
+        block::Block,
+    },
+    ledger::{
+        eval::{self, BlockEvaluator, EvalResult},
+        Ledger, LedgerError,
+    },
+    pools::{AssembledBlock, PoolResult, TransactionPool},
+    transactions::{signedtxn::SignedTxn, verified_cache::VerifiedTxnCache, verify::GroupVerifier},
+};
+pub use error::{NodeError, NodeResult};
 use std::{
     fs,
     path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex, MutexGuard, PoisonError},
+    thread,
 };
 use top_account_listener::TopAccountListener;
-use util::execpool::{Backlog, DedicatedExecutor};
+use util::{
+    execpool::{Backlog, DedicatedExecutor},
+    rng::NodeRng,
+};
+
+/// NodeRngs are the streams of randomness of the subsystems of the node,
+/// split from the one it was started with so that a seeded node makes the
+/// same choices from run to run. Only participation key generation draws
+/// from a stream yet: the node has no peer selection, catchup or agreement,
+/// whose streams are to be split after `keygen` when they are added.
+#[derive(Debug, Clone)]
+pub struct NodeRngs {
+    /// Generates participation keys.
+    pub keygen: NodeRng,
+}
+
+impl NodeRngs {
+    pub fn new(rng: &NodeRng) -> Self {
+        // The order of the splits is part of what a seed reproduces; new
+        // streams are split after the existing ones.
+        Self {
+            keygen: rng.split(),
+        }
+    }
+}
 
 pub struct AlgorandFullNode {
     pub config: config::Local,
     pub root_dir: PathBuf,
+    pub genesis_dir: PathBuf,
     pub genesis_id: String,
     pub genesis_hash: crypto::util::HashDigest,
     pub dev_mode: bool,
+    pub ledger: Arc<Ledger>,
+    /// The groups waiting to be added to a block, evaluated on top of the
+    /// latest block of the ledger.
+    pool: Mutex<TransactionPool>,
+    /// The groups whose signatures were verified, shared by the transaction
+    /// pool and the block validator so that a group is verified once.
+    verified_txns: Arc<VerifiedTxnCache>,
     crypto_pool: DedicatedExecutor,
-    low_priority_verification_pool: Backlog,
-    high_priority_verification_pool: Backlog,
+    /// Verifies the groups submitted to the transaction pool.
+    low_priority_verification_pool: Arc<GroupVerifier>,
+    /// Verifies the groups of the blocks added to the ledger.
+    high_priority_verification_pool: GroupVerifier,
+    participation_registry: Arc<Mutex<ParticipationRegistry>>,
+    participation_refresh: Option<(mpsc::Sender<()>, thread::JoinHandle<()>)>,
+    rngs: NodeRngs,
 }
 
-pub type NodeResult<T> = Result<T, Box<dyn std::error::Error>>;
-
 impl AlgorandFullNode {
     pub fn new(
         root_dir: PathBuf,
         mut config: config::Local,
         genesis: &bookkeeping::genesis::Genesis,
+        rng: NodeRng,
     ) -> NodeResult<Self> {
         let genesis_id = genesis.id();
         let genesis_hash = genesis.hash();
@@ -34,22 +87,21 @@ impl AlgorandFullNode {
         }
         //let p2p_node = network::new_web_socket_network();
         let account_listner = TopAccountListener::new();
-        let genesis_dir = Path::join(&root_dir, &genesis_id);
+        let genesis_dir = create_genesis_dir(&root_dir, &genesis_id)?;
         let ledger_pathname_prefix = Path::join(&genesis_dir, config::LEDGER_FILENAME_PREFIX);
-        let create_dir_result = fs::create_dir(&genesis_dir);
-        if let Err(e) = create_dir_result && e.kind() != std::io::ErrorKind::AlreadyExists{
-            panic!("Unable to create genesis directory");
-        }
         let gen_alloc = genesis.balances()?;
+        let verified_txns = Arc::new(VerifiedTxnCache::new(
+            config.verified_transcations_cache_size.max(0) as usize,
+        ));
         let crypto_pool = DedicatedExecutor::new("node_crypto_pool", None);
-        let low_priority_backlog = util::execpool::Backlog::new(
+        let low_priority_verifier = Arc::new(GroupVerifier::new(Backlog::new(
             crypto_pool.clone(),
             util::execpool::Priority::LowPriority,
-        );
-        let high_priority_backlog = util::execpool::Backlog::new(
+        )));
+        let high_priority_verifier = GroupVerifier::new(Backlog::new(
             crypto_pool.clone(),
             util::execpool::Priority::HighPriority,
-        );
+        ));
         let ledger = data::ledger::load_ledger(
             ledger_pathname_prefix.to_str().unwrap().to_string(),
             false,
@@ -59,17 +111,233 @@ impl AlgorandFullNode {
             genesis_hash,
             vec![],
             config.clone(),
-        );
+        )?;
+        let ledger = Arc::new(ledger);
+        let pool = TransactionPool::new(
+            ledger.clone(),
+            &ledger.latest_header(),
+            &config,
+            Arc::clone(&verified_txns),
+            Some(Arc::clone(&low_priority_verifier)),
+        )?;
+        let registry = ParticipationRegistry::open(Path::join(
+            &genesis_dir,
+            config::PARTICIPATION_REGISTRY_FILENAME,
+        ))?;
 
         Ok(Self {
             config,
             root_dir,
+            genesis_dir,
             genesis_id,
             genesis_hash,
             dev_mode,
+            ledger,
+            pool: Mutex::new(pool),
+            verified_txns,
             crypto_pool,
-            low_priority_verification_pool: low_priority_backlog,
-            high_priority_verification_pool: high_priority_backlog,
+            low_priority_verification_pool: low_priority_verifier,
+            high_priority_verification_pool: high_priority_verifier,
+            participation_registry: Arc::new(Mutex::new(registry)),
+            participation_refresh: None,
+            rngs: NodeRngs::new(&rng),
         })
     }
+
+    /// The randomness of the subsystems of the node.
+    pub fn rngs(&self) -> &NodeRngs {
+        &self.rngs
+    }
+
+    fn pool(&self) -> MutexGuard<'_, TransactionPool> {
+        // A panic while the pool was held leaves it evaluated up to the
+        // last group it took, so it can still be used.
+        self.pool.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Verifies the signatures of `group`, received from gossip or
+    /// submitted locally, on the low priority verification backlog and
+    /// remembers it in the transaction pool. Fails with
+    /// [`PoolError::Busy`](data::pools::PoolError::Busy) when the backlog is
+    /// full.
+    pub fn remember(&self, group: &[SignedTxn]) -> PoolResult<()> {
+        self.pool().verify_and_remember(group)
+    }
+
+    /// Assembles a proposal for the block following the latest block of the
+    /// ledger from the groups pending in the transaction pool.
+    pub fn assemble_block(&self) -> PoolResult<AssembledBlock> {
+        self.pool().assemble_block()
+    }
+
+    /// Validates `block`, which follows the latest block of the ledger,
+    /// skipping the signatures the transaction pool already verified and
+    /// verifying the others on the high priority verification backlog.
+    pub fn validate_block(&self, block: &Block) -> EvalResult<BlockEvaluator> {
+        let prev = self.ledger.latest_header();
+        eval::validate_block(
+            self.ledger.clone(),
+            block,
+            &prev,
+            &self.verified_txns,
+            Some(&self.high_priority_verification_pool),
+        )
+    }
+
+    /// Adds `block` to the ledger, then evaluates the pending groups of
+    /// the transaction pool on top of it.
+    pub fn add_block(&self, block: &Block) -> NodeResult<()> {
+        let verifier = &self.high_priority_verification_pool;
+        self.ledger
+            .add_block(block, &self.verified_txns, Some(verifier))?;
+        self.pool().on_new_block(&block.header)?;
+        Ok(())
+    }
+
+    /// Initializes the data directory `root_dir` for `genesis` without
+    /// starting a node: creates the genesis directory and the participation
+    /// registry, and checks that the genesis makes a valid genesis block.
+    pub fn initialize(root_dir: &Path, genesis: &bookkeeping::genesis::Genesis) -> NodeResult<()> {
+        let genesis_dir = create_genesis_dir(root_dir, &genesis.id())?;
+        bookkeeping::genesis::make_genesis_block(
+            genesis.proto.clone(),
+            genesis.balances()?,
+            genesis.id(),
+            genesis.hash(),
+        )
+        .map_err(LedgerError::Genesis)?;
+        ParticipationRegistry::open(Path::join(
+            &genesis_dir,
+            config::PARTICIPATION_REGISTRY_FILENAME,
+        ))?;
+        Ok(())
+    }
+
+    /// Starts the background services of the node.
+    pub fn start(&mut self) -> NodeResult<()> {
+        participation::check_for_participation_keys(
+            &self.genesis_dir,
+            &self.participation_registry,
+            self.ledger.latest(),
+        )?;
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let genesis_dir = self.genesis_dir.clone();
+        let registry = Arc::clone(&self.participation_registry);
+        let ledger = Arc::clone(&self.ledger);
+        let interval = self.config.participation_keys_refresh_interval;
+        let handle = thread::Builder::new()
+            .name("participation_refresh".to_string())
+            .spawn(move || {
+                while let Err(mpsc::RecvTimeoutError::Timeout) = stop_rx.recv_timeout(interval) {
+                    if let Err(e) = participation::check_for_participation_keys(
+                        &genesis_dir,
+                        &registry,
+                        ledger.latest(),
+                    ) {
+                        tracing::warn!("could not refresh participation keys: {}", e);
+                    }
+                }
+            })?;
+        self.participation_refresh = Some((stop_tx, handle));
+        // The node does not run the gossip network yet, so it has no peers;
+        // the network metrics are registered to export that from the start.
+        network::metrics::network_metrics().peers.set(0);
+        Ok(())
+    }
+
+    /// Stops the background services started by `start`.
+    pub fn stop(&mut self) {
+        if let Some((stop_tx, handle)) = self.participation_refresh.take() {
+            stop_tx.send(()).ok();
+            handle.join().ok();
+        }
+        if let Ok(mut registry) = self.participation_registry.lock() {
+            if let Err(e) = registry.flush() {
+                tracing::warn!("could not flush participation registry: {}", e);
+            }
+        }
+    }
+}
+
+/// Creates the directory of the ledger and keys of `genesis_id`, unless it
+/// exists.
+fn create_genesis_dir(root_dir: &Path, genesis_id: &str) -> NodeResult<PathBuf> {
+    let genesis_dir = Path::join(root_dir, genesis_id);
+    match fs::create_dir(&genesis_dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => Err(NodeError::GenesisDir {
+            path: genesis_dir,
+            source: e,
+        }),
+        _ => Ok(genesis_dir),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::curve25519::SignatureSecrets;
+    use data::basics::{AccountData, Address, MicroAlgos};
+    use data::bookkeeping::genesis::{Genesis, GenesisAllocation};
+    use data::ledger::cow::LedgerForCowBase;
+    use protocol::TxType;
+
+    #[test]
+    fn assembled_blocks_are_added_to_the_ledger() {
+        config::consensus::init();
+        let dir = std::env::temp_dir().join(format!("node-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let alice = SignatureSecrets::generate(&[1; 32]);
+        let sink = Address::from([9; 32]);
+        let bob = Address::from([2; 32]);
+        let alloc = |address: Address, microalgos| GenesisAllocation {
+            address: address.string(),
+            state: AccountData {
+                microalgos: MicroAlgos(microalgos),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let genesis = Genesis {
+            schema_id: "v1".to_string(),
+            network: "nodetest".to_string(),
+            proto: protocol::CONSENSUS_V31.to_string(),
+            allocation: vec![
+                alloc(Address::from(alice.signature_verifier), 10_000_000),
+                alloc(sink, 1_000_000),
+            ],
+            rewards_pool: sink.string(),
+            fee_sink: sink.string(),
+            dev_mode: true,
+            ..Default::default()
+        };
+        let node = AlgorandFullNode::new(
+            dir.clone(),
+            config::default_local(),
+            &genesis,
+            NodeRng::seeded(1),
+        )
+        .unwrap();
+
+        let mut payment = SignedTxn::default();
+        let txn = &mut payment.txn;
+        txn.tx_type = TxType::PaymentTx;
+        txn.header.sender = Address::from(alice.signature_verifier);
+        txn.header.fee = MicroAlgos(1000);
+        txn.header.last_valid = 100;
+        txn.header.genesis_hash = node.genesis_hash;
+        txn.payment_txn_fields.receiver = bob;
+        txn.payment_txn_fields.amount = MicroAlgos(200_000);
+        payment.sig = alice.sign(&payment.txn);
+        node.remember(&[payment]).unwrap();
+
+        let assembled = node.assemble_block().unwrap();
+        assert_eq!(assembled.block.payset.0.len(), 1);
+        node.validate_block(&assembled.block).unwrap();
+        node.add_block(&assembled.block).unwrap();
+        assert_eq!(node.ledger.latest(), 1);
+        assert_eq!(node.ledger.lookup(&bob).microalgos, MicroAlgos(200_000));
+        // The confirmed payment left the pool.
+        assert_eq!(node.pool().pending_count(), 0);
+        fs::remove_dir_all(&dir).ok();
+    }
 }
@@ -0,0 +1,144 @@
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use data::{
+    account::{Participation, ParticipationId, ParticipationRecord, ParticipationRegistry},
+    basics::{Address, Round},
+};
+
+use crate::{AlgorandFullNode, NodeError, NodeResult};
+
+/// Refreshes the registry: reloads it from disk, imports every
+/// participation key file found in `genesis_dir` and deletes the keys that
+/// expired before the `latest` round. Imported files are removed, the
+/// registry keeps the secrets.
+pub(crate) fn check_for_participation_keys(
+    genesis_dir: &Path,
+    registry: &Arc<Mutex<ParticipationRegistry>>,
+    latest: Round,
+) -> NodeResult<()> {
+    let mut registry = registry.lock().map_err(|_| NodeError::RegistryPoisoned)?;
+    registry.reload()?;
+    for entry in fs::read_dir(genesis_dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(data::account::PARTKEY_FILE_EXTENSION)
+        {
+            continue;
+        }
+        let part = match Participation::load(&path) {
+            Ok(part) => part,
+            Err(e) => {
+                tracing::warn!("skipping participation key file {:?}: {}", path, e);
+                continue;
+            }
+        };
+        if registry.get(&part.id()).is_none() {
+            registry.insert(&part)?;
+        }
+        fs::remove_file(&path)?;
+    }
+    registry.delete_expired(latest)?;
+    Ok(registry.flush()?)
+}
+
+impl AlgorandFullNode {
+    fn registry(&self) -> NodeResult<std::sync::MutexGuard<'_, ParticipationRegistry>> {
+        self.participation_registry
+            .lock()
+            .map_err(|_| NodeError::RegistryPoisoned)
+    }
+
+    /// Installs the participation key file at `path` and returns its id.
+    pub fn install_participation_key<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> NodeResult<ParticipationId> {
+        let part = Participation::load(path)?;
+        Ok(self.registry()?.insert(&part)?)
+    }
+
+    /// Lists the metadata of every installed participation key.
+    pub fn list_participation_keys(&self) -> NodeResult<Vec<ParticipationRecord>> {
+        let mut records = self.registry()?.get_all();
+        for record in records.iter_mut() {
+            record.vrf = None;
+            record.voting = None;
+        }
+        Ok(records)
+    }
+
+    pub fn remove_participation_key(&self, id: ParticipationId) -> NodeResult<()> {
+        Ok(self.registry()?.delete(id)?)
+    }
+
+    /// Generates and installs keys for `address` covering the given interval.
+    pub fn generate_participation_key(
+        &self,
+        address: Address,
+        first_valid: Round,
+        last_valid: Round,
+        key_dilution: u64,
+    ) -> NodeResult<ParticipationId> {
+        let part = Participation::generate(
+            address,
+            first_valid,
+            last_valid,
+            key_dilution,
+            &mut self.rngs.keygen.clone(),
+        )?;
+        Ok(self.registry()?.insert(&part)?)
+    }
+
+    /// Generates keys that continue the validity of `id` until `last_valid`,
+    /// using the same account and key dilution. The new keys still need to be
+    /// registered on chain with a key registration transaction.
+    pub fn renew_participation_key(
+        &self,
+        id: ParticipationId,
+        last_valid: Round,
+    ) -> NodeResult<ParticipationId> {
+        let (account, first_valid, key_dilution) = {
+            let registry = self.registry()?;
+            let record = registry
+                .get(&id)
+                .ok_or(NodeError::ParticipationKeyNotFound(id))?;
+            (record.account, record.last_valid + 1, record.key_dilution)
+        };
+        self.generate_participation_key(account, first_valid, last_valid, key_dilution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::rng::NodeRng;
+
+    #[test]
+    fn refresh_imports_key_files_and_deletes_expired_keys() {
+        let dir = std::env::temp_dir().join(format!("partkeys-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let registry =
+            ParticipationRegistry::open(dir.join(config::PARTICIPATION_REGISTRY_FILENAME)).unwrap();
+        let registry = Arc::new(Mutex::new(registry));
+        let mut rng = NodeRng::seeded(1);
+        let account = Address::from([7u8; 32]);
+        let old = Participation::generate(account, 1, 100, 10, &mut rng).unwrap();
+        let new = Participation::generate(account, 50, 300, 10, &mut rng).unwrap();
+        for part in [&old, &new] {
+            part.persist(dir.join(part.file_name())).unwrap();
+        }
+
+        check_for_participation_keys(&dir, &registry, 50).unwrap();
+        assert_eq!(registry.lock().unwrap().get_all().len(), 2);
+        assert!(!dir.join(old.file_name()).exists());
+
+        check_for_participation_keys(&dir, &registry, 200).unwrap();
+        let registry = registry.lock().unwrap();
+        assert!(registry.get(&old.id()).is_none());
+        assert!(registry.get(&new.id()).is_some());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,4 +1,4 @@
-use msgp::Marshaler;
+use msgp::{DecodeError, Marshaler, Unmarshaler};
 
 fn encode_msgp<M: Marshaler>(m: &M) -> Vec<u8> {
     let mut buffer = vec![];
@@ -9,3 +9,7 @@ fn encode_msgp<M: Marshaler>(m: &M) -> Vec<u8> {
 pub fn encode<M: Marshaler>(m: &M) -> Vec<u8> {
     encode_msgp(m)
 }
+
+pub fn decode<U: Unmarshaler>(bytes: &[u8]) -> Result<U, DecodeError> {
+    U::unmarshal_msg(bytes)
+}
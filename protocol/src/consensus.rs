@@ -1,5 +1,12 @@
 pub type ConsensusVersion = String;
 
+/// CompactCertType identifies a particular configuration of compact certs.
+pub type CompactCertType = u64;
+
+/// COMPACT_CERT_BASIC is our initial compact cert setup, using Ed25519
+/// one-time signatures and the SHA512/256 hash function.
+pub const COMPACT_CERT_BASIC: CompactCertType = 0;
+
 pub const CONSENSUS_V7: &str = "v7";
 pub const CONSENSUS_V8: &str = "v8";
 pub const CONSENSUS_V9: &str = "v9";
@@ -29,6 +29,7 @@ pub const KEYS_IN_MSS: HashId = "KP";
 pub const MERKLE_ARRAY_NODE: HashId = "MA";
 pub const MERKLE_VECTOR_COMMITMENT_BOTTOM_LEAF: HashId = "MB";
 pub const MESSAGE: HashId = "MX";
+pub const MULTISIG_ADDR: HashId = "MultisigAddr";
 pub const NET_PRIO_RESPONSE: HashId = "NPR";
 pub const ONE_TIME_SIG_KEY1: HashId = "OT1";
 pub const ONE_TIME_SIG_KEY2: HashId = "OT2";
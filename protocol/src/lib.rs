@@ -1,8 +1,10 @@
 mod codec;
 mod consensus;
 mod hash;
+mod txntype;
 pub use codec::*;
 pub use consensus::*;
 pub use hash::*;
+pub use txntype::*;
 
 pub type NetworkId = String;
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+/// TxType is the type of the transaction written to the ledger.
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TxType {
+    #[default]
+    #[serde(rename = "")]
+    Unknown,
+    /// PaymentTx indicates a payment transaction.
+    #[serde(rename = "pay")]
+    PaymentTx,
+    /// KeyRegistrationTx indicates a transaction that registers participation keys.
+    #[serde(rename = "keyreg")]
+    KeyRegistrationTx,
+    /// AssetConfigTx creates, re-configures, or destroys an asset.
+    #[serde(rename = "acfg")]
+    AssetConfigTx,
+    /// AssetTransferTx transfers assets between accounts (optionally closing).
+    #[serde(rename = "axfer")]
+    AssetTransferTx,
+    /// AssetFreezeTx changes the freeze status of an asset.
+    #[serde(rename = "afrz")]
+    AssetFreezeTx,
+    /// ApplicationCallTx allows creating, deleting, and interacting with an
+    /// application.
+    #[serde(rename = "appl")]
+    ApplicationCallTx,
+    /// CompactCertTx records a compact certificate.
+    #[serde(rename = "cert")]
+    CompactCertTx,
+}
+
+impl TxType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TxType::Unknown => "",
+            TxType::PaymentTx => "pay",
+            TxType::KeyRegistrationTx => "keyreg",
+            TxType::AssetConfigTx => "acfg",
+            TxType::AssetTransferTx => "axfer",
+            TxType::AssetFreezeTx => "afrz",
+            TxType::ApplicationCallTx => "appl",
+            TxType::CompactCertTx => "cert",
+        }
+    }
+}
+
+impl std::fmt::Display for TxType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
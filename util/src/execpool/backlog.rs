@@ -1,5 +1,17 @@
 use super::*;
 use futures::Future;
+use parking_lot::Mutex;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::oneshot;
+
+/// The number of items of a verification batch, which amortizes the cost
+/// of ed25519 batch verification, as the worksets of go-algorand's stream
+/// verifier.
+pub const VERIFICATION_BATCH_SIZE: usize = 32;
+
+/// How long an incomplete batch waits for more items before it is
+/// processed anyway.
+pub const BATCH_WAIT: Duration = Duration::from_millis(2);
 
 pub struct Backlog {
     executor: DedicatedExecutor,
@@ -14,11 +26,183 @@ impl Backlog {
         };
     }
 
-    pub fn enqueue<T>(&self, task: T) -> Job<T::Output>
+    /// Queues `task`, waiting for room in the backlog when it is full.
+    pub async fn enqueue<T>(&self, task: T) -> Result<Job<T::Output>, SpawnError>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        self.executor.spawn_wait(task, self.priority).await
+    }
+
+    /// Queues `task`, failing with [`SpawnError::BacklogFull`] when the
+    /// backlog is full.
+    pub fn try_enqueue<T>(&self, task: T) -> Result<Job<T::Output>, SpawnError>
     where
         T: Future + Send + 'static,
         T::Output: Send + 'static,
     {
         self.executor.spawn(task, self.priority)
     }
+
+    /// Queues the synchronous, CPU bound function `f`, such as a signature
+    /// verification, failing with [`SpawnError::BacklogFull`] when the CPU
+    /// queue is full. Dropping the returned [`Job`] cancels it if it has
+    /// not started yet.
+    pub fn enqueue_blocking<F, R>(&self, f: F) -> Result<Job<R>, SpawnError>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.executor.spawn_blocking(f, self.priority)
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    /// The number of tasks waiting in the backlog.
+    pub fn queue_depth(&self) -> usize {
+        self.executor.queue_depth(self.priority)
+    }
+}
+
+type Process<I, O> = Arc<dyn Fn(Vec<I>) -> Vec<O> + Send + Sync>;
+
+/// A batch being filled, with the senders of the results of its items.
+type Pending<I, O> = Vec<(I, oneshot::Sender<O>)>;
+
+/// BatchBacklog coalesces many small jobs, such as verifying the
+/// signature of a single transaction, into batches processed by one task
+/// of a [`Backlog`]. A batch is processed once it holds `batch_size` items
+/// or [`BATCH_WAIT`] after its first item arrived.
+pub struct BatchBacklog<I, O> {
+    backlog: Arc<Backlog>,
+    batch_size: usize,
+    /// Processes a batch, returning the result of each item in order.
+    process: Process<I, O>,
+    pending: Arc<Mutex<Pending<I, O>>>,
+}
+
+impl<I, O> BatchBacklog<I, O>
+where
+    I: Send + 'static,
+    O: Send + 'static,
+{
+    pub fn new<F>(backlog: Backlog, batch_size: usize, process: F) -> Self
+    where
+        F: Fn(Vec<I>) -> Vec<O> + Send + Sync + 'static,
+    {
+        Self {
+            backlog: Arc::new(backlog),
+            batch_size: batch_size.max(1),
+            process: Arc::new(process),
+            pending: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Adds `item` to the current batch, which is processed on the CPU
+    /// workers of the executor. The returned receiver resolves to the
+    /// result of the item once its batch was processed, or to an error if
+    /// the batch could not be processed.
+    pub fn push(&self, item: I) -> Result<oneshot::Receiver<O>, SpawnError> {
+        let (tx, rx) = oneshot::channel();
+        let full = {
+            let mut pending = self.pending.lock();
+            if pending.is_empty() && self.batch_size > 1 {
+                // Process the batch after a while even if it is not full.
+                // The timer is queued before the item joins the batch so
+                // that a full backlog leaves no item behind.
+                self.backlog.try_enqueue(self.timer())?.detach();
+            }
+            pending.push((item, tx));
+            let full = pending.len() >= self.batch_size;
+            full.then(|| std::mem::take(&mut *pending))
+        };
+        if let Some(batch) = full {
+            dispatch(&self.backlog, &self.process, batch);
+        }
+        Ok(rx)
+    }
+
+    /// Processes the current batch once it waited [`BATCH_WAIT`].
+    fn timer(&self) -> impl Future<Output = ()> + Send + 'static {
+        let backlog = Arc::clone(&self.backlog);
+        let process = Arc::clone(&self.process);
+        let pending = Arc::clone(&self.pending);
+        async move {
+            tokio::time::sleep(BATCH_WAIT).await;
+            let batch = std::mem::take(&mut *pending.lock());
+            if !batch.is_empty() {
+                dispatch(&backlog, &process, batch);
+            }
+        }
+    }
+}
+
+/// Queues the processing of `batch` on the CPU workers, sending the result
+/// of each item to its receiver. If the CPU queue is full or the executor
+/// was shut down, the receivers see an error.
+fn dispatch<I, O>(backlog: &Backlog, process: &Process<I, O>, batch: Pending<I, O>)
+where
+    I: Send + 'static,
+    O: Send + 'static,
+{
+    let process = Arc::clone(process);
+    let job = backlog.enqueue_blocking(move || {
+        let (items, senders): (Vec<I>, Vec<oneshot::Sender<O>>) = batch.into_iter().unzip();
+        for (result, sender) in process(items).into_iter().zip(senders) {
+            sender.send(result).ok();
+        }
+    });
+    match job {
+        Ok(job) => job.detach(),
+        Err(e) => tracing::warn!("dropping a batch: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn batches_fill_or_time_out() {
+        let exec = DedicatedExecutor::new("Test BatchBacklog", Some(1));
+        let sizes = Arc::new(Mutex::new(vec![]));
+        let seen = Arc::clone(&sizes);
+        let batches = BatchBacklog::new(
+            Backlog::new(exec.clone(), Priority::HighPriority),
+            2,
+            move |items: Vec<u64>| {
+                seen.lock().push(items.len());
+                items.into_iter().map(|item| item * 2).collect()
+            },
+        );
+
+        let first = batches.push(1).unwrap();
+        let second = batches.push(2).unwrap();
+        assert_eq!(first.await.unwrap(), 2);
+        assert_eq!(second.await.unwrap(), 4);
+
+        // A lone item is processed once the batch waited long enough.
+        let lone = batches.push(3).unwrap();
+        assert_eq!(lone.await.unwrap(), 6);
+        assert_eq!(*sizes.lock(), vec![2, 1]);
+
+        exec.join().await;
+    }
+
+    #[tokio::test]
+    async fn rejected_items_leave_the_batch_empty() {
+        let exec = DedicatedExecutor::new("Test BatchBacklog", Some(1));
+        let batches = BatchBacklog::new(
+            Backlog::new(exec.clone(), Priority::HighPriority),
+            2,
+            |items: Vec<u64>| items,
+        );
+        exec.join().await;
+
+        assert_eq!(batches.push(1).unwrap_err(), SpawnError::Shutdown);
+        assert!(batches.pending.lock().is_empty());
+    }
 }
@@ -0,0 +1,151 @@
+use std::{collections::VecDeque, sync::Arc, thread::JoinHandle};
+
+use parking_lot::{Condvar, Mutex};
+use tokio_util::sync::CancellationToken;
+
+use super::metrics::Tracker;
+use super::*;
+
+/// Synchronous task that can be added to a [`CpuPool`] queue.
+pub(crate) struct CpuTask {
+    pub(crate) run: Box<dyn FnOnce() + Send>,
+    pub(crate) cancel: CancellationToken,
+    pub(crate) tracker: Tracker,
+
+    #[allow(dead_code)]
+    pub(crate) task_ref: Arc<()>,
+}
+
+#[derive(Default)]
+struct Queues {
+    high: VecDeque<CpuTask>,
+    low: VecDeque<CpuTask>,
+    /// Consecutive high priority tasks taken while low priority tasks were
+    /// waiting.
+    high_streak: usize,
+    shutdown: bool,
+}
+
+impl Queues {
+    /// Takes the next task: high priority first, except that a waiting low
+    /// priority task gets its turn after a streak of high priority tasks.
+    fn pop(&mut self) -> Option<CpuTask> {
+        if self.low.is_empty() {
+            self.high_streak = 0;
+            return self.high.pop_front();
+        }
+        if self.high.is_empty() || self.high_streak >= MAX_HIGH_PRIORITY_STREAK {
+            self.high_streak = 0;
+            return self.low.pop_front();
+        }
+        self.high_streak += 1;
+        self.high.pop_front()
+    }
+}
+
+/// CpuPool runs synchronous, CPU bound tasks on a fixed set of threads, so
+/// that they do not block the threads of an async runtime.
+pub(crate) struct CpuPool {
+    queues: Arc<(Mutex<Queues>, Condvar)>,
+    /// The number of tasks each priority queue holds.
+    capacity: usize,
+    threads: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl CpuPool {
+    /// Starts `num_threads` workers named `thread_name`, whose queues hold
+    /// up to `capacity` tasks each.
+    pub(crate) fn new(thread_name: &str, num_threads: usize, capacity: usize) -> Self {
+        let queues = Arc::new((Mutex::new(Queues::default()), Condvar::new()));
+        let threads = (0..num_threads.max(1))
+            .map(|_| {
+                let queues = Arc::clone(&queues);
+                std::thread::Builder::new()
+                    .name(thread_name.to_string())
+                    .spawn(move || work(&queues))
+                    .expect("Creating CPU worker")
+            })
+            .collect();
+        Self {
+            queues,
+            capacity,
+            threads: Mutex::new(threads),
+        }
+    }
+
+    /// Queues `task`, failing with [`SpawnError::BacklogFull`] when the
+    /// queue of `priority` is full.
+    pub(crate) fn push(&self, task: CpuTask, priority: Priority) -> Result<(), SpawnError> {
+        let (queues, available) = &*self.queues;
+        let mut queues = queues.lock();
+        if queues.shutdown {
+            task.tracker.dropped();
+            return Err(SpawnError::Shutdown);
+        }
+        let queue = match priority {
+            Priority::HighPriority => &mut queues.high,
+            Priority::LowPriority => &mut queues.low,
+        };
+        if queue.len() >= self.capacity {
+            task.tracker.dropped();
+            return Err(SpawnError::BacklogFull(priority));
+        }
+        queue.push_back(task);
+        available.notify_one();
+        Ok(())
+    }
+
+    /// The number of tasks of `priority` waiting for a worker.
+    pub(crate) fn queue_depth(&self, priority: Priority) -> usize {
+        let queues = self.queues.0.lock();
+        match priority {
+            Priority::HighPriority => queues.high.len(),
+            Priority::LowPriority => queues.low.len(),
+        }
+    }
+
+    /// Stops accepting tasks. The workers exit once the queued tasks ran.
+    pub(crate) fn shutdown(&self) {
+        let (queues, available) = &*self.queues;
+        queues.lock().shutdown = true;
+        available.notify_all();
+    }
+
+    /// Shuts down and waits for the workers to exit.
+    pub(crate) fn join(&self) {
+        self.shutdown();
+        for thread in self.threads.lock().drain(..) {
+            thread.join().ok();
+        }
+    }
+}
+
+/// The loop of a worker: runs the queued tasks whose [`Job`] was not
+/// dropped, until the pool is shut down and drained.
+fn work(queues: &(Mutex<Queues>, Condvar)) {
+    let (queues, available) = queues;
+    loop {
+        let task = {
+            let mut guard = queues.lock();
+            loop {
+                if let Some(task) = guard.pop() {
+                    break task;
+                }
+                if guard.shutdown {
+                    return;
+                }
+                available.wait(&mut guard);
+            }
+        };
+        if task.cancel.is_cancelled() {
+            task.tracker.skipped();
+            continue;
+        }
+        let started = task.tracker.started();
+        // A panicking task only fails its own job.
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(task.run)) {
+            Ok(()) => task.tracker.completed(started),
+            Err(_) => task.tracker.panicked(),
+        }
+    }
+}
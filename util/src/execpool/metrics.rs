@@ -0,0 +1,217 @@
+use std::{sync::Arc, time::Instant};
+
+use crate::metrics::{Counter, Gauge, Histogram, Labels, Metric, MetricName, Sample};
+
+use super::*;
+
+const ENQUEUED: MetricName = MetricName {
+    name: "algod_execpool_tasks_enqueued_total",
+    description: "Tasks queued on the executor",
+};
+const STARTED: MetricName = MetricName {
+    name: "algod_execpool_tasks_started_total",
+    description: "Tasks that started running",
+};
+const COMPLETED: MetricName = MetricName {
+    name: "algod_execpool_tasks_completed_total",
+    description: "Tasks that ran to completion",
+};
+const CANCELLED: MetricName = MetricName {
+    name: "algod_execpool_tasks_cancelled_total",
+    description: "Tasks cancelled by dropping their job",
+};
+const DROPPED: MetricName = MetricName {
+    name: "algod_execpool_tasks_dropped_total",
+    description: "Tasks refused because the queue was full or the executor shut down",
+};
+const PANICKED: MetricName = MetricName {
+    name: "algod_execpool_tasks_panicked_total",
+    description: "Tasks that panicked while running",
+};
+const QUEUE_DEPTH: MetricName = MetricName {
+    name: "algod_execpool_queue_depth",
+    description: "Tasks queued on the executor that did not start yet",
+};
+const QUEUE_WAIT: MetricName = MetricName {
+    name: "algod_execpool_queue_wait_seconds",
+    description: "Time tasks waited in the queue before starting",
+};
+const RUN_TIME: MetricName = MetricName {
+    name: "algod_execpool_run_seconds",
+    description: "Time tasks ran",
+};
+
+/// The metrics of the tasks of one priority of an executor.
+#[derive(Debug)]
+pub struct PriorityMetrics {
+    pub enqueued: Counter,
+    pub started: Counter,
+    pub completed: Counter,
+    pub cancelled: Counter,
+    pub dropped: Counter,
+    pub panicked: Counter,
+    pub queue_depth: Gauge,
+    pub queue_wait: Histogram,
+    pub run_time: Histogram,
+}
+
+/// A snapshot of the counters of [`PriorityMetrics`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PriorityStats {
+    pub enqueued: u64,
+    pub started: u64,
+    pub completed: u64,
+    pub cancelled: u64,
+    pub dropped: u64,
+    pub panicked: u64,
+}
+
+impl PriorityMetrics {
+    fn new(executor: &str, priority: Priority) -> Self {
+        let labels: Labels = vec![
+            ("executor".to_string(), executor.to_string()),
+            ("priority".to_string(), priority.label().to_string()),
+        ];
+        Self {
+            enqueued: Counter::new(ENQUEUED, labels.clone()),
+            started: Counter::new(STARTED, labels.clone()),
+            completed: Counter::new(COMPLETED, labels.clone()),
+            cancelled: Counter::new(CANCELLED, labels.clone()),
+            dropped: Counter::new(DROPPED, labels.clone()),
+            panicked: Counter::new(PANICKED, labels.clone()),
+            queue_depth: Gauge::new(QUEUE_DEPTH, labels.clone()),
+            queue_wait: Histogram::new(QUEUE_WAIT, labels.clone()),
+            run_time: Histogram::new(RUN_TIME, labels),
+        }
+    }
+
+    /// A task was queued.
+    pub(crate) fn enqueued(&self) {
+        self.enqueued.inc();
+        self.queue_depth.add(1);
+    }
+
+    pub fn stats(&self) -> PriorityStats {
+        PriorityStats {
+            enqueued: self.enqueued.get(),
+            started: self.started.get(),
+            completed: self.completed.get(),
+            cancelled: self.cancelled.get(),
+            dropped: self.dropped.get(),
+            panicked: self.panicked.get(),
+        }
+    }
+
+    fn collect(&self, out: &mut Vec<Sample>) {
+        self.enqueued.collect(out);
+        self.started.collect(out);
+        self.completed.collect(out);
+        self.cancelled.collect(out);
+        self.dropped.collect(out);
+        self.panicked.collect(out);
+        self.queue_depth.collect(out);
+        self.queue_wait.collect(out);
+        self.run_time.collect(out);
+    }
+}
+
+/// ExecutorMetrics counts the tasks of an executor, per priority. The
+/// executor registers them in the default registry while it lives.
+#[derive(Debug)]
+pub struct ExecutorMetrics {
+    high: PriorityMetrics,
+    low: PriorityMetrics,
+}
+
+impl ExecutorMetrics {
+    pub fn new(executor: &str) -> Self {
+        Self {
+            high: PriorityMetrics::new(executor, Priority::HighPriority),
+            low: PriorityMetrics::new(executor, Priority::LowPriority),
+        }
+    }
+
+    pub fn priority(&self, priority: Priority) -> &PriorityMetrics {
+        match priority {
+            Priority::HighPriority => &self.high,
+            Priority::LowPriority => &self.low,
+        }
+    }
+}
+
+impl Metric for ExecutorMetrics {
+    fn collect(&self, out: &mut Vec<Sample>) {
+        self.high.collect(out);
+        self.low.collect(out);
+    }
+}
+
+/// Registers `metrics` in the default registry.
+pub(crate) fn register(metrics: &Arc<ExecutorMetrics>) {
+    crate::metrics::default_registry().register(Arc::clone(metrics) as Arc<dyn Metric>);
+}
+
+/// Removes `metrics` from the default registry.
+pub(crate) fn deregister(metrics: &Arc<ExecutorMetrics>) {
+    let metric: Arc<dyn Metric> = Arc::clone(metrics) as Arc<dyn Metric>;
+    crate::metrics::default_registry().deregister(&metric);
+}
+
+/// Follows a task from its queuing to its end in the metrics of its
+/// executor.
+pub(crate) struct Tracker {
+    metrics: Arc<ExecutorMetrics>,
+    priority: Priority,
+    enqueued_at: Instant,
+}
+
+impl Tracker {
+    pub(crate) fn new(metrics: &Arc<ExecutorMetrics>, priority: Priority) -> Self {
+        Self {
+            metrics: Arc::clone(metrics),
+            priority,
+            enqueued_at: Instant::now(),
+        }
+    }
+
+    fn metrics(&self) -> &PriorityMetrics {
+        self.metrics.priority(self.priority)
+    }
+
+    /// The task was refused.
+    pub(crate) fn dropped(&self) {
+        self.metrics().dropped.inc();
+    }
+
+    /// The task starts running, after waiting since it was queued.
+    pub(crate) fn started(&self) -> Instant {
+        self.metrics().started.inc();
+        self.metrics().queue_depth.add(-1);
+        self.metrics()
+            .queue_wait
+            .observe(self.enqueued_at.elapsed());
+        Instant::now()
+    }
+
+    /// The task that started at `started` completed.
+    pub(crate) fn completed(&self, started: Instant) {
+        self.metrics().completed.inc();
+        self.metrics().run_time.observe(started.elapsed());
+    }
+
+    /// The task panicked while running.
+    pub(crate) fn panicked(&self) {
+        self.metrics().panicked.inc();
+    }
+
+    /// The task was cancelled while running.
+    pub(crate) fn cancelled(&self) {
+        self.metrics().cancelled.inc();
+    }
+
+    /// The task was cancelled while queued.
+    pub(crate) fn skipped(&self) {
+        self.metrics().cancelled.inc();
+        self.metrics().queue_depth.add(-1);
+    }
+}
@@ -1,4 +1,7 @@
+mod cpu;
 mod pool;
 mod backlog;
+mod metrics;
 pub use pool::*;
 pub use backlog::*;
+pub use metrics::{ExecutorMetrics, PriorityMetrics, PriorityStats};
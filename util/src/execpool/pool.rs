@@ -13,14 +13,34 @@ pub enum Priority {
     HighPriority,
 }
 
+impl Priority {
+    /// The value of the `priority` label of the metrics.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::LowPriority => "low",
+            Self::HighPriority => "high",
+        }
+    }
+}
+
 pub fn get_parallelism() -> usize {
     num_cpus::get()
 }
+
+/// The number of high priority tasks the executor starts in a row while
+/// low priority tasks are waiting.
+pub const MAX_HIGH_PRIORITY_STREAK: usize = 8;
+
+/// The number of tasks each priority queue of an executor holds.
+pub fn queue_capacity() -> usize {
+    get_parallelism() * 2
+}
 use tokio::{select, sync::mpsc};
 
 use parking_lot::Mutex;
 use pin_project::{pin_project, pinned_drop};
-use std::{pin::Pin, sync::Arc};
+use std::{panic::AssertUnwindSafe, pin::Pin, sync::Arc};
+use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::oneshot::{error::RecvError, Receiver};
 use tokio_util::sync::CancellationToken;
 
@@ -31,12 +51,16 @@ use futures::{
 
 use tracing::warn;
 
+use super::cpu::{CpuPool, CpuTask};
+use super::metrics::{self, ExecutorMetrics, Tracker};
+
 /// Task that can be added to the executor-internal queue.
 ///
 /// Every task within the executor is represented by a [`Job`] that can be polled by the API user.
 struct Task {
     fut: Pin<Box<dyn Future<Output = ()> + Send>>,
     cancel: CancellationToken,
+    tracker: Tracker,
 
     #[allow(dead_code)]
     task_ref: Arc<()>,
@@ -47,9 +71,18 @@ impl Task {
     ///
     /// This runs the payload or cancels if the linked [`Job`] is dropped.
     async fn run(self) {
+        if self.cancel.is_cancelled() {
+            self.tracker.skipped();
+            return;
+        }
+        let started = self.tracker.started();
+        // A panicking task only fails its own job.
         tokio::select! {
-            _ = self.cancel.cancelled() => (),
-            _ = self.fut => (),
+            _ = self.cancel.cancelled() => self.tracker.cancelled(),
+            result = AssertUnwindSafe(self.fut).catch_unwind() => match result {
+                Ok(()) => self.tracker.completed(started),
+                Err(_) => self.tracker.panicked(),
+            },
         }
     }
 }
@@ -57,6 +90,27 @@ impl Task {
 /// The type of error that is returned from tasks in this module
 pub type Error = tokio::sync::oneshot::error::RecvError;
 
+/// The reason a task could not be queued on the executor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnError {
+    /// The queue of the priority is full; the caller should retry later or
+    /// shed the work.
+    BacklogFull(Priority),
+    /// The executor was shut down.
+    Shutdown,
+}
+
+impl std::fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BacklogFull(priority) => write!(f, "{:?} backlog is full", priority),
+            Self::Shutdown => write!(f, "executor was shut down"),
+        }
+    }
+}
+
+impl std::error::Error for SpawnError {}
+
 /// Job within the executor.
 ///
 /// Dropping the job will cancel its linked task.
@@ -125,6 +179,12 @@ struct State {
 
     /// The inner thread that can be used to join during drop.
     thread: Option<std::thread::JoinHandle<()>>,
+
+    /// The metrics of the tasks, registered in the default registry.
+    metrics: Arc<ExecutorMetrics>,
+
+    /// The workers running synchronous tasks.
+    cpu: Arc<CpuPool>,
     shutdown_send: mpsc::Sender<()>,
 }
 
@@ -147,11 +207,31 @@ impl Drop for State {
             warn!("DedicatedExecutor dropped without waiting for worker termination",);
         }
 
+        // the worker loop only ends on the shutdown signal
+        self.shutdown_send.try_send(()).ok();
+
         // join thread but don't care about the results
         self.thread.take().expect("not dropped yet").join().ok();
+        self.cpu.join();
+        metrics::deregister(&self.metrics);
     }
 }
 
+/// Starts `task` on the runtime of the executor, holding `join` and its
+/// `slot` until it completes.
+async fn run_task(
+    join: &Arc<tokio::sync::RwLock<()>>,
+    task: Task,
+    slot: tokio::sync::OwnedSemaphorePermit,
+) {
+    let handle = Arc::clone(join).read_owned().await;
+    tokio::task::spawn(async move {
+        task.run().await;
+        std::mem::drop(handle);
+        std::mem::drop(slot);
+    });
+}
+
 impl std::fmt::Debug for DedicatedExecutor {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // Avoid taking the mutex in debug formatting
@@ -178,10 +258,16 @@ impl DedicatedExecutor {
     /// context.', .../tokio-1.4.0/src/runtime/blocking/shutdown.rs:51:21
     pub fn new(thread_name: &str, num_threads: Option<usize>) -> Self {
         let thread_name = thread_name.to_string();
-
-        let (high_pri_tx_tasks, mut high_pri_rx_tasks) =
-            mpsc::channel::<Task>(get_parallelism() * 2);
-        let (low_pri_tx_tasks, mut low_pri_rx_tasks) = mpsc::channel::<Task>(get_parallelism() * 2);
+        let metrics = Arc::new(ExecutorMetrics::new(&thread_name));
+        metrics::register(&metrics);
+        let cpu = Arc::new(CpuPool::new(
+            &thread_name,
+            num_threads.unwrap_or(get_parallelism()),
+            queue_capacity(),
+        ));
+
+        let (high_pri_tx_tasks, mut high_pri_rx_tasks) = mpsc::channel::<Task>(queue_capacity());
+        let (low_pri_tx_tasks, mut low_pri_rx_tasks) = mpsc::channel::<Task>(queue_capacity());
         let (shutdown_send, mut shutdown_recv) = mpsc::channel(1);
 
         let (tx_shutdown, rx_shutdown) = tokio::sync::oneshot::channel();
@@ -199,24 +285,39 @@ impl DedicatedExecutor {
                 //
                 // We therefore use a RwLock to wait for tasks to complete
                 let join = Arc::new(tokio::sync::RwLock::new(()));
+                // Consecutive high priority tasks taken while low priority
+                // tasks were waiting.
+                let mut high_streak = 0;
+                // Tasks beyond the slots wait in the queues, where they are
+                // taken by priority.
+                let slots = Arc::new(tokio::sync::Semaphore::new(queue_capacity()));
                 loop {
+                    let slot = Arc::clone(&slots)
+                        .acquire_owned()
+                        .await
+                        .expect("slots are never closed");
+                    // Starvation protection: after a streak of high priority
+                    // tasks, a waiting low priority task gets its turn.
+                    if high_streak >= MAX_HIGH_PRIORITY_STREAK {
+                        high_streak = 0;
+                        if let Ok(task) = low_pri_rx_tasks.try_recv() {
+                            run_task(&join, task, slot).await;
+                            continue;
+                        }
+                    }
                     select! {
+                    biased;
                     Some(task) = high_pri_rx_tasks.recv() => {
-                        let join = Arc::clone(&join);
-                        let handle = join.read_owned().await;
-                        tokio::task::spawn(async move {
-                            task.run().await;
-                            std::mem::drop(handle);
-                        });
-                        continue;
+                        if low_pri_rx_tasks.is_empty() {
+                            high_streak = 0;
+                        } else {
+                            high_streak += 1;
+                        }
+                        run_task(&join, task, slot).await;
                     }
                     Some(task) = low_pri_rx_tasks.recv() => {
-                        let join = Arc::clone(&join);
-                        let handle = join.read_owned().await;
-                        tokio::task::spawn(async move {
-                            task.run().await;
-                            std::mem::drop(handle);
-                        });
+                        high_streak = 0;
+                        run_task(&join, task, slot).await;
                     }
                     _ = shutdown_recv.recv() => {break;},
                     }
@@ -236,6 +337,8 @@ impl DedicatedExecutor {
             task_refs: Arc::new(()),
             completed_shutdown: rx_shutdown.map_err(Arc::new).boxed().shared(),
             thread: Some(thread),
+            metrics,
+            cpu,
             shutdown_send,
         };
 
@@ -247,9 +350,64 @@ impl DedicatedExecutor {
     /// Runs the specified Future (and any tasks it spawns) on the
     /// `DedicatedExecutor`.
     ///
-    /// Currently all tasks are added to the tokio executor
-    /// immediately and compete for the threadpool's resources.
-    pub fn spawn<T>(&self, task: T, priority: Priority) -> Job<T::Output>
+    /// Up to [`queue_capacity`] tasks run at once and compete for the
+    /// threadpool's resources; the others wait in the queue of their
+    /// priority, where high priority tasks are started first.
+    ///
+    /// Fails with [`SpawnError::BacklogFull`] when the queue of `priority`
+    /// is full; use [`spawn_wait`](Self::spawn_wait) to wait for room
+    /// instead.
+    pub fn spawn<T>(&self, task: T, priority: Priority) -> Result<Job<T::Output>, SpawnError>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        let (task, job) = self.task(task, priority);
+        self.try_send(task, priority)?;
+        Ok(job)
+    }
+
+    /// Queues the specified Future like [`spawn`](Self::spawn), waiting
+    /// for room in the queue of `priority` when it is full.
+    pub async fn spawn_wait<T>(
+        &self,
+        task: T,
+        priority: Priority,
+    ) -> Result<Job<T::Output>, SpawnError>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        let (task, job) = self.task(task, priority);
+        // Wait for room without holding the lock.
+        let requests = match self.requests(priority) {
+            Some(requests) => requests,
+            None => {
+                task.tracker.dropped();
+                return Err(SpawnError::Shutdown);
+            }
+        };
+        match requests.send(task).await {
+            Ok(()) => {
+                self.metrics().priority(priority).enqueued();
+                Ok(job)
+            }
+            Err(mpsc::error::SendError(task)) => {
+                task.tracker.dropped();
+                Err(SpawnError::Shutdown)
+            }
+        }
+    }
+
+    /// The number of tasks of `priority` waiting to start.
+    pub fn queue_depth(&self, priority: Priority) -> usize {
+        self.requests(priority)
+            .map(|requests| requests.max_capacity() - requests.capacity())
+            .unwrap_or_default()
+    }
+
+    /// Wraps `task` to run on the executor, linked to the returned [`Job`].
+    fn task<T>(&self, task: T, priority: Priority) -> (Task, Job<T::Output>)
     where
         T: Future + Send + 'static,
         T::Output: Send + 'static,
@@ -263,29 +421,99 @@ impl DedicatedExecutor {
             }
         });
         let cancel = CancellationToken::new();
-        let mut state = self.state.lock();
+        let state = self.state.lock();
         let task = Task {
             fut,
             cancel: cancel.clone(),
+            tracker: Tracker::new(&state.metrics, priority),
             task_ref: Arc::clone(&state.task_refs),
         };
-        if let Some(requests) = &mut state.high_pri_requests && priority == Priority::HighPriority{
-            // would fail if someone has started shutdown
-            requests.try_send(task).ok();
-        } else if let Some(requests) = &mut state.low_pri_requests && priority == Priority::LowPriority{
-            // would fail if someone has started shutdown
-            requests.try_send(task).ok();
-        } else {
-            warn!("tried to schedule task on an executor that was shutdown");
-        }
+        let job = Job {
+            rx,
+            cancel,
+            detached: false,
+        };
+        (task, job)
+    }
 
-        Job {
+    /// Runs the synchronous, CPU bound function `f` on the CPU workers of
+    /// the `DedicatedExecutor`, so that it does not block an async runtime.
+    ///
+    /// The workers take high priority tasks first. Dropping the returned
+    /// [`Job`] cancels the task if it has not started yet. Fails with
+    /// [`SpawnError::BacklogFull`] when [`queue_capacity`] tasks of
+    /// `priority` are already waiting for a worker.
+    pub fn spawn_blocking<F, R>(&self, f: F, priority: Priority) -> Result<Job<R>, SpawnError>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let cancel = CancellationToken::new();
+        let state = self.state.lock();
+        let task = CpuTask {
+            run: Box::new(move || {
+                if tx.send(f()).is_err() {
+                    warn!("Spawned task output ignored: receiver dropped")
+                }
+            }),
+            cancel: cancel.clone(),
+            tracker: Tracker::new(&state.metrics, priority),
+            task_ref: Arc::clone(&state.task_refs),
+        };
+        state.cpu.push(task, priority)?;
+        state.metrics.priority(priority).enqueued();
+        Ok(Job {
             rx,
             cancel,
             detached: false,
+        })
+    }
+
+    /// The number of synchronous tasks of `priority` waiting for a CPU
+    /// worker.
+    pub fn blocking_queue_depth(&self, priority: Priority) -> usize {
+        self.state.lock().cpu.queue_depth(priority)
+    }
+
+    /// The queue of `priority`, unless the executor was shut down.
+    fn requests(&self, priority: Priority) -> Option<mpsc::Sender<Task>> {
+        let state = self.state.lock();
+        match priority {
+            Priority::HighPriority => state.high_pri_requests.clone(),
+            Priority::LowPriority => state.low_pri_requests.clone(),
         }
     }
 
+    fn try_send(&self, task: Task, priority: Priority) -> Result<(), SpawnError> {
+        let requests = match self.requests(priority) {
+            Some(requests) => requests,
+            None => {
+                task.tracker.dropped();
+                return Err(SpawnError::Shutdown);
+            }
+        };
+        match requests.try_send(task) {
+            Ok(()) => {
+                self.metrics().priority(priority).enqueued();
+                Ok(())
+            }
+            Err(TrySendError::Full(task)) => {
+                task.tracker.dropped();
+                Err(SpawnError::BacklogFull(priority))
+            }
+            Err(TrySendError::Closed(task)) => {
+                task.tracker.dropped();
+                Err(SpawnError::Shutdown)
+            }
+        }
+    }
+
+    /// The metrics of the tasks of this executor and its clones.
+    pub fn metrics(&self) -> Arc<ExecutorMetrics> {
+        Arc::clone(&self.state.lock().metrics)
+    }
+
     /// Number of currently active tasks.
     pub fn tasks(&self) -> usize {
         let state = self.state.lock();
@@ -302,6 +530,7 @@ impl DedicatedExecutor {
         state.shutdown_send.try_send(()).ok();
         state.high_pri_requests = None;
         state.low_pri_requests = None;
+        state.cpu.shutdown();
     }
 
     /// Stops all subsequent task executions, and waits for the worker
@@ -327,13 +556,18 @@ impl DedicatedExecutor {
 
         // wait for completion while not holding the mutex to avoid
         // deadlocks
-        handle.await.expect("Thread died?")
+        handle.await.expect("Thread died?");
+
+        // the CPU workers exit once their queues are drained
+        let cpu = Arc::clone(&self.state.lock().cpu);
+        tokio::task::spawn_blocking(move || cpu.join()).await.ok();
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::execpool::{Backlog, PriorityStats};
     use std::{
         sync::{Arc, Barrier},
         time::Duration,
@@ -344,8 +578,10 @@ mod tests {
     async fn basic() {
         let barrier = Arc::new(Barrier::new(2));
 
-        let exec = DedicatedExecutor::new("Test DedicatedExecutor", 1);
-        let dedicated_task = exec.spawn(do_work(42, Arc::clone(&barrier)), Priority::LowPriority);
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
+        let dedicated_task = exec
+            .spawn(do_work(42, Arc::clone(&barrier)), Priority::LowPriority)
+            .unwrap();
 
         // Note the dedicated task will never complete if it runs on
         // the main tokio thread (as this test is not using the
@@ -362,11 +598,12 @@ mod tests {
     #[tokio::test]
     async fn basic_clone() {
         let barrier = Arc::new(Barrier::new(2));
-        let exec = DedicatedExecutor::new("Test DedicatedExecutor", 1);
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
         // Run task on clone should work fine
         let dedicated_task = exec
             .clone()
-            .spawn(do_work(42, Arc::clone(&barrier)), Priority::LowPriority);
+            .spawn(do_work(42, Arc::clone(&barrier)), Priority::LowPriority)
+            .unwrap();
         barrier.wait();
         assert_eq!(dedicated_task.await.unwrap(), 42);
 
@@ -376,11 +613,13 @@ mod tests {
     #[tokio::test]
     async fn drop_clone() {
         let barrier = Arc::new(Barrier::new(2));
-        let exec = DedicatedExecutor::new("Test DedicatedExecutor", 1);
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
 
         drop(exec.clone());
 
-        let task = exec.spawn(do_work(42, Arc::clone(&barrier)), Priority::LowPriority);
+        let task = exec
+            .spawn(do_work(42, Arc::clone(&barrier)), Priority::LowPriority)
+            .unwrap();
         barrier.wait();
         assert_eq!(task.await.unwrap(), 42);
 
@@ -398,7 +637,7 @@ mod tests {
             }
         }
 
-        let exec = DedicatedExecutor::new("Test DedicatedExecutor", 1);
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
         let _s = S(exec);
 
         // this must not lead to a double-panic and SIGILL
@@ -410,9 +649,13 @@ mod tests {
         let barrier = Arc::new(Barrier::new(3));
 
         // make an executor with two threads
-        let exec = DedicatedExecutor::new("Test DedicatedExecutor", 2);
-        let dedicated_task1 = exec.spawn(do_work(11, Arc::clone(&barrier)), Priority::LowPriority);
-        let dedicated_task2 = exec.spawn(do_work(42, Arc::clone(&barrier)), Priority::HighPriority);
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(2));
+        let dedicated_task1 = exec
+            .spawn(do_work(11, Arc::clone(&barrier)), Priority::LowPriority)
+            .unwrap();
+        let dedicated_task2 = exec
+            .spawn(do_work(42, Arc::clone(&barrier)), Priority::HighPriority)
+            .unwrap();
 
         // block main thread until completion of other two tasks
         barrier.wait();
@@ -426,24 +669,26 @@ mod tests {
 
     #[tokio::test]
     async fn tokio_spawn() {
-        let exec = DedicatedExecutor::new("Test DedicatedExecutor", 2);
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(2));
 
         // spawn a task that spawns to other tasks and ensure they run on the dedicated
         // executor
-        let dedicated_task = exec.spawn(
-            async move {
-                // spawn separate tasks
-                let t1 = tokio::task::spawn(async {
-                    assert_eq!(
-                        std::thread::current().name(),
-                        Some("Test DedicatedExecutor")
-                    );
-                    25usize
-                });
-                t1.await.unwrap()
-            },
-            Priority::LowPriority,
-        );
+        let dedicated_task = exec
+            .spawn(
+                async move {
+                    // spawn separate tasks
+                    let t1 = tokio::task::spawn(async {
+                        assert_eq!(
+                            std::thread::current().name(),
+                            Some("Test DedicatedExecutor")
+                        );
+                        25usize
+                    });
+                    t1.await.unwrap()
+                },
+                Priority::LowPriority,
+            )
+            .unwrap();
 
         // Validate the inner task ran to completion (aka it did not panic)
         assert_eq!(dedicated_task.await.unwrap(), 25);
@@ -453,22 +698,26 @@ mod tests {
 
     #[tokio::test]
     async fn panic_on_executor() {
-        let exec = DedicatedExecutor::new("Test DedicatedExecutor", 1);
-        let dedicated_task = exec.spawn(
-            async move {
-                if true {
-                    panic!("At the disco, on the dedicated task scheduler");
-                } else {
-                    42
-                }
-            },
-            Priority::LowPriority,
-        );
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
+        let dedicated_task = exec
+            .spawn(
+                async move {
+                    if true {
+                        panic!("At the disco, on the dedicated task scheduler");
+                    } else {
+                        42
+                    }
+                },
+                Priority::LowPriority,
+            )
+            .unwrap();
 
         // should not be able to get the result
         dedicated_task.await.unwrap_err();
 
         exec.join().await;
+        let low = exec.metrics().priority(Priority::LowPriority).stats();
+        assert_eq!((low.completed, low.panicked), (0, 1));
     }
 
     #[tokio::test]
@@ -476,14 +725,16 @@ mod tests {
         let barrier = Arc::new(Barrier::new(2));
         let captured = Arc::clone(&barrier);
 
-        let exec = DedicatedExecutor::new("Test DedicatedExecutor", 1);
-        let dedicated_task = exec.spawn(
-            async move {
-                tokio::time::sleep(tokio::time::Duration::from_millis(1)).await;
-                do_work(42, captured).await
-            },
-            Priority::LowPriority,
-        );
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
+        let dedicated_task = exec
+            .spawn(
+                async move {
+                    tokio::time::sleep(tokio::time::Duration::from_millis(1)).await;
+                    do_work(42, captured).await
+                },
+                Priority::LowPriority,
+            )
+            .unwrap();
 
         exec.shutdown();
         // block main thread until completion of the outstanding task
@@ -497,44 +748,324 @@ mod tests {
 
     #[tokio::test]
     async fn executor_submit_task_after_shutdown() {
-        let exec = DedicatedExecutor::new("Test DedicatedExecutor", 1);
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
 
         // Simulate trying to submit tasks once executor has shutdown
         exec.shutdown();
-        let dedicated_task = exec.spawn(async { 11 }, Priority::LowPriority);
-
-        // task should complete, but return an error
-        dedicated_task.await.unwrap_err();
+        // the task is refused
+        assert_eq!(
+            exec.spawn(async { 11 }, Priority::LowPriority).unwrap_err(),
+            SpawnError::Shutdown
+        );
 
         exec.join().await;
     }
 
     #[tokio::test]
     async fn executor_submit_task_after_clone_shutdown() {
-        let exec = DedicatedExecutor::new("Test DedicatedExecutor", 1);
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
 
         // shutdown the clone (but not the exec)
         exec.clone().join().await;
 
         // Simulate trying to submit tasks once executor has shutdown
-        let dedicated_task = exec.spawn(async { 11 }, Priority::LowPriority);
+        // the task is refused
+        assert_eq!(
+            exec.spawn(async { 11 }, Priority::LowPriority).unwrap_err(),
+            SpawnError::Shutdown
+        );
 
-        // task should complete, but return an error
-        dedicated_task.await.unwrap_err();
+        exec.join().await;
+    }
+
+    #[tokio::test]
+    async fn spawn_wait_and_spawn() {
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
+        let waited = exec
+            .spawn_wait(async { 11 }, Priority::HighPriority)
+            .await
+            .unwrap();
+        assert_eq!(waited.await.unwrap(), 11);
+        let spawned = exec.spawn(async { 22 }, Priority::LowPriority).unwrap();
+        assert_eq!(spawned.await.unwrap(), 22);
+        assert_eq!(exec.queue_depth(Priority::LowPriority), 0);
+
+        // Once shut down, tasks are refused instead of dropped.
+        exec.shutdown();
+        assert_eq!(
+            exec.spawn(async { 33 }, Priority::LowPriority).unwrap_err(),
+            SpawnError::Shutdown
+        );
+        let backlog = Backlog::new(exec.clone(), Priority::HighPriority);
+        assert_eq!(
+            backlog.enqueue(async { 44 }).await.unwrap_err(),
+            SpawnError::Shutdown
+        );
+
+        exec.join().await;
+    }
+
+    #[tokio::test]
+    async fn high_priority_tasks_start_first() {
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
+
+        // Occupy every slot, so that the next tasks wait in the queues.
+        let gate = Arc::new(tokio::sync::Semaphore::new(0));
+        let blockers: Vec<_> = (0..queue_capacity())
+            .map(|_| {
+                let gate = Arc::clone(&gate);
+                exec.spawn(
+                    async move {
+                        gate.acquire().await.unwrap().forget();
+                    },
+                    Priority::LowPriority,
+                )
+                .unwrap()
+            })
+            .collect();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while exec.queue_depth(Priority::LowPriority) > 0 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let order = Arc::new(Mutex::new(vec![]));
+        let low_order = Arc::clone(&order);
+        let low = exec
+            .spawn(
+                async move { low_order.lock().push(Priority::LowPriority) },
+                Priority::LowPriority,
+            )
+            .unwrap();
+        let high_order = Arc::clone(&order);
+        let high = exec
+            .spawn(
+                async move { high_order.lock().push(Priority::HighPriority) },
+                Priority::HighPriority,
+            )
+            .unwrap();
+        assert_eq!(exec.queue_depth(Priority::HighPriority), 1);
+
+        gate.add_permits(queue_capacity());
+        high.await.unwrap();
+        low.await.unwrap();
+        for blocker in blockers {
+            blocker.await.unwrap();
+        }
+        assert_eq!(
+            *order.lock(),
+            vec![Priority::HighPriority, Priority::LowPriority]
+        );
+
+        exec.join().await;
+    }
+
+    #[tokio::test]
+    async fn full_queues_refuse_tasks() {
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
+
+        // Occupy every slot and fill the low priority queue.
+        let gate = Arc::new(tokio::sync::Semaphore::new(0));
+        let block = || {
+            let gate = Arc::clone(&gate);
+            async move {
+                gate.acquire().await.unwrap().forget();
+            }
+        };
+        let running: Vec<_> = (0..queue_capacity())
+            .map(|_| exec.spawn(block(), Priority::LowPriority).unwrap())
+            .collect();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while exec.queue_depth(Priority::LowPriority) > 0 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let queued: Vec<_> = (0..queue_capacity())
+            .map(|_| exec.spawn(block(), Priority::LowPriority).unwrap())
+            .collect();
+
+        assert_eq!(
+            exec.spawn(async { 1 }, Priority::LowPriority).unwrap_err(),
+            SpawnError::BacklogFull(Priority::LowPriority)
+        );
+        // The other queue still takes tasks.
+        let high = exec.spawn(async { 2 }, Priority::HighPriority).unwrap();
+
+        gate.add_permits(2 * queue_capacity());
+        assert_eq!(high.await.unwrap(), 2);
+        for job in running.into_iter().chain(queued) {
+            job.await.unwrap();
+        }
+
+        exec.join().await;
+    }
+
+    #[tokio::test]
+    async fn blocking_tasks() {
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
+        let job = exec
+            .spawn_blocking(
+                || std::thread::current().name().map(String::from),
+                Priority::HighPriority,
+            )
+            .unwrap();
+        assert_eq!(
+            job.await.unwrap().as_deref(),
+            Some("Test DedicatedExecutor")
+        );
+
+        // Hold the only worker, so that the next tasks wait in the queue.
+        let barrier = Arc::new(Barrier::new(2));
+        let captured = Arc::clone(&barrier);
+        let busy = exec
+            .spawn_blocking(move || captured.wait(), Priority::LowPriority)
+            .unwrap();
+        wait_for_blocking_queue(&exec, 0).await;
+        let ran = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flag = Arc::clone(&ran);
+        let cancelled = exec
+            .spawn_blocking(
+                move || flag.store(true, std::sync::atomic::Ordering::SeqCst),
+                Priority::LowPriority,
+            )
+            .unwrap();
+        let panicking = exec
+            .spawn_blocking(|| panic!("in a CPU task"), Priority::LowPriority)
+            .unwrap();
+        wait_for_blocking_queue(&exec, 2).await;
+
+        // Dropping a job cancels its task before it starts.
+        drop(cancelled);
+        barrier.wait();
+        busy.await.unwrap();
+        panicking.await.unwrap_err();
+        assert!(!ran.load(std::sync::atomic::Ordering::SeqCst));
+
+        exec.shutdown();
+        assert_eq!(
+            exec.spawn_blocking(|| 1, Priority::HighPriority)
+                .unwrap_err(),
+            SpawnError::Shutdown
+        );
 
         exec.join().await;
+        let low = exec.metrics().priority(Priority::LowPriority).stats();
+        assert_eq!((low.completed, low.panicked), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn full_cpu_queues_refuse_tasks() {
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
+
+        // Hold the only worker and fill the low priority queue.
+        let barrier = Arc::new(Barrier::new(2));
+        let captured = Arc::clone(&barrier);
+        let busy = exec
+            .spawn_blocking(move || captured.wait(), Priority::LowPriority)
+            .unwrap();
+        let low = exec.metrics();
+        let low = low.priority(Priority::LowPriority);
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while low.started.get() == 0 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let queued: Vec<_> = (0..queue_capacity())
+            .map(|i| {
+                exec.spawn_blocking(move || i, Priority::LowPriority)
+                    .unwrap()
+            })
+            .collect();
+
+        assert_eq!(
+            exec.spawn_blocking(|| 0, Priority::LowPriority)
+                .unwrap_err(),
+            SpawnError::BacklogFull(Priority::LowPriority)
+        );
+        assert_eq!(low.dropped.get(), 1);
+        // The other queue still takes tasks.
+        let high = exec.spawn_blocking(|| 1, Priority::HighPriority).unwrap();
+
+        barrier.wait();
+        busy.await.unwrap();
+        assert_eq!(high.await.unwrap(), 1);
+        for (i, job) in queued.into_iter().enumerate() {
+            assert_eq!(job.await.unwrap(), i);
+        }
+
+        exec.join().await;
+    }
+
+    #[tokio::test]
+    async fn metrics() {
+        let exec = DedicatedExecutor::new("Test ExecutorMetrics", Some(1));
+        exec.spawn(async { 1 }, Priority::HighPriority)
+            .unwrap()
+            .await
+            .unwrap();
+        exec.spawn_blocking(|| 2, Priority::LowPriority)
+            .unwrap()
+            .await
+            .unwrap();
+
+        // Hold the only CPU worker to cancel a queued task.
+        let barrier = Arc::new(Barrier::new(2));
+        let captured = Arc::clone(&barrier);
+        let busy = exec
+            .spawn_blocking(move || captured.wait(), Priority::LowPriority)
+            .unwrap();
+        let cancelled = exec.spawn_blocking(|| 3, Priority::LowPriority).unwrap();
+        wait_for_blocking_queue(&exec, 1).await;
+        drop(cancelled);
+        barrier.wait();
+        busy.await.unwrap();
+
+        exec.shutdown();
+        exec.spawn(async { 4 }, Priority::HighPriority).unwrap_err();
+        exec.join().await;
+
+        let metrics = exec.metrics();
+        let high = metrics.priority(Priority::HighPriority);
+        assert_eq!(
+            high.stats(),
+            PriorityStats {
+                enqueued: 1,
+                started: 1,
+                completed: 1,
+                cancelled: 0,
+                dropped: 1,
+                panicked: 0,
+            }
+        );
+        assert_eq!(high.run_time.count(), 1);
+        let low = metrics.priority(Priority::LowPriority).stats();
+        assert_eq!((low.enqueued, low.completed, low.cancelled), (3, 2, 1));
+        for priority in [Priority::HighPriority, Priority::LowPriority] {
+            assert_eq!(metrics.priority(priority).queue_depth.get(), 0);
+        }
+
+        let text = crate::metrics::default_registry().write_metrics(&[]);
+        assert!(text.contains(
+            "algod_execpool_tasks_completed_total{executor=\"Test ExecutorMetrics\",priority=\"low\"} 2"
+        ));
     }
 
     #[tokio::test]
     async fn executor_join() {
-        let exec = DedicatedExecutor::new("Test DedicatedExecutor", 1);
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
         // test it doesn't hang
         exec.join().await;
     }
 
     #[tokio::test]
     async fn executor_join2() {
-        let exec = DedicatedExecutor::new("Test DedicatedExecutor", 1);
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
         // test it doesn't hang
         exec.join().await;
         exec.join().await;
@@ -543,7 +1074,7 @@ mod tests {
     #[tokio::test]
     #[allow(clippy::redundant_clone)]
     async fn executor_clone_join() {
-        let exec = DedicatedExecutor::new("Test DedicatedExecutor", 1);
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
         // test it doesn't hang
         exec.clone().join().await;
         exec.clone().join().await;
@@ -553,23 +1084,27 @@ mod tests {
     #[tokio::test]
     async fn drop_receiver() {
         // create empty executor
-        let exec = DedicatedExecutor::new("Test DedicatedExecutor", 1);
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
         assert_eq!(exec.tasks(), 0);
 
         // create first blocked task
         let barrier1 = Arc::new(AsyncBarrier::new(2));
-        let dedicated_task1 = exec.spawn(
-            do_work_async(11, Arc::clone(&barrier1)),
-            Priority::LowPriority,
-        );
+        let dedicated_task1 = exec
+            .spawn(
+                do_work_async(11, Arc::clone(&barrier1)),
+                Priority::LowPriority,
+            )
+            .unwrap();
         assert_eq!(exec.tasks(), 1);
 
         // create second blocked task
         let barrier2 = Arc::new(AsyncBarrier::new(2));
-        let dedicated_task2 = exec.spawn(
-            do_work_async(22, Arc::clone(&barrier2)),
-            Priority::LowPriority,
-        );
+        let dedicated_task2 = exec
+            .spawn(
+                do_work_async(22, Arc::clone(&barrier2)),
+                Priority::LowPriority,
+            )
+            .unwrap();
         assert_eq!(exec.tasks(), 2);
 
         // cancel task
@@ -590,25 +1125,29 @@ mod tests {
     #[tokio::test]
     async fn detach_receiver() {
         // create empty executor
-        let exec = DedicatedExecutor::new("Test DedicatedExecutor", 1);
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", Some(1));
         assert_eq!(exec.tasks(), 0);
 
         // create first task
         // `detach()` consumes the task but doesn't abort the task (in contrast to `drop`). We'll proof the that the
         // task is still running by linking it to a 2nd task using a barrier with size 3 (two tasks plus the main thread).
         let barrier = Arc::new(AsyncBarrier::new(3));
-        let dedicated_task = exec.spawn(
-            do_work_async(11, Arc::clone(&barrier)),
-            Priority::LowPriority,
-        );
+        let dedicated_task = exec
+            .spawn(
+                do_work_async(11, Arc::clone(&barrier)),
+                Priority::LowPriority,
+            )
+            .unwrap();
         dedicated_task.detach();
         assert_eq!(exec.tasks(), 1);
 
         // create second task
-        let dedicated_task = exec.spawn(
-            do_work_async(22, Arc::clone(&barrier)),
-            Priority::HighPriority,
-        );
+        let dedicated_task = exec
+            .spawn(
+                do_work_async(22, Arc::clone(&barrier)),
+                Priority::HighPriority,
+            )
+            .unwrap();
         assert_eq!(exec.tasks(), 2);
 
         // wait a bit just to make sure that our tasks doesn't get dropped
@@ -637,6 +1176,17 @@ mod tests {
         result
     }
 
+    // waits for up to 1 sec for the number of queued synchronous tasks
+    async fn wait_for_blocking_queue(exec: &DedicatedExecutor, num: usize) {
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while exec.blocking_queue_depth(Priority::LowPriority) != num {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .expect("Did not find expected num queued tasks within a second")
+    }
+
     // waits for up to 1 sec for the correct number of tasks
     async fn wait_for_tasks(exec: &DedicatedExecutor, num: usize) {
         tokio::time::timeout(Duration::from_secs(1), async {
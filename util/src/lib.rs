@@ -1,4 +1,8 @@
 pub mod execpool;
+pub mod logging;
+pub mod metrics;
+pub mod rng;
+pub mod telemetry;
 
 pub fn is_default<T: ?Sized>(t: &T) -> bool
 where
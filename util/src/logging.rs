@@ -0,0 +1,365 @@
+//! Logging of the node, modeled on go-algorand's logging package: events
+//! are written as lines of JSON to stdout or to the live log, `node.log`,
+//! which is moved to the archive once it reaches its size limit.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use parking_lot::Mutex;
+use serde_json::{Map, Value};
+use tracing::{
+    field::{Field, Visit},
+    Event, Subscriber,
+};
+use tracing_subscriber::{
+    filter::LevelFilter,
+    layer::{Context, SubscriberExt},
+    Layer, Registry,
+};
+
+/// LogOptions describes where and what the node logs.
+#[derive(Debug, Clone)]
+pub struct LogOptions {
+    /// The live log.
+    pub live: PathBuf,
+    /// Where the live log is moved once full. `{{.Year}}`, `{{.Month}}`,
+    /// `{{.Day}}`, `{{.Hour}}`, `{{.Minute}}` and `{{.Second}}` are replaced
+    /// by the UTC time of the move, to keep several archives.
+    pub archive: PathBuf,
+    /// The size at which the live log is archived; 0 never archives it.
+    pub size_limit: u64,
+    /// Archives older than this are removed when the live log is archived.
+    pub max_age: Option<Duration>,
+    pub level: LevelFilter,
+    /// Logs to stdout instead of the live log.
+    pub to_stdout: bool,
+}
+
+/// The most verbose level logged at a go-algorand `base_logger_debug_level`:
+/// 0 panic, 1 fatal, 2 error, 3 warn, 4 info and 5 debug.
+pub fn level_filter(debug_level: u32) -> LevelFilter {
+    match debug_level {
+        0..=2 => LevelFilter::ERROR,
+        3 => LevelFilter::WARN,
+        4 => LevelFilter::INFO,
+        _ => LevelFilter::DEBUG,
+    }
+}
+
+/// Parses a duration in the format of Go's `time.ParseDuration`, such as
+/// `"720h"` or `"1h30m"`.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    const UNITS: [(&str, f64); 7] = [
+        ("ns", 1e-9),
+        ("us", 1e-6),
+        ("ms", 1e-3),
+        ("s", 1.0),
+        ("m", 60.0),
+        ("h", 3600.0),
+        ("µs", 1e-6),
+    ];
+    if s.is_empty() {
+        return None;
+    }
+    if s == "0" {
+        return Some(Duration::ZERO);
+    }
+    let mut rest = s;
+    let mut seconds = 0.0;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..number_len].parse().ok()?;
+        rest = &rest[number_len..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let (_, scale) = UNITS.iter().find(|(unit, _)| *unit == &rest[..unit_len])?;
+        rest = &rest[unit_len..];
+        seconds += number * scale;
+    }
+    Some(Duration::from_secs_f64(seconds))
+}
+
+/// Sets the logger of the process, which writes events at `options.level`
+/// and above.
+pub fn init(options: &LogOptions) -> io::Result<()> {
+    let writer: Box<dyn Write + Send> = if options.to_stdout {
+        Box::new(io::stdout())
+    } else {
+        Box::new(RotatingFile::open(options)?)
+    };
+    let subscriber = Registry::default()
+        .with(options.level)
+        .with(JsonLayer::new(writer));
+    tracing::subscriber::set_global_default(subscriber).map_err(io::Error::other)
+}
+
+/// JsonLayer writes every event as a line of JSON with its time, level,
+/// target, message and fields.
+pub struct JsonLayer<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write> JsonLayer<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+impl<S: Subscriber, W: Write + Send + 'static> Layer<S> for JsonLayer<W> {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut entry = JsonFields(Map::new());
+        event.record(&mut entry);
+        let mut entry = entry.0;
+        // Named as in the logs of go-algorand.
+        if let Some(message) = entry.remove("message") {
+            entry.insert("msg".to_string(), message);
+        }
+        entry.insert("time".to_string(), rfc3339(SystemTime::now()).into());
+        let level = event.metadata().level().as_str().to_lowercase();
+        entry.insert("level".to_string(), level.into());
+        entry.insert("target".to_string(), event.metadata().target().into());
+
+        let mut line = Value::Object(entry).to_string();
+        line.push('\n');
+        let mut writer = self.writer.lock();
+        if let Err(e) = writer
+            .write_all(line.as_bytes())
+            .and_then(|_| writer.flush())
+        {
+            eprintln!("cannot write log: {}", e);
+        }
+    }
+}
+
+struct JsonFields(Map<String, Value>);
+
+impl Visit for JsonFields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value).into());
+    }
+}
+
+/// RotatingFile appends to the live log and moves it to the archive once
+/// the next write would exceed the size limit.
+pub struct RotatingFile {
+    live: PathBuf,
+    archive: PathBuf,
+    size_limit: u64,
+    max_age: Option<Duration>,
+    file: fs::File,
+    size: u64,
+}
+
+impl RotatingFile {
+    pub fn open(options: &LogOptions) -> io::Result<Self> {
+        let file = open_append(&options.live)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            live: options.live.clone(),
+            archive: options.archive.clone(),
+            size_limit: options.size_limit,
+            max_age: options.max_age,
+            file,
+            size,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let now = SystemTime::now();
+        fs::rename(&self.live, archive_path(&self.archive, now))?;
+        self.file = open_append(&self.live)?;
+        self.size = 0;
+        if let Some(oldest) = self.max_age.and_then(|age| now.checked_sub(age)) {
+            remove_old_archives(&self.archive, oldest)?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let full = self.size + buf.len() as u64 > self.size_limit;
+        if self.size_limit > 0 && self.size > 0 && full {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<fs::File> {
+    fs::OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// The archive of the live log moved at `time`.
+fn archive_path(template: &Path, time: SystemTime) -> PathBuf {
+    let (year, month, day, hour, minute, second) = civil(time);
+    let path = template
+        .to_string_lossy()
+        .replace("{{.Year}}", &format!("{:04}", year))
+        .replace("{{.Month}}", &format!("{:02}", month))
+        .replace("{{.Day}}", &format!("{:02}", day))
+        .replace("{{.Hour}}", &format!("{:02}", hour))
+        .replace("{{.Minute}}", &format!("{:02}", minute))
+        .replace("{{.Second}}", &format!("{:02}", second));
+    PathBuf::from(path)
+}
+
+/// Removes the archives named after `template` last modified before
+/// `oldest`.
+fn remove_old_archives(template: &Path, oldest: SystemTime) -> io::Result<()> {
+    let (Some(dir), Some(name)) = (template.parent(), template.file_name()) else {
+        return Ok(());
+    };
+    let name = name.to_string_lossy();
+    // The archives share the text around the time placeholders.
+    let around = match (name.find("{{"), name.rfind("}}")) {
+        (Some(start), Some(end)) if start < end => Some((&name[..start], &name[end + 2..])),
+        _ => None,
+    };
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+        let is_archive = match around {
+            Some((prefix, suffix)) => {
+                file_name.len() >= prefix.len() + suffix.len()
+                    && file_name.starts_with(prefix)
+                    && file_name.ends_with(suffix)
+            }
+            None => file_name == name,
+        };
+        if is_archive && entry.metadata()?.modified()? < oldest {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Formats `time` in UTC, as RFC 3339 with seconds.
+pub(crate) fn rfc3339(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = civil(time);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// The UTC date and time of `time`: year, month, day, hour, minute and
+/// second, after Howard Hinnant's `civil_from_days`.
+fn civil(time: SystemTime) -> (i64, u32, u32, u32, u32, u32) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (
+        year,
+        month,
+        day,
+        (secs / 3600) as u32,
+        (secs % 3600 / 60) as u32,
+        (secs % 60) as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_and_dates() {
+        assert_eq!(
+            parse_duration("720h"),
+            Some(Duration::from_secs(720 * 3600))
+        );
+        assert_eq!(parse_duration("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("10 days"), None);
+
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        assert_eq!(rfc3339(time), "2024-02-29T12:34:56Z");
+        assert_eq!(
+            archive_path(Path::new("node.{{.Year}}{{.Month}}{{.Day}}.log"), time),
+            PathBuf::from("node.20240229.log")
+        );
+    }
+
+    #[test]
+    fn live_log_is_archived_when_full() {
+        let dir = std::env::temp_dir().join(format!("logging-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let options = LogOptions {
+            live: dir.join("node.log"),
+            archive: dir.join("node.archive.log"),
+            size_limit: 200,
+            max_age: None,
+            level: LevelFilter::INFO,
+            to_stdout: false,
+        };
+        fs::remove_file(&options.live).ok();
+        let layer = JsonLayer::new(RotatingFile::open(&options).unwrap());
+        let subscriber = Registry::default().with(options.level).with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("not logged");
+            tracing::info!(round = 7, "first");
+            tracing::warn!(round = 8, "second");
+        });
+
+        let archived = fs::read_to_string(&options.archive).unwrap();
+        let entry: Value = serde_json::from_str(archived.trim()).unwrap();
+        assert_eq!(entry["msg"], "first");
+        assert_eq!(entry["level"], "info");
+        assert_eq!(entry["round"], 7);
+        let live = fs::read_to_string(&options.live).unwrap();
+        assert_eq!(live.lines().count(), 1);
+        assert!(live.contains("\"msg\":\"second\""));
+        fs::remove_dir_all(&dir).ok();
+    }
+}
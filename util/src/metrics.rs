@@ -0,0 +1,418 @@
+//! Metrics of the node, modeled on go-algorand's util/metrics: counters,
+//! gauges and histograms registered in a [`Registry`], which renders them
+//! in the Prometheus text format for scraping.
+
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+/// Labels of a series, such as `[("priority", "high")]`.
+pub type Labels = Vec<(String, String)>;
+
+/// MetricName describes a family of series.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetricName {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+/// Sample is a value of a series, as collected for rendering.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub family: MetricName,
+    /// The Prometheus type of the family: counter, gauge or histogram.
+    pub kind: &'static str,
+    /// Appended to the family name, such as `_bucket` for histograms.
+    pub suffix: &'static str,
+    pub labels: Labels,
+    pub value: f64,
+}
+
+/// Metric is a set of series that can be registered.
+pub trait Metric: Send + Sync {
+    /// Appends the current samples of the metric to `out`.
+    fn collect(&self, out: &mut Vec<Sample>);
+}
+
+/// Counter is a monotonically increasing series.
+#[derive(Debug)]
+pub struct Counter {
+    name: MetricName,
+    labels: Labels,
+    value: AtomicU64,
+}
+
+impl Counter {
+    pub fn new(name: MetricName, labels: Labels) -> Self {
+        Self {
+            name,
+            labels,
+            value: AtomicU64::new(0),
+        }
+    }
+
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, n: u64) {
+        self.value.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+impl Metric for Counter {
+    fn collect(&self, out: &mut Vec<Sample>) {
+        out.push(Sample {
+            family: self.name,
+            kind: "counter",
+            suffix: "",
+            labels: self.labels.clone(),
+            value: self.get() as f64,
+        });
+    }
+}
+
+/// Gauge is a series that may go up and down.
+#[derive(Debug)]
+pub struct Gauge {
+    name: MetricName,
+    labels: Labels,
+    value: AtomicI64,
+}
+
+impl Gauge {
+    pub fn new(name: MetricName, labels: Labels) -> Self {
+        Self {
+            name,
+            labels,
+            value: AtomicI64::new(0),
+        }
+    }
+
+    pub fn set(&self, value: i64) {
+        self.value.store(value, Ordering::Relaxed);
+    }
+
+    /// Adds `n`, which may be negative, to the value.
+    pub fn add(&self, n: i64) {
+        self.value.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+impl Metric for Gauge {
+    fn collect(&self, out: &mut Vec<Sample>) {
+        out.push(Sample {
+            family: self.name,
+            kind: "gauge",
+            suffix: "",
+            labels: self.labels.clone(),
+            value: self.get() as f64,
+        });
+    }
+}
+
+/// The upper bounds, in seconds, of the buckets of a [`Histogram`].
+pub const DURATION_BUCKETS: [f64; 10] =
+    [0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// Histogram counts durations in the [`DURATION_BUCKETS`].
+#[derive(Debug)]
+pub struct Histogram {
+    name: MetricName,
+    labels: Labels,
+    /// The number of observations in each bucket, not cumulated.
+    buckets: [AtomicU64; DURATION_BUCKETS.len()],
+    count: AtomicU64,
+    sum_nanos: AtomicU64,
+}
+
+impl Histogram {
+    pub fn new(name: MetricName, labels: Labels) -> Self {
+        Self {
+            name,
+            labels,
+            buckets: Default::default(),
+            count: AtomicU64::new(0),
+            sum_nanos: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = DURATION_BUCKETS.iter().position(|le| seconds <= *le) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// The number of observations.
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+impl Metric for Histogram {
+    fn collect(&self, out: &mut Vec<Sample>) {
+        let sample = |suffix, labels, value| Sample {
+            family: self.name,
+            kind: "histogram",
+            suffix,
+            labels,
+            value,
+        };
+        let mut cumulated = 0;
+        for (le, bucket) in DURATION_BUCKETS.iter().zip(&self.buckets) {
+            cumulated += bucket.load(Ordering::Relaxed);
+            let mut labels = self.labels.clone();
+            labels.push(("le".to_string(), le.to_string()));
+            out.push(sample("_bucket", labels, cumulated as f64));
+        }
+        let mut labels = self.labels.clone();
+        labels.push(("le".to_string(), "+Inf".to_string()));
+        out.push(sample("_bucket", labels, self.count() as f64));
+        let sum = self.sum_nanos.load(Ordering::Relaxed) as f64 / 1e9;
+        out.push(sample("_sum", self.labels.clone(), sum));
+        out.push(sample("_count", self.labels.clone(), self.count() as f64));
+    }
+}
+
+/// Registry holds the registered metrics.
+#[derive(Default)]
+pub struct Registry {
+    metrics: Mutex<Vec<Arc<dyn Metric>>>,
+}
+
+impl std::fmt::Debug for Registry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Registry({} metrics)", self.metrics.lock().len())
+    }
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, metric: Arc<dyn Metric>) {
+        self.metrics.lock().push(metric);
+    }
+
+    pub fn deregister(&self, metric: &Arc<dyn Metric>) {
+        self.metrics
+            .lock()
+            .retain(|registered| !Arc::ptr_eq(registered, metric));
+    }
+
+    /// The samples of every registered metric.
+    pub fn collect(&self) -> Vec<Sample> {
+        let mut samples = vec![];
+        for metric in self.metrics.lock().iter() {
+            metric.collect(&mut samples);
+        }
+        samples
+    }
+
+    /// Renders the registered metrics in the Prometheus text format, each
+    /// family once, with `labels` added to every series.
+    pub fn write_metrics(&self, labels: &[(String, String)]) -> String {
+        let samples = self.collect();
+        let mut families: Vec<MetricName> = vec![];
+        for sample in &samples {
+            if !families.contains(&sample.family) {
+                families.push(sample.family);
+            }
+        }
+        let mut out = String::new();
+        for family in families {
+            let mut first = true;
+            for sample in samples.iter().filter(|sample| sample.family == family) {
+                if first {
+                    writeln!(out, "# HELP {} {}", family.name, family.description).ok();
+                    writeln!(out, "# TYPE {} {}", family.name, sample.kind).ok();
+                    first = false;
+                }
+                let series: Vec<String> = labels
+                    .iter()
+                    .chain(&sample.labels)
+                    .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+                    .collect();
+                if series.is_empty() {
+                    writeln!(out, "{}{} {}", family.name, sample.suffix, sample.value).ok();
+                } else {
+                    writeln!(
+                        out,
+                        "{}{}{{{}}} {}",
+                        family.name,
+                        sample.suffix,
+                        series.join(","),
+                        sample.value
+                    )
+                    .ok();
+                }
+            }
+        }
+        out
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The registry of the process, which the node exports.
+pub fn default_registry() -> &'static Registry {
+    static DEFAULT: OnceLock<Registry> = OnceLock::new();
+    DEFAULT.get_or_init(Registry::new)
+}
+
+const UPTIME: MetricName = MetricName {
+    name: "algod_process_uptime_seconds",
+    description: "Time since the metrics of the process were enabled",
+};
+const RESIDENT_MEMORY: MetricName = MetricName {
+    name: "algod_process_resident_memory_bytes",
+    description: "Memory of the process held in RAM",
+};
+const THREADS: MetricName = MetricName {
+    name: "algod_process_threads",
+    description: "Threads of the process",
+};
+
+/// RuntimeMetrics reports the resources of the process, as go-algorand's
+/// runtime metrics report those of the Go runtime. They are read when
+/// collected; the memory and threads only on Linux, from `/proc`.
+#[derive(Debug)]
+pub struct RuntimeMetrics {
+    started: Instant,
+}
+
+impl RuntimeMetrics {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Default for RuntimeMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metric for RuntimeMetrics {
+    fn collect(&self, out: &mut Vec<Sample>) {
+        let gauge = |family, value| Sample {
+            family,
+            kind: "gauge",
+            suffix: "",
+            labels: vec![],
+            value,
+        };
+        out.push(gauge(UPTIME, self.started.elapsed().as_secs_f64()));
+        let status = std::fs::read_to_string("/proc/self/status").unwrap_or_default();
+        for line in status.lines() {
+            let (key, value) = line.split_once(':').unwrap_or_default();
+            let value = value.trim().trim_end_matches(" kB").parse::<f64>();
+            match (key, value) {
+                ("VmRSS", Ok(kib)) => out.push(gauge(RESIDENT_MEMORY, kib * 1024.0)),
+                ("Threads", Ok(threads)) => out.push(gauge(THREADS, threads)),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Registers the [`RuntimeMetrics`] in the default registry, once, with
+/// `enable_runtime_metrics`.
+pub fn register_runtime_metrics() {
+    static REGISTERED: OnceLock<()> = OnceLock::new();
+    REGISTERED.get_or_init(|| default_registry().register(Arc::new(RuntimeMetrics::new())));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUESTS: MetricName = MetricName {
+        name: "algod_test_requests_total",
+        description: "Requests served",
+    };
+    const LATENCY: MetricName = MetricName {
+        name: "algod_test_latency_seconds",
+        description: "Time to serve a request",
+    };
+
+    #[test]
+    fn registry_renders_prometheus_text() {
+        let registry = Registry::new();
+        let ok = Arc::new(Counter::new(
+            REQUESTS,
+            vec![("code".to_string(), "200".to_string())],
+        ));
+        let failed = Arc::new(Counter::new(
+            REQUESTS,
+            vec![("code".to_string(), "500".to_string())],
+        ));
+        let latency = Arc::new(Histogram::new(LATENCY, vec![]));
+        registry.register(ok.clone());
+        registry.register(failed.clone());
+        registry.register(latency.clone());
+        ok.add(3);
+        latency.observe(Duration::from_millis(2));
+
+        let text = registry.write_metrics(&[("host".to_string(), "n1".to_string())]);
+        assert_eq!(
+            text.matches("# TYPE algod_test_requests_total counter")
+                .count(),
+            1
+        );
+        assert!(text.contains("algod_test_requests_total{host=\"n1\",code=\"200\"} 3\n"));
+        assert!(text.contains("algod_test_requests_total{host=\"n1\",code=\"500\"} 0\n"));
+        assert!(text.contains("algod_test_latency_seconds_bucket{host=\"n1\",le=\"0.001\"} 0\n"));
+        assert!(text.contains("algod_test_latency_seconds_bucket{host=\"n1\",le=\"0.005\"} 1\n"));
+        assert!(text.contains("algod_test_latency_seconds_count{host=\"n1\"} 1\n"));
+
+        let failed: Arc<dyn Metric> = failed;
+        registry.deregister(&failed);
+        assert!(!registry.write_metrics(&[]).contains("code=\"500\""));
+    }
+
+    #[test]
+    fn runtime_metrics_read_the_process() {
+        let registry = Registry::new();
+        registry.register(Arc::new(RuntimeMetrics::new()));
+        let samples = registry.collect();
+        let value = |name| {
+            samples
+                .iter()
+                .find(|sample| sample.family.name == name)
+                .map(|sample| sample.value)
+        };
+        assert!(value("algod_process_uptime_seconds").unwrap() >= 0.0);
+        if cfg!(target_os = "linux") {
+            assert!(value("algod_process_threads").unwrap() >= 1.0);
+            assert!(value("algod_process_resident_memory_bytes").unwrap() > 0.0);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! The randomness of the node. Every random choice of the node draws from a
+//! [`NodeRng`] so that, given the same seed and inputs, a test network
+//! reproduces the same run.
+
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use rand::{rngs::StdRng, seq::SliceRandom, RngCore, SeedableRng};
+
+/// NodeRng is a shared stream of randomness. Clones draw from the same
+/// stream; [`NodeRng::split`] makes an independent one.
+#[derive(Clone)]
+pub struct NodeRng {
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl std::fmt::Debug for NodeRng {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("NodeRng")
+    }
+}
+
+impl NodeRng {
+    /// A stream seeded from the operating system, for production runs.
+    pub fn from_entropy() -> Self {
+        Self::new(StdRng::from_entropy())
+    }
+
+    /// A stream reproducing the same draws for the same `seed`.
+    pub fn seeded(seed: u64) -> Self {
+        Self::new(StdRng::seed_from_u64(seed))
+    }
+
+    fn new(rng: StdRng) -> Self {
+        Self {
+            rng: Arc::new(Mutex::new(rng)),
+        }
+    }
+
+    /// Splits off an independent stream, for a subsystem that draws
+    /// concurrently with others: the draws of one stream do not shift those
+    /// of another. Streams split in the same order are the same from run to
+    /// run.
+    pub fn split(&self) -> Self {
+        let mut seed = <StdRng as SeedableRng>::Seed::default();
+        self.rng.lock().fill_bytes(&mut seed);
+        Self::new(StdRng::from_seed(seed))
+    }
+
+    /// Picks one of `items`, such as a peer to ask for a block.
+    pub fn choose<'a, T>(&self, items: &'a [T]) -> Option<&'a T> {
+        items.choose(&mut *self.rng.lock())
+    }
+
+    pub fn shuffle<T>(&self, items: &mut [T]) {
+        items.shuffle(&mut *self.rng.lock());
+    }
+}
+
+impl RngCore for NodeRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.lock().next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.lock().next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.lock().fill_bytes(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.lock().try_fill_bytes(dest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_streams_reproduce() {
+        let draws = |seed| {
+            let rng = NodeRng::seeded(seed);
+            let (mut peers, mut agreement) = (rng.split(), rng.split());
+            // Draws from one stream leave the other unchanged.
+            let mut shuffled: Vec<u32> = (0..16).collect();
+            peers.shuffle(&mut shuffled);
+            let tie_break = agreement.next_u64();
+            let peer = *peers.choose(&shuffled).unwrap();
+            (shuffled, tie_break, peer, peers.next_u64())
+        };
+        assert_eq!(draws(42), draws(42));
+        assert_ne!(draws(42), draws(43));
+
+        let rng = NodeRng::seeded(42);
+        let (_, mut agreement) = (rng.split(), rng.split());
+        assert_eq!(agreement.next_u64(), draws(42).1);
+    }
+}
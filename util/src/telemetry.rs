@@ -0,0 +1,432 @@
+//! Telemetry of the node, modeled on go-algorand's telemetry: structured
+//! events of the life of the node, tagged with the GUID of the session and
+//! sent in the background to the configured sinks, the node log or an HTTP
+//! collector.
+
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::{mpsc, Arc, OnceLock},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use parking_lot::Mutex;
+use rand::RngCore;
+use serde_json::{json, Value};
+
+/// TelemetryConfig selects where the events of the node are sent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TelemetryConfig {
+    pub enable: bool,
+    /// Tags every event of this run of the node.
+    pub session_guid: String,
+    /// Writes the events to the node log, as `telemetry_to_log`.
+    pub to_log: bool,
+    /// The `http://` URL of a collector the events are posted to.
+    pub endpoint: Option<String>,
+}
+
+/// The reason a telemetry setting is refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TelemetryError {
+    /// The override is none of `true`, `false`, `1`, `0` or a URL.
+    InvalidOverride(String),
+    /// Only plain HTTP collectors are supported.
+    UnsupportedEndpoint(String),
+}
+
+impl std::fmt::Display for TelemetryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidOverride(value) => write!(
+                f,
+                "invalid telemetry override {:?}: use true, false, 1, 0 or an http:// URL",
+                value
+            ),
+            Self::UnsupportedEndpoint(url) => {
+                write!(f, "unsupported telemetry endpoint {:?}", url)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TelemetryError {}
+
+impl TelemetryConfig {
+    /// Applies the `--telemetry-override` of the command line: `true` or
+    /// `1` enables telemetry, `false` or `0` disables it and a URL enables
+    /// it with that collector.
+    pub fn apply_override(&mut self, value: &str) -> Result<(), TelemetryError> {
+        match value {
+            "true" | "1" => self.enable = true,
+            "false" | "0" => self.enable = false,
+            url if url.starts_with("http://") => {
+                self.enable = true;
+                self.endpoint = Some(url.to_string());
+            }
+            url if url.contains("://") => {
+                return Err(TelemetryError::UnsupportedEndpoint(url.to_string()))
+            }
+            _ => return Err(TelemetryError::InvalidOverride(value.to_string())),
+        }
+        Ok(())
+    }
+}
+
+/// Makes a random (version 4) GUID for a session.
+pub fn new_session_guid(rng: &mut dyn RngCore) -> String {
+    let mut bytes = [0u8; 16];
+    rng.fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// An event of the life of the node.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TelemetryEvent {
+    Startup {
+        version: String,
+        genesis_id: String,
+    },
+    Shutdown,
+    CatchupStart {
+        round: u64,
+    },
+    /// Catchup fetched blocks up to `round`, on the way to `target`.
+    CatchupProgress {
+        round: u64,
+        target: u64,
+    },
+    CatchupStop {
+        round: u64,
+        elapsed: Duration,
+    },
+    /// Agreement moved on to `period` of `round`.
+    AgreementPeriod {
+        round: u64,
+        period: u64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl TelemetryEvent {
+    /// The category and name of the event, as in go-algorand.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Startup { .. } => "ApplicationState/Startup",
+            Self::Shutdown => "ApplicationState/Shutdown",
+            Self::CatchupStart { .. } => "ApplicationState/CatchupStart",
+            Self::CatchupProgress { .. } => "ApplicationState/CatchupProgress",
+            Self::CatchupStop { .. } => "ApplicationState/CatchupStop",
+            Self::AgreementPeriod { .. } => "Agreement/Period",
+            Self::Error { .. } => "ErrorOutput/Error",
+        }
+    }
+
+    pub fn details(&self) -> Value {
+        match self {
+            Self::Startup {
+                version,
+                genesis_id,
+            } => json!({ "version": version, "genesis_id": genesis_id }),
+            Self::Shutdown => json!({}),
+            Self::CatchupStart { round } => json!({ "round": round }),
+            Self::CatchupProgress { round, target } => {
+                json!({ "round": round, "target": target })
+            }
+            Self::CatchupStop { round, elapsed } => {
+                json!({ "round": round, "elapsed_ms": elapsed.as_millis() as u64 })
+            }
+            Self::AgreementPeriod { round, period } => {
+                json!({ "round": round, "period": period })
+            }
+            Self::Error { message } => json!({ "message": message }),
+        }
+    }
+}
+
+/// TelemetrySink delivers the entries of the telemetry stream: objects
+/// with the `guid` of the session, the `event` name, its `time` and its
+/// `details`.
+pub trait TelemetrySink: Send {
+    fn send(&mut self, entry: &Value) -> io::Result<()>;
+}
+
+/// LogSink writes the entries to the node log.
+pub struct LogSink;
+
+impl TelemetrySink for LogSink {
+    fn send(&mut self, entry: &Value) -> io::Result<()> {
+        tracing::info!(
+            target: "telemetry",
+            guid = entry["guid"].as_str().unwrap_or_default(),
+            event = entry["event"].as_str().unwrap_or_default(),
+            details = %entry["details"],
+            "telemetry event"
+        );
+        Ok(())
+    }
+}
+
+/// How long the HTTP sink waits to connect to, write to or hear from the
+/// collector, so that a collector that stopped answering can not hold up
+/// `flush` at exit.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// HttpSink posts the entries, as JSON, to a collector.
+pub struct HttpSink {
+    /// The host and port of the collector.
+    address: String,
+    path: String,
+    timeout: Duration,
+}
+
+impl HttpSink {
+    pub fn new(url: &str) -> Result<Self, TelemetryError> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| TelemetryError::UnsupportedEndpoint(url.to_string()))?;
+        let (host, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/"),
+        };
+        let address = if host.contains(':') {
+            host.to_string()
+        } else {
+            format!("{}:80", host)
+        };
+        Ok(Self {
+            address,
+            path: path.to_string(),
+            timeout: IO_TIMEOUT,
+        })
+    }
+}
+
+impl HttpSink {
+    /// Connects to the first address of the collector that accepts within
+    /// the timeout.
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last = io::Error::new(io::ErrorKind::NotFound, "collector has no address");
+        for addr in self.address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last = e,
+            }
+        }
+        Err(last)
+    }
+}
+
+impl TelemetrySink for HttpSink {
+    fn send(&mut self, entry: &Value) -> io::Result<()> {
+        let body = entry.to_string();
+        let mut stream = self.connect()?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.address,
+            body.len(),
+            body
+        )?;
+        let mut status = [0; 12];
+        stream.read_exact(&mut status)?;
+        // "HTTP/1.1 2xx"
+        if status[9] != b'2' {
+            let status = String::from_utf8_lossy(&status[9..]).into_owned();
+            return Err(io::Error::other(format!("collector answered {}", status)));
+        }
+        Ok(())
+    }
+}
+
+/// Telemetry is the stream of the events of a session. The entries are
+/// delivered to the sinks by a thread of their own, so that slow sinks do
+/// not hold up the node.
+pub struct Telemetry {
+    session_guid: String,
+    entries: Mutex<Option<mpsc::Sender<Value>>>,
+    sender: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+impl Telemetry {
+    pub fn start(session_guid: String, sinks: Vec<Box<dyn TelemetrySink>>) -> io::Result<Self> {
+        let (tx, rx) = mpsc::channel::<Value>();
+        let sender = thread::Builder::new()
+            .name("telemetry".to_string())
+            .spawn(move || {
+                let mut sinks = sinks;
+                for entry in rx {
+                    for sink in sinks.iter_mut() {
+                        if let Err(e) = sink.send(&entry) {
+                            tracing::debug!("could not send telemetry: {}", e);
+                        }
+                    }
+                }
+            })?;
+        Ok(Self {
+            session_guid,
+            entries: Mutex::new(Some(tx)),
+            sender: Mutex::new(Some(sender)),
+        })
+    }
+
+    pub fn session_guid(&self) -> &str {
+        &self.session_guid
+    }
+
+    /// Queues `event` for the sinks, unless the stream was closed.
+    pub fn event(&self, event: TelemetryEvent) {
+        let entry = json!({
+            "guid": self.session_guid,
+            "event": event.name(),
+            "time": crate::logging::rfc3339(SystemTime::now()),
+            "details": event.details(),
+        });
+        if let Some(entries) = self.entries.lock().as_ref() {
+            entries.send(entry).ok();
+        }
+    }
+
+    /// Closes the stream and waits for the queued events to be delivered.
+    pub fn close(&self) {
+        self.entries.lock().take();
+        if let Some(sender) = self.sender.lock().take() {
+            sender.join().ok();
+        }
+    }
+}
+
+static TELEMETRY: OnceLock<Arc<Telemetry>> = OnceLock::new();
+
+/// Starts the telemetry of the process, when `config` enables it.
+pub fn init(config: &TelemetryConfig) -> io::Result<()> {
+    if !config.enable {
+        return Ok(());
+    }
+    let mut sinks: Vec<Box<dyn TelemetrySink>> = vec![];
+    if config.to_log {
+        sinks.push(Box::new(LogSink));
+    }
+    if let Some(endpoint) = &config.endpoint {
+        let sink =
+            HttpSink::new(endpoint).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        sinks.push(Box::new(sink));
+    }
+    let telemetry = Telemetry::start(config.session_guid.clone(), sinks)?;
+    TELEMETRY
+        .set(Arc::new(telemetry))
+        .map_err(|_| io::Error::other("telemetry is already started"))
+}
+
+/// Sends `event` to the telemetry of the process, if it was started.
+pub fn event(event: TelemetryEvent) {
+    if let Some(telemetry) = TELEMETRY.get() {
+        telemetry.event(event);
+    }
+}
+
+/// Delivers the pending events of the process before it exits.
+pub fn flush() {
+    if let Some(telemetry) = TELEMETRY.get() {
+        telemetry.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    struct Collect(Arc<Mutex<Vec<Value>>>);
+
+    impl TelemetrySink for Collect {
+        fn send(&mut self, entry: &Value) -> io::Result<()> {
+            self.0.lock().push(entry.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn overrides() {
+        let mut config = TelemetryConfig::default();
+        config.apply_override("1").unwrap();
+        assert!(config.enable);
+        config.apply_override("false").unwrap();
+        assert!(!config.enable);
+        config
+            .apply_override("http://127.0.0.1:8080/events")
+            .unwrap();
+        assert!(config.enable);
+        assert_eq!(
+            config.endpoint.as_deref(),
+            Some("http://127.0.0.1:8080/events")
+        );
+        assert!(matches!(
+            config.apply_override("https://collector"),
+            Err(TelemetryError::UnsupportedEndpoint(_))
+        ));
+        assert!(matches!(
+            config.apply_override("yes"),
+            Err(TelemetryError::InvalidOverride(_))
+        ));
+    }
+
+    #[test]
+    fn events_are_tagged_with_the_session() {
+        let guid = new_session_guid(&mut StdRng::seed_from_u64(7));
+        assert_eq!(guid, new_session_guid(&mut StdRng::seed_from_u64(7)));
+        assert_eq!(guid.len(), 36);
+        assert_eq!(&guid[14..15], "4");
+
+        let entries = Arc::new(Mutex::new(vec![]));
+        let telemetry =
+            Telemetry::start(guid.clone(), vec![Box::new(Collect(entries.clone()))]).unwrap();
+        telemetry.event(TelemetryEvent::CatchupProgress {
+            round: 10,
+            target: 20,
+        });
+        telemetry.close();
+        telemetry.event(TelemetryEvent::Shutdown);
+
+        let entries = entries.lock();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["guid"], guid.as_str());
+        assert_eq!(entries[0]["event"], "ApplicationState/CatchupProgress");
+        assert_eq!(entries[0]["details"]["target"], 20);
+    }
+
+    #[test]
+    fn silent_collectors_time_out() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/events", listener.local_addr().unwrap());
+        let mut sink = HttpSink::new(&url).unwrap();
+        assert_eq!(sink.timeout, IO_TIMEOUT);
+        sink.timeout = Duration::from_millis(100);
+
+        // The collector accepts the connection but never answers.
+        let started = std::time::Instant::now();
+        let err = sink.send(&json!({"event": "test"})).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        drop(listener);
+    }
+}